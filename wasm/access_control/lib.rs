@@ -0,0 +1,167 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! Storage components for the admin checks of a contract: an owner that is handed over in two
+//! steps, and roles held by accounts. A contract embeds them in its storage, passes in the
+//! caller and turns the errors into its own, so every contract guards its messages the same way.
+
+use ink::{primitives::AccountId, storage::Mapping};
+
+/// Why a guard refused the caller
+#[derive(Debug, PartialEq, Eq, Clone, Copy, scale::Encode, scale::Decode)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+pub enum AccessControlError {
+	/// The caller is not the owner
+	NotOwner,
+	/// The caller is not the account proposed as the next owner
+	NotPendingOwner,
+	/// The caller does not hold the role
+	MissingRole,
+}
+
+/// The owner of a contract. Ownership is proposed by the owner and only moves once the proposed
+/// account accepts, so a typo cannot lock the contract.
+#[ink::storage_item]
+#[derive(Debug)]
+pub struct Ownable {
+	owner: AccountId,
+	pending_owner: Option<AccountId>,
+}
+
+impl Ownable {
+	pub fn new(owner: AccountId) -> Self {
+		Self { owner, pending_owner: None }
+	}
+
+	pub fn owner(&self) -> AccountId {
+		self.owner
+	}
+
+	/// The account proposed as the next owner, until it accepts
+	pub fn pending_owner(&self) -> Option<AccountId> {
+		self.pending_owner
+	}
+
+	/// Fails unless `caller` is the owner
+	pub fn ensure_owner(&self, caller: AccountId) -> Result<(), AccessControlError> {
+		if caller != self.owner {
+			return Err(AccessControlError::NotOwner);
+		}
+
+		Ok(())
+	}
+
+	/// Proposes `new_owner` as the next owner on behalf of `caller`. A new proposal replaces any
+	/// earlier one.
+	pub fn transfer_ownership(
+		&mut self,
+		caller: AccountId,
+		new_owner: AccountId,
+	) -> Result<(), AccessControlError> {
+		self.ensure_owner(caller)?;
+		self.pending_owner = Some(new_owner);
+		Ok(())
+	}
+
+	/// Makes `caller` the owner if it was proposed, and returns the previous owner
+	pub fn accept_ownership(&mut self, caller: AccountId) -> Result<AccountId, AccessControlError> {
+		if self.pending_owner != Some(caller) {
+			return Err(AccessControlError::NotPendingOwner);
+		}
+
+		self.pending_owner = None;
+		Ok(core::mem::replace(&mut self.owner, caller))
+	}
+}
+
+/// The accounts holding each role of the contract's role type `R`, usually a fieldless enum.
+/// Only holders are stored.
+#[ink::storage_item]
+#[derive(Debug)]
+pub struct Roles<R>
+where
+	R: scale::Encode + scale::EncodeLike,
+{
+	holders: Mapping<(R, AccountId), ()>,
+}
+
+impl<R> Default for Roles<R>
+where
+	R: scale::Encode + scale::EncodeLike,
+{
+	fn default() -> Self {
+		Self { holders: Mapping::default() }
+	}
+}
+
+impl<R> Roles<R>
+where
+	R: scale::Encode + scale::EncodeLike,
+{
+	pub fn has_role(&self, role: R, account: AccountId) -> bool {
+		self.holders.contains((role, account))
+	}
+
+	/// Fails unless `caller` holds `role`
+	pub fn ensure_role(&self, role: R, caller: AccountId) -> Result<(), AccessControlError> {
+		if !self.has_role(role, caller) {
+			return Err(AccessControlError::MissingRole);
+		}
+
+		Ok(())
+	}
+
+	/// Gives `role` to `account`. Checking who may grant it is up to the contract.
+	pub fn grant_role(&mut self, role: R, account: AccountId) {
+		self.holders.insert((role, account), &());
+	}
+
+	/// Takes `role` away from `account`. Checking who may revoke it is up to the contract.
+	pub fn revoke_role(&mut self, role: R, account: AccountId) {
+		self.holders.remove((role, account));
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn accounts() -> ink::env::test::DefaultAccounts<ink::env::DefaultEnvironment> {
+		ink::env::test::default_accounts::<ink::env::DefaultEnvironment>()
+	}
+
+	#[ink::test]
+	fn test_ownership_moves_once_accepted() {
+		let accounts = accounts();
+		let mut ownable = Ownable::new(accounts.alice);
+
+		assert_eq!(
+			ownable.transfer_ownership(accounts.bob, accounts.bob),
+			Err(AccessControlError::NotOwner)
+		);
+		assert_eq!(ownable.transfer_ownership(accounts.alice, accounts.bob), Ok(()));
+		assert_eq!(ownable.owner(), accounts.alice);
+		assert_eq!(
+			ownable.accept_ownership(accounts.charlie),
+			Err(AccessControlError::NotPendingOwner)
+		);
+
+		assert_eq!(ownable.accept_ownership(accounts.bob), Ok(accounts.alice));
+		assert_eq!(ownable.owner(), accounts.bob);
+		assert_eq!(ownable.pending_owner(), None);
+		assert_eq!(ownable.ensure_owner(accounts.alice), Err(AccessControlError::NotOwner));
+	}
+
+	#[ink::test]
+	fn test_roles_are_held_until_revoked() {
+		let accounts = accounts();
+		let mut roles = Roles::<u8>::default();
+
+		roles.grant_role(1, accounts.bob);
+		assert_eq!(roles.ensure_role(1, accounts.bob), Ok(()));
+		assert_eq!(roles.ensure_role(2, accounts.bob), Err(AccessControlError::MissingRole));
+		assert_eq!(roles.ensure_role(1, accounts.alice), Err(AccessControlError::MissingRole));
+
+		roles.revoke_role(1, accounts.bob);
+		assert!(!roles.has_role(1, accounts.bob));
+	}
+}
@@ -0,0 +1,586 @@
+#![cfg_attr(not(feature = "std"), no_std, no_main)]
+
+pub use self::bridge::{Bridge, BridgeError, BridgeRef, Custody};
+
+/// Moves a PSP22 token between two chains, with one bridge deployed on each.
+///
+/// On the token's home chain the bridge locks the tokens it sends and releases them when they
+/// come back. On the other chain it mints a wrapped token for what arrives and burns what is
+/// sent back, so the wrapped supply never exceeds what is locked. Every transfer sent gets a
+/// nonce, and relayers watching the sending bridge sign a message about it. The receiving
+/// bridge pays out once it has signatures from enough of its relayers, and only once per
+/// transfer.
+///
+/// Relayers are ECDSA keys, identified by the account the key controls. Anyone may submit the
+/// signatures they made, so relayers don't need funds on the receiving chain.
+#[ink::contract]
+mod bridge {
+	use access_control::Ownable;
+	use ink::{
+		codegen::EmitEvent,
+		env::hash::{Blake2x256, HashOutput},
+		prelude::vec::Vec,
+		reflect::ContractEventBase,
+		storage::Mapping,
+	};
+	use psp22_traits::{PSP22Burnable, PSP22Error, PSP22Mintable, PSP22};
+
+	/// How the bridge holds the tokens on its side
+	#[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+	#[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+	pub enum Custody {
+		/// The bridge locks what it sends and releases what it receives, on the token's home
+		/// chain
+		Lock,
+		/// The bridge burns what it sends and mints what it receives, on the chain of the
+		/// wrapped token, which must let the bridge mint and burn
+		Mint,
+	}
+
+	/// The chains transfers are sent from and to
+	pub type ChainId = u32;
+
+	/// Numbers transfers per sending bridge
+	pub type Nonce = u64;
+
+	/// A recoverable ECDSA signature
+	pub type Signature = [u8; 65];
+
+	#[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+	#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+	pub enum BridgeError {
+		NotOwner,
+		/// The threshold must be positive and at most the number of relayers
+		InvalidConfig,
+		/// The account already is a relayer
+		RelayerExists,
+		/// The account isn't a relayer
+		RelayerNotFound,
+		/// Transfers move a positive amount to another chain
+		InvalidTransfer,
+		/// Fewer relayers than the threshold signed the transfer
+		NotEnoughSignatures,
+		/// A signature doesn't recover to a public key
+		InvalidSignature,
+		/// The transfer was already paid out
+		AlreadyReceived,
+		/// A token call failed
+		PSP22(PSP22Error),
+	}
+
+	impl From<PSP22Error> for BridgeError {
+		fn from(e: PSP22Error) -> Self {
+			BridgeError::PSP22(e)
+		}
+	}
+
+	pub type Result<T> = core::result::Result<T, BridgeError>;
+
+	type Event = <Bridge as ContractEventBase>::Type;
+
+	/// `from` sent `amount` to `to` on chain `destination`
+	#[ink(event)]
+	pub struct Sent {
+		#[ink(topic)]
+		nonce: Nonce,
+		#[ink(topic)]
+		from: AccountId,
+		to: AccountId,
+		amount: Balance,
+		#[ink(topic)]
+		destination: ChainId,
+	}
+
+	/// `to` got `amount` of what was sent from chain `source`
+	#[ink(event)]
+	pub struct Received {
+		#[ink(topic)]
+		source: ChainId,
+		#[ink(topic)]
+		nonce: Nonce,
+		#[ink(topic)]
+		to: AccountId,
+		amount: Balance,
+	}
+
+	#[ink(event)]
+	pub struct RelayerAdded {
+		#[ink(topic)]
+		relayer: AccountId,
+	}
+
+	#[ink(event)]
+	pub struct RelayerRemoved {
+		#[ink(topic)]
+		relayer: AccountId,
+	}
+
+	#[ink(storage)]
+	pub struct Bridge {
+		ownership: Ownable,
+		token: AccountId,
+		custody: Custody,
+		/// The chain the bridge is deployed on
+		chain: ChainId,
+		relayers: Vec<AccountId>,
+		/// How many relayers must sign a transfer
+		threshold: u32,
+		/// The nonce of the next transfer sent
+		next_nonce: Nonce,
+		/// The transfers paid out, by source chain and nonce
+		received: Mapping<(ChainId, Nonce), ()>,
+	}
+
+	impl Bridge {
+		/// Bridges `token` for chain `chain`, holding it according to `custody`. Transfers are
+		/// paid out once `threshold` of `relayers` signed them. The caller becomes the owner.
+		#[ink(constructor)]
+		pub fn new(
+			token: AccountId,
+			custody: Custody,
+			chain: ChainId,
+			relayers: Vec<AccountId>,
+			threshold: u32,
+		) -> Result<Self> {
+			let mut bridge = Self {
+				ownership: Ownable::new(Self::env().caller()),
+				token,
+				custody,
+				chain,
+				relayers: Vec::new(),
+				threshold,
+				next_nonce: 0,
+				received: Mapping::default(),
+			};
+			for relayer in relayers {
+				bridge.insert_relayer(relayer)?;
+			}
+			bridge.check_threshold(threshold)?;
+			Ok(bridge)
+		}
+
+		/// Returns the bridged token, how the bridge holds it, and the chain of the bridge
+		#[ink(message)]
+		pub fn token(&self) -> (AccountId, Custody, ChainId) {
+			(self.token, self.custody, self.chain)
+		}
+
+		/// Returns the relayers and how many of them must sign a transfer
+		#[ink(message)]
+		pub fn relayers(&self) -> (Vec<AccountId>, u32) {
+			(self.relayers.clone(), self.threshold)
+		}
+
+		/// Returns whether the transfer with `nonce` from chain `source` was paid out
+		#[ink(message)]
+		pub fn is_received(&self, source: ChainId, nonce: Nonce) -> bool {
+			self.received.contains((source, nonce))
+		}
+
+		/// Sends `amount` tokens of the caller to `to` on chain `destination`. On the home
+		/// chain the bridge takes them from the caller, who approves the bridge first, and
+		/// elsewhere it burns them. Returns the nonce of the transfer.
+		#[ink(message)]
+		pub fn send(
+			&mut self,
+			to: AccountId,
+			amount: Balance,
+			destination: ChainId,
+		) -> Result<Nonce> {
+			if amount == 0 || destination == self.chain {
+				return Err(BridgeError::InvalidTransfer);
+			}
+			let from = self.env().caller();
+			let amount = match self.custody {
+				Custody::Lock => {
+					let this = self.env().account_id();
+					let mut psp22: ink::contract_ref!(PSP22) = self.token.into();
+					let before = psp22.balance_of(this);
+					psp22.transfer_from(from, this, amount, Vec::new())?;
+					// Send what arrived, tokens may charge a fee on transfers
+					psp22.balance_of(this).saturating_sub(before)
+				},
+				Custody::Mint => {
+					let mut psp22: ink::contract_ref!(PSP22Burnable) = self.token.into();
+					psp22.burn(from, amount)?;
+					amount
+				},
+			};
+
+			let nonce = self.next_nonce;
+			self.next_nonce += 1;
+
+			Self::emit_event(
+				self.env(),
+				Event::Sent(Sent { nonce, from, to, amount, destination }),
+			);
+
+			Ok(nonce)
+		}
+
+		/// Pays out the transfer of `amount` to `to` sent from chain `source` with `nonce`, as
+		/// signed by relayers in `signatures`. On the home chain the bridge releases what it
+		/// locked, elsewhere it mints. Anyone may submit the signatures.
+		#[ink(message)]
+		pub fn receive(
+			&mut self,
+			source: ChainId,
+			nonce: Nonce,
+			to: AccountId,
+			amount: Balance,
+			signatures: Vec<Signature>,
+		) -> Result<()> {
+			if self.is_received(source, nonce) {
+				return Err(BridgeError::AlreadyReceived);
+			}
+			let hash = self.transfer_hash(source, nonce, to, amount);
+			self.verify_signatures(&hash, &signatures)?;
+			self.received.insert((source, nonce), &());
+
+			match self.custody {
+				Custody::Lock => {
+					let mut psp22: ink::contract_ref!(PSP22) = self.token.into();
+					psp22.transfer(to, amount, Vec::new())?;
+				},
+				Custody::Mint => {
+					let mut psp22: ink::contract_ref!(PSP22Mintable) = self.token.into();
+					psp22.mint(to, amount)?;
+				},
+			}
+
+			Self::emit_event(self.env(), Event::Received(Received { source, nonce, to, amount }));
+
+			Ok(())
+		}
+
+		/// The hash relayers sign to have this bridge pay out the transfer of `amount` to `to`
+		/// sent from chain `source` with `nonce`. It commits to the bridge and its chain, so
+		/// signatures can't be replayed elsewhere.
+		#[ink(message)]
+		pub fn transfer_hash(
+			&self,
+			source: ChainId,
+			nonce: Nonce,
+			to: AccountId,
+			amount: Balance,
+		) -> [u8; 32] {
+			let mut hash = <Blake2x256 as HashOutput>::Type::default();
+			ink::env::hash_encoded::<Blake2x256, _>(
+				&(self.env().account_id(), self.chain, source, nonce, to, amount),
+				&mut hash,
+			);
+			hash
+		}
+
+		/// Lets `relayer` sign transfers. Only the owner may manage relayers.
+		#[ink(message)]
+		pub fn add_relayer(&mut self, relayer: AccountId) -> Result<()> {
+			self.ensure_owner()?;
+			self.insert_relayer(relayer)?;
+			Self::emit_event(self.env(), Event::RelayerAdded(RelayerAdded { relayer }));
+			Ok(())
+		}
+
+		/// Stops `relayer` from signing transfers. The remaining relayers must still reach the
+		/// threshold. Only the owner may manage relayers.
+		#[ink(message)]
+		pub fn remove_relayer(&mut self, relayer: AccountId) -> Result<()> {
+			self.ensure_owner()?;
+			let index = self
+				.relayers
+				.iter()
+				.position(|r| *r == relayer)
+				.ok_or(BridgeError::RelayerNotFound)?;
+			if self.relayers.len() <= self.threshold as usize {
+				return Err(BridgeError::InvalidConfig);
+			}
+			self.relayers.swap_remove(index);
+			Self::emit_event(self.env(), Event::RelayerRemoved(RelayerRemoved { relayer }));
+			Ok(())
+		}
+
+		/// Changes how many relayers must sign a transfer. Only the owner may configure the
+		/// bridge.
+		#[ink(message)]
+		pub fn set_threshold(&mut self, threshold: u32) -> Result<()> {
+			self.ensure_owner()?;
+			self.check_threshold(threshold)?;
+			self.threshold = threshold;
+			Ok(())
+		}
+
+		fn insert_relayer(&mut self, relayer: AccountId) -> Result<()> {
+			if self.relayers.contains(&relayer) {
+				return Err(BridgeError::RelayerExists);
+			}
+			self.relayers.push(relayer);
+			Ok(())
+		}
+
+		fn check_threshold(&self, threshold: u32) -> Result<()> {
+			if threshold == 0 || threshold as usize > self.relayers.len() {
+				return Err(BridgeError::InvalidConfig);
+			}
+			Ok(())
+		}
+
+		/// Checks that at least `threshold` distinct relayers signed `hash`. Signatures of
+		/// other keys don't count.
+		fn verify_signatures(&self, hash: &[u8; 32], signatures: &[Signature]) -> Result<()> {
+			let mut signers = Vec::new();
+			for signature in signatures {
+				let signer = recover_signer(hash, signature)?;
+				if self.relayers.contains(&signer) && !signers.contains(&signer) {
+					signers.push(signer);
+				}
+			}
+			if signers.len() < self.threshold as usize {
+				return Err(BridgeError::NotEnoughSignatures);
+			}
+			Ok(())
+		}
+
+		fn ensure_owner(&self) -> Result<()> {
+			self.ownership
+				.ensure_owner(self.env().caller())
+				.map_err(|_| BridgeError::NotOwner)
+		}
+
+		fn emit_event<EE>(emitter: EE, event: Event)
+		where
+			EE: EmitEvent<Self>,
+		{
+			emitter.emit_event(event);
+		}
+	}
+
+	/// Returns the account controlled by the key that signed `hash`
+	fn recover_signer(hash: &[u8; 32], signature: &Signature) -> Result<AccountId> {
+		let mut public_key = [0; 33];
+		ink::env::ecdsa_recover(signature, hash, &mut public_key)
+			.map_err(|_| BridgeError::InvalidSignature)?;
+		let mut account = <Blake2x256 as HashOutput>::Type::default();
+		ink::env::hash_bytes::<Blake2x256>(&public_key, &mut account);
+		Ok(AccountId::from(account))
+	}
+
+	#[cfg(test)]
+	mod tests {
+		use secp256k1::{Message, PublicKey, SecretKey, SECP256K1};
+
+		use super::*;
+
+		fn default_accounts() -> ink::env::test::DefaultAccounts<Environment> {
+			ink::env::test::default_accounts::<Environment>()
+		}
+
+		/// The relayer key with every byte set to `seed`
+		fn secret_key(seed: u8) -> SecretKey {
+			SecretKey::from_slice(&[seed; 32]).unwrap()
+		}
+
+		fn relayer(seed: u8) -> AccountId {
+			let public_key = PublicKey::from_secret_key(SECP256K1, &secret_key(seed)).serialize();
+			let mut account = <Blake2x256 as HashOutput>::Type::default();
+			ink::env::hash_bytes::<Blake2x256>(&public_key, &mut account);
+			AccountId::from(account)
+		}
+
+		fn sign(seed: u8, hash: [u8; 32]) -> Signature {
+			let message = Message::from_slice(&hash).unwrap();
+			let (recovery_id, compact) = SECP256K1
+				.sign_ecdsa_recoverable(&message, &secret_key(seed))
+				.serialize_compact();
+			let mut signature = [0; 65];
+			signature[..64].copy_from_slice(&compact);
+			signature[64] = recovery_id.to_i32() as u8;
+			signature
+		}
+
+		/// A bridge on chain 2 needing two of three relayers
+		fn bridge() -> Bridge {
+			let default_accounts = default_accounts();
+			ink::env::test::set_caller::<Environment>(default_accounts.alice);
+			Bridge::new(
+				default_accounts.django,
+				Custody::Mint,
+				2,
+				vec![relayer(1), relayer(2), relayer(3)],
+				2,
+			)
+			.expect("valid config")
+		}
+
+		#[ink::test]
+		fn test_transfers_need_enough_relayers() {
+			let default_accounts = default_accounts();
+			let bridge = bridge();
+			let hash = bridge.transfer_hash(1, 0, default_accounts.bob, 100);
+
+			assert_eq!(
+				bridge.verify_signatures(&hash, &[sign(1, hash)]),
+				Err(BridgeError::NotEnoughSignatures)
+			);
+			// A relayer signing twice still counts once
+			assert_eq!(
+				bridge.verify_signatures(&hash, &[sign(1, hash), sign(1, hash)]),
+				Err(BridgeError::NotEnoughSignatures)
+			);
+			// Keys that aren't relayers don't count
+			assert_eq!(
+				bridge.verify_signatures(&hash, &[sign(1, hash), sign(4, hash)]),
+				Err(BridgeError::NotEnoughSignatures)
+			);
+			assert_eq!(bridge.verify_signatures(&hash, &[sign(3, hash), sign(1, hash)]), Ok(()));
+
+			// Signatures only hold for the transfer they were made for
+			let other = bridge.transfer_hash(1, 0, default_accounts.bob, 101);
+			assert_eq!(
+				bridge.verify_signatures(&other, &[sign(3, hash), sign(1, hash)]),
+				Err(BridgeError::NotEnoughSignatures)
+			);
+		}
+
+		#[ink::test]
+		fn test_transfers_are_received_once() {
+			let default_accounts = default_accounts();
+			let mut bridge = bridge();
+			bridge.received.insert((1, 0), &());
+
+			let hash = bridge.transfer_hash(1, 0, default_accounts.bob, 100);
+			let signatures = vec![sign(1, hash), sign(2, hash)];
+			assert_eq!(
+				bridge.receive(1, 0, default_accounts.bob, 100, signatures),
+				Err(BridgeError::AlreadyReceived)
+			);
+			assert!(bridge.is_received(1, 0));
+			assert!(!bridge.is_received(3, 0));
+		}
+
+		#[ink::test]
+		fn test_only_the_owner_configures() {
+			let default_accounts = default_accounts();
+			let mut bridge = bridge();
+
+			assert_eq!(bridge.set_threshold(4), Err(BridgeError::InvalidConfig));
+			assert_eq!(bridge.set_threshold(0), Err(BridgeError::InvalidConfig));
+			assert_eq!(bridge.add_relayer(relayer(3)), Err(BridgeError::RelayerExists));
+			assert_eq!(bridge.remove_relayer(relayer(4)), Err(BridgeError::RelayerNotFound));
+			assert_eq!(bridge.set_threshold(3), Ok(()));
+			assert_eq!(bridge.remove_relayer(relayer(1)), Err(BridgeError::InvalidConfig));
+			assert_eq!(bridge.set_threshold(2), Ok(()));
+			assert_eq!(bridge.remove_relayer(relayer(1)), Ok(()));
+			assert_eq!(bridge.relayers(), (vec![relayer(3), relayer(2)], 2));
+
+			ink::env::test::set_caller::<Environment>(default_accounts.bob);
+			assert_eq!(bridge.add_relayer(default_accounts.bob), Err(BridgeError::NotOwner));
+			assert_eq!(bridge.remove_relayer(relayer(2)), Err(BridgeError::NotOwner));
+			assert_eq!(bridge.set_threshold(1), Err(BridgeError::NotOwner));
+		}
+	}
+
+	#[cfg(all(test, feature = "e2e-tests"))]
+	mod e2e_tests {
+		use ink_e2e::build_message;
+		use psp22_mock::Psp22MockRef;
+		use secp256k1::{Message, PublicKey, SecretKey, SECP256K1};
+
+		use super::*;
+
+		type E2EResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+		#[ink_e2e::test(additional_contracts = "../psp22_mock/Cargo.toml")]
+		async fn test_locked_tokens_are_minted_on_the_other_side(
+			mut client: ink_e2e::Client<C, E>,
+		) -> E2EResult<()> {
+			let bob = ink_e2e::account_id(ink_e2e::AccountKeyring::Bob);
+			let secret_key = SecretKey::from_slice(&[0x11; 32]).unwrap();
+			let public_key = PublicKey::from_secret_key(SECP256K1, &secret_key).serialize();
+			let mut relayer = <Blake2x256 as HashOutput>::Type::default();
+			ink::env::hash_bytes::<Blake2x256>(&public_key, &mut relayer);
+			let relayer = AccountId::from(relayer);
+
+			// Both sides live on the same chain here, posing as chains 1 and 2
+			let token = client
+				.instantiate("psp22_mock", &ink_e2e::alice(), Psp22MockRef::new(1_000), 0, None)
+				.await
+				.expect("instantiate token failed")
+				.account_id;
+			let wrapped = client
+				.instantiate("psp22_mock", &ink_e2e::alice(), Psp22MockRef::new(0), 0, None)
+				.await
+				.expect("instantiate wrapped token failed")
+				.account_id;
+			let home = client
+				.instantiate(
+					"bridge",
+					&ink_e2e::alice(),
+					BridgeRef::new(token.clone(), Custody::Lock, 1, vec![relayer.clone()], 1),
+					0,
+					None,
+				)
+				.await
+				.expect("instantiate home bridge failed")
+				.account_id;
+			let remote = client
+				.instantiate(
+					"bridge",
+					&ink_e2e::alice(),
+					BridgeRef::new(wrapped.clone(), Custody::Mint, 2, vec![relayer.clone()], 1),
+					0,
+					None,
+				)
+				.await
+				.expect("instantiate remote bridge failed")
+				.account_id;
+
+			let approve = build_message::<Psp22MockRef>(token.clone())
+				.call(|token| token.approve(home.clone(), 100));
+			client.call(&ink_e2e::alice(), approve, 0, None).await.expect("approve failed");
+			let send = build_message::<BridgeRef>(home.clone())
+				.call(|bridge| bridge.send(bob.clone(), 100, 2));
+			let nonce = client
+				.call(&ink_e2e::alice(), send, 0, None)
+				.await
+				.expect("send failed")
+				.return_value()
+				.expect("send rejected");
+
+			// The relayer saw the transfer and signs it for the remote bridge
+			let transfer_hash = build_message::<BridgeRef>(remote.clone())
+				.call(|bridge| bridge.transfer_hash(1, nonce, bob.clone(), 100));
+			let hash = client
+				.call_dry_run(&ink_e2e::bob(), &transfer_hash, 0, None)
+				.await
+				.return_value();
+			let message = Message::from_slice(&hash).unwrap();
+			let (recovery_id, compact) =
+				SECP256K1.sign_ecdsa_recoverable(&message, &secret_key).serialize_compact();
+			let mut signature = [0; 65];
+			signature[..64].copy_from_slice(&compact);
+			signature[64] = recovery_id.to_i32() as u8;
+
+			let receive = build_message::<BridgeRef>(remote.clone())
+				.call(|bridge| bridge.receive(1, nonce, bob.clone(), 100, vec![signature]));
+			client
+				.call(&ink_e2e::bob(), receive, 0, None)
+				.await
+				.expect("receive failed")
+				.return_value()
+				.expect("receive rejected");
+
+			let locked = build_message::<Psp22MockRef>(token.clone())
+				.call(|token| token.balance_of(home.clone()));
+			let locked =
+				client.call_dry_run(&ink_e2e::bob(), &locked, 0, None).await.return_value();
+			assert_eq!(locked, 100);
+			let minted = build_message::<Psp22MockRef>(wrapped.clone())
+				.call(|token| token.balance_of(bob.clone()));
+			let minted =
+				client.call_dry_run(&ink_e2e::bob(), &minted, 0, None).await.return_value();
+			assert_eq!(minted, 100);
+
+			Ok(())
+		}
+	}
+}
@@ -0,0 +1,483 @@
+#![cfg_attr(not(feature = "std"), no_std, no_main)]
+
+pub use self::crowdsale::{Crowdsale, CrowdsaleRef};
+
+/// Sells a PSP22 token for the native currency at a fixed rate during a window of blocks.
+///
+/// Contributions are held until the sale ends. If at least the soft cap was raised, contributors
+/// claim their tokens and the owner withdraws the proceeds, otherwise contributors get their money
+/// back. Reaching the hard cap ends the sale early. Tokens are either minted on claim, which needs
+/// the sale to be a minter of the token, or paid out of a stock the owner transfers to the sale.
+#[ink::contract]
+mod crowdsale {
+	use access_control::Ownable;
+	use ink::{
+		codegen::EmitEvent, prelude::vec::Vec, reflect::ContractEventBase, storage::Mapping,
+	};
+	use psp22_traits::{PSP22Error, PSP22Mintable, PSP22};
+
+	/// How sold tokens reach contributors
+	#[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+	#[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+	pub enum Delivery {
+		/// The sale mints them
+		Mint,
+		/// The sale transfers them from what it holds
+		Transfer,
+	}
+
+	#[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+	#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+	pub enum SaleState {
+		/// The sale hasn't started yet
+		Pending,
+		/// Contributions are accepted
+		Open,
+		/// The soft cap was reached, tokens can be claimed
+		Succeeded,
+		/// The sale ended below the soft cap, contributions can be refunded
+		Failed,
+	}
+
+	#[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+	#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+	pub enum CrowdsaleError {
+		/// The rate must be positive, the soft cap may not exceed the hard cap and the sale must
+		/// end after it starts
+		InvalidSale,
+		NotOwner,
+		/// The sale is in the wrong state for this
+		InvalidState(SaleState),
+		/// Nothing was transferred along with the contribution
+		ZeroContribution,
+		/// The contribution would raise more than the hard cap
+		HardCapExceeded,
+		/// The caller has no contribution left to claim or refund
+		NothingToClaim,
+		/// The owner already withdrew
+		AlreadyWithdrawn,
+		/// The amount of tokens doesn't fit in a `Balance`
+		Overflow,
+		/// Paying out the native currency failed
+		NativeTransferFailed,
+		/// A token transfer or mint failed
+		PSP22(PSP22Error),
+	}
+
+	impl From<PSP22Error> for CrowdsaleError {
+		fn from(e: PSP22Error) -> Self {
+			CrowdsaleError::PSP22(e)
+		}
+	}
+
+	pub type Result<T> = core::result::Result<T, CrowdsaleError>;
+
+	type Event = <Crowdsale as ContractEventBase>::Type;
+
+	#[ink(event)]
+	pub struct Contributed {
+		#[ink(topic)]
+		contributor: AccountId,
+		amount: Balance,
+	}
+
+	#[ink(event)]
+	pub struct Claimed {
+		#[ink(topic)]
+		contributor: AccountId,
+		tokens: Balance,
+	}
+
+	#[ink(event)]
+	pub struct Refunded {
+		#[ink(topic)]
+		contributor: AccountId,
+		amount: Balance,
+	}
+
+	#[ink(event)]
+	pub struct Withdrawn {
+		/// The native currency paid to the owner
+		raised: Balance,
+		/// The tokens returned to the owner
+		unsold: Balance,
+	}
+
+	#[ink(storage)]
+	pub struct Crowdsale {
+		ownership: Ownable,
+		token: AccountId,
+		delivery: Delivery,
+		/// How many tokens one unit of the native currency buys
+		rate: Balance,
+		soft_cap: Balance,
+		hard_cap: Balance,
+		/// The first block in which contributions are accepted
+		start: BlockNumber,
+		/// The first block in which they no longer are
+		end: BlockNumber,
+		raised: Balance,
+		/// How much of what was raised has been claimed
+		claimed: Balance,
+		contributions: Mapping<AccountId, Balance>,
+		withdrawn: bool,
+	}
+
+	impl Crowdsale {
+		/// Sells `token` at `rate` tokens per unit of the native currency from block `start` up to
+		/// block `end`. The caller becomes the owner, who receives the proceeds.
+		#[allow(clippy::too_many_arguments)] // Mirrors the terms of the sale
+		#[ink(constructor)]
+		pub fn new(
+			token: AccountId,
+			delivery: Delivery,
+			rate: Balance,
+			soft_cap: Balance,
+			hard_cap: Balance,
+			start: BlockNumber,
+			end: BlockNumber,
+		) -> Result<Self> {
+			if rate == 0 || soft_cap > hard_cap || hard_cap == 0 || end <= start {
+				return Err(CrowdsaleError::InvalidSale);
+			}
+
+			Ok(Self {
+				ownership: Ownable::new(Self::env().caller()),
+				token,
+				delivery,
+				rate,
+				soft_cap,
+				hard_cap,
+				start,
+				end,
+				raised: 0,
+				claimed: 0,
+				contributions: Mapping::default(),
+				withdrawn: false,
+			})
+		}
+
+		/// Returns where the sale stands in the current block
+		#[ink(message)]
+		pub fn state(&self) -> SaleState {
+			let block = self.env().block_number();
+			if block < self.start {
+				SaleState::Pending
+			} else if self.raised >= self.hard_cap {
+				SaleState::Succeeded
+			} else if block < self.end {
+				SaleState::Open
+			} else if self.raised >= self.soft_cap {
+				SaleState::Succeeded
+			} else {
+				SaleState::Failed
+			}
+		}
+
+		/// Returns how much has been raised
+		#[ink(message)]
+		pub fn raised(&self) -> Balance {
+			self.raised
+		}
+
+		/// Returns what `contributor` paid and hasn't claimed or been refunded yet
+		#[ink(message)]
+		pub fn contribution_of(&self, contributor: AccountId) -> Balance {
+			self.contributions.get(contributor).unwrap_or(0)
+		}
+
+		/// Records what the caller transferred along with the call as a contribution. The sale
+		/// must be open and the contribution may not take it past the hard cap.
+		#[ink(message, payable)]
+		pub fn contribute(&mut self) -> Result<()> {
+			self.ensure_state(SaleState::Open)?;
+			let amount = self.env().transferred_value();
+			if amount == 0 {
+				return Err(CrowdsaleError::ZeroContribution);
+			}
+			let raised = self
+				.raised
+				.checked_add(amount)
+				.filter(|raised| *raised <= self.hard_cap)
+				.ok_or(CrowdsaleError::HardCapExceeded)?;
+
+			let contributor = self.env().caller();
+			self.raised = raised;
+			self.contributions
+				.insert(contributor, &(self.contribution_of(contributor) + amount));
+
+			Self::emit_event(self.env(), Event::Contributed(Contributed { contributor, amount }));
+
+			Ok(())
+		}
+
+		/// Delivers the caller the tokens their contribution bought, once the sale succeeded.
+		/// Returns the amount of tokens.
+		#[ink(message)]
+		pub fn claim(&mut self) -> Result<Balance> {
+			self.ensure_state(SaleState::Succeeded)?;
+			let contributor = self.env().caller();
+			let contribution = self.take_contribution(contributor)?;
+			let tokens = contribution.checked_mul(self.rate).ok_or(CrowdsaleError::Overflow)?;
+			self.claimed += contribution;
+
+			match self.delivery {
+				Delivery::Mint => {
+					let mut psp22: ink::contract_ref!(PSP22Mintable) = self.token.into();
+					psp22.mint(contributor, tokens)?;
+				},
+				Delivery::Transfer => {
+					let mut psp22: ink::contract_ref!(PSP22) = self.token.into();
+					psp22.transfer(contributor, tokens, Vec::new())?;
+				},
+			}
+
+			Self::emit_event(self.env(), Event::Claimed(Claimed { contributor, tokens }));
+
+			Ok(tokens)
+		}
+
+		/// Pays the caller their contribution back, once the sale failed. Returns the amount.
+		#[ink(message)]
+		pub fn refund(&mut self) -> Result<Balance> {
+			self.ensure_state(SaleState::Failed)?;
+			let contributor = self.env().caller();
+			let amount = self.take_contribution(contributor)?;
+
+			self.env()
+				.transfer(contributor, amount)
+				.map_err(|_| CrowdsaleError::NativeTransferFailed)?;
+
+			Self::emit_event(self.env(), Event::Refunded(Refunded { contributor, amount }));
+
+			Ok(amount)
+		}
+
+		/// Pays the owner what was raised if the sale succeeded, and returns the tokens the sale
+		/// holds beyond what contributors may still claim. Only the owner may withdraw, once the
+		/// sale is over.
+		#[ink(message)]
+		pub fn withdraw(&mut self) -> Result<()> {
+			let owner = self.env().caller();
+			self.ownership.ensure_owner(owner).map_err(|_| CrowdsaleError::NotOwner)?;
+			let state = self.state();
+			if !matches!(state, SaleState::Succeeded | SaleState::Failed) {
+				return Err(CrowdsaleError::InvalidState(state));
+			}
+			if self.withdrawn {
+				return Err(CrowdsaleError::AlreadyWithdrawn);
+			}
+			self.withdrawn = true;
+
+			let (raised, owed) = match state {
+				SaleState::Succeeded => (
+					self.raised,
+					(self.raised - self.claimed)
+						.checked_mul(self.rate)
+						.ok_or(CrowdsaleError::Overflow)?,
+				),
+				_ => (0, 0),
+			};
+			if raised > 0 {
+				self.env()
+					.transfer(owner, raised)
+					.map_err(|_| CrowdsaleError::NativeTransferFailed)?;
+			}
+
+			let mut unsold = 0;
+			if self.delivery == Delivery::Transfer {
+				let mut psp22: ink::contract_ref!(PSP22) = self.token.into();
+				unsold = psp22.balance_of(self.env().account_id()).saturating_sub(owed);
+				if unsold > 0 {
+					psp22.transfer(owner, unsold, Vec::new())?;
+				}
+			}
+
+			Self::emit_event(self.env(), Event::Withdrawn(Withdrawn { raised, unsold }));
+
+			Ok(())
+		}
+
+		fn ensure_state(&self, expected: SaleState) -> Result<()> {
+			match self.state() {
+				state if state == expected => Ok(()),
+				state => Err(CrowdsaleError::InvalidState(state)),
+			}
+		}
+
+		fn take_contribution(&mut self, contributor: AccountId) -> Result<Balance> {
+			self.contributions
+				.take(contributor)
+				.filter(|contribution| *contribution > 0)
+				.ok_or(CrowdsaleError::NothingToClaim)
+		}
+
+		fn emit_event<EE>(emitter: EE, event: Event)
+		where
+			EE: EmitEvent<Self>,
+		{
+			emitter.emit_event(event);
+		}
+	}
+
+	#[cfg(test)]
+	mod tests {
+		use super::*;
+
+		fn default_accounts() -> ink::env::test::DefaultAccounts<Environment> {
+			ink::env::test::default_accounts::<Environment>()
+		}
+
+		fn contribute(
+			crowdsale: &mut Crowdsale,
+			contributor: AccountId,
+			amount: Balance,
+		) -> Result<()> {
+			ink::env::test::set_caller::<Environment>(contributor);
+			ink::env::test::set_value_transferred::<Environment>(amount);
+			crowdsale.contribute()
+		}
+
+		fn advance_to(block: BlockNumber) {
+			while ink::env::block_number::<Environment>() < block {
+				ink::env::test::advance_block::<Environment>();
+			}
+		}
+
+		fn crowdsale(start: BlockNumber, end: BlockNumber) -> Crowdsale {
+			let default_accounts = default_accounts();
+			ink::env::test::set_caller::<Environment>(default_accounts.alice);
+			Crowdsale::new(default_accounts.django, Delivery::Mint, 10, 500, 1_000, start, end)
+				.expect("valid sale")
+		}
+
+		#[ink::test]
+		fn test_contributions_respect_the_window_and_the_hard_cap() {
+			let default_accounts = default_accounts();
+			assert_eq!(
+				Crowdsale::new(default_accounts.django, Delivery::Mint, 10, 1_001, 1_000, 0, 5)
+					.err(),
+				Some(CrowdsaleError::InvalidSale)
+			);
+			let mut crowdsale = crowdsale(2, 5);
+
+			assert_eq!(
+				contribute(&mut crowdsale, default_accounts.bob, 100),
+				Err(CrowdsaleError::InvalidState(SaleState::Pending))
+			);
+
+			advance_to(2);
+			assert_eq!(
+				contribute(&mut crowdsale, default_accounts.bob, 0),
+				Err(CrowdsaleError::ZeroContribution)
+			);
+			assert_eq!(contribute(&mut crowdsale, default_accounts.bob, 600), Ok(()));
+			assert_eq!(
+				contribute(&mut crowdsale, default_accounts.charlie, 401),
+				Err(CrowdsaleError::HardCapExceeded)
+			);
+			assert_eq!(contribute(&mut crowdsale, default_accounts.bob, 300), Ok(()));
+			assert_eq!(crowdsale.contribution_of(default_accounts.bob), 900);
+			assert_eq!(crowdsale.state(), SaleState::Open);
+
+			assert_eq!(contribute(&mut crowdsale, default_accounts.charlie, 100), Ok(()));
+			assert_eq!(crowdsale.state(), SaleState::Succeeded);
+			assert_eq!(crowdsale.raised(), 1_000);
+			assert_eq!(crowdsale.refund(), Err(CrowdsaleError::InvalidState(SaleState::Succeeded)));
+		}
+
+		#[ink::test]
+		fn test_contributors_are_refunded_below_the_soft_cap() {
+			let default_accounts = default_accounts();
+			let mut crowdsale = crowdsale(0, 3);
+			assert_eq!(contribute(&mut crowdsale, default_accounts.bob, 200), Ok(()));
+			assert_eq!(contribute(&mut crowdsale, default_accounts.charlie, 100), Ok(()));
+			ink::env::test::set_account_balance::<Environment>(
+				ink::env::account_id::<Environment>(),
+				300,
+			);
+			ink::env::test::set_value_transferred::<Environment>(0);
+
+			assert_eq!(crowdsale.refund(), Err(CrowdsaleError::InvalidState(SaleState::Open)));
+			advance_to(3);
+			assert_eq!(crowdsale.state(), SaleState::Failed);
+			assert_eq!(crowdsale.claim(), Err(CrowdsaleError::InvalidState(SaleState::Failed)));
+
+			ink::env::test::set_caller::<Environment>(default_accounts.bob);
+			let balance = ink::env::test::get_account_balance::<Environment>(default_accounts.bob)
+				.expect("bob has a balance");
+			assert_eq!(crowdsale.refund(), Ok(200));
+			assert_eq!(crowdsale.refund(), Err(CrowdsaleError::NothingToClaim));
+			assert_eq!(
+				ink::env::test::get_account_balance::<Environment>(default_accounts.bob),
+				Ok(balance + 200)
+			);
+			assert_eq!(crowdsale.withdraw(), Err(CrowdsaleError::NotOwner));
+		}
+	}
+
+	#[cfg(all(test, feature = "e2e-tests"))]
+	mod e2e_tests {
+		use ink_e2e::build_message;
+		use psp22_mock::Psp22MockRef;
+
+		use super::*;
+
+		type E2EResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+		#[ink_e2e::test(additional_contracts = "../psp22_mock/Cargo.toml")]
+		async fn test_successful_sale_mints_to_contributors(
+			mut client: ink_e2e::Client<C, E>,
+		) -> E2EResult<()> {
+			let bob = ink_e2e::account_id(ink_e2e::AccountKeyring::Bob);
+
+			let token = client
+				.instantiate("psp22_mock", &ink_e2e::alice(), Psp22MockRef::new(0), 0, None)
+				.await
+				.expect("instantiate token failed")
+				.account_id;
+			let constructor =
+				CrowdsaleRef::new(token.clone(), Delivery::Mint, 10, 1_000, 1_000, 0, 1_000_000);
+			let crowdsale = client
+				.instantiate("crowdsale", &ink_e2e::alice(), constructor, 0, None)
+				.await
+				.expect("instantiate crowdsale failed")
+				.account_id;
+
+			let contribute =
+				build_message::<CrowdsaleRef>(crowdsale.clone()).call(|sale| sale.contribute());
+			client
+				.call(&ink_e2e::bob(), contribute, 1_000, None)
+				.await
+				.expect("contribute failed")
+				.return_value()
+				.expect("contribution rejected");
+
+			let claim = build_message::<CrowdsaleRef>(crowdsale.clone()).call(|sale| sale.claim());
+			let tokens = client
+				.call(&ink_e2e::bob(), claim, 0, None)
+				.await
+				.expect("claim failed")
+				.return_value()
+				.expect("claim rejected");
+			assert_eq!(tokens, 10_000);
+
+			let balance = build_message::<Psp22MockRef>(token.clone())
+				.call(|token| token.balance_of(bob.clone()));
+			let balance =
+				client.call_dry_run(&ink_e2e::bob(), &balance, 0, None).await.return_value();
+			assert_eq!(balance, 10_000);
+
+			let withdraw =
+				build_message::<CrowdsaleRef>(crowdsale.clone()).call(|sale| sale.withdraw());
+			client
+				.call(&ink_e2e::alice(), withdraw, 0, None)
+				.await
+				.expect("withdraw failed")
+				.return_value()
+				.expect("withdraw rejected");
+
+			Ok(())
+		}
+	}
+}
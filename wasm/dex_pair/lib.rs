@@ -0,0 +1,690 @@
+#![cfg_attr(not(feature = "std"), no_std, no_main)]
+
+pub use self::dex_pair::{DexPair, DexPairRef, PairError};
+
+/// A constant-product pool between two PSP22 tokens, e.g. two of the resource tokens.
+///
+/// Liquidity providers deposit both tokens in the ratio of the reserves and get pool shares in
+/// return, which are PSP22 tokens themselves. Swaps keep the product of the reserves constant
+/// and pay a 0.3% fee into the pool, so shares grow in value with every trade.
+///
+/// The pair pulls tokens with `transfer_from`, so callers approve it first. It counts what
+/// actually arrived, which makes it work with tokens that charge a fee on transfers.
+#[ink::contract]
+mod dex_pair {
+	use ink::{codegen::EmitEvent, prelude::vec::Vec, reflect::ContractEventBase};
+	use math::BPS_DENOMINATOR;
+	use psp22_traits::{notify_receiver, PSP22Data, PSP22Error, PSP22};
+
+	/// The fee taken from every amount swapped in, in basis points
+	pub const SWAP_FEE_BPS: u16 = 30;
+	/// Shares minted with the first deposit that are held by the pair itself and never
+	/// redeemed, so that the pool can never be drained to the point where one share is worth
+	/// more than anybody can deposit
+	pub const MINIMUM_LIQUIDITY: Balance = 1_000;
+
+	#[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+	#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+	pub enum PairError {
+		/// Both tokens of a pair must differ
+		IdenticalTokens,
+		/// The token is not one of the pair's tokens
+		UnknownToken,
+		/// The deposit, withdrawal or swap is too small to move anything
+		InsufficientAmount,
+		/// The pool doesn't hold enough of the tokens
+		InsufficientLiquidity,
+		/// The caller would get less than the minimum they asked for
+		TooMuchSlippage,
+		/// The amounts are too large to compute with
+		Overflow,
+		/// A token transfer failed
+		PSP22(PSP22Error),
+	}
+
+	impl From<PSP22Error> for PairError {
+		fn from(e: PSP22Error) -> Self {
+			PairError::PSP22(e)
+		}
+	}
+
+	pub type Result<T> = core::result::Result<T, PairError>;
+
+	type Event = <DexPair as ContractEventBase>::Type;
+
+	#[ink(event)]
+	pub struct Approval {
+		#[ink(topic)]
+		owner: AccountId,
+		#[ink(topic)]
+		spender: AccountId,
+		amount: Balance,
+	}
+
+	#[ink(event)]
+	pub struct Transfer {
+		#[ink(topic)]
+		from: AccountId,
+		#[ink(topic)]
+		to: AccountId,
+		value: Balance,
+	}
+
+	/// `provider` deposited `amount_0` and `amount_1` of the tokens for `shares`
+	#[ink(event)]
+	pub struct LiquidityAdded {
+		#[ink(topic)]
+		provider: AccountId,
+		amount_0: Balance,
+		amount_1: Balance,
+		shares: Balance,
+	}
+
+	/// `provider` redeemed `shares` for `amount_0` and `amount_1` of the tokens
+	#[ink(event)]
+	pub struct LiquidityRemoved {
+		#[ink(topic)]
+		provider: AccountId,
+		amount_0: Balance,
+		amount_1: Balance,
+		shares: Balance,
+	}
+
+	#[ink(event)]
+	pub struct Swapped {
+		#[ink(topic)]
+		caller: AccountId,
+		#[ink(topic)]
+		token_in: AccountId,
+		amount_in: Balance,
+		amount_out: Balance,
+	}
+
+	#[ink(storage)]
+	pub struct DexPair {
+		/// The pool shares
+		shares: PSP22Data,
+		token_0: AccountId,
+		token_1: AccountId,
+		/// How much of each token the pool holds. Tokens that reach the pair other than through
+		/// its messages are not counted.
+		reserve_0: Balance,
+		reserve_1: Balance,
+	}
+
+	impl DexPair {
+		#[ink(constructor)]
+		pub fn new(token_0: AccountId, token_1: AccountId) -> Result<Self> {
+			if token_0 == token_1 {
+				return Err(PairError::IdenticalTokens);
+			}
+
+			Ok(Self { shares: PSP22Data::default(), token_0, token_1, reserve_0: 0, reserve_1: 0 })
+		}
+
+		/// Returns the two tokens of the pair
+		#[ink(message)]
+		pub fn tokens(&self) -> (AccountId, AccountId) {
+			(self.token_0, self.token_1)
+		}
+
+		/// Returns how much of `token_0` and `token_1` the pool holds
+		#[ink(message)]
+		pub fn reserves(&self) -> (Balance, Balance) {
+			(self.reserve_0, self.reserve_1)
+		}
+
+		/// Returns how much of the other token swapping `amount_in` of `token_in` pays out
+		#[ink(message)]
+		pub fn quote(&self, token_in: AccountId, amount_in: Balance) -> Result<Balance> {
+			let (reserve_in, reserve_out) = self.reserves_for(token_in)?;
+			amount_out(amount_in, reserve_in, reserve_out)
+		}
+
+		/// Deposits up to `amount_0` and `amount_1` of the tokens, as much of both as keeps the
+		/// ratio of the reserves, and returns the shares minted for the caller. The first
+		/// deposit sets the ratio.
+		#[ink(message)]
+		pub fn add_liquidity(
+			&mut self,
+			amount_0: Balance,
+			amount_1: Balance,
+			min_shares: Balance,
+		) -> Result<Balance> {
+			let caller = self.env().caller();
+			let (amount_0, amount_1) = self.deposit_amounts(amount_0, amount_1)?;
+
+			let received_0 = self.pull(self.token_0, caller, amount_0)?;
+			let received_1 = self.pull(self.token_1, caller, amount_1)?;
+
+			let total_shares = self.shares.total_supply();
+			let shares =
+				shares_for(received_0, received_1, self.reserve_0, self.reserve_1, total_shares)?;
+			if shares < min_shares {
+				return Err(PairError::TooMuchSlippage);
+			}
+
+			if total_shares == 0 {
+				let events = self.shares.mint(self.env().account_id(), MINIMUM_LIQUIDITY)?;
+				self.emit_events(events);
+			}
+			let events = self.shares.mint(caller, shares)?;
+			self.emit_events(events);
+			self.reserve_0 += received_0;
+			self.reserve_1 += received_1;
+
+			Self::emit_event(
+				self.env(),
+				Event::LiquidityAdded(LiquidityAdded {
+					provider: caller,
+					amount_0: received_0,
+					amount_1: received_1,
+					shares,
+				}),
+			);
+
+			Ok(shares)
+		}
+
+		/// Redeems `shares` of the caller for their part of both reserves, and returns the
+		/// amounts paid out
+		#[ink(message)]
+		pub fn remove_liquidity(
+			&mut self,
+			shares: Balance,
+			min_amount_0: Balance,
+			min_amount_1: Balance,
+		) -> Result<(Balance, Balance)> {
+			let caller = self.env().caller();
+			let total_shares = self.shares.total_supply();
+			if total_shares == 0 {
+				return Err(PairError::InsufficientLiquidity);
+			}
+
+			let amount_0 =
+				math::mul_div(shares, self.reserve_0, total_shares).ok_or(PairError::Overflow)?;
+			let amount_1 =
+				math::mul_div(shares, self.reserve_1, total_shares).ok_or(PairError::Overflow)?;
+			if amount_0 == 0 || amount_1 == 0 {
+				return Err(PairError::InsufficientAmount);
+			}
+			if amount_0 < min_amount_0 || amount_1 < min_amount_1 {
+				return Err(PairError::TooMuchSlippage);
+			}
+
+			let events = self.shares.burn(caller, shares)?;
+			self.emit_events(events);
+			self.reserve_0 -= amount_0;
+			self.reserve_1 -= amount_1;
+
+			self.push(self.token_0, caller, amount_0)?;
+			self.push(self.token_1, caller, amount_1)?;
+
+			Self::emit_event(
+				self.env(),
+				Event::LiquidityRemoved(LiquidityRemoved {
+					provider: caller,
+					amount_0,
+					amount_1,
+					shares,
+				}),
+			);
+
+			Ok((amount_0, amount_1))
+		}
+
+		/// Swaps `amount_in` of `token_in` for the other token and returns the amount paid out
+		#[ink(message)]
+		pub fn swap(
+			&mut self,
+			token_in: AccountId,
+			amount_in: Balance,
+			min_amount_out: Balance,
+		) -> Result<Balance> {
+			let caller = self.env().caller();
+			let (reserve_in, reserve_out) = self.reserves_for(token_in)?;
+			if reserve_in == 0 || reserve_out == 0 {
+				return Err(PairError::InsufficientLiquidity);
+			}
+
+			let received = self.pull(token_in, caller, amount_in)?;
+			let amount_out = amount_out(received, reserve_in, reserve_out)?;
+			if amount_out == 0 {
+				return Err(PairError::InsufficientAmount);
+			}
+			if amount_out < min_amount_out {
+				return Err(PairError::TooMuchSlippage);
+			}
+
+			let token_out = if token_in == self.token_0 {
+				self.reserve_0 += received;
+				self.reserve_1 -= amount_out;
+				self.token_1
+			} else {
+				self.reserve_1 += received;
+				self.reserve_0 -= amount_out;
+				self.token_0
+			};
+			self.push(token_out, caller, amount_out)?;
+
+			Self::emit_event(
+				self.env(),
+				Event::Swapped(Swapped { caller, token_in, amount_in: received, amount_out }),
+			);
+
+			Ok(amount_out)
+		}
+
+		/// The reserves of `token_in` and of the other token
+		fn reserves_for(&self, token_in: AccountId) -> Result<(Balance, Balance)> {
+			if token_in == self.token_0 {
+				Ok((self.reserve_0, self.reserve_1))
+			} else if token_in == self.token_1 {
+				Ok((self.reserve_1, self.reserve_0))
+			} else {
+				Err(PairError::UnknownToken)
+			}
+		}
+
+		/// The largest amounts up to `amount_0` and `amount_1` in the ratio of the reserves
+		fn deposit_amounts(
+			&self,
+			amount_0: Balance,
+			amount_1: Balance,
+		) -> Result<(Balance, Balance)> {
+			if self.reserve_0 == 0 || self.reserve_1 == 0 {
+				return Ok((amount_0, amount_1));
+			}
+
+			let matching_1 = math::mul_div(amount_0, self.reserve_1, self.reserve_0)
+				.ok_or(PairError::Overflow)?;
+			if matching_1 <= amount_1 {
+				return Ok((amount_0, matching_1));
+			}
+			let matching_0 = math::mul_div(amount_1, self.reserve_0, self.reserve_1)
+				.ok_or(PairError::Overflow)?;
+			Ok((matching_0, amount_1))
+		}
+
+		/// Moves `amount` of `token` from `from` to the pair and returns how much arrived
+		fn pull(&self, token: AccountId, from: AccountId, amount: Balance) -> Result<Balance> {
+			let pair = self.env().account_id();
+			let mut psp22: ink::contract_ref!(PSP22) = token.into();
+			let before = psp22.balance_of(pair);
+			psp22.transfer_from(from, pair, amount, Vec::new())?;
+			Ok(psp22.balance_of(pair).saturating_sub(before))
+		}
+
+		/// Moves `amount` of `token` from the pair to `to`. Nothing may call back into the pair
+		/// meanwhile, the reserves are only saved once the message returns.
+		fn push(&self, token: AccountId, to: AccountId, amount: Balance) -> Result<()> {
+			let mut psp22: ink::contract_ref!(PSP22) = token.into();
+			psp22.transfer(to, amount, Vec::new())?;
+			Ok(())
+		}
+
+		psp22_traits::impl_emit_events!();
+
+		fn emit_event<EE>(emitter: EE, event: Event)
+		where
+			EE: EmitEvent<Self>,
+		{
+			emitter.emit_event(event);
+		}
+	}
+
+	/// What swapping `amount_in` into a pool with the given reserves pays out, after the fee.
+	/// Rounds down, so the product of the reserves never shrinks.
+	pub fn amount_out(
+		amount_in: Balance,
+		reserve_in: Balance,
+		reserve_out: Balance,
+	) -> Result<Balance> {
+		let fee_factor = Balance::from(BPS_DENOMINATOR - SWAP_FEE_BPS);
+		let in_after_fee = math::mul_div(amount_in, fee_factor, Balance::from(BPS_DENOMINATOR))
+			.ok_or(PairError::Overflow)?;
+		let reserve_in = reserve_in.checked_add(in_after_fee).ok_or(PairError::Overflow)?;
+		math::mul_div(in_after_fee, reserve_out, reserve_in).ok_or(PairError::InsufficientLiquidity)
+	}
+
+	/// The shares a deposit of `amount_0` and `amount_1` is worth. The first deposit gets the
+	/// geometric mean of the amounts less `MINIMUM_LIQUIDITY`, later ones the smaller of their
+	/// parts of each reserve.
+	pub fn shares_for(
+		amount_0: Balance,
+		amount_1: Balance,
+		reserve_0: Balance,
+		reserve_1: Balance,
+		total_shares: Balance,
+	) -> Result<Balance> {
+		let shares = if total_shares == 0 {
+			let product = amount_0.checked_mul(amount_1).ok_or(PairError::Overflow)?;
+			math::sqrt(product).saturating_sub(MINIMUM_LIQUIDITY)
+		} else {
+			let shares_0 =
+				math::mul_div(amount_0, total_shares, reserve_0).ok_or(PairError::Overflow)?;
+			let shares_1 =
+				math::mul_div(amount_1, total_shares, reserve_1).ok_or(PairError::Overflow)?;
+			shares_0.min(shares_1)
+		};
+
+		if shares == 0 {
+			return Err(PairError::InsufficientAmount);
+		}
+		Ok(shares)
+	}
+
+	impl PSP22 for DexPair {
+		#[ink(message)]
+		fn total_supply(&self) -> Balance {
+			self.shares.total_supply()
+		}
+
+		#[ink(message)]
+		fn balance_of(&self, owner: AccountId) -> Balance {
+			self.shares.balance_of(owner)
+		}
+
+		#[ink(message)]
+		fn allowance(&self, owner: AccountId, spender: AccountId) -> Balance {
+			self.shares.allowance(owner, spender)
+		}
+
+		#[ink(message)]
+		fn approve(
+			&mut self,
+			spender: AccountId,
+			amount: Balance,
+		) -> core::result::Result<(), PSP22Error> {
+			let events = self.shares.approve(self.env().caller(), spender, amount)?;
+			self.emit_events(events);
+			Ok(())
+		}
+
+		#[ink(message)]
+		fn increase_allowance(
+			&mut self,
+			spender: AccountId,
+			by: Balance,
+		) -> core::result::Result<(), PSP22Error> {
+			let events = self.shares.increase_allowance(self.env().caller(), spender, by)?;
+			self.emit_events(events);
+			Ok(())
+		}
+
+		#[ink(message)]
+		fn decrease_allowance(
+			&mut self,
+			spender: AccountId,
+			by: Balance,
+		) -> core::result::Result<(), PSP22Error> {
+			let events = self.shares.decrease_allowance(self.env().caller(), spender, by)?;
+			self.emit_events(events);
+			Ok(())
+		}
+
+		#[ink(message)]
+		fn transfer(
+			&mut self,
+			to: AccountId,
+			value: Balance,
+			data: Vec<u8>,
+		) -> core::result::Result<(), PSP22Error> {
+			let caller = self.env().caller();
+			let events = self.shares.transfer(caller, to, value)?;
+			self.emit_events(events);
+			notify_receiver(self.env().caller(), caller, to, value, data)
+		}
+
+		#[ink(message)]
+		fn transfer_from(
+			&mut self,
+			from: AccountId,
+			to: AccountId,
+			value: Balance,
+			data: Vec<u8>,
+		) -> core::result::Result<(), PSP22Error> {
+			let events = self.shares.transfer_from(self.env().caller(), from, to, value)?;
+			self.emit_events(events);
+			notify_receiver(self.env().caller(), from, to, value, data)
+		}
+	}
+
+	#[cfg(test)]
+	mod tests {
+		use super::*;
+
+		fn default_accounts() -> ink::env::test::DefaultAccounts<Environment> {
+			ink::env::test::default_accounts::<Environment>()
+		}
+
+		#[test]
+		fn test_swaps_keep_the_product_and_pay_the_fee() {
+			// 0.3% of 1000 stays in the pool, 997 * 2000 / 1997 is paid out
+			assert_eq!(amount_out(1_000, 1_000, 2_000), Ok(998));
+			assert_eq!(amount_out(0, 1_000, 2_000), Ok(0));
+			assert_eq!(amount_out(u128::MAX, u128::MAX, 1), Err(PairError::Overflow));
+
+			for (amount_in, reserve_in, reserve_out) in [
+				(1, 10, 10),
+				(10, 1_000_000, 3),
+				(123_456, 7_890, 1_000_000),
+				(u64::MAX.into(), 1, 1),
+			] {
+				let out = amount_out(amount_in, reserve_in, reserve_out).unwrap();
+				assert!(out < reserve_out);
+				assert!((reserve_in + amount_in) * (reserve_out - out) >= reserve_in * reserve_out);
+			}
+		}
+
+		#[test]
+		fn test_shares_follow_the_smaller_part_of_the_reserves() {
+			assert_eq!(shares_for(4_000, 9_000, 0, 0, 0), Ok(6_000 - MINIMUM_LIQUIDITY));
+			assert_eq!(shares_for(1_000, 1_000, 0, 0, 0), Err(PairError::InsufficientAmount));
+			assert_eq!(shares_for(u128::MAX, 2, 0, 0, 0), Err(PairError::Overflow));
+
+			assert_eq!(shares_for(100, 300, 1_000, 2_000, 500), Ok(50));
+			assert_eq!(shares_for(1, 1, 1_000, 1_000, 500), Err(PairError::InsufficientAmount));
+		}
+
+		#[ink::test]
+		fn test_messages_reject_unknown_tokens_and_empty_pools() {
+			let default_accounts = default_accounts();
+			assert_eq!(
+				DexPair::new(default_accounts.django, default_accounts.django).err(),
+				Some(PairError::IdenticalTokens)
+			);
+			let mut pair = DexPair::new(default_accounts.django, default_accounts.eve).unwrap();
+
+			assert_eq!(pair.quote(default_accounts.frank, 10), Err(PairError::UnknownToken));
+			assert_eq!(pair.swap(default_accounts.frank, 10, 0), Err(PairError::UnknownToken));
+			assert_eq!(
+				pair.swap(default_accounts.django, 10, 0),
+				Err(PairError::InsufficientLiquidity)
+			);
+			assert_eq!(pair.remove_liquidity(10, 0, 0), Err(PairError::InsufficientLiquidity));
+		}
+	}
+
+	#[cfg(all(test, feature = "e2e-tests"))]
+	mod e2e_tests {
+		use ink_e2e::build_message;
+		use psp22_mock::{Psp22MockRef, Reentry};
+
+		use super::*;
+
+		type E2EResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+		#[ink_e2e::test(additional_contracts = "../psp22_mock/Cargo.toml")]
+		async fn test_add_swap_and_remove_liquidity(
+			mut client: ink_e2e::Client<C, E>,
+		) -> E2EResult<()> {
+			let mut tokens = Vec::new();
+			for _ in 0..2 {
+				let token = client
+					.instantiate(
+						"psp22_mock",
+						&ink_e2e::alice(),
+						Psp22MockRef::new(1_000_000),
+						0,
+						None,
+					)
+					.await
+					.expect("instantiate token failed")
+					.account_id;
+				tokens.push(token);
+			}
+			let (token_0, token_1) = (tokens[0].clone(), tokens[1].clone());
+
+			let pair = client
+				.instantiate(
+					"dex_pair",
+					&ink_e2e::alice(),
+					DexPairRef::new(token_0.clone(), token_1.clone()),
+					0,
+					None,
+				)
+				.await
+				.expect("instantiate pair failed")
+				.account_id;
+
+			for token in &tokens {
+				let approve = build_message::<Psp22MockRef>(token.clone())
+					.call(|token| token.approve(pair.clone(), 1_000_000));
+				client.call(&ink_e2e::alice(), approve, 0, None).await.expect("approve failed");
+			}
+
+			let add = build_message::<DexPairRef>(pair.clone())
+				.call(|pair| pair.add_liquidity(100_000, 400_000, 0));
+			let shares = client
+				.call(&ink_e2e::alice(), add, 0, None)
+				.await
+				.expect("add_liquidity failed")
+				.return_value();
+			assert_eq!(shares, Ok(200_000 - MINIMUM_LIQUIDITY));
+
+			let swap = build_message::<DexPairRef>(pair.clone())
+				.call(|pair| pair.swap(token_0.clone(), 1_000, 3_900));
+			let paid_out = client
+				.call(&ink_e2e::alice(), swap, 0, None)
+				.await
+				.expect("swap failed")
+				.return_value();
+			assert_eq!(paid_out, amount_out(1_000, 100_000, 400_000));
+
+			let reserves = build_message::<DexPairRef>(pair.clone()).call(|pair| pair.reserves());
+			let reserves =
+				client.call_dry_run(&ink_e2e::alice(), &reserves, 0, None).await.return_value();
+			assert_eq!(reserves, (101_000, 400_000 - paid_out.unwrap()));
+
+			let remove = build_message::<DexPairRef>(pair.clone())
+				.call(|pair| pair.remove_liquidity(200_000 - MINIMUM_LIQUIDITY, 0, 0));
+			let removed = client
+				.call(&ink_e2e::alice(), remove, 0, None)
+				.await
+				.expect("remove_liquidity failed")
+				.return_value()
+				.unwrap();
+			// The locked shares keep a sliver of both reserves in the pool
+			assert!(removed.0 < reserves.0 && removed.1 < reserves.1);
+
+			Ok(())
+		}
+
+		#[ink_e2e::test(additional_contracts = "../psp22_mock/Cargo.toml")]
+		async fn test_tokens_cannot_call_back_into_the_pair(
+			mut client: ink_e2e::Client<C, E>,
+		) -> E2EResult<()> {
+			let mut tokens = Vec::new();
+			for _ in 0..2 {
+				let token = client
+					.instantiate(
+						"psp22_mock",
+						&ink_e2e::alice(),
+						Psp22MockRef::new(1_000_000),
+						0,
+						None,
+					)
+					.await
+					.expect("instantiate token failed")
+					.account_id;
+				tokens.push(token);
+			}
+			let (token_0, token_1) = (tokens[0].clone(), tokens[1].clone());
+
+			let pair = client
+				.instantiate(
+					"dex_pair",
+					&ink_e2e::alice(),
+					DexPairRef::new(token_0.clone(), token_1.clone()),
+					0,
+					None,
+				)
+				.await
+				.expect("instantiate pair failed")
+				.account_id;
+
+			for token in &tokens {
+				let approve = build_message::<Psp22MockRef>(token.clone())
+					.call(|token| token.approve(pair.clone(), 1_000_000));
+				client.call(&ink_e2e::alice(), approve, 0, None).await.expect("approve failed");
+			}
+			let add = build_message::<DexPairRef>(pair.clone())
+				.call(|pair| pair.add_liquidity(100_000, 400_000, 0));
+			client
+				.call(&ink_e2e::alice(), add, 0, None)
+				.await
+				.expect("add_liquidity failed");
+
+			// Hand the token some shares, so that it could redeem them if it got back in
+			let give = build_message::<DexPairRef>(pair.clone())
+				.call(|pair| pair.transfer(token_1.clone(), 1_000, Vec::new()));
+			client.call(&ink_e2e::alice(), give, 0, None).await.expect("transfer failed");
+
+			// The token pushes the output of the swap, and tries to redeem the shares meanwhile
+			let reentry = Reentry {
+				target: pair.clone(),
+				selector: ink::selector_bytes!("remove_liquidity"),
+				args: scale::Encode::encode(&(1_000 as Balance, 0 as Balance, 0 as Balance)),
+			};
+			let set_reentry = build_message::<Psp22MockRef>(token_1.clone())
+				.call(|token| token.set_reentry(Some(reentry.clone())));
+			client
+				.call(&ink_e2e::alice(), set_reentry, 0, None)
+				.await
+				.expect("set_reentry failed");
+			let swap = build_message::<DexPairRef>(pair.clone())
+				.call(|pair| pair.swap(token_0.clone(), 1_000, 0));
+			let _ = client.call(&ink_e2e::alice(), swap, 0, None).await;
+
+			// Whether the runtime fails the whole swap or only the call back, no shares were
+			// redeemed and the reserves are what the pair holds
+			let succeeded = build_message::<Psp22MockRef>(token_1.clone())
+				.call(|token| token.reentry_succeeded());
+			let succeeded =
+				client.call_dry_run(&ink_e2e::alice(), &succeeded, 0, None).await.return_value();
+			assert_ne!(succeeded, Some(true));
+
+			let shares = build_message::<DexPairRef>(pair.clone())
+				.call(|pair| pair.balance_of(token_1.clone()));
+			let shares =
+				client.call_dry_run(&ink_e2e::alice(), &shares, 0, None).await.return_value();
+			assert_eq!(shares, 1_000);
+
+			let reserves = build_message::<DexPairRef>(pair.clone()).call(|pair| pair.reserves());
+			let reserves =
+				client.call_dry_run(&ink_e2e::alice(), &reserves, 0, None).await.return_value();
+			let mut held = Vec::new();
+			for token in &tokens {
+				let balance = build_message::<Psp22MockRef>(token.clone())
+					.call(|token| token.balance_of(pair.clone()));
+				held.push(
+					client.call_dry_run(&ink_e2e::alice(), &balance, 0, None).await.return_value(),
+				);
+			}
+			assert_eq!(reserves, (held[0], held[1]));
+
+			Ok(())
+		}
+	}
+}
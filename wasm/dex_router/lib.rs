@@ -0,0 +1,418 @@
+#![cfg_attr(not(feature = "std"), no_std, no_main)]
+
+pub use self::dex_router::{DexRouter, DexRouterRef, RouterError};
+
+/// Swaps tokens along a path of `dex_pair` pools, e.g. Wood to Water to Food where there is no
+/// Wood/Food pool.
+///
+/// The owner registers the pairs. A swap pulls the input from the caller, who approves the
+/// router first, swaps it through one pair after the other and pays the output to the caller.
+#[ink::contract]
+mod dex_router {
+	use access_control::Ownable;
+	use dex_pair::{DexPairRef, PairError};
+	use ink::{
+		codegen::{EmitEvent, TraitCallBuilder},
+		env::{call::FromAccountId, CallFlags},
+		prelude::{format, vec::Vec},
+		reflect::ContractEventBase,
+		storage::Mapping,
+	};
+	use psp22_traits::{PSP22Error, PSP22Receiver, PSP22ReceiverError, PSP22};
+
+	#[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+	#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+	pub enum RouterError {
+		/// Caller is not the owner of the router
+		NotOwner,
+		/// A pair of the same two tokens is already registered
+		PairExists,
+		/// No pair is registered for two consecutive tokens of the path
+		PairNotFound,
+		/// A path needs at least two tokens
+		InvalidPath,
+		/// The deadline of the swap has passed
+		Expired,
+		/// The caller would get less than the minimum they asked for
+		TooMuchSlippage,
+		/// A pair refused a swap
+		Pair(PairError),
+		/// A token transfer failed
+		PSP22(PSP22Error),
+	}
+
+	impl From<PairError> for RouterError {
+		fn from(e: PairError) -> Self {
+			RouterError::Pair(e)
+		}
+	}
+
+	impl From<PSP22Error> for RouterError {
+		fn from(e: PSP22Error) -> Self {
+			RouterError::PSP22(e)
+		}
+	}
+
+	pub type Result<T> = core::result::Result<T, RouterError>;
+
+	type Event = <DexRouter as ContractEventBase>::Type;
+
+	#[ink(event)]
+	pub struct PairRegistered {
+		#[ink(topic)]
+		pair: AccountId,
+		token_0: AccountId,
+		token_1: AccountId,
+	}
+
+	#[ink(event)]
+	pub struct Swapped {
+		#[ink(topic)]
+		caller: AccountId,
+		#[ink(topic)]
+		token_in: AccountId,
+		#[ink(topic)]
+		token_out: AccountId,
+		amount_in: Balance,
+		amount_out: Balance,
+	}
+
+	#[ink(storage)]
+	pub struct DexRouter {
+		/// The account that registers pairs, defaults to the deployer
+		ownership: Ownable,
+		/// The pair of each two tokens, keyed by the tokens in ascending order
+		pairs: Mapping<(AccountId, AccountId), AccountId>,
+		/// Existence of a key means the account is a registered pair
+		registered: Mapping<AccountId, ()>,
+	}
+
+	impl DexRouter {
+		#[ink(constructor)]
+		pub fn new() -> Self {
+			Self {
+				ownership: Ownable::new(Self::env().caller()),
+				pairs: Mapping::default(),
+				registered: Mapping::default(),
+			}
+		}
+
+		/// Makes `pair` the pool swaps between its two tokens go through. Only the owner may
+		/// register pairs.
+		#[ink(message)]
+		pub fn register_pair(&mut self, pair: AccountId) -> Result<()> {
+			self.ownership
+				.ensure_owner(self.env().caller())
+				.map_err(|_| RouterError::NotOwner)?;
+
+			let pair_ref: DexPairRef = FromAccountId::from_account_id(pair);
+			let (token_0, token_1) = pair_ref.tokens();
+			let key = sorted(token_0, token_1);
+			if self.pairs.contains(key) {
+				return Err(RouterError::PairExists);
+			}
+			self.pairs.insert(key, &pair);
+			self.registered.insert(pair, &());
+
+			Self::emit_event(
+				self.env(),
+				Event::PairRegistered(PairRegistered { pair, token_0, token_1 }),
+			);
+
+			Ok(())
+		}
+
+		/// Returns the pair registered for `token_a` and `token_b`, if any
+		#[ink(message)]
+		pub fn pair_for(&self, token_a: AccountId, token_b: AccountId) -> Option<AccountId> {
+			self.pairs.get(sorted(token_a, token_b))
+		}
+
+		/// Returns what swapping `amount_in` of the first token of `path` pays out at every step
+		/// along it, the input included. Tokens that charge a fee on transfers pay out less.
+		#[ink(message)]
+		pub fn amounts_out(
+			&self,
+			path: Vec<AccountId>,
+			amount_in: Balance,
+		) -> Result<Vec<Balance>> {
+			let pairs = self.pairs_along(&path)?;
+
+			let mut amounts = Vec::with_capacity(path.len());
+			let mut amount = amount_in;
+			amounts.push(amount);
+			for (pair, token_in) in pairs.into_iter().zip(&path) {
+				let pair: DexPairRef = FromAccountId::from_account_id(pair);
+				amount = pair.quote(*token_in, amount)?;
+				amounts.push(amount);
+			}
+
+			Ok(amounts)
+		}
+
+		/// Swaps `amount_in` of the first token of `path` for the last one, through the pair of
+		/// every two consecutive tokens, and returns the amount paid out. Fails if that is less
+		/// than `min_amount_out`, or once the chain is past the `deadline` block.
+		#[ink(message)]
+		pub fn swap(
+			&mut self,
+			path: Vec<AccountId>,
+			amount_in: Balance,
+			min_amount_out: Balance,
+			deadline: BlockNumber,
+		) -> Result<Balance> {
+			if self.env().block_number() > deadline {
+				return Err(RouterError::Expired);
+			}
+			let pairs = self.pairs_along(&path)?;
+
+			let caller = self.env().caller();
+			let router = self.env().account_id();
+			let token_in = path[0];
+			let mut psp22: ink::contract_ref!(PSP22) = token_in.into();
+			let before = self.balance_of(token_in);
+			psp22.transfer_from(caller, router, amount_in, Vec::new())?;
+			// Count what arrived, tokens may charge a fee on transfers
+			let mut amount = self.balance_of(token_in).saturating_sub(before);
+
+			for (pair, hop) in pairs.into_iter().zip(path.windows(2)) {
+				let mut psp22: ink::contract_ref!(PSP22) = hop[0].into();
+				psp22.approve(pair, amount)?;
+
+				let mut pair: DexPairRef = FromAccountId::from_account_id(pair);
+				let before = self.balance_of(hop[1]);
+				// The pair pushes the output to the router, which takes it in `before_received`
+				pair.call_mut()
+					.swap(hop[0], amount, 0)
+					.call_flags(CallFlags::default().set_allow_reentry(true))
+					.invoke()?;
+				amount = self.balance_of(hop[1]).saturating_sub(before);
+			}
+
+			if amount < min_amount_out {
+				return Err(RouterError::TooMuchSlippage);
+			}
+			let token_out = path[path.len() - 1];
+			let mut psp22: ink::contract_ref!(PSP22) = token_out.into();
+			psp22.transfer(caller, amount, Vec::new())?;
+
+			Self::emit_event(
+				self.env(),
+				Event::Swapped(Swapped {
+					caller,
+					token_in,
+					token_out,
+					amount_in,
+					amount_out: amount,
+				}),
+			);
+
+			Ok(amount)
+		}
+
+		/// The pairs between every two consecutive tokens of `path`
+		fn pairs_along(&self, path: &[AccountId]) -> Result<Vec<AccountId>> {
+			if path.len() < 2 {
+				return Err(RouterError::InvalidPath);
+			}
+
+			path.windows(2)
+				.map(|hop| self.pair_for(hop[0], hop[1]).ok_or(RouterError::PairNotFound))
+				.collect()
+		}
+
+		/// How much of `token` the router holds
+		fn balance_of(&self, token: AccountId) -> Balance {
+			let psp22: ink::contract_ref!(PSP22) = token.into();
+			psp22.balance_of(self.env().account_id())
+		}
+
+		fn emit_event<EE>(emitter: EE, event: Event)
+		where
+			EE: EmitEvent<Self>,
+		{
+			emitter.emit_event(event);
+		}
+	}
+
+	impl Default for DexRouter {
+		fn default() -> Self {
+			Self::new()
+		}
+	}
+
+	impl PSP22Receiver for DexRouter {
+		/// Takes the output of the pairs mid-swap. Tokens from anywhere else would be stuck in
+		/// the router, so they are turned away.
+		#[ink(message)]
+		fn before_received(
+			&mut self,
+			operator: AccountId,
+			_from: AccountId,
+			_value: Balance,
+			_data: Vec<u8>,
+		) -> core::result::Result<(), PSP22ReceiverError> {
+			if !self.registered.contains(operator) {
+				return Err(PSP22ReceiverError::TransferRejected(format!(
+					"{:?}",
+					RouterError::PairNotFound
+				)));
+			}
+
+			Ok(())
+		}
+	}
+
+	/// The two tokens in ascending order, so either order finds the same pair
+	fn sorted(token_a: AccountId, token_b: AccountId) -> (AccountId, AccountId) {
+		if token_a <= token_b {
+			(token_a, token_b)
+		} else {
+			(token_b, token_a)
+		}
+	}
+
+	#[cfg(test)]
+	mod tests {
+		use super::*;
+
+		fn default_accounts() -> ink::env::test::DefaultAccounts<Environment> {
+			ink::env::test::default_accounts::<Environment>()
+		}
+
+		fn set_next_caller(caller: AccountId) {
+			ink::env::test::set_caller::<Environment>(caller);
+		}
+
+		#[ink::test]
+		fn test_swaps_check_deadline_and_path_before_moving_tokens() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut router = DexRouter::new();
+			let (wood, water) = (default_accounts.django, default_accounts.eve);
+
+			ink::env::test::advance_block::<Environment>();
+			assert_eq!(router.swap(vec![wood, water], 10, 0, 0), Err(RouterError::Expired));
+			assert_eq!(router.swap(vec![wood], 10, 0, 1), Err(RouterError::InvalidPath));
+			assert_eq!(router.swap(vec![wood, water], 10, 0, 1), Err(RouterError::PairNotFound));
+			assert_eq!(router.amounts_out(vec![wood, water], 10), Err(RouterError::PairNotFound));
+		}
+
+		#[ink::test]
+		fn test_only_the_owner_registers_pairs_and_only_pairs_pay_the_router() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut router = DexRouter::new();
+
+			set_next_caller(default_accounts.bob);
+			assert_eq!(router.register_pair(default_accounts.frank), Err(RouterError::NotOwner));
+			assert!(router
+				.before_received(default_accounts.bob, default_accounts.bob, 10, Vec::new())
+				.is_err());
+		}
+	}
+
+	#[cfg(all(test, feature = "e2e-tests"))]
+	mod e2e_tests {
+		use dex_pair::DexPairRef;
+		use ink_e2e::build_message;
+		use psp22_mock::Psp22MockRef;
+
+		use super::*;
+
+		type E2EResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+		#[ink_e2e::test(additional_contracts = "../dex_pair/Cargo.toml ../psp22_mock/Cargo.toml")]
+		async fn test_swaps_wood_for_food_through_water(
+			mut client: ink_e2e::Client<C, E>,
+		) -> E2EResult<()> {
+			let mut tokens = Vec::new();
+			for _ in 0..3 {
+				let token = client
+					.instantiate(
+						"psp22_mock",
+						&ink_e2e::alice(),
+						Psp22MockRef::new(1_000_000),
+						0,
+						None,
+					)
+					.await
+					.expect("instantiate token failed")
+					.account_id;
+				tokens.push(token);
+			}
+			let (wood, water, food) = (tokens[0].clone(), tokens[1].clone(), tokens[2].clone());
+
+			let router = client
+				.instantiate("dex_router", &ink_e2e::alice(), DexRouterRef::new(), 0, None)
+				.await
+				.expect("instantiate router failed")
+				.account_id;
+
+			for (token_0, token_1) in [(wood.clone(), water.clone()), (water.clone(), food.clone())]
+			{
+				let pair = client
+					.instantiate(
+						"dex_pair",
+						&ink_e2e::alice(),
+						DexPairRef::new(token_0.clone(), token_1.clone()),
+						0,
+						None,
+					)
+					.await
+					.expect("instantiate pair failed")
+					.account_id;
+
+				for token in [&token_0, &token_1] {
+					let approve = build_message::<Psp22MockRef>(token.clone())
+						.call(|token| token.approve(pair.clone(), 100_000));
+					client.call(&ink_e2e::alice(), approve, 0, None).await.expect("approve failed");
+				}
+				let add = build_message::<DexPairRef>(pair.clone())
+					.call(|pair| pair.add_liquidity(100_000, 100_000, 0));
+				client
+					.call(&ink_e2e::alice(), add, 0, None)
+					.await
+					.expect("add_liquidity failed");
+
+				let register = build_message::<DexRouterRef>(router.clone())
+					.call(|router| router.register_pair(pair.clone()));
+				client
+					.call(&ink_e2e::alice(), register, 0, None)
+					.await
+					.expect("register_pair failed");
+			}
+
+			let approve = build_message::<Psp22MockRef>(wood.clone())
+				.call(|token| token.approve(router.clone(), 1_000));
+			client.call(&ink_e2e::alice(), approve, 0, None).await.expect("approve failed");
+
+			let path = vec![wood.clone(), water.clone(), food.clone()];
+			let quote = build_message::<DexRouterRef>(router.clone())
+				.call(|router| router.amounts_out(path.clone(), 1_000));
+			let amounts = client
+				.call_dry_run(&ink_e2e::alice(), &quote, 0, None)
+				.await
+				.return_value()
+				.expect("quote failed");
+
+			let swap = build_message::<DexRouterRef>(router.clone())
+				.call(|router| router.swap(path.clone(), 1_000, amounts[2], BlockNumber::MAX));
+			let amount_out = client
+				.call(&ink_e2e::alice(), swap, 0, None)
+				.await
+				.expect("swap failed")
+				.return_value();
+			assert_eq!(amount_out, Ok(amounts[2]));
+
+			let balance = build_message::<Psp22MockRef>(food.clone()).call(|token| {
+				token.balance_of(ink_e2e::account_id(ink_e2e::AccountKeyring::Alice))
+			});
+			let balance =
+				client.call_dry_run(&ink_e2e::alice(), &balance, 0, None).await.return_value();
+			assert_eq!(balance, 1_000_000 - 100_000 + amounts[2]);
+
+			Ok(())
+		}
+	}
+}
@@ -0,0 +1,379 @@
+#![cfg_attr(not(feature = "std"), no_std, no_main)]
+
+pub use self::dutch_auction::{DutchAuction, DutchAuctionRef};
+
+/// Sells PSP22 amounts for the native currency at a price that falls every block.
+///
+/// A seller lists an amount of a token with a start price, a floor and how much the price drops
+/// per block. The first buyer to pay the current price gets the whole amount, and anything they
+/// paid above it is returned. This suits surplus resources of the resource market, which are
+/// PSP22 tokens, when their holder wants them gone at whatever price finds a buyer first. The
+/// seller may cancel an unsold sale and get the tokens back.
+#[ink::contract]
+mod dutch_auction {
+	use ink::{
+		codegen::EmitEvent, prelude::vec::Vec, reflect::ContractEventBase, storage::Mapping,
+	};
+	use psp22_traits::{PSP22Error, PSP22};
+
+	pub type SaleId = u32;
+
+	#[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+	#[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+	pub struct Sale {
+		pub seller: AccountId,
+		pub token: AccountId,
+		/// The amount of `token` sold
+		pub amount: Balance,
+		/// The price in the block the sale was created
+		pub start_price: Balance,
+		/// The price never drops below this
+		pub floor_price: Balance,
+		/// How much the price drops every block
+		pub decay_per_block: Balance,
+		/// The block in which the sale was created
+		pub start: BlockNumber,
+		/// Whether the sale was bought or cancelled
+		pub closed: bool,
+	}
+
+	impl Sale {
+		/// The price in `block`
+		pub fn price_at(&self, block: BlockNumber) -> Balance {
+			let elapsed = Balance::from(block.saturating_sub(self.start));
+			self.start_price
+				.saturating_sub(self.decay_per_block.saturating_mul(elapsed))
+				.max(self.floor_price)
+		}
+	}
+
+	#[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+	#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+	pub enum DutchAuctionError {
+		/// The amount must be positive and the floor may not be above the start price
+		InvalidSale,
+		SaleNotFound,
+		/// The sale was already bought or cancelled
+		SaleClosed,
+		/// Only the seller may cancel
+		NotSeller,
+		/// Less than the current price was transferred
+		PriceNotMet,
+		/// Paying out the native currency failed
+		NativeTransferFailed,
+		/// A token transfer failed
+		PSP22(PSP22Error),
+	}
+
+	impl From<PSP22Error> for DutchAuctionError {
+		fn from(e: PSP22Error) -> Self {
+			DutchAuctionError::PSP22(e)
+		}
+	}
+
+	pub type Result<T> = core::result::Result<T, DutchAuctionError>;
+
+	type Event = <DutchAuction as ContractEventBase>::Type;
+
+	#[ink(event)]
+	pub struct SaleCreated {
+		#[ink(topic)]
+		id: SaleId,
+		#[ink(topic)]
+		seller: AccountId,
+		#[ink(topic)]
+		token: AccountId,
+		amount: Balance,
+		start_price: Balance,
+		floor_price: Balance,
+	}
+
+	#[ink(event)]
+	pub struct Bought {
+		#[ink(topic)]
+		id: SaleId,
+		#[ink(topic)]
+		buyer: AccountId,
+		price: Balance,
+	}
+
+	#[ink(event)]
+	pub struct SaleCancelled {
+		#[ink(topic)]
+		id: SaleId,
+	}
+
+	#[ink(storage)]
+	#[derive(Default)]
+	pub struct DutchAuction {
+		sales: Mapping<SaleId, Sale>,
+		next_sale_id: SaleId,
+	}
+
+	impl DutchAuction {
+		#[ink(constructor)]
+		pub fn new() -> Self {
+			Self::default()
+		}
+
+		/// Takes `amount` of `token` from the caller, who approves the auction first, and offers
+		/// it at `start_price`, dropping by `decay_per_block` every block down to `floor_price`.
+		/// Returns the id of the sale.
+		#[ink(message)]
+		pub fn create_sale(
+			&mut self,
+			token: AccountId,
+			amount: Balance,
+			start_price: Balance,
+			floor_price: Balance,
+			decay_per_block: Balance,
+		) -> Result<SaleId> {
+			if amount == 0 || floor_price > start_price {
+				return Err(DutchAuctionError::InvalidSale);
+			}
+
+			let seller = self.env().caller();
+			let this = self.env().account_id();
+			let mut psp22: ink::contract_ref!(PSP22) = token.into();
+			let before = psp22.balance_of(this);
+			psp22.transfer_from(seller, this, amount, Vec::new())?;
+			// Sell what arrived, tokens may charge a fee on transfers
+			let amount = psp22.balance_of(this).saturating_sub(before);
+
+			let id = self.next_sale_id;
+			self.next_sale_id += 1;
+			self.sales.insert(
+				id,
+				&Sale {
+					seller,
+					token,
+					amount,
+					start_price,
+					floor_price,
+					decay_per_block,
+					start: self.env().block_number(),
+					closed: false,
+				},
+			);
+
+			Self::emit_event(
+				self.env(),
+				Event::SaleCreated(SaleCreated {
+					id,
+					seller,
+					token,
+					amount,
+					start_price,
+					floor_price,
+				}),
+			);
+
+			Ok(id)
+		}
+
+		/// Buys sale `id` at its current price out of what the caller transferred along with the
+		/// call, and returns the rest. Returns the price paid.
+		#[ink(message, payable)]
+		pub fn buy(&mut self, id: SaleId) -> Result<Balance> {
+			let mut sale = self.get_open_sale(id)?;
+			let price = sale.price_at(self.env().block_number());
+			let paid = self.env().transferred_value();
+			if paid < price {
+				return Err(DutchAuctionError::PriceNotMet);
+			}
+			sale.closed = true;
+			self.sales.insert(id, &sale);
+
+			let buyer = self.env().caller();
+			let mut psp22: ink::contract_ref!(PSP22) = sale.token.into();
+			psp22.transfer(buyer, sale.amount, Vec::new())?;
+			self.pay(sale.seller, price)?;
+			self.pay(buyer, paid - price)?;
+
+			Self::emit_event(self.env(), Event::Bought(Bought { id, buyer, price }));
+
+			Ok(price)
+		}
+
+		/// Closes unsold sale `id` and returns its tokens to the seller, the only one allowed to
+		/// cancel.
+		#[ink(message)]
+		pub fn cancel(&mut self, id: SaleId) -> Result<()> {
+			let mut sale = self.get_open_sale(id)?;
+			if self.env().caller() != sale.seller {
+				return Err(DutchAuctionError::NotSeller);
+			}
+			sale.closed = true;
+			self.sales.insert(id, &sale);
+
+			let mut psp22: ink::contract_ref!(PSP22) = sale.token.into();
+			psp22.transfer(sale.seller, sale.amount, Vec::new())?;
+
+			Self::emit_event(self.env(), Event::SaleCancelled(SaleCancelled { id }));
+
+			Ok(())
+		}
+
+		/// Returns sale `id`, if there is one
+		#[ink(message)]
+		pub fn sale(&self, id: SaleId) -> Option<Sale> {
+			self.sales.get(id)
+		}
+
+		/// Returns the price sale `id` can be bought at in this block, if it is still open
+		#[ink(message)]
+		pub fn current_price(&self, id: SaleId) -> Option<Balance> {
+			self.sales
+				.get(id)
+				.filter(|sale| !sale.closed)
+				.map(|sale| sale.price_at(self.env().block_number()))
+		}
+
+		fn get_open_sale(&self, id: SaleId) -> Result<Sale> {
+			let sale = self.sales.get(id).ok_or(DutchAuctionError::SaleNotFound)?;
+			if sale.closed {
+				return Err(DutchAuctionError::SaleClosed);
+			}
+			Ok(sale)
+		}
+
+		fn pay(&self, to: AccountId, amount: Balance) -> Result<()> {
+			if amount == 0 {
+				return Ok(());
+			}
+			self.env()
+				.transfer(to, amount)
+				.map_err(|_| DutchAuctionError::NativeTransferFailed)
+		}
+
+		fn emit_event<EE>(emitter: EE, event: Event)
+		where
+			EE: EmitEvent<Self>,
+		{
+			emitter.emit_event(event);
+		}
+	}
+
+	#[cfg(test)]
+	mod tests {
+		use super::*;
+
+		fn default_accounts() -> ink::env::test::DefaultAccounts<Environment> {
+			ink::env::test::default_accounts::<Environment>()
+		}
+
+		fn sale() -> Sale {
+			let default_accounts = default_accounts();
+			Sale {
+				seller: default_accounts.alice,
+				token: default_accounts.django,
+				amount: 1_000,
+				start_price: 500,
+				floor_price: 200,
+				decay_per_block: 40,
+				start: 10,
+				closed: false,
+			}
+		}
+
+		#[ink::test]
+		fn test_the_price_decays_down_to_the_floor() {
+			let sale = sale();
+
+			assert_eq!(sale.price_at(0), 500);
+			assert_eq!(sale.price_at(10), 500);
+			assert_eq!(sale.price_at(11), 460);
+			assert_eq!(sale.price_at(17), 220);
+			assert_eq!(sale.price_at(18), 200);
+			assert_eq!(sale.price_at(BlockNumber::MAX), 200);
+
+			let steep = Sale { decay_per_block: Balance::MAX, ..sale };
+			assert_eq!(steep.price_at(11), 200);
+		}
+
+		#[ink::test]
+		fn test_buyers_must_meet_the_current_price() {
+			let default_accounts = default_accounts();
+			let mut dutch_auction = DutchAuction::new();
+			assert_eq!(
+				dutch_auction.create_sale(default_accounts.django, 1_000, 100, 101, 1),
+				Err(DutchAuctionError::InvalidSale)
+			);
+			dutch_auction.sales.insert(0, &sale());
+			dutch_auction.sales.insert(1, &Sale { closed: true, ..sale() });
+
+			assert_eq!(dutch_auction.current_price(0), Some(500));
+			assert_eq!(dutch_auction.current_price(1), None);
+
+			ink::env::test::set_caller::<Environment>(default_accounts.bob);
+			ink::env::test::set_value_transferred::<Environment>(499);
+			assert_eq!(dutch_auction.buy(0), Err(DutchAuctionError::PriceNotMet));
+			assert_eq!(dutch_auction.buy(1), Err(DutchAuctionError::SaleClosed));
+			assert_eq!(dutch_auction.buy(2), Err(DutchAuctionError::SaleNotFound));
+			assert_eq!(dutch_auction.cancel(0), Err(DutchAuctionError::NotSeller));
+		}
+	}
+
+	#[cfg(all(test, feature = "e2e-tests"))]
+	mod e2e_tests {
+		use ink_e2e::build_message;
+		use psp22_mock::Psp22MockRef;
+
+		use super::*;
+
+		type E2EResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+		#[ink_e2e::test(additional_contracts = "../psp22_mock/Cargo.toml")]
+		async fn test_first_buyer_at_the_floor_wins(
+			mut client: ink_e2e::Client<C, E>,
+		) -> E2EResult<()> {
+			let bob = ink_e2e::account_id(ink_e2e::AccountKeyring::Bob);
+
+			let token = client
+				.instantiate("psp22_mock", &ink_e2e::alice(), Psp22MockRef::new(1_000), 0, None)
+				.await
+				.expect("instantiate token failed")
+				.account_id;
+			let auction = client
+				.instantiate("dutch_auction", &ink_e2e::alice(), DutchAuctionRef::new(), 0, None)
+				.await
+				.expect("instantiate auction failed")
+				.account_id;
+
+			let approve = build_message::<Psp22MockRef>(token.clone())
+				.call(|token| token.approve(auction.clone(), 1_000));
+			client.call(&ink_e2e::alice(), approve, 0, None).await.expect("approve failed");
+			// The price reaches the floor in the next block
+			let create = build_message::<DutchAuctionRef>(auction.clone())
+				.call(|auction| auction.create_sale(token.clone(), 1_000, 1_000, 100, 900));
+			let id = client
+				.call(&ink_e2e::alice(), create, 0, None)
+				.await
+				.expect("create_sale failed")
+				.return_value()
+				.expect("sale rejected");
+
+			let buy =
+				build_message::<DutchAuctionRef>(auction.clone()).call(|auction| auction.buy(id));
+			let price = client
+				.call(&ink_e2e::bob(), buy, 1_000, None)
+				.await
+				.expect("buy failed")
+				.return_value()
+				.expect("buy rejected");
+			assert_eq!(price, 100);
+
+			let buy =
+				build_message::<DutchAuctionRef>(auction.clone()).call(|auction| auction.buy(id));
+			assert!(client.call(&ink_e2e::charlie(), buy, 1_000, None).await.is_err());
+
+			let balance = build_message::<Psp22MockRef>(token.clone())
+				.call(|token| token.balance_of(bob.clone()));
+			let balance =
+				client.call_dry_run(&ink_e2e::bob(), &balance, 0, None).await.return_value();
+			assert_eq!(balance, 1_000);
+
+			Ok(())
+		}
+	}
+}
@@ -0,0 +1,542 @@
+#![cfg_attr(not(feature = "std"), no_std, no_main)]
+
+pub use self::english_auction::{EnglishAuction, EnglishAuctionRef};
+
+/// Auctions PSP22 amounts to the highest bidder in the native currency.
+///
+/// A seller lists an amount of a token, which the auction house holds until settlement, with a
+/// reserve price, a minimum raise and a last block for bids. Bids are escrowed with the house. A
+/// bidder who is outbid gets their bid credited and withdraws it whenever they like. Once bidding
+/// is over anyone may settle the auction, which pays the winning bid to the seller and credits the
+/// tokens to the winner, or back to the seller if nobody bid. Tokens credited are claimed whenever
+/// their owner likes, so a recipient that can't take them doesn't hold up the settlement.
+///
+/// Only fungible amounts can be listed, there is no PSP34 contract for NFTs in the workspace yet.
+#[ink::contract]
+mod english_auction {
+	use ink::{
+		codegen::EmitEvent, prelude::vec::Vec, reflect::ContractEventBase, storage::Mapping,
+	};
+	use psp22_traits::{PSP22Error, PSP22};
+
+	pub type AuctionId = u32;
+
+	#[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+	#[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+	pub struct Auction {
+		pub seller: AccountId,
+		pub token: AccountId,
+		/// The amount of `token` sold
+		pub amount: Balance,
+		/// The lowest first bid
+		pub reserve_price: Balance,
+		/// How much each bid must raise the previous one by
+		pub min_increment: Balance,
+		/// The last block in which bids are accepted
+		pub end: BlockNumber,
+		pub highest_bidder: Option<AccountId>,
+		pub highest_bid: Balance,
+		pub settled: bool,
+	}
+
+	impl Auction {
+		/// The lowest bid that is accepted now
+		pub fn min_bid(&self) -> Balance {
+			match self.highest_bidder {
+				Some(_) => self.highest_bid.saturating_add(self.min_increment.max(1)),
+				None => self.reserve_price,
+			}
+		}
+	}
+
+	#[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+	#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+	pub enum AuctionError {
+		/// The amount must be positive
+		InvalidAuction,
+		AuctionNotFound,
+		/// Bidding is over
+		AuctionEnded,
+		/// Bidding is still going on
+		AuctionNotEnded,
+		AlreadySettled,
+		/// Sellers may not bid on their own auctions
+		SellerCannotBid,
+		/// The bid is below the reserve price or doesn't raise the highest bid enough
+		BidTooLow,
+		/// The caller has no outbid funds to withdraw
+		NothingToWithdraw,
+		/// Paying out the native currency failed
+		NativeTransferFailed,
+		/// A token transfer failed
+		PSP22(PSP22Error),
+		/// The caller has no tokens of this kind to claim
+		NothingToClaim,
+	}
+
+	impl From<PSP22Error> for AuctionError {
+		fn from(e: PSP22Error) -> Self {
+			AuctionError::PSP22(e)
+		}
+	}
+
+	pub type Result<T> = core::result::Result<T, AuctionError>;
+
+	type Event = <EnglishAuction as ContractEventBase>::Type;
+
+	#[ink(event)]
+	pub struct AuctionCreated {
+		#[ink(topic)]
+		id: AuctionId,
+		#[ink(topic)]
+		seller: AccountId,
+		#[ink(topic)]
+		token: AccountId,
+		amount: Balance,
+		end: BlockNumber,
+	}
+
+	#[ink(event)]
+	pub struct BidPlaced {
+		#[ink(topic)]
+		id: AuctionId,
+		#[ink(topic)]
+		bidder: AccountId,
+		amount: Balance,
+	}
+
+	#[ink(event)]
+	pub struct Withdrawn {
+		#[ink(topic)]
+		bidder: AccountId,
+		amount: Balance,
+	}
+
+	#[ink(event)]
+	pub struct Claimed {
+		#[ink(topic)]
+		account: AccountId,
+		#[ink(topic)]
+		token: AccountId,
+		amount: Balance,
+	}
+
+	#[ink(event)]
+	pub struct AuctionSettled {
+		#[ink(topic)]
+		id: AuctionId,
+		winner: Option<AccountId>,
+		price: Balance,
+	}
+
+	#[ink(storage)]
+	#[derive(Default)]
+	pub struct EnglishAuction {
+		auctions: Mapping<AuctionId, Auction>,
+		next_auction_id: AuctionId,
+		/// Outbid funds not withdrawn yet
+		refunds: Mapping<AccountId, Balance>,
+		/// Tokens of settled auctions not claimed yet, by account and token
+		claimable: Mapping<(AccountId, AccountId), Balance>,
+	}
+
+	impl EnglishAuction {
+		#[ink(constructor)]
+		pub fn new() -> Self {
+			Self::default()
+		}
+
+		/// Takes `amount` of `token` from the caller, who approves the auction house first, and
+		/// auctions it for the next `duration` blocks. Returns the id of the auction.
+		#[ink(message)]
+		pub fn create_auction(
+			&mut self,
+			token: AccountId,
+			amount: Balance,
+			reserve_price: Balance,
+			min_increment: Balance,
+			duration: BlockNumber,
+		) -> Result<AuctionId> {
+			if amount == 0 {
+				return Err(AuctionError::InvalidAuction);
+			}
+
+			let seller = self.env().caller();
+			let this = self.env().account_id();
+			let mut psp22: ink::contract_ref!(PSP22) = token.into();
+			let before = psp22.balance_of(this);
+			psp22.transfer_from(seller, this, amount, Vec::new())?;
+			// Sell what arrived, tokens may charge a fee on transfers
+			let amount = psp22.balance_of(this).saturating_sub(before);
+			let end = self.env().block_number().saturating_add(duration);
+
+			let id = self.next_auction_id;
+			self.next_auction_id += 1;
+			self.auctions.insert(
+				id,
+				&Auction {
+					seller,
+					token,
+					amount,
+					reserve_price,
+					min_increment,
+					end,
+					highest_bidder: None,
+					highest_bid: 0,
+					settled: false,
+				},
+			);
+
+			Self::emit_event(
+				self.env(),
+				Event::AuctionCreated(AuctionCreated { id, seller, token, amount, end }),
+			);
+
+			Ok(id)
+		}
+
+		/// Bids what the caller transferred along with the call on auction `id`. The bid must
+		/// reach `min_bid`, and the bidder it beats gets their bid credited for withdrawal.
+		#[ink(message, payable)]
+		pub fn bid(&mut self, id: AuctionId) -> Result<()> {
+			let mut auction = self.get_auction(id)?;
+			if self.env().block_number() > auction.end {
+				return Err(AuctionError::AuctionEnded);
+			}
+			let bidder = self.env().caller();
+			if bidder == auction.seller {
+				return Err(AuctionError::SellerCannotBid);
+			}
+			let amount = self.env().transferred_value();
+			if amount < auction.min_bid() {
+				return Err(AuctionError::BidTooLow);
+			}
+
+			if let Some(outbid) = auction.highest_bidder {
+				self.refunds.insert(outbid, &(self.refund_of(outbid) + auction.highest_bid));
+			}
+			auction.highest_bidder = Some(bidder);
+			auction.highest_bid = amount;
+			self.auctions.insert(id, &auction);
+
+			Self::emit_event(self.env(), Event::BidPlaced(BidPlaced { id, bidder, amount }));
+
+			Ok(())
+		}
+
+		/// Pays the caller the bids they were outbid on, and returns the amount
+		#[ink(message)]
+		pub fn withdraw(&mut self) -> Result<Balance> {
+			let bidder = self.env().caller();
+			let amount = self.refunds.take(bidder).unwrap_or(0);
+			if amount == 0 {
+				return Err(AuctionError::NothingToWithdraw);
+			}
+
+			self.env()
+				.transfer(bidder, amount)
+				.map_err(|_| AuctionError::NativeTransferFailed)?;
+
+			Self::emit_event(self.env(), Event::Withdrawn(Withdrawn { bidder, amount }));
+
+			Ok(amount)
+		}
+
+		/// Pays the winning bid of auction `id` to the seller and credits the tokens to the highest
+		/// bidder, or back to the seller if nobody bid. Anyone may settle an auction once bidding
+		/// is over.
+		#[ink(message)]
+		pub fn settle(&mut self, id: AuctionId) -> Result<()> {
+			let mut auction = self.get_auction(id)?;
+			if self.env().block_number() <= auction.end {
+				return Err(AuctionError::AuctionNotEnded);
+			}
+			if auction.settled {
+				return Err(AuctionError::AlreadySettled);
+			}
+			auction.settled = true;
+			self.auctions.insert(id, &auction);
+
+			let recipient = auction.highest_bidder.unwrap_or(auction.seller);
+			let key = (recipient, auction.token);
+			self.claimable
+				.insert(key, &(self.claimable.get(key).unwrap_or(0) + auction.amount));
+			if auction.highest_bidder.is_some() {
+				self.env()
+					.transfer(auction.seller, auction.highest_bid)
+					.map_err(|_| AuctionError::NativeTransferFailed)?;
+			}
+
+			Self::emit_event(
+				self.env(),
+				Event::AuctionSettled(AuctionSettled {
+					id,
+					winner: auction.highest_bidder,
+					price: auction.highest_bid,
+				}),
+			);
+
+			Ok(())
+		}
+
+		/// Pays the caller the `token`s credited to them by settled auctions, and returns the
+		/// amount
+		#[ink(message)]
+		pub fn claim(&mut self, token: AccountId) -> Result<Balance> {
+			let account = self.env().caller();
+			let amount = self.claimable.take((account, token)).unwrap_or(0);
+			if amount == 0 {
+				return Err(AuctionError::NothingToClaim);
+			}
+
+			let mut psp22: ink::contract_ref!(PSP22) = token.into();
+			psp22.transfer(account, amount, Vec::new())?;
+
+			Self::emit_event(self.env(), Event::Claimed(Claimed { account, token, amount }));
+
+			Ok(amount)
+		}
+
+		/// Returns the `token`s `account` can claim from settled auctions
+		#[ink(message)]
+		pub fn claimable_of(&self, account: AccountId, token: AccountId) -> Balance {
+			self.claimable.get((account, token)).unwrap_or(0)
+		}
+
+		/// Returns auction `id`, if there is one
+		#[ink(message)]
+		pub fn auction(&self, id: AuctionId) -> Option<Auction> {
+			self.auctions.get(id)
+		}
+
+		/// Returns the outbid funds `bidder` can withdraw
+		#[ink(message)]
+		pub fn refund_of(&self, bidder: AccountId) -> Balance {
+			self.refunds.get(bidder).unwrap_or(0)
+		}
+
+		fn get_auction(&self, id: AuctionId) -> Result<Auction> {
+			self.auctions.get(id).ok_or(AuctionError::AuctionNotFound)
+		}
+
+		fn emit_event<EE>(emitter: EE, event: Event)
+		where
+			EE: EmitEvent<Self>,
+		{
+			emitter.emit_event(event);
+		}
+	}
+
+	#[cfg(test)]
+	mod tests {
+		use super::*;
+
+		fn default_accounts() -> ink::env::test::DefaultAccounts<Environment> {
+			ink::env::test::default_accounts::<Environment>()
+		}
+
+		fn bid(
+			english_auction: &mut EnglishAuction,
+			bidder: AccountId,
+			amount: Balance,
+		) -> Result<()> {
+			ink::env::test::set_caller::<Environment>(bidder);
+			ink::env::test::set_value_transferred::<Environment>(amount);
+			english_auction.bid(0)
+		}
+
+		fn english_auction() -> EnglishAuction {
+			let default_accounts = default_accounts();
+			let mut english_auction = EnglishAuction::new();
+			english_auction.auctions.insert(
+				0,
+				&Auction {
+					seller: default_accounts.alice,
+					token: default_accounts.django,
+					amount: 1_000,
+					reserve_price: 100,
+					min_increment: 10,
+					end: 3,
+					highest_bidder: None,
+					highest_bid: 0,
+					settled: false,
+				},
+			);
+			english_auction
+		}
+
+		#[ink::test]
+		fn test_bids_must_raise_the_highest_bid() {
+			let default_accounts = default_accounts();
+			let mut english_auction = english_auction();
+
+			assert_eq!(
+				bid(&mut english_auction, default_accounts.alice, 100),
+				Err(AuctionError::SellerCannotBid)
+			);
+			assert_eq!(
+				bid(&mut english_auction, default_accounts.bob, 99),
+				Err(AuctionError::BidTooLow)
+			);
+			assert_eq!(bid(&mut english_auction, default_accounts.bob, 100), Ok(()));
+			assert_eq!(
+				bid(&mut english_auction, default_accounts.charlie, 109),
+				Err(AuctionError::BidTooLow)
+			);
+			assert_eq!(bid(&mut english_auction, default_accounts.charlie, 110), Ok(()));
+			assert_eq!(bid(&mut english_auction, default_accounts.bob, 150), Ok(()));
+
+			let auction = english_auction.auction(0).expect("auction exists");
+			assert_eq!(auction.highest_bidder, Some(default_accounts.bob));
+			assert_eq!(auction.highest_bid, 150);
+			assert_eq!(english_auction.refund_of(default_accounts.bob), 100);
+			assert_eq!(english_auction.refund_of(default_accounts.charlie), 110);
+
+			for _ in 0..4 {
+				ink::env::test::advance_block::<Environment>();
+			}
+			assert_eq!(
+				bid(&mut english_auction, default_accounts.charlie, 200),
+				Err(AuctionError::AuctionEnded)
+			);
+		}
+
+		#[ink::test]
+		fn test_outbid_funds_are_withdrawn_once() {
+			let default_accounts = default_accounts();
+			let mut english_auction = english_auction();
+			assert_eq!(bid(&mut english_auction, default_accounts.bob, 100), Ok(()));
+			assert_eq!(bid(&mut english_auction, default_accounts.charlie, 200), Ok(()));
+			ink::env::test::set_value_transferred::<Environment>(0);
+			ink::env::test::set_account_balance::<Environment>(
+				ink::env::account_id::<Environment>(),
+				300,
+			);
+
+			assert_eq!(english_auction.withdraw(), Err(AuctionError::NothingToWithdraw));
+			assert_eq!(english_auction.settle(0), Err(AuctionError::AuctionNotEnded));
+
+			ink::env::test::set_caller::<Environment>(default_accounts.bob);
+			let balance = ink::env::test::get_account_balance::<Environment>(default_accounts.bob)
+				.expect("bob has a balance");
+			assert_eq!(english_auction.withdraw(), Ok(100));
+			assert_eq!(english_auction.withdraw(), Err(AuctionError::NothingToWithdraw));
+			assert_eq!(
+				ink::env::test::get_account_balance::<Environment>(default_accounts.bob),
+				Ok(balance + 100)
+			);
+		}
+
+		#[ink::test]
+		fn test_settlement_credits_the_tokens_for_claiming() {
+			let default_accounts = default_accounts();
+			let (alice, bob, django) =
+				(default_accounts.alice, default_accounts.bob, default_accounts.django);
+			let mut english_auction = english_auction();
+			assert_eq!(bid(&mut english_auction, bob, 150), Ok(()));
+			ink::env::test::set_value_transferred::<Environment>(0);
+			ink::env::test::set_account_balance::<Environment>(
+				ink::env::account_id::<Environment>(),
+				150,
+			);
+			for _ in 0..4 {
+				ink::env::test::advance_block::<Environment>();
+			}
+
+			let seller_balance = ink::env::test::get_account_balance::<Environment>(alice)
+				.expect("alice has a balance");
+			assert_eq!(english_auction.settle(0), Ok(()));
+			assert_eq!(english_auction.settle(0), Err(AuctionError::AlreadySettled));
+			assert_eq!(english_auction.claimable_of(bob, django), 1_000);
+			assert_eq!(english_auction.claimable_of(alice, django), 0);
+			assert_eq!(
+				ink::env::test::get_account_balance::<Environment>(alice),
+				Ok(seller_balance + 150)
+			);
+
+			ink::env::test::set_caller::<Environment>(alice);
+			assert_eq!(english_auction.claim(django), Err(AuctionError::NothingToClaim));
+		}
+	}
+
+	#[cfg(all(test, feature = "e2e-tests"))]
+	mod e2e_tests {
+		use ink_e2e::build_message;
+		use psp22_mock::Psp22MockRef;
+
+		use super::*;
+
+		type E2EResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+		#[ink_e2e::test(additional_contracts = "../psp22_mock/Cargo.toml")]
+		async fn test_settlement_pays_the_seller_and_the_winner(
+			mut client: ink_e2e::Client<C, E>,
+		) -> E2EResult<()> {
+			let bob = ink_e2e::account_id(ink_e2e::AccountKeyring::Bob);
+
+			let token = client
+				.instantiate("psp22_mock", &ink_e2e::alice(), Psp22MockRef::new(1_000), 0, None)
+				.await
+				.expect("instantiate token failed")
+				.account_id;
+			let house = client
+				.instantiate(
+					"english_auction",
+					&ink_e2e::alice(),
+					EnglishAuctionRef::new(),
+					0,
+					None,
+				)
+				.await
+				.expect("instantiate auction house failed")
+				.account_id;
+
+			let approve = build_message::<Psp22MockRef>(token.clone())
+				.call(|token| token.approve(house.clone(), 1_000));
+			client.call(&ink_e2e::alice(), approve, 0, None).await.expect("approve failed");
+			// Bidding ends in the block of the bid below
+			let create = build_message::<EnglishAuctionRef>(house.clone())
+				.call(|house| house.create_auction(token.clone(), 1_000, 100, 10, 1));
+			let id = client
+				.call(&ink_e2e::alice(), create, 0, None)
+				.await
+				.expect("create_auction failed")
+				.return_value()
+				.expect("auction rejected");
+
+			let bid = build_message::<EnglishAuctionRef>(house.clone()).call(|house| house.bid(id));
+			client
+				.call(&ink_e2e::bob(), bid, 100, None)
+				.await
+				.expect("bid failed")
+				.return_value()
+				.expect("bid rejected");
+
+			let settle =
+				build_message::<EnglishAuctionRef>(house.clone()).call(|house| house.settle(id));
+			client
+				.call(&ink_e2e::charlie(), settle, 0, None)
+				.await
+				.expect("settle failed")
+				.return_value()
+				.expect("settle rejected");
+
+			let claim = build_message::<EnglishAuctionRef>(house.clone())
+				.call(|house| house.claim(token.clone()));
+			client
+				.call(&ink_e2e::bob(), claim, 0, None)
+				.await
+				.expect("claim failed")
+				.return_value()
+				.expect("claim rejected");
+
+			let balance = build_message::<Psp22MockRef>(token.clone())
+				.call(|token| token.balance_of(bob.clone()));
+			let balance =
+				client.call_dry_run(&ink_e2e::bob(), &balance, 0, None).await.return_value();
+			assert_eq!(balance, 1_000);
+
+			Ok(())
+		}
+	}
+}
@@ -0,0 +1,494 @@
+#![cfg_attr(not(feature = "std"), no_std, no_main)]
+
+pub use self::escrow::{Escrow, EscrowRef};
+
+/// Holds a buyer's payment while a seller delivers off-chain.
+///
+/// The buyer locks native funds or a PSP22 token in a deal naming the seller and an arbiter. The
+/// funds go to the seller once buyer and seller both confirm the release, or back to the buyer once
+/// both confirm a refund. Until the deadline either of them may raise a dispute instead, which
+/// only the arbiter can settle. A deal nobody disputed by its deadline can be refunded to the
+/// buyer by anyone, so a seller whose buyer goes quiet has to dispute in time.
+#[ink::contract]
+mod escrow {
+	use ink::{
+		codegen::EmitEvent, prelude::vec::Vec, reflect::ContractEventBase, storage::Mapping,
+	};
+	use psp22_traits::{PSP22Error, PSP22};
+
+	pub type DealId = u32;
+
+	/// Where the funds of a deal go
+	#[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+	#[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+	pub enum Outcome {
+		/// To the seller
+		Release,
+		/// Back to the buyer
+		Refund,
+	}
+
+	#[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+	#[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+	pub enum DealState {
+		/// The funds are locked and the parties may confirm an outcome
+		Funded,
+		/// Only the arbiter may settle the deal
+		Disputed,
+		/// The deal was settled with the given outcome
+		Settled(Outcome),
+	}
+
+	#[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+	#[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+	pub struct Deal {
+		pub buyer: AccountId,
+		pub seller: AccountId,
+		/// Settles the deal when it is disputed
+		pub arbiter: AccountId,
+		/// The PSP22 token locked, or `None` for the native currency
+		pub token: Option<AccountId>,
+		pub amount: Balance,
+		/// The last block in which the deal can be disputed
+		pub deadline: BlockNumber,
+		pub state: DealState,
+		/// What the buyer confirmed, if anything
+		pub buyer_confirmed: Option<Outcome>,
+		/// What the seller confirmed, if anything
+		pub seller_confirmed: Option<Outcome>,
+	}
+
+	#[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+	#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+	pub enum EscrowError {
+		/// Buyer, seller and arbiter must be three different accounts, and the amount positive
+		InvalidDeal,
+		DealNotFound,
+		/// Only the buyer or the seller may do this
+		NotParty,
+		/// Only the arbiter may settle a dispute
+		NotArbiter,
+		/// The deal is in the wrong state for this
+		InvalidState(DealState),
+		/// The deal can no longer be disputed
+		DeadlinePassed,
+		/// The deal can still be disputed
+		DeadlineNotReached,
+		/// Native deals must transfer exactly `amount`, PSP22 deals nothing
+		UnexpectedTransferredValue,
+		/// Paying out the native currency failed
+		NativeTransferFailed,
+		/// A token transfer failed
+		PSP22(PSP22Error),
+	}
+
+	impl From<PSP22Error> for EscrowError {
+		fn from(e: PSP22Error) -> Self {
+			EscrowError::PSP22(e)
+		}
+	}
+
+	pub type Result<T> = core::result::Result<T, EscrowError>;
+
+	type Event = <Escrow as ContractEventBase>::Type;
+
+	#[ink(event)]
+	pub struct DealOpened {
+		#[ink(topic)]
+		id: DealId,
+		#[ink(topic)]
+		buyer: AccountId,
+		#[ink(topic)]
+		seller: AccountId,
+		amount: Balance,
+	}
+
+	#[ink(event)]
+	pub struct Confirmed {
+		#[ink(topic)]
+		id: DealId,
+		#[ink(topic)]
+		by: AccountId,
+		outcome: Outcome,
+	}
+
+	#[ink(event)]
+	pub struct DisputeRaised {
+		#[ink(topic)]
+		id: DealId,
+		#[ink(topic)]
+		by: AccountId,
+	}
+
+	#[ink(event)]
+	pub struct Settled {
+		#[ink(topic)]
+		id: DealId,
+		outcome: Outcome,
+	}
+
+	#[ink(storage)]
+	#[derive(Default)]
+	pub struct Escrow {
+		deals: Mapping<DealId, Deal>,
+		next_deal_id: DealId,
+	}
+
+	impl Escrow {
+		#[ink(constructor)]
+		pub fn new() -> Self {
+			Self::default()
+		}
+
+		/// Locks `amount` for `seller` and returns the id of the deal. Native funds are
+		/// transferred along with the call, a PSP22 `token` is taken from the caller, who approves
+		/// the escrow first. The deal can be disputed up to and including block `deadline`.
+		#[ink(message, payable)]
+		pub fn open(
+			&mut self,
+			seller: AccountId,
+			arbiter: AccountId,
+			token: Option<AccountId>,
+			amount: Balance,
+			deadline: BlockNumber,
+		) -> Result<DealId> {
+			let buyer = self.env().caller();
+			if amount == 0 || buyer == seller || arbiter == buyer || arbiter == seller {
+				return Err(EscrowError::InvalidDeal);
+			}
+			if self.env().block_number() > deadline {
+				return Err(EscrowError::DeadlinePassed);
+			}
+			let expected_value = if token.is_none() { amount } else { 0 };
+			if self.env().transferred_value() != expected_value {
+				return Err(EscrowError::UnexpectedTransferredValue);
+			}
+
+			let amount = match token {
+				Some(token) => {
+					let this = self.env().account_id();
+					let mut psp22: ink::contract_ref!(PSP22) = token.into();
+					let before = psp22.balance_of(this);
+					psp22.transfer_from(buyer, this, amount, Vec::new())?;
+					// Lock what arrived, tokens may charge a fee on transfers
+					psp22.balance_of(this).saturating_sub(before)
+				},
+				None => amount,
+			};
+
+			let id = self.next_deal_id;
+			self.next_deal_id += 1;
+			self.deals.insert(
+				id,
+				&Deal {
+					buyer,
+					seller,
+					arbiter,
+					token,
+					amount,
+					deadline,
+					state: DealState::Funded,
+					buyer_confirmed: None,
+					seller_confirmed: None,
+				},
+			);
+
+			Self::emit_event(
+				self.env(),
+				Event::DealOpened(DealOpened { id, buyer, seller, amount }),
+			);
+
+			Ok(id)
+		}
+
+		/// Records the outcome the caller, the buyer or the seller of deal `id`, agrees to. The
+		/// deal is settled once both confirmed the same outcome.
+		#[ink(message)]
+		pub fn confirm(&mut self, id: DealId, outcome: Outcome) -> Result<()> {
+			let mut deal = self.get_deal(id)?;
+			let caller = self.env().caller();
+			if caller != deal.buyer && caller != deal.seller {
+				return Err(EscrowError::NotParty);
+			}
+			if deal.state != DealState::Funded {
+				return Err(EscrowError::InvalidState(deal.state));
+			}
+
+			if caller == deal.buyer {
+				deal.buyer_confirmed = Some(outcome);
+			} else {
+				deal.seller_confirmed = Some(outcome);
+			}
+
+			Self::emit_event(self.env(), Event::Confirmed(Confirmed { id, by: caller, outcome }));
+
+			if deal.buyer_confirmed == Some(outcome) && deal.seller_confirmed == Some(outcome) {
+				return self.settle(id, deal, outcome);
+			}
+			self.deals.insert(id, &deal);
+
+			Ok(())
+		}
+
+		/// Hands deal `id` over to its arbiter. Only the buyer or the seller may dispute, and
+		/// only until the deadline.
+		#[ink(message)]
+		pub fn dispute(&mut self, id: DealId) -> Result<()> {
+			let mut deal = self.get_deal(id)?;
+			let caller = self.env().caller();
+			if caller != deal.buyer && caller != deal.seller {
+				return Err(EscrowError::NotParty);
+			}
+			if deal.state != DealState::Funded {
+				return Err(EscrowError::InvalidState(deal.state));
+			}
+			if self.env().block_number() > deal.deadline {
+				return Err(EscrowError::DeadlinePassed);
+			}
+
+			deal.state = DealState::Disputed;
+			self.deals.insert(id, &deal);
+
+			Self::emit_event(self.env(), Event::DisputeRaised(DisputeRaised { id, by: caller }));
+
+			Ok(())
+		}
+
+		/// Settles disputed deal `id` with `outcome`. Only the arbiter of the deal may resolve it.
+		#[ink(message)]
+		pub fn resolve(&mut self, id: DealId, outcome: Outcome) -> Result<()> {
+			let deal = self.get_deal(id)?;
+			if self.env().caller() != deal.arbiter {
+				return Err(EscrowError::NotArbiter);
+			}
+			if deal.state != DealState::Disputed {
+				return Err(EscrowError::InvalidState(deal.state));
+			}
+
+			self.settle(id, deal, outcome)
+		}
+
+		/// Refunds deal `id` to the buyer when nobody disputed or settled it by its deadline.
+		/// Anyone may call this.
+		#[ink(message)]
+		pub fn expire(&mut self, id: DealId) -> Result<()> {
+			let deal = self.get_deal(id)?;
+			if deal.state != DealState::Funded {
+				return Err(EscrowError::InvalidState(deal.state));
+			}
+			if self.env().block_number() <= deal.deadline {
+				return Err(EscrowError::DeadlineNotReached);
+			}
+
+			self.settle(id, deal, Outcome::Refund)
+		}
+
+		/// Returns deal `id`, if there is one
+		#[ink(message)]
+		pub fn deal(&self, id: DealId) -> Option<Deal> {
+			self.deals.get(id)
+		}
+
+		fn get_deal(&self, id: DealId) -> Result<Deal> {
+			self.deals.get(id).ok_or(EscrowError::DealNotFound)
+		}
+
+		/// Marks `deal` settled and pays its funds out according to `outcome`
+		fn settle(&mut self, id: DealId, mut deal: Deal, outcome: Outcome) -> Result<()> {
+			deal.state = DealState::Settled(outcome);
+			self.deals.insert(id, &deal);
+
+			let to = match outcome {
+				Outcome::Release => deal.seller,
+				Outcome::Refund => deal.buyer,
+			};
+			match deal.token {
+				Some(token) => {
+					let mut psp22: ink::contract_ref!(PSP22) = token.into();
+					psp22.transfer(to, deal.amount, Vec::new())?;
+				},
+				None => {
+					self.env()
+						.transfer(to, deal.amount)
+						.map_err(|_| EscrowError::NativeTransferFailed)?;
+				},
+			}
+
+			Self::emit_event(self.env(), Event::Settled(Settled { id, outcome }));
+
+			Ok(())
+		}
+
+		fn emit_event<EE>(emitter: EE, event: Event)
+		where
+			EE: EmitEvent<Self>,
+		{
+			emitter.emit_event(event);
+		}
+	}
+
+	#[cfg(test)]
+	mod tests {
+		use super::*;
+
+		fn default_accounts() -> ink::env::test::DefaultAccounts<Environment> {
+			ink::env::test::default_accounts::<Environment>()
+		}
+
+		fn balance_of(account: AccountId) -> Balance {
+			ink::env::test::get_account_balance::<Environment>(account).expect("account exists")
+		}
+
+		/// Opens a native deal of 100 from alice to bob, arbitrated by charlie
+		fn open_deal(escrow: &mut Escrow, deadline: BlockNumber) -> DealId {
+			let default_accounts = default_accounts();
+			ink::env::test::set_caller::<Environment>(default_accounts.alice);
+			ink::env::test::set_value_transferred::<Environment>(100);
+			let id = escrow
+				.open(default_accounts.bob, default_accounts.charlie, None, 100, deadline)
+				.expect("valid deal");
+			ink::env::test::set_value_transferred::<Environment>(0);
+			ink::env::test::set_account_balance::<Environment>(
+				ink::env::account_id::<Environment>(),
+				1_000,
+			);
+			id
+		}
+
+		#[ink::test]
+		fn test_deals_settle_once_both_parties_agree() {
+			let default_accounts = default_accounts();
+			let mut escrow = Escrow::new();
+			ink::env::test::set_value_transferred::<Environment>(50);
+			assert_eq!(
+				escrow.open(default_accounts.bob, default_accounts.charlie, None, 100, 5),
+				Err(EscrowError::UnexpectedTransferredValue)
+			);
+			assert_eq!(
+				escrow.open(default_accounts.bob, default_accounts.bob, None, 50, 5),
+				Err(EscrowError::InvalidDeal)
+			);
+			let id = open_deal(&mut escrow, 5);
+			let seller_balance = balance_of(default_accounts.bob);
+
+			assert_eq!(escrow.confirm(id, Outcome::Release), Ok(()));
+			ink::env::test::set_caller::<Environment>(default_accounts.bob);
+			assert_eq!(escrow.confirm(id, Outcome::Refund), Ok(()));
+			assert_eq!(escrow.deal(id).map(|deal| deal.state), Some(DealState::Funded));
+
+			assert_eq!(escrow.confirm(id, Outcome::Release), Ok(()));
+			assert_eq!(
+				escrow.deal(id).map(|deal| deal.state),
+				Some(DealState::Settled(Outcome::Release))
+			);
+			assert_eq!(balance_of(default_accounts.bob), seller_balance + 100);
+			assert_eq!(
+				escrow.dispute(id),
+				Err(EscrowError::InvalidState(DealState::Settled(Outcome::Release)))
+			);
+
+			ink::env::test::set_caller::<Environment>(default_accounts.django);
+			assert_eq!(escrow.confirm(id, Outcome::Refund), Err(EscrowError::NotParty));
+		}
+
+		#[ink::test]
+		fn test_disputes_are_settled_by_the_arbiter_until_the_deadline() {
+			let default_accounts = default_accounts();
+			let mut escrow = Escrow::new();
+			let disputed = open_deal(&mut escrow, 2);
+			let expiring = open_deal(&mut escrow, 2);
+			let buyer_balance = balance_of(default_accounts.alice);
+
+			ink::env::test::set_caller::<Environment>(default_accounts.bob);
+			assert_eq!(escrow.dispute(disputed), Ok(()));
+			assert_eq!(
+				escrow.confirm(disputed, Outcome::Release),
+				Err(EscrowError::InvalidState(DealState::Disputed))
+			);
+			assert_eq!(escrow.resolve(disputed, Outcome::Release), Err(EscrowError::NotArbiter));
+			ink::env::test::set_caller::<Environment>(default_accounts.charlie);
+			assert_eq!(
+				escrow.resolve(expiring, Outcome::Release),
+				Err(EscrowError::InvalidState(DealState::Funded))
+			);
+			assert_eq!(escrow.resolve(disputed, Outcome::Refund), Ok(()));
+			assert_eq!(balance_of(default_accounts.alice), buyer_balance + 100);
+
+			assert_eq!(escrow.expire(expiring), Err(EscrowError::DeadlineNotReached));
+			for _ in 0..3 {
+				ink::env::test::advance_block::<Environment>();
+			}
+			ink::env::test::set_caller::<Environment>(default_accounts.alice);
+			assert_eq!(escrow.dispute(expiring), Err(EscrowError::DeadlinePassed));
+			assert_eq!(escrow.expire(expiring), Ok(()));
+			assert_eq!(balance_of(default_accounts.alice), buyer_balance + 200);
+		}
+	}
+
+	#[cfg(all(test, feature = "e2e-tests"))]
+	mod e2e_tests {
+		use ink_e2e::build_message;
+		use psp22_mock::Psp22MockRef;
+
+		use super::*;
+
+		type E2EResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+		#[ink_e2e::test(additional_contracts = "../psp22_mock/Cargo.toml")]
+		async fn test_token_deal_released_by_the_arbiter(
+			mut client: ink_e2e::Client<C, E>,
+		) -> E2EResult<()> {
+			let bob = ink_e2e::account_id(ink_e2e::AccountKeyring::Bob);
+			let charlie = ink_e2e::account_id(ink_e2e::AccountKeyring::Charlie);
+
+			let token = client
+				.instantiate("psp22_mock", &ink_e2e::alice(), Psp22MockRef::new(1_000), 0, None)
+				.await
+				.expect("instantiate token failed")
+				.account_id;
+			let escrow = client
+				.instantiate("escrow", &ink_e2e::alice(), EscrowRef::new(), 0, None)
+				.await
+				.expect("instantiate escrow failed")
+				.account_id;
+
+			let approve = build_message::<Psp22MockRef>(token.clone())
+				.call(|token| token.approve(escrow.clone(), 1_000));
+			client.call(&ink_e2e::alice(), approve, 0, None).await.expect("approve failed");
+			let open = build_message::<EscrowRef>(escrow.clone()).call(|escrow| {
+				escrow.open(bob.clone(), charlie.clone(), Some(token.clone()), 1_000, 1_000_000)
+			});
+			let id = client
+				.call(&ink_e2e::alice(), open, 0, None)
+				.await
+				.expect("open failed")
+				.return_value()
+				.expect("deal rejected");
+
+			let dispute =
+				build_message::<EscrowRef>(escrow.clone()).call(|escrow| escrow.dispute(id));
+			client
+				.call(&ink_e2e::bob(), dispute, 0, None)
+				.await
+				.expect("dispute failed")
+				.return_value()
+				.expect("dispute rejected");
+			let resolve = build_message::<EscrowRef>(escrow.clone())
+				.call(|escrow| escrow.resolve(id, Outcome::Release));
+			client
+				.call(&ink_e2e::charlie(), resolve, 0, None)
+				.await
+				.expect("resolve failed")
+				.return_value()
+				.expect("resolve rejected");
+
+			let balance = build_message::<Psp22MockRef>(token.clone())
+				.call(|token| token.balance_of(bob.clone()));
+			let balance =
+				client.call_dry_run(&ink_e2e::bob(), &balance, 0, None).await.return_value();
+			assert_eq!(balance, 1_000);
+
+			Ok(())
+		}
+	}
+}
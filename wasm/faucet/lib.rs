@@ -0,0 +1,387 @@
+#![cfg_attr(not(feature = "std"), no_std, no_main)]
+
+pub use self::faucet::{Faucet, FaucetRef};
+
+/// Hands out native funds and PSP22 tokens on workshop testnets, so participants can get going
+/// without being funded by hand.
+///
+/// Every drip sends an account the configured amount of the native currency and of each
+/// configured token, and the account can't get another one until the cooldown has passed.
+/// Anyone may ask for a drip to any account, so that a fresh account without funds for fees can
+/// be onboarded by someone else. Whatever the faucet has run out of is skipped. The owner
+/// configures the amounts and the cooldown, and refills the faucet.
+#[ink::contract]
+mod faucet {
+	use access_control::Ownable;
+	use ink::{
+		codegen::EmitEvent, prelude::vec::Vec, reflect::ContractEventBase, storage::Mapping,
+	};
+	use psp22_traits::{PSP22Error, PSP22};
+
+	#[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+	#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+	pub enum FaucetError {
+		NotOwner,
+		/// The account got a drip too recently, it can get the next one in the given block
+		CooldownActive(BlockNumber),
+		/// The faucet has run out of everything it hands out
+		FaucetEmpty,
+		/// Paying out the native currency failed
+		NativeTransferFailed,
+		/// A token transfer failed
+		PSP22(PSP22Error),
+	}
+
+	impl From<PSP22Error> for FaucetError {
+		fn from(e: PSP22Error) -> Self {
+			FaucetError::PSP22(e)
+		}
+	}
+
+	pub type Result<T> = core::result::Result<T, FaucetError>;
+
+	type Event = <Faucet as ContractEventBase>::Type;
+
+	/// `to` got `amount` of `token`, or of the native currency if `token` is `None`
+	#[ink(event)]
+	pub struct Dripped {
+		#[ink(topic)]
+		to: AccountId,
+		#[ink(topic)]
+		token: Option<AccountId>,
+		amount: Balance,
+	}
+
+	/// The faucet got `amount` of `token`, or of the native currency if `token` is `None`
+	#[ink(event)]
+	pub struct Refilled {
+		#[ink(topic)]
+		token: Option<AccountId>,
+		amount: Balance,
+	}
+
+	#[ink(storage)]
+	pub struct Faucet {
+		ownership: Ownable,
+		/// How much of the native currency a drip sends
+		native_drip: Balance,
+		/// The tokens a drip sends, each with a positive amount in `token_drips`
+		tokens: Vec<AccountId>,
+		token_drips: Mapping<AccountId, Balance>,
+		/// How many blocks an account waits between drips
+		cooldown: BlockNumber,
+		/// The block every account last got a drip in
+		last_drips: Mapping<AccountId, BlockNumber>,
+	}
+
+	impl Faucet {
+		/// Hands out `native_drip` of the native currency every `cooldown` blocks per account.
+		/// What is transferred along with the call funds the faucet. The caller becomes the
+		/// owner.
+		#[ink(constructor, payable)]
+		pub fn new(native_drip: Balance, cooldown: BlockNumber) -> Self {
+			Self {
+				ownership: Ownable::new(Self::env().caller()),
+				native_drip,
+				tokens: Vec::new(),
+				token_drips: Mapping::default(),
+				cooldown,
+				last_drips: Mapping::default(),
+			}
+		}
+
+		/// Sends `to` a drip of everything the faucet hands out and still has. Anyone may ask
+		/// for a drip to any account, as long as that account's cooldown has passed.
+		#[ink(message)]
+		pub fn drip(&mut self, to: AccountId) -> Result<()> {
+			let block = self.env().block_number();
+			if let Some(next) = self.next_drip(to).filter(|next| block < *next) {
+				return Err(FaucetError::CooldownActive(next));
+			}
+
+			let mut dripped = false;
+			if self.native_drip > 0
+				&& self.env().balance() >= self.native_drip
+				&& self.env().transfer(to, self.native_drip).is_ok()
+			{
+				dripped = true;
+				self.emit_dripped(to, None, self.native_drip);
+			}
+			let this = self.env().account_id();
+			for token in &self.tokens {
+				let amount = self.token_drips.get(token).unwrap_or(0);
+				let mut psp22: ink::contract_ref!(PSP22) = (*token).into();
+				if psp22.balance_of(this) >= amount
+					&& psp22.transfer(to, amount, Vec::new()).is_ok()
+				{
+					dripped = true;
+					self.emit_dripped(to, Some(*token), amount);
+				}
+			}
+			if !dripped {
+				return Err(FaucetError::FaucetEmpty);
+			}
+
+			self.last_drips.insert(to, &block);
+
+			Ok(())
+		}
+
+		/// Returns the first block in which `account` can get another drip, if it had one
+		#[ink(message)]
+		pub fn next_drip(&self, account: AccountId) -> Option<BlockNumber> {
+			self.last_drips.get(account).map(|last| last.saturating_add(self.cooldown))
+		}
+
+		/// Returns how much of the native currency a drip sends, how much of each token, and
+		/// the cooldown between drips
+		#[ink(message)]
+		pub fn config(&self) -> (Balance, Vec<(AccountId, Balance)>, BlockNumber) {
+			let token_drips = self
+				.tokens
+				.iter()
+				.map(|token| (*token, self.token_drips.get(token).unwrap_or(0)))
+				.collect();
+			(self.native_drip, token_drips, self.cooldown)
+		}
+
+		/// Funds the faucet with what is transferred along with the call. Only the owner may
+		/// refill.
+		#[ink(message, payable)]
+		pub fn refill(&mut self) -> Result<()> {
+			self.ensure_owner()?;
+			let amount = self.env().transferred_value();
+			Self::emit_event(self.env(), Event::Refilled(Refilled { token: None, amount }));
+			Ok(())
+		}
+
+		/// Funds the faucet with `amount` of `token`, taken from the owner, who approves the
+		/// faucet first. Only the owner may refill.
+		#[ink(message)]
+		pub fn refill_token(&mut self, token: AccountId, amount: Balance) -> Result<()> {
+			self.ensure_owner()?;
+			let mut psp22: ink::contract_ref!(PSP22) = token.into();
+			psp22.transfer_from(
+				self.env().caller(),
+				self.env().account_id(),
+				amount,
+				Vec::new(),
+			)?;
+			Self::emit_event(self.env(), Event::Refilled(Refilled { token: Some(token), amount }));
+			Ok(())
+		}
+
+		/// Changes how much of the native currency a drip sends. Only the owner may configure
+		/// the faucet.
+		#[ink(message)]
+		pub fn set_native_drip(&mut self, amount: Balance) -> Result<()> {
+			self.ensure_owner()?;
+			self.native_drip = amount;
+			Ok(())
+		}
+
+		/// Changes how much of `token` a drip sends, where zero stops handing it out. Only the
+		/// owner may configure the faucet.
+		#[ink(message)]
+		pub fn set_token_drip(&mut self, token: AccountId, amount: Balance) -> Result<()> {
+			self.ensure_owner()?;
+			match amount {
+				0 => {
+					self.tokens.retain(|t| *t != token);
+					self.token_drips.remove(token);
+				},
+				amount => {
+					if !self.tokens.contains(&token) {
+						self.tokens.push(token);
+					}
+					self.token_drips.insert(token, &amount);
+				},
+			}
+			Ok(())
+		}
+
+		/// Changes how many blocks an account waits between drips. Only the owner may
+		/// configure the faucet.
+		#[ink(message)]
+		pub fn set_cooldown(&mut self, cooldown: BlockNumber) -> Result<()> {
+			self.ensure_owner()?;
+			self.cooldown = cooldown;
+			Ok(())
+		}
+
+		/// Takes `amount` of `token`, or of the native currency if `token` is `None`, back out
+		/// of the faucet. Only the owner may withdraw.
+		#[ink(message)]
+		pub fn withdraw(&mut self, token: Option<AccountId>, amount: Balance) -> Result<()> {
+			self.ensure_owner()?;
+			let owner = self.env().caller();
+			match token {
+				Some(token) => {
+					let mut psp22: ink::contract_ref!(PSP22) = token.into();
+					psp22.transfer(owner, amount, Vec::new())?;
+				},
+				None => {
+					self.env()
+						.transfer(owner, amount)
+						.map_err(|_| FaucetError::NativeTransferFailed)?;
+				},
+			}
+			Ok(())
+		}
+
+		fn ensure_owner(&self) -> Result<()> {
+			self.ownership
+				.ensure_owner(self.env().caller())
+				.map_err(|_| FaucetError::NotOwner)
+		}
+
+		fn emit_dripped(&self, to: AccountId, token: Option<AccountId>, amount: Balance) {
+			Self::emit_event(self.env(), Event::Dripped(Dripped { to, token, amount }));
+		}
+
+		fn emit_event<EE>(emitter: EE, event: Event)
+		where
+			EE: EmitEvent<Self>,
+		{
+			emitter.emit_event(event);
+		}
+	}
+
+	#[cfg(test)]
+	mod tests {
+		use super::*;
+
+		fn default_accounts() -> ink::env::test::DefaultAccounts<Environment> {
+			ink::env::test::default_accounts::<Environment>()
+		}
+
+		fn balance_of(account: AccountId) -> Balance {
+			ink::env::test::get_account_balance::<Environment>(account).expect("account exists")
+		}
+
+		fn faucet(funds: Balance) -> Faucet {
+			let default_accounts = default_accounts();
+			ink::env::test::set_caller::<Environment>(default_accounts.alice);
+			let faucet = Faucet::new(100, 3);
+			ink::env::test::set_account_balance::<Environment>(
+				ink::env::account_id::<Environment>(),
+				funds,
+			);
+			faucet
+		}
+
+		#[ink::test]
+		fn test_accounts_wait_for_the_cooldown() {
+			let default_accounts = default_accounts();
+			let mut faucet = faucet(1_000);
+			let balance = balance_of(default_accounts.bob);
+
+			// Anyone may ask for bob's drip
+			ink::env::test::set_caller::<Environment>(default_accounts.charlie);
+			assert_eq!(faucet.drip(default_accounts.bob), Ok(()));
+			assert_eq!(balance_of(default_accounts.bob), balance + 100);
+			assert_eq!(faucet.next_drip(default_accounts.bob), Some(3));
+			assert_eq!(faucet.drip(default_accounts.bob), Err(FaucetError::CooldownActive(3)));
+			assert_eq!(faucet.drip(default_accounts.charlie), Ok(()));
+
+			for _ in 0..3 {
+				ink::env::test::advance_block::<Environment>();
+			}
+			assert_eq!(faucet.drip(default_accounts.bob), Ok(()));
+			assert_eq!(balance_of(default_accounts.bob), balance + 200);
+		}
+
+		#[ink::test]
+		fn test_an_empty_faucet_drips_nothing() {
+			let default_accounts = default_accounts();
+			let mut faucet = faucet(50);
+
+			assert_eq!(faucet.drip(default_accounts.bob), Err(FaucetError::FaucetEmpty));
+			assert_eq!(faucet.next_drip(default_accounts.bob), None);
+
+			assert_eq!(faucet.set_native_drip(50), Ok(()));
+			assert_eq!(faucet.drip(default_accounts.bob), Ok(()));
+		}
+
+		#[ink::test]
+		fn test_only_the_owner_configures() {
+			let default_accounts = default_accounts();
+			let mut faucet = faucet(1_000);
+
+			assert_eq!(faucet.set_token_drip(default_accounts.django, 10), Ok(()));
+			assert_eq!(faucet.set_token_drip(default_accounts.eve, 20), Ok(()));
+			assert_eq!(faucet.set_token_drip(default_accounts.django, 0), Ok(()));
+			assert_eq!(faucet.set_cooldown(5), Ok(()));
+			assert_eq!(faucet.config(), (100, vec![(default_accounts.eve, 20)], 5));
+
+			ink::env::test::set_caller::<Environment>(default_accounts.bob);
+			assert_eq!(faucet.set_native_drip(1_000), Err(FaucetError::NotOwner));
+			assert_eq!(faucet.set_cooldown(0), Err(FaucetError::NotOwner));
+			assert_eq!(faucet.refill(), Err(FaucetError::NotOwner));
+			assert_eq!(faucet.withdraw(None, 1_000), Err(FaucetError::NotOwner));
+		}
+	}
+
+	#[cfg(all(test, feature = "e2e-tests"))]
+	mod e2e_tests {
+		use ink_e2e::build_message;
+		use psp22_mock::Psp22MockRef;
+
+		use super::*;
+
+		type E2EResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+		#[ink_e2e::test(additional_contracts = "../psp22_mock/Cargo.toml")]
+		async fn test_drips_include_tokens(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+			let bob = ink_e2e::account_id(ink_e2e::AccountKeyring::Bob);
+
+			let token = client
+				.instantiate("psp22_mock", &ink_e2e::alice(), Psp22MockRef::new(1_000), 0, None)
+				.await
+				.expect("instantiate token failed")
+				.account_id;
+			let faucet = client
+				.instantiate("faucet", &ink_e2e::alice(), FaucetRef::new(0, 100), 0, None)
+				.await
+				.expect("instantiate faucet failed")
+				.account_id;
+
+			let approve = build_message::<Psp22MockRef>(token.clone())
+				.call(|token| token.approve(faucet.clone(), 1_000));
+			client.call(&ink_e2e::alice(), approve, 0, None).await.expect("approve failed");
+			let refill = build_message::<FaucetRef>(faucet.clone())
+				.call(|faucet| faucet.refill_token(token.clone(), 1_000));
+			client
+				.call(&ink_e2e::alice(), refill, 0, None)
+				.await
+				.expect("refill failed")
+				.return_value()
+				.expect("refill rejected");
+			let configure = build_message::<FaucetRef>(faucet.clone())
+				.call(|faucet| faucet.set_token_drip(token.clone(), 300));
+			client
+				.call(&ink_e2e::alice(), configure, 0, None)
+				.await
+				.expect("set_token_drip failed")
+				.return_value()
+				.expect("set_token_drip rejected");
+
+			let drip =
+				build_message::<FaucetRef>(faucet.clone()).call(|faucet| faucet.drip(bob.clone()));
+			client
+				.call(&ink_e2e::charlie(), drip, 0, None)
+				.await
+				.expect("drip failed")
+				.return_value()
+				.expect("drip rejected");
+
+			let balance = build_message::<Psp22MockRef>(token.clone())
+				.call(|token| token.balance_of(bob.clone()));
+			let balance =
+				client.call_dry_run(&ink_e2e::bob(), &balance, 0, None).await.return_value();
+			assert_eq!(balance, 300);
+
+			Ok(())
+		}
+	}
+}
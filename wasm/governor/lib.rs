@@ -0,0 +1,520 @@
+#![cfg_attr(not(feature = "std"), no_std, no_main)]
+
+pub use self::governor::{Governor, GovernorRef};
+
+/// Lets the holders of a token decide what the governor does.
+///
+/// A proposal is a list of calls for the governor to make, e.g. paying out of its treasury or
+/// granting a role on a contract the governor owns. Holders vote with the voting power they had
+/// when the proposal was made, so tokens bought or borrowed afterwards don't count. A proposal
+/// that gets more votes for than against and reaches the quorum is queued, and can be executed
+/// by anyone once the execution delay has passed, which gives holders who disagree time to act.
+#[ink::contract]
+mod governor {
+	use ink::{
+		codegen::EmitEvent,
+		env::call::{build_call, ExecutionInput, Selector},
+		prelude::{string::String, vec::Vec},
+		reflect::ContractEventBase,
+		storage::Mapping,
+	};
+	use psp22_traits::{EncodedArgs, PSP22Receiver, PSP22ReceiverError, PSP22Votes};
+
+	pub type ProposalId = u32;
+
+	/// A message the governor sends when a proposal is executed
+	#[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+	#[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+	pub struct Call {
+		pub callee: AccountId,
+		pub selector: [u8; 4],
+		/// The SCALE encoded arguments
+		pub input: Vec<u8>,
+		/// The native currency sent along, out of the governor's balance
+		pub transferred_value: Balance,
+	}
+
+	#[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+	#[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+	pub enum VoteType {
+		Against,
+		For,
+		/// Counts towards the quorum only
+		Abstain,
+	}
+
+	#[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+	#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+	pub enum ProposalState {
+		/// Votes are being cast
+		Active,
+		/// Voting is over and the proposal didn't pass
+		Defeated,
+		/// Voting is over and the proposal passed, it can be queued
+		Succeeded,
+		/// Waiting for the execution delay to pass
+		Queued,
+		Executed,
+	}
+
+	#[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+	#[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+	pub struct Proposal {
+		pub proposer: AccountId,
+		pub calls: Vec<Call>,
+		pub description: String,
+		/// Votes are counted as of the end of this block, the one the proposal was made in
+		pub snapshot: BlockNumber,
+		/// The last block in which votes are accepted
+		pub vote_end: BlockNumber,
+		pub for_votes: Balance,
+		pub against_votes: Balance,
+		pub abstain_votes: Balance,
+		/// The first block in which the proposal may be executed, once it is queued
+		pub eta: Option<BlockNumber>,
+		pub executed: bool,
+	}
+
+	#[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+	#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+	pub enum GovernorError {
+		/// The caller doesn't have enough voting power to propose
+		BelowProposalThreshold,
+		/// A proposal needs at least one call
+		EmptyProposal,
+		ProposalNotFound,
+		/// The proposal is not in a state that allows this, e.g. voting on it is over
+		InvalidState(ProposalState),
+		/// Each account votes once per proposal
+		AlreadyVoted,
+		/// The execution delay of the proposal has not passed yet
+		TimelockNotExpired,
+		/// The call at this index of the proposal failed, so none of them were made
+		CallFailed(u32),
+	}
+
+	pub type Result<T> = core::result::Result<T, GovernorError>;
+
+	type Event = <Governor as ContractEventBase>::Type;
+
+	#[ink(event)]
+	pub struct ProposalCreated {
+		#[ink(topic)]
+		id: ProposalId,
+		#[ink(topic)]
+		proposer: AccountId,
+		vote_end: BlockNumber,
+	}
+
+	#[ink(event)]
+	pub struct VoteCast {
+		#[ink(topic)]
+		id: ProposalId,
+		#[ink(topic)]
+		voter: AccountId,
+		vote: VoteType,
+		weight: Balance,
+	}
+
+	#[ink(event)]
+	pub struct ProposalQueued {
+		#[ink(topic)]
+		id: ProposalId,
+		eta: BlockNumber,
+	}
+
+	#[ink(event)]
+	pub struct ProposalExecuted {
+		#[ink(topic)]
+		id: ProposalId,
+	}
+
+	#[ink(storage)]
+	pub struct Governor {
+		/// The token whose voting power counts
+		token: AccountId,
+		/// How many blocks a proposal is open for votes
+		voting_period: BlockNumber,
+		/// The votes for and abstaining a proposal needs to pass
+		quorum: Balance,
+		/// The voting power an account needs to propose
+		proposal_threshold: Balance,
+		/// How many blocks a passed proposal waits in the queue
+		execution_delay: BlockNumber,
+		proposals: Mapping<ProposalId, Proposal>,
+		next_proposal_id: ProposalId,
+		/// Existence of a key means the account voted on the proposal
+		voted: Mapping<(ProposalId, AccountId), ()>,
+	}
+
+	impl Governor {
+		#[ink(constructor)]
+		pub fn new(
+			token: AccountId,
+			voting_period: BlockNumber,
+			quorum: Balance,
+			proposal_threshold: Balance,
+			execution_delay: BlockNumber,
+		) -> Self {
+			Self {
+				token,
+				voting_period,
+				quorum,
+				proposal_threshold,
+				execution_delay,
+				proposals: Mapping::default(),
+				next_proposal_id: 0,
+				voted: Mapping::default(),
+			}
+		}
+
+		/// Proposes that the governor make `calls`, and returns the id of the proposal. Voting
+		/// starts in the next block.
+		#[ink(message)]
+		pub fn propose(&mut self, calls: Vec<Call>, description: String) -> Result<ProposalId> {
+			if calls.is_empty() {
+				return Err(GovernorError::EmptyProposal);
+			}
+
+			let caller = self.env().caller();
+			let now = self.env().block_number();
+			// The voting power of the current block can still change
+			if self.proposal_threshold > 0
+				&& self.votes_at(caller, now.saturating_sub(1)) < self.proposal_threshold
+			{
+				return Err(GovernorError::BelowProposalThreshold);
+			}
+
+			let id = self.next_proposal_id;
+			self.next_proposal_id += 1;
+			let vote_end = now.saturating_add(self.voting_period);
+			self.proposals.insert(
+				id,
+				&Proposal {
+					proposer: caller,
+					calls,
+					description,
+					snapshot: now,
+					vote_end,
+					for_votes: 0,
+					against_votes: 0,
+					abstain_votes: 0,
+					eta: None,
+					executed: false,
+				},
+			);
+
+			Self::emit_event(
+				self.env(),
+				Event::ProposalCreated(ProposalCreated { id, proposer: caller, vote_end }),
+			);
+
+			Ok(id)
+		}
+
+		/// Votes on proposal `id` with all the voting power the caller had when it was made,
+		/// and returns that voting power
+		#[ink(message)]
+		pub fn cast_vote(&mut self, id: ProposalId, vote: VoteType) -> Result<Balance> {
+			let mut proposal = self.proposal(id).ok_or(GovernorError::ProposalNotFound)?;
+			let state = self.state_of(&proposal);
+			if state != ProposalState::Active || self.env().block_number() <= proposal.snapshot {
+				return Err(GovernorError::InvalidState(state));
+			}
+			let voter = self.env().caller();
+			if self.voted.contains((id, voter)) {
+				return Err(GovernorError::AlreadyVoted);
+			}
+
+			let weight = self.votes_at(voter, proposal.snapshot);
+			let tally = match vote {
+				VoteType::Against => &mut proposal.against_votes,
+				VoteType::For => &mut proposal.for_votes,
+				VoteType::Abstain => &mut proposal.abstain_votes,
+			};
+			// Can't overflow, all votes together are at most the token supply
+			*tally += weight;
+			self.proposals.insert(id, &proposal);
+			self.voted.insert((id, voter), &());
+
+			Self::emit_event(self.env(), Event::VoteCast(VoteCast { id, voter, vote, weight }));
+
+			Ok(weight)
+		}
+
+		/// Starts the execution delay of a passed proposal. Anyone may queue it.
+		#[ink(message)]
+		pub fn queue(&mut self, id: ProposalId) -> Result<BlockNumber> {
+			let mut proposal = self.proposal(id).ok_or(GovernorError::ProposalNotFound)?;
+			let state = self.state_of(&proposal);
+			if state != ProposalState::Succeeded {
+				return Err(GovernorError::InvalidState(state));
+			}
+
+			let eta = self.env().block_number().saturating_add(self.execution_delay);
+			proposal.eta = Some(eta);
+			self.proposals.insert(id, &proposal);
+
+			Self::emit_event(self.env(), Event::ProposalQueued(ProposalQueued { id, eta }));
+
+			Ok(eta)
+		}
+
+		/// Makes the calls of a queued proposal whose execution delay has passed. Anyone may
+		/// execute it. If any call fails, none of them take effect.
+		#[ink(message)]
+		pub fn execute(&mut self, id: ProposalId) -> Result<()> {
+			let mut proposal = self.proposal(id).ok_or(GovernorError::ProposalNotFound)?;
+			let state = self.state_of(&proposal);
+			if state != ProposalState::Queued {
+				return Err(GovernorError::InvalidState(state));
+			}
+			if proposal.eta.is_some_and(|eta| self.env().block_number() < eta) {
+				return Err(GovernorError::TimelockNotExpired);
+			}
+
+			proposal.executed = true;
+			self.proposals.insert(id, &proposal);
+
+			for (index, call) in proposal.calls.into_iter().enumerate() {
+				let result = build_call::<Environment>()
+					.call(call.callee)
+					.transferred_value(call.transferred_value)
+					.exec_input(
+						ExecutionInput::new(Selector::new(call.selector))
+							.push_arg(EncodedArgs(call.input)),
+					)
+					.returns::<()>()
+					.try_invoke();
+				// Messages that return an error revert, so whatever they return, a call that
+				// went through did what it was asked to
+				if !matches!(result, Ok(Ok(()))) {
+					return Err(GovernorError::CallFailed(index as u32));
+				}
+			}
+
+			Self::emit_event(self.env(), Event::ProposalExecuted(ProposalExecuted { id }));
+
+			Ok(())
+		}
+
+		/// Returns proposal `id`, if there is one
+		#[ink(message)]
+		pub fn proposal(&self, id: ProposalId) -> Option<Proposal> {
+			self.proposals.get(id)
+		}
+
+		/// Returns the state of proposal `id`, if there is one
+		#[ink(message)]
+		pub fn state(&self, id: ProposalId) -> Option<ProposalState> {
+			self.proposal(id).map(|proposal| self.state_of(&proposal))
+		}
+
+		/// Returns true if `account` voted on proposal `id`
+		#[ink(message)]
+		pub fn has_voted(&self, id: ProposalId, account: AccountId) -> bool {
+			self.voted.contains((id, account))
+		}
+
+		fn state_of(&self, proposal: &Proposal) -> ProposalState {
+			if proposal.executed {
+				ProposalState::Executed
+			} else if proposal.eta.is_some() {
+				ProposalState::Queued
+			} else if self.env().block_number() <= proposal.vote_end {
+				ProposalState::Active
+			} else if proposal.for_votes > proposal.against_votes
+				&& proposal.for_votes.saturating_add(proposal.abstain_votes) >= self.quorum
+			{
+				ProposalState::Succeeded
+			} else {
+				ProposalState::Defeated
+			}
+		}
+
+		/// The voting power `account` had at the end of `block`
+		fn votes_at(&self, account: AccountId, block: BlockNumber) -> Balance {
+			let token: ink::contract_ref!(PSP22Votes) = self.token.into();
+			token.get_votes_at(account, block)
+		}
+
+		fn emit_event<EE>(emitter: EE, event: Event)
+		where
+			EE: EmitEvent<Self>,
+		{
+			emitter.emit_event(event);
+		}
+	}
+
+	impl PSP22Receiver for Governor {
+		/// Takes any token into the treasury, which proposals pay out of
+		#[ink(message)]
+		fn before_received(
+			&mut self,
+			_operator: AccountId,
+			_from: AccountId,
+			_value: Balance,
+			_data: Vec<u8>,
+		) -> core::result::Result<(), PSP22ReceiverError> {
+			Ok(())
+		}
+	}
+
+	#[cfg(test)]
+	mod tests {
+		use super::*;
+
+		fn default_accounts() -> ink::env::test::DefaultAccounts<Environment> {
+			ink::env::test::default_accounts::<Environment>()
+		}
+
+		fn set_next_caller(caller: AccountId) {
+			ink::env::test::set_caller::<Environment>(caller);
+		}
+
+		fn call() -> Call {
+			Call {
+				callee: default_accounts().django,
+				selector: ink::selector_bytes!("PSP22::transfer"),
+				input: Vec::new(),
+				transferred_value: 0,
+			}
+		}
+
+		#[ink::test]
+		fn test_proposals_without_votes_are_defeated() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut governor = Governor::new(default_accounts.django, 3, 100, 0, 2);
+
+			assert_eq!(
+				governor.propose(Vec::new(), String::new()),
+				Err(GovernorError::EmptyProposal)
+			);
+			assert_eq!(governor.propose(vec![call()], "pay bob".into()), Ok(0));
+			assert_eq!(governor.state(0), Some(ProposalState::Active));
+			assert_eq!(governor.state(1), None);
+			assert_eq!(governor.proposal(0).map(|proposal| proposal.vote_end), Some(3));
+
+			for _ in 0..4 {
+				ink::env::test::advance_block::<Environment>();
+			}
+			assert_eq!(governor.state(0), Some(ProposalState::Defeated));
+			assert_eq!(
+				governor.cast_vote(0, VoteType::For),
+				Err(GovernorError::InvalidState(ProposalState::Defeated))
+			);
+			assert_eq!(
+				governor.queue(0),
+				Err(GovernorError::InvalidState(ProposalState::Defeated))
+			);
+			assert_eq!(
+				governor.execute(0),
+				Err(GovernorError::InvalidState(ProposalState::Defeated))
+			);
+		}
+
+		#[ink::test]
+		fn test_votes_open_the_block_after_the_proposal() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut governor = Governor::new(default_accounts.django, 3, 100, 0, 2);
+			governor.propose(vec![call()], String::new()).unwrap();
+
+			// Voting power in the proposal's own block can still change
+			assert_eq!(
+				governor.cast_vote(0, VoteType::For),
+				Err(GovernorError::InvalidState(ProposalState::Active))
+			);
+			assert_eq!(governor.cast_vote(1, VoteType::For), Err(GovernorError::ProposalNotFound));
+		}
+	}
+
+	#[cfg(all(test, feature = "e2e-tests"))]
+	mod e2e_tests {
+		use ink_e2e::build_message;
+		use psp22::TokenRef;
+		use psp22_traits::PSP22;
+
+		use super::*;
+
+		type E2EResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+		#[ink_e2e::test(additional_contracts = "../psp22/Cargo.toml")]
+		async fn test_passed_proposal_pays_out_of_the_treasury(
+			mut client: ink_e2e::Client<C, E>,
+		) -> E2EResult<()> {
+			let alice = ink_e2e::account_id(ink_e2e::AccountKeyring::Alice);
+			let bob = ink_e2e::account_id(ink_e2e::AccountKeyring::Bob);
+
+			let constructor = TokenRef::new(1000, None, None, 0, None, Hash::default());
+			let token = client
+				.instantiate("psp22", &ink_e2e::alice(), constructor, 0, None)
+				.await
+				.expect("instantiate token failed")
+				.account_id;
+			let delegate = build_message::<TokenRef>(token.clone())
+				.call(|token| token.delegate(alice.clone()));
+			client
+				.call(&ink_e2e::alice(), delegate, 0, None)
+				.await
+				.expect("delegate failed");
+
+			let constructor = GovernorRef::new(token.clone(), 2, 500, 100, 0);
+			let governor = client
+				.instantiate("governor", &ink_e2e::alice(), constructor, 0, None)
+				.await
+				.expect("instantiate governor failed")
+				.account_id;
+			let fund = build_message::<TokenRef>(token.clone())
+				.call(|token| token.transfer(governor.clone(), 100, Vec::new()));
+			client.call(&ink_e2e::alice(), fund, 0, None).await.expect("transfer failed");
+
+			let pay_bob = Call {
+				callee: token.clone(),
+				selector: ink::selector_bytes!("PSP22::transfer"),
+				input: scale::Encode::encode(&(bob.clone(), 40 as Balance, Vec::<u8>::new())),
+				transferred_value: 0,
+			};
+			let propose = build_message::<GovernorRef>(governor.clone())
+				.call(|governor| governor.propose(vec![pay_bob.clone()], "pay bob".into()));
+			let id = client
+				.call(&ink_e2e::alice(), propose, 0, None)
+				.await
+				.expect("propose failed")
+				.return_value()
+				.expect("proposal rejected");
+
+			let vote = build_message::<GovernorRef>(governor.clone())
+				.call(|governor| governor.cast_vote(id, VoteType::For));
+			let weight = client
+				.call(&ink_e2e::alice(), vote, 0, None)
+				.await
+				.expect("cast_vote failed")
+				.return_value();
+			assert_eq!(weight, Ok(900));
+
+			// Every call is a block, so these close the voting period
+			for _ in 0..2 {
+				let state = build_message::<GovernorRef>(governor.clone())
+					.call(|governor| governor.state(id));
+				client.call(&ink_e2e::bob(), state, 0, None).await.expect("state failed");
+			}
+
+			let queue =
+				build_message::<GovernorRef>(governor.clone()).call(|governor| governor.queue(id));
+			client.call(&ink_e2e::bob(), queue, 0, None).await.expect("queue failed");
+			let execute = build_message::<GovernorRef>(governor.clone())
+				.call(|governor| governor.execute(id));
+			client.call(&ink_e2e::bob(), execute, 0, None).await.expect("execute failed");
+
+			let balance = build_message::<TokenRef>(token.clone())
+				.call(|token| token.balance_of(bob.clone()));
+			let balance =
+				client.call_dry_run(&ink_e2e::bob(), &balance, 0, None).await.return_value();
+			assert_eq!(balance, 40);
+
+			Ok(())
+		}
+	}
+}
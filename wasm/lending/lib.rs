@@ -0,0 +1,831 @@
+#![cfg_attr(not(feature = "std"), no_std, no_main)]
+
+pub use self::lending::{InterestRateModel, LendingError, LendingPool, LendingPoolRef};
+
+/// A pool that lends out one PSP22 token, the asset, against other PSP22 tokens as collateral.
+///
+/// Lenders deposit the asset and get pool shares in return, which are PSP22 tokens themselves
+/// and grow in value as borrowers pay interest. The interest rate rises with the share of the
+/// deposits that is lent out. Borrowers deposit collateral tokens the owner listed, each with a
+/// collateral factor, and may owe up to the sum of their collateral's value times its factor.
+/// Collateral is valued in the asset by a `PriceOracle`. Once a borrower owes more than that,
+/// anyone may repay part of the debt and seize collateral worth a bonus more than they repaid.
+///
+/// The pool pulls tokens with `transfer_from`, so callers approve it first. It counts what
+/// actually arrived, which makes it work with tokens that charge a fee on transfers.
+#[ink::contract]
+mod lending {
+	use access_control::Ownable;
+	use ink::{
+		codegen::EmitEvent, prelude::vec::Vec, reflect::ContractEventBase, storage::Mapping,
+	};
+	use math::{bps_of, BPS_DENOMINATOR, ONE};
+	use psp22_traits::{notify_receiver, OracleError, PSP22Data, PSP22Error, PriceOracle, PSP22};
+
+	/// How much of a borrower's debt a single liquidation may repay, in basis points
+	pub const CLOSE_FACTOR_BPS: u16 = 5_000;
+	/// How much more collateral a liquidator seizes than the value they repaid, in basis points
+	pub const LIQUIDATION_BONUS_BPS: u16 = 500;
+
+	/// The interest rate borrowers pay per block, which grows linearly with utilization, the
+	/// share of the pool's assets that is lent out. Rates are fixed-point numbers with `ONE` as
+	/// one.
+	#[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+	#[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+	pub struct InterestRateModel {
+		/// The rate when nothing is lent out
+		pub base_rate: u128,
+		/// How much the rate grows from nothing to everything lent out
+		pub slope: u128,
+	}
+
+	impl InterestRateModel {
+		/// The rate when `borrows` of `cash + borrows` are lent out
+		pub fn borrow_rate(&self, cash: Balance, borrows: Balance) -> u128 {
+			let utilization =
+				math::mul_div(borrows, ONE, cash.saturating_add(borrows)).unwrap_or(0);
+			// Utilization is at most `ONE`, so this only overflows for absurd slopes
+			let variable = math::mul_div(self.slope, utilization, ONE).unwrap_or(u128::MAX);
+			self.base_rate.saturating_add(variable)
+		}
+	}
+
+	#[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+	#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+	pub enum LendingError {
+		NotOwner,
+		/// Collateral factors must be below 100%, and the asset can't back itself
+		InvalidCollateral,
+		/// The owner hasn't listed the token as collateral
+		CollateralNotListed,
+		/// The amount is too small to move anything
+		InsufficientAmount,
+		/// The pool doesn't hold that much of the asset, the rest is lent out
+		InsufficientLiquidity,
+		/// The account's collateral wouldn't cover its debt, or doesn't hold that much
+		InsufficientCollateral,
+		/// The account has no debt to repay
+		NoDebt,
+		/// The borrower's collateral still covers their debt
+		NotLiquidatable,
+		/// A liquidation may repay at most `CLOSE_FACTOR_BPS` of the debt
+		RepayTooLarge,
+		/// The amounts are too large to compute with
+		Overflow,
+		/// The oracle had no usable price for a collateral token
+		Oracle(OracleError),
+		/// A token transfer failed
+		PSP22(PSP22Error),
+	}
+
+	impl From<PSP22Error> for LendingError {
+		fn from(e: PSP22Error) -> Self {
+			LendingError::PSP22(e)
+		}
+	}
+
+	impl From<OracleError> for LendingError {
+		fn from(e: OracleError) -> Self {
+			LendingError::Oracle(e)
+		}
+	}
+
+	pub type Result<T> = core::result::Result<T, LendingError>;
+
+	type Event = <LendingPool as ContractEventBase>::Type;
+
+	#[ink(event)]
+	pub struct Approval {
+		#[ink(topic)]
+		owner: AccountId,
+		#[ink(topic)]
+		spender: AccountId,
+		amount: Balance,
+	}
+
+	#[ink(event)]
+	pub struct Transfer {
+		#[ink(topic)]
+		from: AccountId,
+		#[ink(topic)]
+		to: AccountId,
+		value: Balance,
+	}
+
+	/// `lender` deposited `amount` of the asset for `shares`
+	#[ink(event)]
+	pub struct Deposited {
+		#[ink(topic)]
+		lender: AccountId,
+		amount: Balance,
+		shares: Balance,
+	}
+
+	/// `lender` redeemed `shares` for `amount` of the asset
+	#[ink(event)]
+	pub struct Withdrawn {
+		#[ink(topic)]
+		lender: AccountId,
+		amount: Balance,
+		shares: Balance,
+	}
+
+	#[ink(event)]
+	pub struct CollateralListed {
+		#[ink(topic)]
+		token: AccountId,
+		collateral_factor_bps: u16,
+	}
+
+	#[ink(event)]
+	pub struct CollateralDeposited {
+		#[ink(topic)]
+		account: AccountId,
+		#[ink(topic)]
+		token: AccountId,
+		amount: Balance,
+	}
+
+	#[ink(event)]
+	pub struct CollateralWithdrawn {
+		#[ink(topic)]
+		account: AccountId,
+		#[ink(topic)]
+		token: AccountId,
+		amount: Balance,
+	}
+
+	#[ink(event)]
+	pub struct Borrowed {
+		#[ink(topic)]
+		borrower: AccountId,
+		amount: Balance,
+	}
+
+	#[ink(event)]
+	pub struct Repaid {
+		#[ink(topic)]
+		borrower: AccountId,
+		amount: Balance,
+	}
+
+	/// `liquidator` repaid `repaid` of `borrower`'s debt and seized `seized` of `token`
+	#[ink(event)]
+	pub struct Liquidated {
+		#[ink(topic)]
+		liquidator: AccountId,
+		#[ink(topic)]
+		borrower: AccountId,
+		#[ink(topic)]
+		token: AccountId,
+		repaid: Balance,
+		seized: Balance,
+	}
+
+	#[ink(storage)]
+	pub struct LendingPool {
+		/// The lenders' shares of the pool
+		shares: PSP22Data,
+		ownership: Ownable,
+		/// The token lent out
+		asset: AccountId,
+		oracle: AccountId,
+		rate_model: InterestRateModel,
+		/// How much of the asset the pool holds. Tokens that reach the pool other than through
+		/// its messages are not counted.
+		cash: Balance,
+		/// How much borrowers owe in total, interest included
+		total_borrows: Balance,
+		/// What one unit borrowed when the pool was created has grown to with interest
+		borrow_index: u128,
+		/// The block interest was last added in
+		last_accrual: BlockNumber,
+		/// What every borrower owes, divided by the borrow index, so that it grows with it
+		debts: Mapping<AccountId, Balance>,
+		/// Every token ever listed as collateral
+		collateral_tokens: Vec<AccountId>,
+		/// Collateral factors of the listed tokens, in basis points
+		collateral_factors: Mapping<AccountId, u16>,
+		/// Collateral deposited by each account, by token
+		collateral: Mapping<(AccountId, AccountId), Balance>,
+	}
+
+	impl LendingPool {
+		/// Lends out `asset` at rates following `rate_model` against collateral valued by
+		/// `oracle`. The caller becomes the owner, who lists collateral tokens.
+		#[ink(constructor)]
+		pub fn new(asset: AccountId, oracle: AccountId, rate_model: InterestRateModel) -> Self {
+			Self {
+				shares: PSP22Data::default(),
+				ownership: Ownable::new(Self::env().caller()),
+				asset,
+				oracle,
+				rate_model,
+				cash: 0,
+				total_borrows: 0,
+				borrow_index: ONE,
+				last_accrual: Self::env().block_number(),
+				debts: Mapping::default(),
+				collateral_tokens: Vec::new(),
+				collateral_factors: Mapping::default(),
+				collateral: Mapping::default(),
+			}
+		}
+
+		/// Accepts `token` as collateral, of which accounts may borrow up to
+		/// `collateral_factor_bps` of the value. Listing a token again changes its factor,
+		/// and a factor of zero stops new borrows against it. Only the owner may list tokens.
+		#[ink(message)]
+		pub fn list_collateral(
+			&mut self,
+			token: AccountId,
+			collateral_factor_bps: u16,
+		) -> Result<()> {
+			self.ownership
+				.ensure_owner(self.env().caller())
+				.map_err(|_| LendingError::NotOwner)?;
+			if token == self.asset || collateral_factor_bps >= BPS_DENOMINATOR {
+				return Err(LendingError::InvalidCollateral);
+			}
+
+			if !self.collateral_factors.contains(token) {
+				self.collateral_tokens.push(token);
+			}
+			self.collateral_factors.insert(token, &collateral_factor_bps);
+
+			Self::emit_event(
+				self.env(),
+				Event::CollateralListed(CollateralListed { token, collateral_factor_bps }),
+			);
+
+			Ok(())
+		}
+
+		/// Returns the asset lent out and the oracle valuing collateral
+		#[ink(message)]
+		pub fn tokens(&self) -> (AccountId, AccountId) {
+			(self.asset, self.oracle)
+		}
+
+		/// Returns every token listed as collateral, with its collateral factor in basis points
+		#[ink(message)]
+		pub fn collateral_tokens(&self) -> Vec<(AccountId, u16)> {
+			self.collateral_tokens
+				.iter()
+				.map(|token| (*token, self.collateral_factors.get(token).unwrap_or(0)))
+				.collect()
+		}
+
+		/// Returns the asset the pool holds and what borrowers owe it, interest up to this block
+		/// included. Together they are what the lenders' shares are worth.
+		#[ink(message)]
+		pub fn totals(&self) -> Result<(Balance, Balance)> {
+			let index = self.accrued_index()?;
+			Ok((self.cash, self.borrows_at(index)?))
+		}
+
+		/// Returns the interest rate borrowers pay per block at the current utilization
+		#[ink(message)]
+		pub fn borrow_rate(&self) -> u128 {
+			self.rate_model.borrow_rate(self.cash, self.total_borrows)
+		}
+
+		/// Returns what `account` owes, interest up to this block included
+		#[ink(message)]
+		pub fn debt_of(&self, account: AccountId) -> Result<Balance> {
+			self.debt_at(account, self.accrued_index()?)
+		}
+
+		/// Returns how much of `token` `account` deposited as collateral
+		#[ink(message)]
+		pub fn collateral_of(&self, account: AccountId, token: AccountId) -> Balance {
+			self.collateral.get((account, token)).unwrap_or(0)
+		}
+
+		/// Returns how much `account` may owe against its collateral at current prices
+		#[ink(message)]
+		pub fn borrow_limit(&self, account: AccountId) -> Result<Balance> {
+			let mut limit: Balance = 0;
+			for token in &self.collateral_tokens {
+				let amount = self.collateral_of(account, *token);
+				if amount == 0 {
+					continue;
+				}
+				let value = math::mul_div(amount, self.price_of(*token)?, ONE)
+					.ok_or(LendingError::Overflow)?;
+				let factor = self.collateral_factors.get(token).unwrap_or(0);
+				limit = limit.saturating_add(bps_of(value, factor));
+			}
+			Ok(limit)
+		}
+
+		/// Lends `amount` of the asset to the pool for shares, and returns the shares
+		#[ink(message)]
+		pub fn deposit(&mut self, amount: Balance) -> Result<Balance> {
+			self.accrue()?;
+			let lender = self.env().caller();
+			let total_assets = self.cash + self.total_borrows;
+			let amount = self.pull(self.asset, lender, amount)?;
+
+			let total_shares = self.shares.total_supply();
+			let shares = if total_shares == 0 || total_assets == 0 {
+				amount
+			} else {
+				math::mul_div(amount, total_shares, total_assets).ok_or(LendingError::Overflow)?
+			};
+			if shares == 0 {
+				return Err(LendingError::InsufficientAmount);
+			}
+
+			self.cash += amount;
+			let events = self.shares.mint(lender, shares)?;
+			self.emit_events(events);
+			Self::emit_event(self.env(), Event::Deposited(Deposited { lender, amount, shares }));
+
+			Ok(shares)
+		}
+
+		/// Redeems `shares` of the caller for the asset they are worth, and returns the amount.
+		/// Only what isn't lent out can be withdrawn.
+		#[ink(message)]
+		pub fn withdraw(&mut self, shares: Balance) -> Result<Balance> {
+			self.accrue()?;
+			let lender = self.env().caller();
+			let total_assets = self.cash + self.total_borrows;
+			let amount =
+				math::mul_div(shares, total_assets, self.shares.total_supply()).unwrap_or(0);
+			if amount == 0 {
+				return Err(LendingError::InsufficientAmount);
+			}
+			if amount > self.cash {
+				return Err(LendingError::InsufficientLiquidity);
+			}
+
+			let events = self.shares.burn(lender, shares)?;
+			self.emit_events(events);
+			self.cash -= amount;
+			self.push(self.asset, lender, amount)?;
+
+			Self::emit_event(self.env(), Event::Withdrawn(Withdrawn { lender, amount, shares }));
+
+			Ok(amount)
+		}
+
+		/// Deposits `amount` of the listed `token` as the caller's collateral
+		#[ink(message)]
+		pub fn deposit_collateral(&mut self, token: AccountId, amount: Balance) -> Result<()> {
+			if !self.collateral_factors.contains(token) {
+				return Err(LendingError::CollateralNotListed);
+			}
+
+			let account = self.env().caller();
+			let amount = self.pull(token, account, amount)?;
+			self.collateral
+				.insert((account, token), &(self.collateral_of(account, token) + amount));
+
+			Self::emit_event(
+				self.env(),
+				Event::CollateralDeposited(CollateralDeposited { account, token, amount }),
+			);
+
+			Ok(())
+		}
+
+		/// Returns `amount` of the caller's collateral in `token`, as long as the rest still
+		/// covers the caller's debt
+		#[ink(message)]
+		pub fn withdraw_collateral(&mut self, token: AccountId, amount: Balance) -> Result<()> {
+			self.accrue()?;
+			let account = self.env().caller();
+			let balance = self.collateral_of(account, token);
+			if amount > balance {
+				return Err(LendingError::InsufficientCollateral);
+			}
+
+			self.collateral.insert((account, token), &(balance - amount));
+			self.ensure_covered(account)?;
+			self.push(token, account, amount)?;
+
+			Self::emit_event(
+				self.env(),
+				Event::CollateralWithdrawn(CollateralWithdrawn { account, token, amount }),
+			);
+
+			Ok(())
+		}
+
+		/// Lends the caller `amount` of the asset, as long as their collateral covers their debt
+		/// afterwards
+		#[ink(message)]
+		pub fn borrow(&mut self, amount: Balance) -> Result<()> {
+			self.accrue()?;
+			if amount == 0 {
+				return Err(LendingError::InsufficientAmount);
+			}
+			if amount > self.cash {
+				return Err(LendingError::InsufficientLiquidity);
+			}
+
+			let borrower = self.env().caller();
+			// Rounded up, so that borrowers never owe less than they got
+			let scaled =
+				math::mul_div_up(amount, ONE, self.borrow_index).ok_or(LendingError::Overflow)?;
+			let debt = self.debts.get(borrower).unwrap_or(0);
+			self.debts
+				.insert(borrower, &debt.checked_add(scaled).ok_or(LendingError::Overflow)?);
+			self.total_borrows =
+				self.total_borrows.checked_add(amount).ok_or(LendingError::Overflow)?;
+			self.cash -= amount;
+			self.ensure_covered(borrower)?;
+			self.push(self.asset, borrower, amount)?;
+
+			Self::emit_event(self.env(), Event::Borrowed(Borrowed { borrower, amount }));
+
+			Ok(())
+		}
+
+		/// Pays back up to `amount` of the caller's debt, and returns how much was repaid
+		#[ink(message)]
+		pub fn repay(&mut self, amount: Balance) -> Result<Balance> {
+			self.accrue()?;
+			let borrower = self.env().caller();
+			let debt = self.debt_at(borrower, self.borrow_index)?;
+			if debt == 0 {
+				return Err(LendingError::NoDebt);
+			}
+
+			let amount = self.pull(self.asset, borrower, amount.min(debt))?;
+			self.reduce_debt(borrower, amount, debt)?;
+
+			Self::emit_event(self.env(), Event::Repaid(Repaid { borrower, amount }));
+
+			Ok(amount)
+		}
+
+		/// Repays `amount` of the debt of `borrower`, whose collateral no longer covers it, and
+		/// seizes their collateral in `token` worth `LIQUIDATION_BONUS_BPS` more than that.
+		/// Returns the collateral seized.
+		#[ink(message)]
+		pub fn liquidate(
+			&mut self,
+			borrower: AccountId,
+			token: AccountId,
+			amount: Balance,
+		) -> Result<Balance> {
+			self.accrue()?;
+			let debt = self.debt_at(borrower, self.borrow_index)?;
+			if debt <= self.borrow_limit(borrower)? {
+				return Err(LendingError::NotLiquidatable);
+			}
+			if amount > bps_of(debt, CLOSE_FACTOR_BPS) {
+				return Err(LendingError::RepayTooLarge);
+			}
+
+			let liquidator = self.env().caller();
+			let price = self.price_of(token)?;
+			let repaid = self.pull(self.asset, liquidator, amount)?;
+			if repaid == 0 {
+				return Err(LendingError::InsufficientAmount);
+			}
+			let value = repaid.saturating_add(bps_of(repaid, LIQUIDATION_BONUS_BPS));
+			let seized = math::mul_div(value, ONE, price).ok_or(LendingError::Overflow)?;
+			let collateral = self.collateral_of(borrower, token);
+			if seized > collateral {
+				return Err(LendingError::InsufficientCollateral);
+			}
+
+			self.collateral.insert((borrower, token), &(collateral - seized));
+			self.reduce_debt(borrower, repaid, debt)?;
+			self.push(token, liquidator, seized)?;
+
+			Self::emit_event(
+				self.env(),
+				Event::Liquidated(Liquidated { liquidator, borrower, token, repaid, seized }),
+			);
+
+			Ok(seized)
+		}
+
+		/// The borrow index with interest up to this block added
+		fn accrued_index(&self) -> Result<u128> {
+			let blocks = self.env().block_number().saturating_sub(self.last_accrual);
+			let rate = self.rate_model.borrow_rate(self.cash, self.total_borrows);
+			math::accrue_interest(self.borrow_index, rate, blocks).ok_or(LendingError::Overflow)
+		}
+
+		/// What borrowers owe in total once the borrow index has grown to `index`
+		fn borrows_at(&self, index: u128) -> Result<Balance> {
+			math::mul_div(self.total_borrows, index, self.borrow_index)
+				.ok_or(LendingError::Overflow)
+		}
+
+		/// What `account` owes at borrow index `index`, rounded up
+		fn debt_at(&self, account: AccountId, index: u128) -> Result<Balance> {
+			let scaled = self.debts.get(account).unwrap_or(0);
+			math::mul_div_up(scaled, index, ONE).ok_or(LendingError::Overflow)
+		}
+
+		/// Adds the interest borrowers owe since the last accrual
+		fn accrue(&mut self) -> Result<()> {
+			let index = self.accrued_index()?;
+			self.total_borrows = self.borrows_at(index)?;
+			self.borrow_index = index;
+			self.last_accrual = self.env().block_number();
+			Ok(())
+		}
+
+		/// Takes `amount` off the `debt` of `borrower`, paid back to the pool
+		fn reduce_debt(
+			&mut self,
+			borrower: AccountId,
+			amount: Balance,
+			debt: Balance,
+		) -> Result<()> {
+			let scaled = match amount >= debt {
+				true => 0,
+				false => {
+					let repaid = math::mul_div(amount, ONE, self.borrow_index)
+						.ok_or(LendingError::Overflow)?;
+					self.debts.get(borrower).unwrap_or(0).saturating_sub(repaid)
+				},
+			};
+			match scaled {
+				0 => self.debts.remove(borrower),
+				scaled => {
+					self.debts.insert(borrower, &scaled);
+				},
+			}
+			self.total_borrows = self.total_borrows.saturating_sub(amount);
+			self.cash += amount;
+			Ok(())
+		}
+
+		fn ensure_covered(&self, account: AccountId) -> Result<()> {
+			let debt = self.debt_at(account, self.borrow_index)?;
+			if debt > 0 && debt > self.borrow_limit(account)? {
+				return Err(LendingError::InsufficientCollateral);
+			}
+			Ok(())
+		}
+
+		/// What one unit of `token` is worth in the asset, according to the oracle
+		fn price_of(&self, token: AccountId) -> Result<u128> {
+			let oracle: ink::contract_ref!(PriceOracle) = self.oracle.into();
+			Ok(oracle.price(token, self.asset)?)
+		}
+
+		/// Moves `amount` of `token` from `from` to the pool and returns how much arrived
+		fn pull(&self, token: AccountId, from: AccountId, amount: Balance) -> Result<Balance> {
+			let pool = self.env().account_id();
+			let mut psp22: ink::contract_ref!(PSP22) = token.into();
+			let before = psp22.balance_of(pool);
+			psp22.transfer_from(from, pool, amount, Vec::new())?;
+			Ok(psp22.balance_of(pool).saturating_sub(before))
+		}
+
+		/// Moves `amount` of `token` from the pool to `to`
+		fn push(&self, token: AccountId, to: AccountId, amount: Balance) -> Result<()> {
+			let mut psp22: ink::contract_ref!(PSP22) = token.into();
+			psp22.transfer(to, amount, Vec::new())?;
+			Ok(())
+		}
+
+		psp22_traits::impl_emit_events!();
+
+		fn emit_event<EE>(emitter: EE, event: Event)
+		where
+			EE: EmitEvent<Self>,
+		{
+			emitter.emit_event(event);
+		}
+	}
+
+	impl PSP22 for LendingPool {
+		#[ink(message)]
+		fn total_supply(&self) -> Balance {
+			self.shares.total_supply()
+		}
+
+		#[ink(message)]
+		fn balance_of(&self, owner: AccountId) -> Balance {
+			self.shares.balance_of(owner)
+		}
+
+		#[ink(message)]
+		fn allowance(&self, owner: AccountId, spender: AccountId) -> Balance {
+			self.shares.allowance(owner, spender)
+		}
+
+		#[ink(message)]
+		fn approve(
+			&mut self,
+			spender: AccountId,
+			amount: Balance,
+		) -> core::result::Result<(), PSP22Error> {
+			let events = self.shares.approve(self.env().caller(), spender, amount)?;
+			self.emit_events(events);
+			Ok(())
+		}
+
+		#[ink(message)]
+		fn increase_allowance(
+			&mut self,
+			spender: AccountId,
+			by: Balance,
+		) -> core::result::Result<(), PSP22Error> {
+			let events = self.shares.increase_allowance(self.env().caller(), spender, by)?;
+			self.emit_events(events);
+			Ok(())
+		}
+
+		#[ink(message)]
+		fn decrease_allowance(
+			&mut self,
+			spender: AccountId,
+			by: Balance,
+		) -> core::result::Result<(), PSP22Error> {
+			let events = self.shares.decrease_allowance(self.env().caller(), spender, by)?;
+			self.emit_events(events);
+			Ok(())
+		}
+
+		#[ink(message)]
+		fn transfer(
+			&mut self,
+			to: AccountId,
+			value: Balance,
+			data: Vec<u8>,
+		) -> core::result::Result<(), PSP22Error> {
+			let caller = self.env().caller();
+			let events = self.shares.transfer(caller, to, value)?;
+			self.emit_events(events);
+			notify_receiver(self.env().caller(), caller, to, value, data)
+		}
+
+		#[ink(message)]
+		fn transfer_from(
+			&mut self,
+			from: AccountId,
+			to: AccountId,
+			value: Balance,
+			data: Vec<u8>,
+		) -> core::result::Result<(), PSP22Error> {
+			let events = self.shares.transfer_from(self.env().caller(), from, to, value)?;
+			self.emit_events(events);
+			notify_receiver(self.env().caller(), from, to, value, data)
+		}
+	}
+
+	#[cfg(test)]
+	mod tests {
+		use super::*;
+
+		/// 1% per block when everything is lent out
+		const RATE_MODEL: InterestRateModel =
+			InterestRateModel { base_rate: ONE / 1_000, slope: ONE / 100 - ONE / 1_000 };
+
+		fn default_accounts() -> ink::env::test::DefaultAccounts<Environment> {
+			ink::env::test::default_accounts::<Environment>()
+		}
+
+		fn lending_pool() -> LendingPool {
+			let default_accounts = default_accounts();
+			ink::env::test::set_caller::<Environment>(default_accounts.alice);
+			LendingPool::new(default_accounts.django, default_accounts.eve, RATE_MODEL)
+		}
+
+		#[test]
+		fn test_the_rate_grows_with_utilization() {
+			assert_eq!(RATE_MODEL.borrow_rate(0, 0), ONE / 1_000);
+			assert_eq!(RATE_MODEL.borrow_rate(1_000, 0), ONE / 1_000);
+			assert_eq!(
+				RATE_MODEL.borrow_rate(500, 500),
+				ONE / 1_000 + (ONE / 100 - ONE / 1_000) / 2
+			);
+			assert_eq!(RATE_MODEL.borrow_rate(0, 1_000), ONE / 100);
+
+			let absurd = InterestRateModel { base_rate: u128::MAX, slope: u128::MAX };
+			assert_eq!(absurd.borrow_rate(0, 1_000), u128::MAX);
+		}
+
+		#[ink::test]
+		fn test_debts_grow_with_interest() {
+			let default_accounts = default_accounts();
+			let mut lending_pool = lending_pool();
+			// Bob borrowed 1000 of the 2000 deposited when the pool was created
+			lending_pool.cash = 1_000;
+			lending_pool.total_borrows = 1_000;
+			lending_pool.debts.insert(default_accounts.bob, &1_000);
+
+			assert_eq!(lending_pool.debt_of(default_accounts.bob), Ok(1_000));
+			ink::env::test::advance_block::<Environment>();
+			ink::env::test::advance_block::<Environment>();
+
+			// 0.55% per block at half utilization, compounded
+			assert_eq!(lending_pool.debt_of(default_accounts.bob), Ok(1_012));
+			assert_eq!(lending_pool.totals(), Ok((1_000, 1_011)));
+			assert_eq!(lending_pool.accrue(), Ok(()));
+			assert_eq!(lending_pool.total_borrows, 1_011);
+			assert_eq!(lending_pool.debt_of(default_accounts.bob), Ok(1_012));
+
+			// Paying back the whole debt clears it, whatever the rounding
+			assert_eq!(lending_pool.reduce_debt(default_accounts.bob, 1_012, 1_012), Ok(()));
+			assert_eq!(lending_pool.debt_of(default_accounts.bob), Ok(0));
+			assert_eq!(lending_pool.totals(), Ok((2_012, 0)));
+		}
+
+		#[ink::test]
+		fn test_only_the_owner_lists_collateral() {
+			let default_accounts = default_accounts();
+			let mut lending_pool = lending_pool();
+
+			assert_eq!(
+				lending_pool.list_collateral(default_accounts.django, 5_000),
+				Err(LendingError::InvalidCollateral)
+			);
+			assert_eq!(
+				lending_pool.list_collateral(default_accounts.frank, BPS_DENOMINATOR),
+				Err(LendingError::InvalidCollateral)
+			);
+			assert_eq!(lending_pool.list_collateral(default_accounts.frank, 5_000), Ok(()));
+			assert_eq!(lending_pool.list_collateral(default_accounts.frank, 7_500), Ok(()));
+			assert_eq!(lending_pool.collateral_tokens(), vec![(default_accounts.frank, 7_500)]);
+
+			ink::env::test::set_caller::<Environment>(default_accounts.bob);
+			assert_eq!(
+				lending_pool.list_collateral(default_accounts.charlie, 5_000),
+				Err(LendingError::NotOwner)
+			);
+			assert_eq!(
+				lending_pool.deposit_collateral(default_accounts.charlie, 100),
+				Err(LendingError::CollateralNotListed)
+			);
+			assert_eq!(lending_pool.borrow(0), Err(LendingError::InsufficientAmount));
+			assert_eq!(lending_pool.borrow(1), Err(LendingError::InsufficientLiquidity));
+			assert_eq!(lending_pool.repay(1), Err(LendingError::NoDebt));
+			assert_eq!(lending_pool.withdraw(1), Err(LendingError::InsufficientAmount));
+		}
+	}
+
+	#[cfg(all(test, feature = "e2e-tests"))]
+	mod e2e_tests {
+		use ink_e2e::build_message;
+		use psp22_mock::Psp22MockRef;
+
+		use super::*;
+
+		type E2EResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+		#[ink_e2e::test(additional_contracts = "../psp22_mock/Cargo.toml")]
+		async fn test_lenders_redeem_their_shares(
+			mut client: ink_e2e::Client<C, E>,
+		) -> E2EResult<()> {
+			let alice = ink_e2e::account_id(ink_e2e::AccountKeyring::Alice);
+
+			let asset = client
+				.instantiate("psp22_mock", &ink_e2e::alice(), Psp22MockRef::new(1_000), 0, None)
+				.await
+				.expect("instantiate asset failed")
+				.account_id;
+			let rate_model = InterestRateModel { base_rate: 0, slope: ONE / 1_000 };
+			// Nothing is borrowed, so the oracle is never asked
+			let constructor = LendingPoolRef::new(asset.clone(), alice.clone(), rate_model);
+			let pool = client
+				.instantiate("lending", &ink_e2e::alice(), constructor, 0, None)
+				.await
+				.expect("instantiate pool failed")
+				.account_id;
+
+			let approve = build_message::<Psp22MockRef>(asset.clone())
+				.call(|asset| asset.approve(pool.clone(), 1_000));
+			client.call(&ink_e2e::alice(), approve, 0, None).await.expect("approve failed");
+			let deposit =
+				build_message::<LendingPoolRef>(pool.clone()).call(|pool| pool.deposit(1_000));
+			let shares = client
+				.call(&ink_e2e::alice(), deposit, 0, None)
+				.await
+				.expect("deposit failed")
+				.return_value()
+				.expect("deposit rejected");
+			assert_eq!(shares, 1_000);
+
+			let withdraw =
+				build_message::<LendingPoolRef>(pool.clone()).call(|pool| pool.withdraw(shares));
+			let amount = client
+				.call(&ink_e2e::alice(), withdraw, 0, None)
+				.await
+				.expect("withdraw failed")
+				.return_value()
+				.expect("withdraw rejected");
+			assert_eq!(amount, 1_000);
+
+			let balance = build_message::<Psp22MockRef>(asset.clone())
+				.call(|asset| asset.balance_of(alice.clone()));
+			let balance =
+				client.call_dry_run(&ink_e2e::alice(), &balance, 0, None).await.return_value();
+			assert_eq!(balance, 1_000);
+
+			Ok(())
+		}
+	}
+}
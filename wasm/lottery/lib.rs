@@ -0,0 +1,401 @@
+#![cfg_attr(not(feature = "std"), no_std, no_main)]
+
+use ink::env::{DefaultEnvironment, Environment};
+
+pub use self::lottery::{Lottery, LotteryRef};
+
+/// Randomness provided by the runtime
+#[ink::chain_extension]
+pub trait FetchRandom {
+	type ErrorCode = RandomReadErr;
+
+	/// Returns 32 random bytes for `subject`. The same subject in the same block gives the same
+	/// bytes, so callers mix in what should make a draw unique.
+	#[ink(extension = 1101)]
+	fn fetch_random(subject: [u8; 32]) -> [u8; 32];
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+pub enum RandomReadErr {
+	/// The runtime has no randomness to hand out
+	FailGetRandomSource,
+}
+
+impl ink::env::chain_extension::FromStatusCode for RandomReadErr {
+	fn from_status_code(status_code: u32) -> Result<(), Self> {
+		match status_code {
+			0 => Ok(()),
+			_ => Err(Self::FailGetRandomSource),
+		}
+	}
+}
+
+/// The default environment with randomness from `FetchRandom`. Runtimes plug in their source of
+/// randomness by serving the extension, tests register a deterministic mock of it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+pub enum LotteryEnvironment {}
+
+impl Environment for LotteryEnvironment {
+	const MAX_EVENT_TOPICS: usize = <DefaultEnvironment as Environment>::MAX_EVENT_TOPICS;
+
+	type AccountId = <DefaultEnvironment as Environment>::AccountId;
+	type Balance = <DefaultEnvironment as Environment>::Balance;
+	type Hash = <DefaultEnvironment as Environment>::Hash;
+	type BlockNumber = <DefaultEnvironment as Environment>::BlockNumber;
+	type Timestamp = <DefaultEnvironment as Environment>::Timestamp;
+
+	type ChainExtension = FetchRandom;
+}
+
+/// Sells tickets for a PSP22 token in rounds and draws one winner per round, who wins the pot.
+///
+/// A round accepts tickets until its last block. After that anyone may draw it, which picks a
+/// ticket at random, credits its holder with everything paid for the round's tickets and opens
+/// the next round. Winners claim their winnings whenever they like.
+#[ink::contract(env = crate::LotteryEnvironment)]
+mod lottery {
+	use ink::{
+		codegen::EmitEvent,
+		env::hash::{Blake2x256, CryptoHash},
+		prelude::vec::Vec,
+		reflect::ContractEventBase,
+		storage::Mapping,
+	};
+	use psp22_traits::{PSP22Error, PSP22};
+
+	use crate::RandomReadErr;
+
+	pub type RoundId = u32;
+
+	#[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+	#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+	pub enum LotteryError {
+		/// The ticket price and the length of a round must be positive
+		InvalidLottery,
+		/// The current round no longer sells tickets
+		RoundClosed,
+		/// The current round still sells tickets
+		RoundNotOver,
+		/// At least one ticket must be bought
+		ZeroTickets,
+		/// Less than the price of the tickets arrived
+		InsufficientPayment,
+		/// The caller has no winnings to claim
+		NothingToClaim,
+		/// The price of the tickets doesn't fit in a `Balance`
+		Overflow,
+		/// The runtime had no randomness to draw with
+		RandomnessUnavailable,
+		/// A token transfer failed
+		PSP22(PSP22Error),
+	}
+
+	impl From<PSP22Error> for LotteryError {
+		fn from(e: PSP22Error) -> Self {
+			LotteryError::PSP22(e)
+		}
+	}
+
+	impl From<RandomReadErr> for LotteryError {
+		fn from(_: RandomReadErr) -> Self {
+			LotteryError::RandomnessUnavailable
+		}
+	}
+
+	pub type Result<T> = core::result::Result<T, LotteryError>;
+
+	type Event = <Lottery as ContractEventBase>::Type;
+
+	#[ink(event)]
+	pub struct TicketsBought {
+		#[ink(topic)]
+		round: RoundId,
+		#[ink(topic)]
+		buyer: AccountId,
+		count: u32,
+	}
+
+	#[ink(event)]
+	pub struct Drawn {
+		#[ink(topic)]
+		round: RoundId,
+		#[ink(topic)]
+		winner: AccountId,
+		prize: Balance,
+	}
+
+	#[ink(event)]
+	pub struct Claimed {
+		#[ink(topic)]
+		winner: AccountId,
+		amount: Balance,
+	}
+
+	#[ink(storage)]
+	pub struct Lottery {
+		token: AccountId,
+		ticket_price: Balance,
+		/// How many blocks each round sells tickets for
+		round_length: BlockNumber,
+		round: RoundId,
+		/// The last block in which the current round sells tickets
+		round_end: BlockNumber,
+		/// The holder of every ticket of the current round, by ticket number
+		tickets: Mapping<(RoundId, u32), AccountId>,
+		ticket_count: u32,
+		/// What was paid for the tickets of the current round
+		pot: Balance,
+		winnings: Mapping<AccountId, Balance>,
+	}
+
+	impl Lottery {
+		/// Sells tickets for `ticket_price` of `token` in rounds of `round_length` blocks, the
+		/// first of which starts now.
+		#[ink(constructor)]
+		pub fn new(
+			token: AccountId,
+			ticket_price: Balance,
+			round_length: BlockNumber,
+		) -> Result<Self> {
+			if ticket_price == 0 || round_length == 0 {
+				return Err(LotteryError::InvalidLottery);
+			}
+
+			Ok(Self {
+				token,
+				ticket_price,
+				round_length,
+				round: 0,
+				round_end: Self::env().block_number().saturating_add(round_length),
+				tickets: Mapping::default(),
+				ticket_count: 0,
+				pot: 0,
+				winnings: Mapping::default(),
+			})
+		}
+
+		/// Returns the current round and its last block for tickets
+		#[ink(message)]
+		pub fn round(&self) -> (RoundId, BlockNumber) {
+			(self.round, self.round_end)
+		}
+
+		/// Returns how many tickets the current round sold and what was paid for them
+		#[ink(message)]
+		pub fn pot(&self) -> (u32, Balance) {
+			(self.ticket_count, self.pot)
+		}
+
+		/// Returns what `account` won and hasn't claimed yet
+		#[ink(message)]
+		pub fn winnings_of(&self, account: AccountId) -> Balance {
+			self.winnings.get(account).unwrap_or(0)
+		}
+
+		/// Sells the caller `count` tickets for the current round, paid in the lottery's token,
+		/// which the caller approves the lottery to take first.
+		#[ink(message)]
+		pub fn buy_tickets(&mut self, count: u32) -> Result<()> {
+			if count == 0 {
+				return Err(LotteryError::ZeroTickets);
+			}
+			if self.env().block_number() > self.round_end {
+				return Err(LotteryError::RoundClosed);
+			}
+			let price = self
+				.ticket_price
+				.checked_mul(Balance::from(count))
+				.ok_or(LotteryError::Overflow)?;
+
+			let buyer = self.env().caller();
+			let this = self.env().account_id();
+			let mut psp22: ink::contract_ref!(PSP22) = self.token.into();
+			let before = psp22.balance_of(this);
+			psp22.transfer_from(buyer, this, price, Vec::new())?;
+			// Tokens charging a fee on transfers can't pay for full tickets
+			if psp22.balance_of(this).saturating_sub(before) < price {
+				return Err(LotteryError::InsufficientPayment);
+			}
+
+			self.issue_tickets(buyer, count, price);
+
+			Ok(())
+		}
+
+		/// Draws the winner of the current round and opens the next one. Anyone may draw once
+		/// the round no longer sells tickets. Returns the winner, unless nobody bought a ticket.
+		#[ink(message)]
+		pub fn draw(&mut self) -> Result<Option<AccountId>> {
+			let block = self.env().block_number();
+			if block <= self.round_end {
+				return Err(LotteryError::RoundNotOver);
+			}
+
+			let round = self.round;
+			let winner = match self.ticket_count {
+				0 => None,
+				count => {
+					let mut subject = [0; 32];
+					Blake2x256::hash(&scale::Encode::encode(&(round, block, count)), &mut subject);
+					let random = self.env().extension().fetch_random(subject)?;
+					let ticket = winning_ticket(random, count);
+					let winner =
+						self.tickets.get((round, ticket)).expect("every ticket has a holder");
+
+					let prize = self.pot;
+					self.winnings.insert(winner, &(self.winnings_of(winner) + prize));
+					Self::emit_event(self.env(), Event::Drawn(Drawn { round, winner, prize }));
+					Some(winner)
+				},
+			};
+
+			self.round += 1;
+			self.round_end = block.saturating_add(self.round_length);
+			self.ticket_count = 0;
+			self.pot = 0;
+
+			Ok(winner)
+		}
+
+		/// Pays the caller their winnings, and returns the amount
+		#[ink(message)]
+		pub fn claim(&mut self) -> Result<Balance> {
+			let winner = self.env().caller();
+			let amount = self.winnings.take(winner).unwrap_or(0);
+			if amount == 0 {
+				return Err(LotteryError::NothingToClaim);
+			}
+
+			let mut psp22: ink::contract_ref!(PSP22) = self.token.into();
+			psp22.transfer(winner, amount, Vec::new())?;
+
+			Self::emit_event(self.env(), Event::Claimed(Claimed { winner, amount }));
+
+			Ok(amount)
+		}
+
+		/// Records `count` tickets of the current round for `buyer`, who paid `price` for them
+		fn issue_tickets(&mut self, buyer: AccountId, count: u32, price: Balance) {
+			for ticket in self.ticket_count..self.ticket_count + count {
+				self.tickets.insert((self.round, ticket), &buyer);
+			}
+			self.ticket_count += count;
+			self.pot += price;
+
+			Self::emit_event(
+				self.env(),
+				Event::TicketsBought(TicketsBought { round: self.round, buyer, count }),
+			);
+		}
+
+		fn emit_event<EE>(emitter: EE, event: Event)
+		where
+			EE: EmitEvent<Self>,
+		{
+			emitter.emit_event(event);
+		}
+	}
+
+	/// The ticket `random` picks out of `count`
+	fn winning_ticket(random: [u8; 32], count: u32) -> u32 {
+		let mut bytes = [0; 8];
+		bytes.copy_from_slice(&random[..8]);
+		(u64::from_le_bytes(bytes) % u64::from(count)) as u32
+	}
+
+	#[cfg(test)]
+	mod tests {
+		use super::*;
+
+		/// Hands out the same bytes every time
+		struct MockedRandomness([u8; 32]);
+
+		impl ink::env::test::ChainExtension for MockedRandomness {
+			fn func_id(&self) -> u32 {
+				1101
+			}
+
+			fn call(&mut self, _input: &[u8], output: &mut Vec<u8>) -> u32 {
+				scale::Encode::encode_to(&self.0, output);
+				0
+			}
+		}
+
+		fn default_accounts() -> ink::env::test::DefaultAccounts<Environment> {
+			ink::env::test::default_accounts::<Environment>()
+		}
+
+		fn advance_past(block: BlockNumber) {
+			while ink::env::block_number::<Environment>() <= block {
+				ink::env::test::advance_block::<Environment>();
+			}
+		}
+
+		#[ink::test]
+		fn test_the_random_ticket_wins_the_pot() {
+			let default_accounts = default_accounts();
+			let mut random = [0; 32];
+			random[0] = 6;
+			ink::env::test::register_chain_extension(MockedRandomness(random));
+			let mut lottery = Lottery::new(default_accounts.django, 10, 5).expect("valid lottery");
+
+			lottery.issue_tickets(default_accounts.alice, 3, 30);
+			lottery.issue_tickets(default_accounts.bob, 4, 40);
+			assert_eq!(lottery.pot(), (7, 70));
+			assert_eq!(lottery.draw(), Err(LotteryError::RoundNotOver));
+
+			advance_past(5);
+			assert_eq!(lottery.buy_tickets(1), Err(LotteryError::RoundClosed));
+			// Ticket 6 of 7 is one of bob's
+			assert_eq!(lottery.draw(), Ok(Some(default_accounts.bob)));
+			assert_eq!(lottery.winnings_of(default_accounts.bob), 70);
+			assert_eq!(lottery.winnings_of(default_accounts.alice), 0);
+			assert_eq!(lottery.pot(), (0, 0));
+			assert_eq!(lottery.round().0, 1);
+
+			advance_past(lottery.round().1);
+			assert_eq!(lottery.draw(), Ok(None));
+			assert_eq!(lottery.round().0, 2);
+		}
+
+		#[ink::test]
+		fn test_a_failing_randomness_source_keeps_the_round() {
+			struct FailingRandomness;
+
+			impl ink::env::test::ChainExtension for FailingRandomness {
+				fn func_id(&self) -> u32 {
+					1101
+				}
+
+				fn call(&mut self, _input: &[u8], _output: &mut Vec<u8>) -> u32 {
+					1
+				}
+			}
+
+			let default_accounts = default_accounts();
+			ink::env::test::register_chain_extension(FailingRandomness);
+			assert_eq!(
+				Lottery::new(default_accounts.django, 0, 5).err(),
+				Some(LotteryError::InvalidLottery)
+			);
+			let mut lottery = Lottery::new(default_accounts.django, 10, 5).expect("valid lottery");
+			lottery.issue_tickets(default_accounts.alice, 1, 10);
+
+			advance_past(5);
+			assert_eq!(lottery.draw(), Err(LotteryError::RandomnessUnavailable));
+			assert_eq!(lottery.buy_tickets(0), Err(LotteryError::ZeroTickets));
+			assert_eq!(lottery.claim(), Err(LotteryError::NothingToClaim));
+		}
+
+		#[test]
+		fn test_winning_tickets_are_in_range() {
+			for count in 1..50 {
+				for byte in 0..=u8::MAX {
+					assert!(winning_ticket([byte; 32], count) < count);
+				}
+			}
+		}
+	}
+}
@@ -0,0 +1,216 @@
+#![cfg_attr(not(feature = "std"), no_std, no_main)]
+
+/// Villages often run more than one resource market. This contract answers questions about all
+/// of them at once: how much of each resource is available in total, and how many credits the
+/// caller holds across the markets. It keeps no state of its own and only reads from the markets.
+/// A market that can't be read is left out of the totals rather than failing the whole query.
+#[ink::contract]
+mod market_aggregator {
+	use ink::{
+		codegen::TraitCallBuilder,
+		env::call::FromAccountId,
+		prelude::{vec, vec::Vec},
+	};
+	use resource_market::{Resource, ResourceMarketRef};
+
+	/// The combined state of several markets
+	#[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+	#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+	pub struct Aggregate {
+		/// The total amount of each resource available across the markets
+		pub stock: Vec<(Resource, u64)>,
+		/// The total amount of credits the caller holds across the markets
+		pub credits: u64,
+		/// The markets that could not be read, e.g. because they are not markets, and which are
+		/// left out of the totals
+		pub failed: Vec<AccountId>,
+	}
+
+	/// What a single market reported
+	#[derive(Debug, Clone, PartialEq, Eq)]
+	struct Reading {
+		/// The stock of each resource, in the order of `Resource::ALL`
+		stock: Vec<u64>,
+		credits: u64,
+	}
+
+	#[ink(storage)]
+	#[derive(Default)]
+	pub struct MarketAggregator {}
+
+	impl MarketAggregator {
+		#[ink(constructor)]
+		pub fn new() -> Self {
+			Self {}
+		}
+
+		/// Combined stock levels and the caller's credits across `markets`. Markets that can't be
+		/// read are listed in the result instead of failing the whole query.
+		#[ink(message)]
+		pub fn aggregate(&self, markets: Vec<AccountId>) -> Aggregate {
+			let caller = self.env().caller();
+			let readings = markets
+				.into_iter()
+				.map(|market| (market, read_market(market, caller)))
+				.collect();
+
+			combine(readings)
+		}
+
+		/// The total amount of `resource` available across `markets`. Markets that can't be read
+		/// are skipped, `aggregate` lists them.
+		#[ink(message)]
+		pub fn total_stock(&self, markets: Vec<AccountId>, resource: Resource) -> u64 {
+			markets
+				.into_iter()
+				.filter_map(|market| stock_of(&market_ref(market), resource))
+				.fold(0u64, u64::saturating_add)
+		}
+	}
+
+	fn market_ref(market: AccountId) -> ResourceMarketRef {
+		FromAccountId::from_account_id(market)
+	}
+
+	/// The stock of `resource` in `market`, or `None` if the market can't be read. A market
+	/// that reads but doesn't trade `resource` holds none of it.
+	fn stock_of(market: &ResourceMarketRef, resource: Resource) -> Option<u64> {
+		match market.call().get_resource(resource).try_invoke() {
+			Ok(Ok(stock)) => Some(stock.unwrap_or(0)),
+			_ => None,
+		}
+	}
+
+	/// Reads the stock and the credits of `account` from `market`, or `None` if any of it can't
+	/// be read
+	fn read_market(market: AccountId, account: AccountId) -> Option<Reading> {
+		let market = market_ref(market);
+		let stock = Resource::ALL
+			.into_iter()
+			.map(|resource| stock_of(&market, resource))
+			.collect::<Option<Vec<_>>>()?;
+		let credits = market.call().credits_of(account).try_invoke().ok()?.ok()?;
+
+		Some(Reading { stock, credits })
+	}
+
+	/// Adds up the markets that could be read, and lists those that couldn't
+	fn combine(readings: Vec<(AccountId, Option<Reading>)>) -> Aggregate {
+		let mut stock = vec![0u64; Resource::ALL.len()];
+		let mut credits = 0u64;
+		let mut failed = Vec::new();
+		for (market, reading) in readings {
+			let Some(reading) = reading else {
+				failed.push(market);
+				continue;
+			};
+			for (total, amount) in stock.iter_mut().zip(reading.stock) {
+				*total = total.saturating_add(amount);
+			}
+			credits = credits.saturating_add(reading.credits);
+		}
+
+		Aggregate { stock: Resource::ALL.into_iter().zip(stock).collect(), credits, failed }
+	}
+
+	#[cfg(test)]
+	mod tests {
+		use super::*;
+
+		#[ink::test]
+		fn test_readable_markets_are_added_up_and_the_rest_listed() {
+			let accounts = ink::env::test::default_accounts::<Environment>();
+			let reading =
+				|stock: [u64; 4], credits| Some(Reading { stock: stock.to_vec(), credits });
+
+			let aggregate = combine(vec![
+				(accounts.alice, reading([10, 20, 30, 0], 5)),
+				(accounts.bob, None),
+				(accounts.charlie, reading([1, 2, u64::MAX, 4], u64::MAX)),
+			]);
+			assert_eq!(
+				aggregate,
+				Aggregate {
+					stock: vec![
+						(Resource::Food, 11),
+						(Resource::Water, 22),
+						(Resource::Wood, u64::MAX),
+						(Resource::Native, 4),
+					],
+					credits: u64::MAX,
+					failed: vec![accounts.bob],
+				}
+			);
+		}
+	}
+
+	#[cfg(all(test, feature = "e2e-tests"))]
+	mod e2e_tests {
+		use ink_e2e::build_message;
+		use resource_market::ResourceMarketRef;
+
+		use super::*;
+
+		type E2EResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+		#[ink_e2e::test(additional_contracts = "../resource_market/Cargo.toml")]
+		async fn test_aggregates_markets(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+			let mut markets = Vec::new();
+			for (food, water, wood) in [(10, 20, 30), (1, 2, 3)] {
+				let constructor = ResourceMarketRef::new(food, water, wood);
+				let market = client
+					.instantiate("resource_market", &ink_e2e::alice(), constructor, 0, None)
+					.await
+					.expect("instantiate market failed")
+					.account_id;
+				markets.push(market);
+			}
+
+			for market in &markets {
+				let contribute = build_message::<ResourceMarketRef>(market.clone())
+					.call(|market| market.contribute(5, Resource::Wood));
+				client
+					.call(&ink_e2e::bob(), contribute, 0, None)
+					.await
+					.expect("contribute failed");
+			}
+
+			// A plain account is not a market, and is listed rather than failing the query
+			let charlie = ink_e2e::account_id(ink_e2e::AccountKeyring::Charlie);
+			markets.push(charlie.clone());
+
+			let aggregator = client
+				.instantiate(
+					"market_aggregator",
+					&ink_e2e::alice(),
+					MarketAggregatorRef::new(),
+					0,
+					None,
+				)
+				.await
+				.expect("instantiate aggregator failed")
+				.account_id;
+
+			let query = build_message::<MarketAggregatorRef>(aggregator.clone())
+				.call(|aggregator| aggregator.aggregate(markets.clone()));
+			let aggregate =
+				client.call_dry_run(&ink_e2e::bob(), &query, 0, None).await.return_value();
+
+			assert_eq!(
+				aggregate,
+				Aggregate {
+					stock: vec![
+						(Resource::Food, 11),
+						(Resource::Water, 22),
+						(Resource::Wood, 43),
+						(Resource::Native, 0),
+					],
+					credits: 10,
+					failed: vec![charlie],
+				}
+			);
+
+			Ok(())
+		}
+	}
+}
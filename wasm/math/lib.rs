@@ -0,0 +1,224 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! The arithmetic behind balances, fees and rates, shared by the contracts of the workspace so
+//! that rounding and overflow are handled the same way everywhere.
+//!
+//! Everything works on `u128`, the balance type of the contracts, and rounds down unless the
+//! name says otherwise.
+
+/// Basis points in a whole, the denominator of every fee expressed in basis points
+pub const BPS_DENOMINATOR: u16 = 10_000;
+
+/// One in the fixed-point numbers rates are expressed in, which have 18 decimals
+pub const ONE: u128 = 1_000_000_000_000_000_000;
+
+/// Computes `a * b / c`, rounded down. The product is computed on 256 bits, so the result is
+/// exact whenever it fits. Returns `None` if `c` is zero or the result doesn't fit in a `u128`.
+pub fn mul_div(a: u128, b: u128, c: u128) -> Option<u128> {
+	div_rem(full_mul(a, b), c).map(|(quotient, _)| quotient)
+}
+
+/// Computes `a * b / c` like `mul_div`, but rounded up
+pub fn mul_div_up(a: u128, b: u128, c: u128) -> Option<u128> {
+	let (quotient, remainder) = div_rem(full_mul(a, b), c)?;
+	match remainder {
+		0 => Some(quotient),
+		_ => quotient.checked_add(1),
+	}
+}
+
+/// Takes `bps` basis points of `amount`, rounded down. Never overflows for fees of up to
+/// `BPS_DENOMINATOR`, larger ones saturate.
+pub fn bps_of(amount: u128, bps: u16) -> u128 {
+	let (bps, denominator) = (u128::from(bps), u128::from(BPS_DENOMINATOR));
+	// Splitting `amount` keeps every intermediate product below `amount * bps / denominator`
+	(amount / denominator)
+		.saturating_mul(bps)
+		.saturating_add(amount % denominator * bps / denominator)
+}
+
+/// Grows `principal` by `rate_per_block` compounded every block for `blocks` blocks, where the
+/// rate is a fixed-point number with `ONE` as one, so `ONE / 100` is one percent. Every
+/// compounding step rounds down. Returns `None` if the result doesn't fit in a `u128`.
+pub fn accrue_interest(principal: u128, rate_per_block: u128, blocks: u32) -> Option<u128> {
+	// (1 + rate) ^ blocks, by squaring
+	let mut base = ONE.checked_add(rate_per_block)?;
+	let mut factor = ONE;
+	let mut exponent = blocks;
+	while exponent > 0 {
+		if exponent & 1 == 1 {
+			factor = mul_div(factor, base, ONE)?;
+		}
+		exponent >>= 1;
+		if exponent > 0 {
+			base = mul_div(base, base, ONE)?;
+		}
+	}
+
+	mul_div(principal, factor, ONE)
+}
+
+/// Computes the square root of `n`, rounded down
+pub fn sqrt(n: u128) -> u128 {
+	if n < 2 {
+		return n;
+	}
+
+	// Newton's method converges from above, so start at a power of two at least the root
+	let mut root = 1u128 << (128 - n.leading_zeros()).div_ceil(2);
+	loop {
+		let next = (root + n / root) / 2;
+		if next >= root {
+			return root;
+		}
+		root = next;
+	}
+}
+
+/// The 256-bit product of `a` and `b`, as its high and low halves
+fn full_mul(a: u128, b: u128) -> (u128, u128) {
+	const MASK: u128 = u64::MAX as u128;
+	let (a_high, a_low) = (a >> 64, a & MASK);
+	let (b_high, b_low) = (b >> 64, b & MASK);
+
+	let low_low = a_low * b_low;
+	let high_low = a_high * b_low;
+	let low_high = a_low * b_high;
+	let high_high = a_high * b_high;
+
+	// The sum of three numbers below 2^64 can't overflow
+	let middle = (low_low >> 64) + (high_low & MASK) + (low_high & MASK);
+	let low = (middle << 64) | (low_low & MASK);
+	let high = high_high + (high_low >> 64) + (low_high >> 64) + (middle >> 64);
+	(high, low)
+}
+
+/// Divides the 256-bit `(high, low)` by `divisor`, returning the quotient and the remainder if
+/// the divisor is not zero and the quotient fits in a `u128`
+fn div_rem((high, low): (u128, u128), divisor: u128) -> Option<(u128, u128)> {
+	if divisor == 0 || high >= divisor {
+		return None;
+	}
+	if high == 0 {
+		return Some((low / divisor, low % divisor));
+	}
+
+	// Long division one bit at a time. The remainder stays below the divisor, but shifting it
+	// can carry out of 128 bits, in which case it is certainly at least the divisor.
+	let (mut quotient, mut remainder) = (0u128, high);
+	for bit in (0..128).rev() {
+		let carry = remainder >> 127 == 1;
+		remainder = (remainder << 1) | ((low >> bit) & 1);
+		quotient <<= 1;
+		if carry || remainder >= divisor {
+			remainder = remainder.wrapping_sub(divisor);
+			quotient |= 1;
+		}
+	}
+	Some((quotient, remainder))
+}
+
+#[cfg(test)]
+mod tests {
+	use proptest::prelude::*;
+
+	use super::*;
+
+	#[test]
+	fn test_mul_div_matches_plain_arithmetic_on_small_numbers() {
+		for a in 0..64u128 {
+			for b in 0..64u128 {
+				for c in 0..64u128 {
+					let expected = (c != 0).then(|| a * b / c);
+					assert_eq!(mul_div(a, b, c), expected, "{a} * {b} / {c}");
+					let expected_up = (c != 0).then(|| (a * b).div_ceil(c));
+					assert_eq!(mul_div_up(a, b, c), expected_up, "{a} * {b} / {c} rounded up");
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn test_mul_div_is_exact_when_the_product_overflows() {
+		assert_eq!(mul_div(u128::MAX, u128::MAX, u128::MAX), Some(u128::MAX));
+		assert_eq!(mul_div(u128::MAX, 3, 4), Some(u128::MAX / 4 * 3 + 2));
+		assert_eq!(mul_div(1 << 100, 1 << 100, 1 << 90), Some(1 << 110));
+		assert_eq!(mul_div(u128::MAX, 2, 1), None);
+		assert_eq!(mul_div(u128::MAX, u128::MAX, u128::MAX - 1), None);
+		assert_eq!(mul_div_up(u128::MAX, u128::MAX - 1, u128::MAX), Some(u128::MAX - 1));
+		assert_eq!(mul_div_up(u128::MAX, 1, u128::MAX - 1), Some(2));
+		assert_eq!(mul_div_up(u128::MAX, u128::MAX, 1), None);
+	}
+
+	#[test]
+	fn test_bps_of_rounds_down_without_overflowing() {
+		for amount in 0..20_000u128 {
+			for bps in [0, 1, 9, 30, 2_500, 9_999, BPS_DENOMINATOR] {
+				assert_eq!(bps_of(amount, bps), amount * u128::from(bps) / 10_000);
+			}
+		}
+		assert_eq!(bps_of(u128::MAX, BPS_DENOMINATOR), u128::MAX);
+		assert_eq!(bps_of(u128::MAX, 5_000), u128::MAX / 2);
+		assert_eq!(bps_of(u128::MAX, u16::MAX), u128::MAX);
+	}
+
+	#[test]
+	fn test_interest_compounds_every_block() {
+		assert_eq!(accrue_interest(1_000, ONE / 100, 0), Some(1_000));
+		assert_eq!(accrue_interest(1_000, 0, 1_000_000), Some(1_000));
+		assert_eq!(accrue_interest(1_000, ONE / 100, 1), Some(1_010));
+		// 1.01 ^ 2 = 1.0201
+		assert_eq!(accrue_interest(10_000, ONE / 100, 2), Some(10_201));
+		// Doubling every block
+		assert_eq!(accrue_interest(3, ONE, 10), Some(3 << 10));
+		assert_eq!(accrue_interest(1, ONE, 60), Some(1 << 60));
+		// The growth factor is a fixed-point number too, so it overflows first
+		assert_eq!(accrue_interest(1, ONE, 70), None);
+		assert_eq!(accrue_interest(1, u128::MAX, 1), None);
+
+		// Compounding one block at a time rounds down more often than all at once, but only
+		// in the last digits
+		let mut principal = ONE;
+		for _ in 0..50 {
+			principal = accrue_interest(principal, ONE / 1_000, 1).unwrap();
+		}
+		let at_once = accrue_interest(ONE, ONE / 1_000, 50).unwrap();
+		assert!(at_once >= principal && at_once - principal < 100);
+	}
+
+	#[test]
+	fn test_sqrt_rounds_down() {
+		let mut root = 0u128;
+		for n in 0..100_000u128 {
+			if (root + 1) * (root + 1) <= n {
+				root += 1;
+			}
+			assert_eq!(sqrt(n), root, "sqrt({n})");
+		}
+		assert_eq!(sqrt(u128::MAX), u128::from(u64::MAX));
+		assert_eq!(sqrt(u128::from(u64::MAX) * u128::from(u64::MAX)), u128::from(u64::MAX));
+		assert_eq!(sqrt(u128::from(u64::MAX) * u128::from(u64::MAX) - 1), u128::from(u64::MAX) - 1);
+	}
+
+	proptest! {
+		#[test]
+		fn test_mul_div_brackets_the_exact_quotient(a: u128, b: u128, c in 1..=u128::MAX) {
+			let product = full_mul(a, b);
+			match mul_div(a, b, c) {
+				Some(quotient) => {
+					prop_assert!(full_mul(quotient, c) <= product);
+					let next = full_mul(quotient + 1, c);
+					prop_assert!(product < next || quotient == u128::MAX);
+				},
+				None => prop_assert!(product.0 >= c),
+			}
+		}
+
+		#[test]
+		fn test_sqrt_brackets_the_root(n: u128) {
+			let root = sqrt(n);
+			prop_assert!(root * root <= n);
+			prop_assert!((root + 1).checked_mul(root + 1).is_none_or(|square| square > n));
+		}
+	}
+}
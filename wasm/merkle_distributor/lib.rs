@@ -0,0 +1,337 @@
+#![cfg_attr(not(feature = "std"), no_std, no_main)]
+
+pub use psp22_traits::{airdrop_leaf, hash_pair};
+
+pub use self::merkle_distributor::{DistributorError, MerkleDistributor, MerkleDistributorRef};
+
+/// Airdrops an existing PSP22 token, for tokens that can't run an airdrop themselves.
+///
+/// The distributor is funded with the airdropped tokens and commits to its `(index, account,
+/// amount)` entries with a Merkle root, so the list itself never goes on-chain. Each account
+/// claims its entry with a proof until the deadline, after which the owner sweeps whatever
+/// wasn't claimed. Leaves and proofs are built the same way as for the token's own airdrops.
+#[ink::contract]
+mod merkle_distributor {
+	use access_control::Ownable;
+	use ink::{
+		codegen::EmitEvent, prelude::vec::Vec, reflect::ContractEventBase, storage::Mapping,
+	};
+	use psp22_traits::{airdrop_leaf, proof_root, PSP22Error, PSP22};
+
+	#[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+	#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+	pub enum DistributorError {
+		NotOwner,
+		/// The proof doesn't show that the entry is part of the airdrop
+		InvalidProof,
+		/// The entry was already claimed
+		AlreadyClaimed,
+		/// Claims are no longer accepted
+		DeadlinePassed,
+		/// Unclaimed tokens can only be swept after the deadline
+		DeadlineNotPassed,
+		/// A token transfer failed
+		PSP22(PSP22Error),
+	}
+
+	impl From<PSP22Error> for DistributorError {
+		fn from(e: PSP22Error) -> Self {
+			DistributorError::PSP22(e)
+		}
+	}
+
+	pub type Result<T> = core::result::Result<T, DistributorError>;
+
+	type Event = <MerkleDistributor as ContractEventBase>::Type;
+
+	/// `account` claimed `amount` tokens from entry `index`
+	#[ink(event)]
+	pub struct Claimed {
+		index: u32,
+		#[ink(topic)]
+		account: AccountId,
+		amount: Balance,
+	}
+
+	/// The owner took back the `amount` unclaimed tokens
+	#[ink(event)]
+	pub struct Swept {
+		amount: Balance,
+	}
+
+	#[ink(storage)]
+	pub struct MerkleDistributor {
+		ownership: Ownable,
+		token: AccountId,
+		/// The Merkle root of the airdrop's `(index, account, amount)` entries
+		root: [u8; 32],
+		/// The first block in which claims are no longer accepted
+		deadline: BlockNumber,
+		/// Claimed bitmap of the entries, in words of 128 entries
+		claimed: Mapping<u32, u128>,
+	}
+
+	impl MerkleDistributor {
+		/// Airdrops `token` to the entries committed to by `root`, which can be claimed until
+		/// block `deadline`. The tokens are sent to the distributor after it is instantiated.
+		/// The caller becomes the owner.
+		#[ink(constructor)]
+		pub fn new(token: AccountId, root: [u8; 32], deadline: BlockNumber) -> Self {
+			Self {
+				ownership: Ownable::new(Self::env().caller()),
+				token,
+				root,
+				deadline,
+				claimed: Mapping::default(),
+			}
+		}
+
+		/// Returns the airdropped token, the Merkle root of the entries and the deadline
+		#[ink(message)]
+		pub fn airdrop(&self) -> (AccountId, [u8; 32], BlockNumber) {
+			(self.token, self.root, self.deadline)
+		}
+
+		/// Returns true if entry `index` was claimed
+		#[ink(message)]
+		pub fn is_claimed(&self, index: u32) -> bool {
+			let word = self.claimed.get(index / 128).unwrap_or(0);
+			word & (1 << (index % 128)) != 0
+		}
+
+		/// Sends `amount` tokens to the caller if `proof` shows that entry `index` belongs to
+		/// them. Each entry can be claimed once, until the deadline.
+		#[ink(message)]
+		pub fn claim(&mut self, index: u32, amount: Balance, proof: Vec<[u8; 32]>) -> Result<()> {
+			if self.env().block_number() >= self.deadline {
+				return Err(DistributorError::DeadlinePassed);
+			}
+			let caller = self.env().caller();
+			self.verify_proof(index, caller, amount, &proof)?;
+			self.set_claimed(index)?;
+
+			let mut psp22: ink::contract_ref!(PSP22) = self.token.into();
+			psp22.transfer(caller, amount, Vec::new())?;
+
+			Self::emit_event(
+				self.env(),
+				Event::Claimed(Claimed { index, account: caller, amount }),
+			);
+
+			Ok(())
+		}
+
+		/// Sends the owner all tokens left unclaimed at the deadline, and returns how many.
+		/// Only the owner may sweep.
+		#[ink(message)]
+		pub fn sweep(&mut self) -> Result<Balance> {
+			let owner = self.env().caller();
+			self.ownership.ensure_owner(owner).map_err(|_| DistributorError::NotOwner)?;
+			if self.env().block_number() < self.deadline {
+				return Err(DistributorError::DeadlineNotPassed);
+			}
+
+			let mut psp22: ink::contract_ref!(PSP22) = self.token.into();
+			let amount = psp22.balance_of(self.env().account_id());
+			if amount > 0 {
+				psp22.transfer(owner, amount, Vec::new())?;
+			}
+
+			Self::emit_event(self.env(), Event::Swept(Swept { amount }));
+
+			Ok(amount)
+		}
+
+		fn verify_proof(
+			&self,
+			index: u32,
+			account: AccountId,
+			amount: Balance,
+			proof: &[[u8; 32]],
+		) -> Result<()> {
+			if proof_root(airdrop_leaf(index, account, amount), proof) != self.root {
+				return Err(DistributorError::InvalidProof);
+			}
+			Ok(())
+		}
+
+		fn set_claimed(&mut self, index: u32) -> Result<()> {
+			if self.is_claimed(index) {
+				return Err(DistributorError::AlreadyClaimed);
+			}
+			let word = self.claimed.get(index / 128).unwrap_or(0);
+			self.claimed.insert(index / 128, &(word | (1 << (index % 128))));
+			Ok(())
+		}
+
+		fn emit_event<EE>(emitter: EE, event: Event)
+		where
+			EE: EmitEvent<Self>,
+		{
+			emitter.emit_event(event);
+		}
+	}
+
+	#[cfg(test)]
+	mod tests {
+		use psp22_traits::hash_pair;
+
+		use super::*;
+
+		fn default_accounts() -> ink::env::test::DefaultAccounts<Environment> {
+			ink::env::test::default_accounts::<Environment>()
+		}
+
+		/// Entries for bob, charlie and eve, and their leaves
+		fn leaves() -> [[u8; 32]; 3] {
+			let default_accounts = default_accounts();
+			[
+				airdrop_leaf(0, default_accounts.bob, 100),
+				airdrop_leaf(1, default_accounts.charlie, 50),
+				airdrop_leaf(200, default_accounts.eve, 25),
+			]
+		}
+
+		fn distributor(deadline: BlockNumber) -> MerkleDistributor {
+			let default_accounts = default_accounts();
+			ink::env::test::set_caller::<Environment>(default_accounts.alice);
+			let [bob, charlie, eve] = leaves();
+			MerkleDistributor::new(
+				default_accounts.django,
+				hash_pair(&hash_pair(&bob, &charlie), &eve),
+				deadline,
+			)
+		}
+
+		#[ink::test]
+		fn test_proofs_show_entries_are_in_the_airdrop() {
+			let default_accounts = default_accounts();
+			let distributor = distributor(10);
+			let [bob, charlie, eve] = leaves();
+			let bob_and_charlie = hash_pair(&bob, &charlie);
+
+			assert_eq!(
+				distributor.verify_proof(0, default_accounts.bob, 100, &[charlie, eve]),
+				Ok(())
+			);
+			assert_eq!(
+				distributor.verify_proof(1, default_accounts.charlie, 50, &[bob, eve]),
+				Ok(())
+			);
+			assert_eq!(
+				distributor.verify_proof(200, default_accounts.eve, 25, &[bob_and_charlie]),
+				Ok(())
+			);
+			assert_eq!(
+				distributor.verify_proof(0, default_accounts.bob, 101, &[charlie, eve]),
+				Err(DistributorError::InvalidProof)
+			);
+			assert_eq!(
+				distributor.verify_proof(0, default_accounts.charlie, 100, &[charlie, eve]),
+				Err(DistributorError::InvalidProof)
+			);
+			assert_eq!(
+				distributor.verify_proof(0, default_accounts.bob, 100, &[charlie]),
+				Err(DistributorError::InvalidProof)
+			);
+		}
+
+		#[ink::test]
+		fn test_entries_are_claimed_once() {
+			let mut distributor = distributor(10);
+
+			assert_eq!(distributor.set_claimed(0), Ok(()));
+			assert_eq!(distributor.set_claimed(200), Ok(()));
+			assert_eq!(distributor.set_claimed(0), Err(DistributorError::AlreadyClaimed));
+			assert!(distributor.is_claimed(0));
+			assert!(!distributor.is_claimed(1));
+			assert!(!distributor.is_claimed(128));
+			assert!(distributor.is_claimed(200));
+		}
+
+		#[ink::test]
+		fn test_the_deadline_ends_claims_and_allows_sweeping() {
+			let default_accounts = default_accounts();
+			let mut distributor = distributor(2);
+			let [_, charlie, eve] = leaves();
+
+			assert_eq!(distributor.sweep(), Err(DistributorError::DeadlineNotPassed));
+
+			ink::env::test::advance_block::<Environment>();
+			ink::env::test::advance_block::<Environment>();
+			ink::env::test::set_caller::<Environment>(default_accounts.bob);
+			assert_eq!(
+				distributor.claim(0, 100, vec![charlie, eve]),
+				Err(DistributorError::DeadlinePassed)
+			);
+			assert_eq!(distributor.sweep(), Err(DistributorError::NotOwner));
+		}
+	}
+
+	#[cfg(all(test, feature = "e2e-tests"))]
+	mod e2e_tests {
+		use ink_e2e::build_message;
+		use psp22_mock::Psp22MockRef;
+		use psp22_traits::hash_pair;
+
+		use super::*;
+
+		type E2EResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+		#[ink_e2e::test(additional_contracts = "../psp22_mock/Cargo.toml")]
+		async fn test_claims_are_paid_out(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+			let bob = ink_e2e::account_id(ink_e2e::AccountKeyring::Bob);
+			let charlie = ink_e2e::account_id(ink_e2e::AccountKeyring::Charlie);
+			let bob_leaf = airdrop_leaf(0, bob.clone(), 100);
+			let charlie_leaf = airdrop_leaf(1, charlie.clone(), 50);
+
+			let token = client
+				.instantiate("psp22_mock", &ink_e2e::alice(), Psp22MockRef::new(1_000), 0, None)
+				.await
+				.expect("instantiate token failed")
+				.account_id;
+			let distributor = client
+				.instantiate(
+					"merkle_distributor",
+					&ink_e2e::alice(),
+					MerkleDistributorRef::new(
+						token.clone(),
+						hash_pair(&bob_leaf, &charlie_leaf),
+						1_000,
+					),
+					0,
+					None,
+				)
+				.await
+				.expect("instantiate distributor failed")
+				.account_id;
+
+			let fund = build_message::<Psp22MockRef>(token.clone())
+				.call(|token| token.transfer(distributor.clone(), 150, Vec::new()));
+			client.call(&ink_e2e::alice(), fund, 0, None).await.expect("funding failed");
+
+			let claim = build_message::<MerkleDistributorRef>(distributor.clone())
+				.call(|distributor| distributor.claim(0, 100, vec![charlie_leaf]));
+			client
+				.call(&ink_e2e::bob(), claim, 0, None)
+				.await
+				.expect("claim failed")
+				.return_value()
+				.expect("claim rejected");
+
+			let balance = build_message::<Psp22MockRef>(token.clone())
+				.call(|token| token.balance_of(bob.clone()));
+			let balance =
+				client.call_dry_run(&ink_e2e::bob(), &balance, 0, None).await.return_value();
+			assert_eq!(balance, 100);
+
+			let claim_again = build_message::<MerkleDistributorRef>(distributor.clone())
+				.call(|distributor| distributor.claim(0, 100, vec![charlie_leaf]));
+			let result =
+				client.call_dry_run(&ink_e2e::bob(), &claim_again, 0, None).await.return_value();
+			assert_eq!(result, Err(DistributorError::AlreadyClaimed));
+
+			Ok(())
+		}
+	}
+}
@@ -0,0 +1,464 @@
+#![cfg_attr(not(feature = "std"), no_std, no_main)]
+
+pub use self::name_service::{NameService, NameServiceError, NameServiceRef};
+
+/// Human-readable names for workshop accounts and contracts.
+///
+/// A name is registered for a number of periods and paid for in the native currency, with
+/// shorter names costing more per period. Its owner points it at any account, renews it, or
+/// transfers it, and once it expires anyone may register it again. Accounts pick one of the
+/// names pointing at them as their reverse record, so UIs can show a name instead of an address.
+/// The owner of the service sets the fee schedule and withdraws the fees.
+#[ink::contract]
+mod name_service {
+	use access_control::Ownable;
+	use ink::{
+		codegen::EmitEvent,
+		prelude::{string::String, vec::Vec},
+		reflect::ContractEventBase,
+		storage::Mapping,
+	};
+
+	/// Names are at most this many characters long
+	pub const MAX_NAME_LENGTH: usize = 32;
+
+	#[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+	#[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+	pub struct Record {
+		pub owner: AccountId,
+		/// The account the name resolves to
+		pub target: AccountId,
+		/// The first block in which the name is expired
+		pub expires: BlockNumber,
+	}
+
+	#[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+	#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+	pub enum NameServiceError {
+		NotOwner,
+		/// Names consist of lowercase letters, digits and hyphens
+		InvalidName,
+		/// The fee schedule needs at least one fee, periods must be positive
+		InvalidConfig,
+		/// Names are registered and renewed for at least one period
+		ZeroPeriods,
+		/// The name is registered and hasn't expired yet
+		NameTaken,
+		/// The name isn't registered or has expired
+		NameNotFound,
+		/// Only the owner of the name may manage it
+		NotNameOwner,
+		/// The name doesn't resolve to the caller
+		NotTarget,
+		/// Less than the fee was transferred along with the call
+		InsufficientPayment,
+		Overflow,
+		/// Paying out the native currency failed
+		NativeTransferFailed,
+	}
+
+	pub type Result<T> = core::result::Result<T, NameServiceError>;
+
+	type Event = <NameService as ContractEventBase>::Type;
+
+	#[ink(event)]
+	pub struct NameRegistered {
+		name: String,
+		#[ink(topic)]
+		owner: AccountId,
+		expires: BlockNumber,
+	}
+
+	#[ink(event)]
+	pub struct NameRenewed {
+		name: String,
+		expires: BlockNumber,
+	}
+
+	#[ink(event)]
+	pub struct NameTransferred {
+		name: String,
+		#[ink(topic)]
+		from: AccountId,
+		#[ink(topic)]
+		to: AccountId,
+	}
+
+	#[ink(event)]
+	pub struct TargetChanged {
+		name: String,
+		#[ink(topic)]
+		target: AccountId,
+	}
+
+	#[ink(storage)]
+	pub struct NameService {
+		ownership: Ownable,
+		/// The fee per period of a name with one character, two characters and so on, where the
+		/// last fee applies to all longer names
+		fees: Vec<Balance>,
+		/// How many blocks a period lasts
+		period: BlockNumber,
+		/// The latest registration of every name, expired or not
+		records: Mapping<String, Record>,
+		/// The name every account picked to be shown for it
+		reverse: Mapping<AccountId, String>,
+	}
+
+	impl NameService {
+		/// Charges `fees[i]` per period of `period` blocks for names with `i + 1` characters,
+		/// and the last fee for all longer names. The caller becomes the owner.
+		#[ink(constructor)]
+		pub fn new(fees: Vec<Balance>, period: BlockNumber) -> Result<Self> {
+			Self::check_config(&fees, period)?;
+			Ok(Self {
+				ownership: Ownable::new(Self::env().caller()),
+				fees,
+				period,
+				records: Mapping::default(),
+				reverse: Mapping::default(),
+			})
+		}
+
+		/// Registers `name` for `periods` periods, paid out of what the caller transferred along
+		/// with the call, and returns the rest. The caller owns the name and it resolves to the
+		/// caller. Returns the first block in which the name is expired.
+		#[ink(message, payable)]
+		pub fn register(&mut self, name: String, periods: u32) -> Result<BlockNumber> {
+			Self::check_name(&name)?;
+			if self.resolve(name.clone()).is_some() {
+				return Err(NameServiceError::NameTaken);
+			}
+			let block = self.env().block_number();
+			let expires = self.extend(block, periods)?;
+			self.charge(&name, periods)?;
+
+			let owner = self.env().caller();
+			self.records.insert(&name, &Record { owner, target: owner, expires });
+
+			Self::emit_event(
+				self.env(),
+				Event::NameRegistered(NameRegistered { name, owner, expires }),
+			);
+
+			Ok(expires)
+		}
+
+		/// Extends unexpired `name` by `periods` periods, paid out of what the caller
+		/// transferred along with the call, and returns the rest. Anyone may pay for a renewal.
+		/// Returns the first block in which the name is expired.
+		#[ink(message, payable)]
+		pub fn renew(&mut self, name: String, periods: u32) -> Result<BlockNumber> {
+			let mut record = self.get_record(&name)?;
+			record.expires = self.extend(record.expires, periods)?;
+			self.charge(&name, periods)?;
+			self.records.insert(&name, &record);
+
+			Self::emit_event(
+				self.env(),
+				Event::NameRenewed(NameRenewed { name, expires: record.expires }),
+			);
+
+			Ok(record.expires)
+		}
+
+		/// Hands unexpired `name` over to `to`, leaving what it resolves to alone. Only the
+		/// owner of the name may transfer it.
+		#[ink(message)]
+		pub fn transfer(&mut self, name: String, to: AccountId) -> Result<()> {
+			let mut record = self.get_owned_record(&name)?;
+			let from = record.owner;
+			record.owner = to;
+			self.records.insert(&name, &record);
+
+			Self::emit_event(
+				self.env(),
+				Event::NameTransferred(NameTransferred { name, from, to }),
+			);
+
+			Ok(())
+		}
+
+		/// Points unexpired `name` at `target`. Only the owner of the name may change what it
+		/// resolves to.
+		#[ink(message)]
+		pub fn set_target(&mut self, name: String, target: AccountId) -> Result<()> {
+			let mut record = self.get_owned_record(&name)?;
+			record.target = target;
+			self.records.insert(&name, &record);
+
+			Self::emit_event(self.env(), Event::TargetChanged(TargetChanged { name, target }));
+
+			Ok(())
+		}
+
+		/// Picks `name`, which must resolve to the caller, as the name shown for the caller
+		#[ink(message)]
+		pub fn set_reverse(&mut self, name: String) -> Result<()> {
+			let caller = self.env().caller();
+			if self.get_record(&name)?.target != caller {
+				return Err(NameServiceError::NotTarget);
+			}
+			self.reverse.insert(caller, &name);
+			Ok(())
+		}
+
+		/// Returns the account `name` resolves to, unless it isn't registered or has expired
+		#[ink(message)]
+		pub fn resolve(&self, name: String) -> Option<AccountId> {
+			self.get_record(&name).ok().map(|record| record.target)
+		}
+
+		/// Returns the name `account` picked, as long as it still resolves to `account`
+		#[ink(message)]
+		pub fn reverse_lookup(&self, account: AccountId) -> Option<String> {
+			self.reverse
+				.get(account)
+				.filter(|name| self.resolve(name.clone()) == Some(account))
+		}
+
+		/// Returns the latest registration of `name`, expired or not
+		#[ink(message)]
+		pub fn record(&self, name: String) -> Option<Record> {
+			self.records.get(&name)
+		}
+
+		/// Returns the fee for registering or renewing `name` for `periods` periods
+		#[ink(message)]
+		pub fn fee(&self, name: String, periods: u32) -> Result<Balance> {
+			Self::check_name(&name)?;
+			let length = name.chars().count();
+			let fee = self.fees[length.min(self.fees.len()) - 1];
+			fee.checked_mul(periods.into()).ok_or(NameServiceError::Overflow)
+		}
+
+		/// Returns the fee schedule and how many blocks a period lasts
+		#[ink(message)]
+		pub fn fee_schedule(&self) -> (Vec<Balance>, BlockNumber) {
+			(self.fees.clone(), self.period)
+		}
+
+		/// Changes the fee schedule and how many blocks a period lasts, which only affects
+		/// future registrations and renewals. Only the owner may configure the service.
+		#[ink(message)]
+		pub fn set_fee_schedule(&mut self, fees: Vec<Balance>, period: BlockNumber) -> Result<()> {
+			self.ensure_owner()?;
+			Self::check_config(&fees, period)?;
+			self.fees = fees;
+			self.period = period;
+			Ok(())
+		}
+
+		/// Takes `amount` of the collected fees. Only the owner may withdraw.
+		#[ink(message)]
+		pub fn withdraw(&mut self, amount: Balance) -> Result<()> {
+			self.ensure_owner()?;
+			self.pay(self.env().caller(), amount)
+		}
+
+		fn check_config(fees: &[Balance], period: BlockNumber) -> Result<()> {
+			if fees.is_empty() || period == 0 {
+				return Err(NameServiceError::InvalidConfig);
+			}
+			Ok(())
+		}
+
+		fn check_name(name: &str) -> Result<()> {
+			let valid = |c: char| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-';
+			if name.is_empty() || name.len() > MAX_NAME_LENGTH || !name.chars().all(valid) {
+				return Err(NameServiceError::InvalidName);
+			}
+			Ok(())
+		}
+
+		/// Returns the block `periods` periods after `from`
+		fn extend(&self, from: BlockNumber, periods: u32) -> Result<BlockNumber> {
+			if periods == 0 {
+				return Err(NameServiceError::ZeroPeriods);
+			}
+			periods
+				.checked_mul(self.period)
+				.and_then(|blocks| from.checked_add(blocks))
+				.ok_or(NameServiceError::Overflow)
+		}
+
+		/// Takes the fee for `name` and `periods` out of what the caller transferred and returns
+		/// the rest
+		fn charge(&self, name: &str, periods: u32) -> Result<()> {
+			let fee = self.fee(name.into(), periods)?;
+			let paid = self.env().transferred_value();
+			if paid < fee {
+				return Err(NameServiceError::InsufficientPayment);
+			}
+			self.pay(self.env().caller(), paid - fee)
+		}
+
+		fn get_record(&self, name: &str) -> Result<Record> {
+			self.records
+				.get(name)
+				.filter(|record| self.env().block_number() < record.expires)
+				.ok_or(NameServiceError::NameNotFound)
+		}
+
+		fn get_owned_record(&self, name: &str) -> Result<Record> {
+			let record = self.get_record(name)?;
+			if self.env().caller() != record.owner {
+				return Err(NameServiceError::NotNameOwner);
+			}
+			Ok(record)
+		}
+
+		fn pay(&self, to: AccountId, amount: Balance) -> Result<()> {
+			if amount == 0 {
+				return Ok(());
+			}
+			self.env()
+				.transfer(to, amount)
+				.map_err(|_| NameServiceError::NativeTransferFailed)
+		}
+
+		fn ensure_owner(&self) -> Result<()> {
+			self.ownership
+				.ensure_owner(self.env().caller())
+				.map_err(|_| NameServiceError::NotOwner)
+		}
+
+		fn emit_event<EE>(emitter: EE, event: Event)
+		where
+			EE: EmitEvent<Self>,
+		{
+			emitter.emit_event(event);
+		}
+	}
+
+	#[cfg(test)]
+	mod tests {
+		use super::*;
+
+		fn default_accounts() -> ink::env::test::DefaultAccounts<Environment> {
+			ink::env::test::default_accounts::<Environment>()
+		}
+
+		fn name(name: &str) -> String {
+			name.into()
+		}
+
+		/// A service charging 100 per period for one character, 50 for two and 10 for longer
+		/// names, with periods of 10 blocks
+		fn service() -> NameService {
+			let default_accounts = default_accounts();
+			ink::env::test::set_caller::<Environment>(default_accounts.alice);
+			let service = NameService::new(vec![100, 50, 10], 10).expect("valid config");
+			ink::env::test::set_account_balance::<Environment>(
+				ink::env::account_id::<Environment>(),
+				1_000,
+			);
+			service
+		}
+
+		fn register(
+			service: &mut NameService,
+			caller: AccountId,
+			name: &str,
+			value: Balance,
+		) -> Result<BlockNumber> {
+			ink::env::test::set_caller::<Environment>(caller);
+			ink::env::test::set_value_transferred::<Environment>(value);
+			service.register(name.into(), 2)
+		}
+
+		#[ink::test]
+		fn test_fees_depend_on_the_name_length() {
+			let service = service();
+
+			assert_eq!(service.fee(name("a"), 1), Ok(100));
+			assert_eq!(service.fee(name("ab"), 3), Ok(150));
+			assert_eq!(service.fee(name("abc"), 2), Ok(20));
+			assert_eq!(service.fee(name("workshop-2023"), 2), Ok(20));
+			assert_eq!(service.fee(name(""), 1), Err(NameServiceError::InvalidName));
+			assert_eq!(service.fee(name("Alice"), 1), Err(NameServiceError::InvalidName));
+			assert_eq!(service.fee(name("a.b"), 1), Err(NameServiceError::InvalidName));
+			assert_eq!(
+				service.fee("a".repeat(MAX_NAME_LENGTH + 1), 1),
+				Err(NameServiceError::InvalidName)
+			);
+		}
+
+		#[ink::test]
+		fn test_names_expire() {
+			let default_accounts = default_accounts();
+			let mut service = service();
+
+			assert_eq!(
+				register(&mut service, default_accounts.bob, "bob", 19),
+				Err(NameServiceError::InsufficientPayment)
+			);
+			assert_eq!(register(&mut service, default_accounts.bob, "bob", 25), Ok(20));
+			assert_eq!(service.resolve(name("bob")), Some(default_accounts.bob));
+			assert_eq!(
+				register(&mut service, default_accounts.charlie, "bob", 20),
+				Err(NameServiceError::NameTaken)
+			);
+
+			// Anyone may pay for a renewal
+			ink::env::test::set_value_transferred::<Environment>(10);
+			assert_eq!(service.renew(name("bob"), 1), Ok(30));
+
+			for _ in 0..30 {
+				ink::env::test::advance_block::<Environment>();
+			}
+			assert_eq!(service.resolve(name("bob")), None);
+			assert_eq!(service.renew(name("bob"), 1), Err(NameServiceError::NameNotFound));
+			assert_eq!(register(&mut service, default_accounts.charlie, "bob", 20), Ok(50));
+			assert_eq!(service.resolve(name("bob")), Some(default_accounts.charlie));
+		}
+
+		#[ink::test]
+		fn test_owners_manage_their_names() {
+			let default_accounts = default_accounts();
+			let mut service = service();
+
+			assert_eq!(register(&mut service, default_accounts.bob, "bob", 20), Ok(20));
+			assert_eq!(service.set_reverse(name("bob")), Ok(()));
+			assert_eq!(service.reverse_lookup(default_accounts.bob), Some(name("bob")));
+
+			ink::env::test::set_caller::<Environment>(default_accounts.charlie);
+			assert_eq!(
+				service.set_target(name("bob"), default_accounts.charlie),
+				Err(NameServiceError::NotNameOwner)
+			);
+			assert_eq!(service.set_reverse(name("bob")), Err(NameServiceError::NotTarget));
+
+			ink::env::test::set_caller::<Environment>(default_accounts.bob);
+			assert_eq!(service.transfer(name("bob"), default_accounts.charlie), Ok(()));
+			assert_eq!(
+				service.set_target(name("bob"), default_accounts.charlie),
+				Err(NameServiceError::NotNameOwner)
+			);
+
+			ink::env::test::set_caller::<Environment>(default_accounts.charlie);
+			assert_eq!(service.set_target(name("bob"), default_accounts.charlie), Ok(()));
+			assert_eq!(service.resolve(name("bob")), Some(default_accounts.charlie));
+			// Bob's reverse record no longer holds once the name points elsewhere
+			assert_eq!(service.reverse_lookup(default_accounts.bob), None);
+		}
+
+		#[ink::test]
+		fn test_only_the_owner_configures() {
+			let default_accounts = default_accounts();
+			let mut service = service();
+
+			assert_eq!(
+				service.set_fee_schedule(Vec::new(), 10),
+				Err(NameServiceError::InvalidConfig)
+			);
+			assert_eq!(service.set_fee_schedule(vec![5], 0), Err(NameServiceError::InvalidConfig));
+			assert_eq!(service.set_fee_schedule(vec![5], 20), Ok(()));
+			assert_eq!(service.fee_schedule(), (vec![5], 20));
+
+			ink::env::test::set_caller::<Environment>(default_accounts.bob);
+			assert_eq!(service.set_fee_schedule(vec![0], 20), Err(NameServiceError::NotOwner));
+			assert_eq!(service.withdraw(100), Err(NameServiceError::NotOwner));
+		}
+	}
+}
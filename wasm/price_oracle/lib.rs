@@ -0,0 +1,379 @@
+#![cfg_attr(not(feature = "std"), no_std, no_main)]
+
+pub use self::price_oracle::{AggregatorError, PriceAggregator, PriceAggregatorRef};
+
+/// Prices of token pairs, aggregated from reporters the owner whitelists.
+///
+/// Every reporter pushes its own price for a pair, and the oracle answers with the median of the
+/// reports that are recent enough, so a single reporter can't move the price on its own. The
+/// owner sets how many blocks a report stays fresh and how many fresh reports a price needs. A
+/// pair only reported the other way round is answered with the inverse price.
+///
+/// Markets, lending pools and DEXes read prices through the `PriceOracle` trait, so they work
+/// with this oracle or any other.
+#[ink::contract]
+mod price_oracle {
+	use access_control::Ownable;
+	use ink::{
+		codegen::EmitEvent, prelude::vec::Vec, reflect::ContractEventBase, storage::Mapping,
+	};
+	use math::ONE;
+	use psp22_traits::{OracleError, PriceOracle};
+
+	#[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+	#[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+	pub struct Report {
+		/// How much of the quote token one unit of the base token is worth, with 18 decimals
+		pub price: u128,
+		/// The block the price was reported in
+		pub block: BlockNumber,
+	}
+
+	/// The base token, quote token and reporter of a report
+	type ReportKey = (AccountId, AccountId, AccountId);
+
+	#[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+	#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+	pub enum AggregatorError {
+		NotOwner,
+		/// Only whitelisted reporters may report prices
+		NotReporter,
+		/// The account already is a reporter
+		ReporterExists,
+		/// A price needs at least one fresh report
+		InvalidConfig,
+		/// Prices must be positive and the tokens of a pair must differ
+		InvalidPrice,
+	}
+
+	pub type Result<T> = core::result::Result<T, AggregatorError>;
+
+	type Event = <PriceAggregator as ContractEventBase>::Type;
+
+	#[ink(event)]
+	pub struct ReporterAdded {
+		#[ink(topic)]
+		reporter: AccountId,
+	}
+
+	#[ink(event)]
+	pub struct ReporterRemoved {
+		#[ink(topic)]
+		reporter: AccountId,
+	}
+
+	#[ink(event)]
+	pub struct PriceReported {
+		#[ink(topic)]
+		base: AccountId,
+		#[ink(topic)]
+		quote: AccountId,
+		#[ink(topic)]
+		reporter: AccountId,
+		price: u128,
+	}
+
+	#[ink(storage)]
+	pub struct PriceAggregator {
+		ownership: Ownable,
+		reporters: Vec<AccountId>,
+		/// The last report of every reporter for every pair
+		reports: Mapping<ReportKey, Report>,
+		/// How many blocks after it was made a report still counts
+		max_age: BlockNumber,
+		/// How many fresh reports a price needs
+		min_reports: u32,
+	}
+
+	impl PriceAggregator {
+		/// Answers with prices reported at most `max_age` blocks ago, as long as there are at
+		/// least `min_reports` of them. The caller becomes the owner, who whitelists reporters.
+		#[ink(constructor)]
+		pub fn new(max_age: BlockNumber, min_reports: u32) -> Result<Self> {
+			if min_reports == 0 {
+				return Err(AggregatorError::InvalidConfig);
+			}
+
+			Ok(Self {
+				ownership: Ownable::new(Self::env().caller()),
+				reporters: Vec::new(),
+				reports: Mapping::default(),
+				max_age,
+				min_reports,
+			})
+		}
+
+		/// Whitelists `reporter`. Only the owner may add reporters.
+		#[ink(message)]
+		pub fn add_reporter(&mut self, reporter: AccountId) -> Result<()> {
+			self.ensure_owner()?;
+			if self.reporters.contains(&reporter) {
+				return Err(AggregatorError::ReporterExists);
+			}
+
+			self.reporters.push(reporter);
+			Self::emit_event(self.env(), Event::ReporterAdded(ReporterAdded { reporter }));
+
+			Ok(())
+		}
+
+		/// Removes `reporter` from the whitelist, and its reports from every price. Only the
+		/// owner may remove reporters.
+		#[ink(message)]
+		pub fn remove_reporter(&mut self, reporter: AccountId) -> Result<()> {
+			self.ensure_owner()?;
+			let index = self
+				.reporters
+				.iter()
+				.position(|r| *r == reporter)
+				.ok_or(AggregatorError::NotReporter)?;
+
+			self.reporters.swap_remove(index);
+			Self::emit_event(self.env(), Event::ReporterRemoved(ReporterRemoved { reporter }));
+
+			Ok(())
+		}
+
+		/// Changes how long reports stay fresh and how many a price needs. Only the owner may
+		/// change them.
+		#[ink(message)]
+		pub fn set_config(&mut self, max_age: BlockNumber, min_reports: u32) -> Result<()> {
+			self.ensure_owner()?;
+			if min_reports == 0 {
+				return Err(AggregatorError::InvalidConfig);
+			}
+
+			self.max_age = max_age;
+			self.min_reports = min_reports;
+
+			Ok(())
+		}
+
+		/// Returns how long reports stay fresh and how many a price needs
+		#[ink(message)]
+		pub fn config(&self) -> (BlockNumber, u32) {
+			(self.max_age, self.min_reports)
+		}
+
+		/// Returns the whitelisted reporters
+		#[ink(message)]
+		pub fn reporters(&self) -> Vec<AccountId> {
+			self.reporters.clone()
+		}
+
+		/// Returns the last report of `reporter` for `base` in `quote`, if it made one
+		#[ink(message)]
+		pub fn report_of(
+			&self,
+			base: AccountId,
+			quote: AccountId,
+			reporter: AccountId,
+		) -> Option<Report> {
+			self.reports.get((base, quote, reporter))
+		}
+
+		/// Records the caller's `price` of `base` in `quote`, with 18 decimals, replacing its
+		/// previous report. Only whitelisted reporters may report.
+		#[ink(message)]
+		pub fn report(&mut self, base: AccountId, quote: AccountId, price: u128) -> Result<()> {
+			let reporter = self.env().caller();
+			if !self.reporters.contains(&reporter) {
+				return Err(AggregatorError::NotReporter);
+			}
+			if price == 0 || base == quote {
+				return Err(AggregatorError::InvalidPrice);
+			}
+
+			let block = self.env().block_number();
+			self.reports.insert((base, quote, reporter), &Report { price, block });
+
+			Self::emit_event(
+				self.env(),
+				Event::PriceReported(PriceReported { base, quote, reporter, price }),
+			);
+
+			Ok(())
+		}
+
+		/// The median of the fresh reports for `base` in `quote`
+		fn fresh_median(
+			&self,
+			base: AccountId,
+			quote: AccountId,
+		) -> core::result::Result<u128, OracleError> {
+			let block = self.env().block_number();
+			let mut reported = false;
+			let mut prices = Vec::new();
+			for reporter in &self.reporters {
+				let Some(report) = self.reports.get((base, quote, *reporter)) else {
+					continue;
+				};
+				reported = true;
+				if block.saturating_sub(report.block) <= self.max_age {
+					prices.push(report.price);
+				}
+			}
+
+			if !reported {
+				return Err(OracleError::PriceNotFound);
+			}
+			if prices.len() < self.min_reports as usize {
+				return Err(OracleError::StalePrice);
+			}
+			Ok(median(&mut prices))
+		}
+
+		fn ensure_owner(&self) -> Result<()> {
+			self.ownership
+				.ensure_owner(self.env().caller())
+				.map_err(|_| AggregatorError::NotOwner)
+		}
+
+		fn emit_event<EE>(emitter: EE, event: Event)
+		where
+			EE: EmitEvent<Self>,
+		{
+			emitter.emit_event(event);
+		}
+	}
+
+	impl PriceOracle for PriceAggregator {
+		/// Returns the median of the fresh reports for the pair, or the inverse of the median
+		/// for the reversed pair if the pair itself was never reported
+		#[ink(message)]
+		fn price(
+			&self,
+			base: AccountId,
+			quote: AccountId,
+		) -> core::result::Result<u128, OracleError> {
+			match self.fresh_median(base, quote) {
+				Err(OracleError::PriceNotFound) => {
+					self.fresh_median(quote, base).and_then(|inverse| {
+						math::mul_div(ONE, ONE, inverse).ok_or(OracleError::PriceNotFound)
+					})
+				},
+				price => price,
+			}
+		}
+	}
+
+	/// The middle one of `prices`, or the mean of the middle two for an even number of prices.
+	/// `prices` must not be empty.
+	fn median(prices: &mut [u128]) -> u128 {
+		prices.sort_unstable();
+		let middle = prices.len() / 2;
+		match prices.len() % 2 {
+			1 => prices[middle],
+			// Halved first, so that the sum can't overflow
+			_ => {
+				let (low, high) = (prices[middle - 1], prices[middle]);
+				low / 2 + high / 2 + (low % 2 + high % 2) / 2
+			},
+		}
+	}
+
+	#[cfg(test)]
+	mod tests {
+		use super::*;
+
+		fn default_accounts() -> ink::env::test::DefaultAccounts<Environment> {
+			ink::env::test::default_accounts::<Environment>()
+		}
+
+		/// An oracle with bob, charlie and django as reporters, of which two must be fresh
+		fn price_aggregator() -> PriceAggregator {
+			let default_accounts = default_accounts();
+			ink::env::test::set_caller::<Environment>(default_accounts.alice);
+			let mut price_aggregator = PriceAggregator::new(2, 2).expect("valid config");
+			for reporter in
+				[default_accounts.bob, default_accounts.charlie, default_accounts.django]
+			{
+				price_aggregator.add_reporter(reporter).expect("alice is the owner");
+			}
+			price_aggregator
+		}
+
+		fn report(
+			price_aggregator: &mut PriceAggregator,
+			reporter: AccountId,
+			price: u128,
+		) -> Result<()> {
+			let default_accounts = default_accounts();
+			ink::env::test::set_caller::<Environment>(reporter);
+			price_aggregator.report(default_accounts.eve, default_accounts.frank, price)
+		}
+
+		#[test]
+		fn test_median_of_odd_and_even_counts() {
+			assert_eq!(median(&mut [3]), 3);
+			assert_eq!(median(&mut [5, 1, 3]), 3);
+			assert_eq!(median(&mut [4, 1, 3, 2]), 2);
+			assert_eq!(median(&mut [1, 2]), 1);
+			assert_eq!(median(&mut [u128::MAX, u128::MAX]), u128::MAX);
+			assert_eq!(median(&mut [u128::MAX - 1, u128::MAX - 3]), u128::MAX - 2);
+		}
+
+		#[ink::test]
+		fn test_prices_are_medians_of_fresh_reports() {
+			let default_accounts = default_accounts();
+			let (eve, frank) = (default_accounts.eve, default_accounts.frank);
+			let mut price_aggregator = price_aggregator();
+
+			assert_eq!(price_aggregator.price(eve, frank), Err(OracleError::PriceNotFound));
+			assert_eq!(report(&mut price_aggregator, default_accounts.bob, 100 * ONE), Ok(()));
+			assert_eq!(price_aggregator.price(eve, frank), Err(OracleError::StalePrice));
+
+			ink::env::test::advance_block::<Environment>();
+			assert_eq!(report(&mut price_aggregator, default_accounts.charlie, 200 * ONE), Ok(()));
+			assert_eq!(report(&mut price_aggregator, default_accounts.django, 120 * ONE), Ok(()));
+			assert_eq!(price_aggregator.price(eve, frank), Ok(120 * ONE));
+			// The reversed pair is answered with the inverse
+			assert_eq!(price_aggregator.price(frank, eve), Ok(ONE / 120));
+
+			// Bob's report is too old now, the others are not
+			ink::env::test::advance_block::<Environment>();
+			ink::env::test::advance_block::<Environment>();
+			assert_eq!(price_aggregator.price(eve, frank), Ok(160 * ONE));
+
+			ink::env::test::advance_block::<Environment>();
+			assert_eq!(price_aggregator.price(eve, frank), Err(OracleError::StalePrice));
+		}
+
+		#[ink::test]
+		fn test_only_whitelisted_reporters_count() {
+			let default_accounts = default_accounts();
+			let (eve, frank) = (default_accounts.eve, default_accounts.frank);
+			let mut price_aggregator = price_aggregator();
+			assert_eq!(
+				price_aggregator.add_reporter(default_accounts.bob),
+				Err(AggregatorError::ReporterExists)
+			);
+			assert_eq!(price_aggregator.set_config(2, 0), Err(AggregatorError::InvalidConfig));
+			assert_eq!(price_aggregator.set_config(2, 1), Ok(()));
+
+			assert_eq!(
+				report(&mut price_aggregator, default_accounts.eve, ONE),
+				Err(AggregatorError::NotReporter)
+			);
+			assert_eq!(
+				report(&mut price_aggregator, default_accounts.bob, 0),
+				Err(AggregatorError::InvalidPrice)
+			);
+			assert_eq!(report(&mut price_aggregator, default_accounts.bob, 3 * ONE), Ok(()));
+			assert_eq!(report(&mut price_aggregator, default_accounts.charlie, ONE), Ok(()));
+			assert_eq!(price_aggregator.price(eve, frank), Ok(2 * ONE));
+			assert_eq!(
+				price_aggregator.remove_reporter(default_accounts.bob),
+				Err(AggregatorError::NotOwner)
+			);
+
+			ink::env::test::set_caller::<Environment>(default_accounts.alice);
+			assert_eq!(price_aggregator.remove_reporter(default_accounts.bob), Ok(()));
+			assert_eq!(
+				price_aggregator.remove_reporter(default_accounts.bob),
+				Err(AggregatorError::NotReporter)
+			);
+			assert_eq!(price_aggregator.price(eve, frank), Ok(ONE));
+		}
+	}
+}
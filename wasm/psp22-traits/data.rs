@@ -0,0 +1,262 @@
+use ink::{
+	prelude::{vec, vec::Vec},
+	primitives::AccountId,
+	storage::Mapping,
+};
+
+use crate::{Approval, Balance, PSP22Error, PSP22Event, Transfer};
+
+/// The balances and allowances of a plain PSP22 token, along with the bookkeeping that keeps
+/// them consistent. A contract embeds it in its storage, checks permissions itself and emits
+/// the returned events as its own `Approval` and `Transfer` events.
+///
+/// Zero balances and allowances are not stored, so they don't hold on to a storage deposit.
+#[ink::storage_item]
+#[derive(Debug, Default)]
+pub struct PSP22Data {
+	total_supply: Balance,
+	balances: Mapping<AccountId, Balance>,
+	allowances: Mapping<(AccountId, AccountId), Balance>,
+}
+
+impl PSP22Data {
+	/// Creates the data of a token whose whole `supply` belongs to `creator`
+	pub fn new(supply: Balance, creator: AccountId) -> (PSP22Data, Vec<PSP22Event>) {
+		let mut data = PSP22Data::default();
+		let events = data.mint(creator, supply).expect("the creator must not be the zero account");
+		(data, events)
+	}
+
+	pub fn total_supply(&self) -> Balance {
+		self.total_supply
+	}
+
+	pub fn balance_of(&self, owner: AccountId) -> Balance {
+		self.balances.get(owner).unwrap_or(0)
+	}
+
+	pub fn allowance(&self, owner: AccountId, spender: AccountId) -> Balance {
+		self.allowances.get((owner, spender)).unwrap_or(0)
+	}
+
+	/// Moves `value` of `caller`'s tokens to `to`. Moving nothing, or to oneself, changes nothing
+	/// and emits nothing.
+	pub fn transfer(
+		&mut self,
+		caller: AccountId,
+		to: AccountId,
+		value: Balance,
+	) -> Result<Vec<PSP22Event>, PSP22Error> {
+		if to == AccountId::from([0u8; 32]) {
+			return Err(PSP22Error::ZeroRecipientAddress);
+		}
+		if caller == to || value == 0 {
+			return Ok(Vec::new());
+		}
+
+		let from_balance = self.balance_of(caller);
+		if from_balance < value {
+			return Err(PSP22Error::InsufficientBalance);
+		}
+		self.write_balance(caller, from_balance - value);
+		// Can't overflow, the sum of all balances is the total supply
+		self.write_balance(to, self.balance_of(to) + value);
+
+		Ok(vec![PSP22Event::Transfer(Transfer { from: caller, to, value })])
+	}
+
+	/// Moves `value` of `from`'s tokens to `to` on behalf of `caller`, using up the allowance
+	/// `from` gave `caller`
+	pub fn transfer_from(
+		&mut self,
+		caller: AccountId,
+		from: AccountId,
+		to: AccountId,
+		value: Balance,
+	) -> Result<Vec<PSP22Event>, PSP22Error> {
+		if to == AccountId::from([0u8; 32]) {
+			return Err(PSP22Error::ZeroRecipientAddress);
+		}
+		if from == to || value == 0 {
+			return Ok(Vec::new());
+		}
+		if caller == from {
+			return self.transfer(caller, to, value);
+		}
+
+		let allowance = self.allowance(from, caller);
+		if allowance < value {
+			return Err(PSP22Error::InsufficientAllowance);
+		}
+		let mut events = self.transfer(from, to, value)?;
+		events.insert(0, self.write_allowance(from, caller, allowance - value));
+
+		Ok(events)
+	}
+
+	/// Sets the allowance of `spender` over `owner`'s tokens to `amount`
+	pub fn approve(
+		&mut self,
+		owner: AccountId,
+		spender: AccountId,
+		amount: Balance,
+	) -> Result<Vec<PSP22Event>, PSP22Error> {
+		if owner == spender {
+			return Ok(Vec::new());
+		}
+
+		Ok(vec![self.write_allowance(owner, spender, amount)])
+	}
+
+	/// Raises the allowance of `spender` over `owner`'s tokens by `by`
+	pub fn increase_allowance(
+		&mut self,
+		owner: AccountId,
+		spender: AccountId,
+		by: Balance,
+	) -> Result<Vec<PSP22Event>, PSP22Error> {
+		if owner == spender || by == 0 {
+			return Ok(Vec::new());
+		}
+
+		let amount = self.allowance(owner, spender).checked_add(by).ok_or(PSP22Error::Overflow)?;
+		Ok(vec![self.write_allowance(owner, spender, amount)])
+	}
+
+	/// Lowers the allowance of `spender` over `owner`'s tokens by `by`
+	pub fn decrease_allowance(
+		&mut self,
+		owner: AccountId,
+		spender: AccountId,
+		by: Balance,
+	) -> Result<Vec<PSP22Event>, PSP22Error> {
+		if owner == spender || by == 0 {
+			return Ok(Vec::new());
+		}
+
+		let amount = self
+			.allowance(owner, spender)
+			.checked_sub(by)
+			.ok_or(PSP22Error::InsufficientAllowance)?;
+		Ok(vec![self.write_allowance(owner, spender, amount)])
+	}
+
+	/// Creates `value` new tokens and credits them to `to`
+	pub fn mint(&mut self, to: AccountId, value: Balance) -> Result<Vec<PSP22Event>, PSP22Error> {
+		if to == AccountId::from([0u8; 32]) {
+			return Err(PSP22Error::ZeroRecipientAddress);
+		}
+		if value == 0 {
+			return Ok(Vec::new());
+		}
+
+		self.total_supply = self.total_supply.checked_add(value).ok_or(PSP22Error::Overflow)?;
+		self.write_balance(to, self.balance_of(to) + value);
+
+		Ok(vec![PSP22Event::Transfer(Transfer { from: AccountId::from([0u8; 32]), to, value })])
+	}
+
+	/// Destroys `value` of the tokens held by `from`
+	pub fn burn(&mut self, from: AccountId, value: Balance) -> Result<Vec<PSP22Event>, PSP22Error> {
+		if value == 0 {
+			return Ok(Vec::new());
+		}
+
+		let balance = self.balance_of(from);
+		if balance < value {
+			return Err(PSP22Error::InsufficientBalance);
+		}
+		self.write_balance(from, balance - value);
+		self.total_supply -= value;
+
+		Ok(vec![PSP22Event::Transfer(Transfer { from, to: AccountId::from([0u8; 32]), value })])
+	}
+
+	fn write_balance(&mut self, owner: AccountId, balance: Balance) {
+		if balance == 0 {
+			self.balances.remove(owner);
+		} else {
+			self.balances.insert(owner, &balance);
+		}
+	}
+
+	fn write_allowance(
+		&mut self,
+		owner: AccountId,
+		spender: AccountId,
+		amount: Balance,
+	) -> PSP22Event {
+		if amount == 0 {
+			self.allowances.remove((owner, spender));
+		} else {
+			self.allowances.insert((owner, spender), &amount);
+		}
+
+		PSP22Event::Approval(Approval { owner, spender, amount })
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn accounts() -> ink::env::test::DefaultAccounts<ink::env::DefaultEnvironment> {
+		ink::env::test::default_accounts::<ink::env::DefaultEnvironment>()
+	}
+
+	#[ink::test]
+	fn test_transfer_from_uses_up_the_allowance() {
+		let accounts = accounts();
+		let (mut data, events) = PSP22Data::new(100, accounts.alice);
+		assert_eq!(
+			events,
+			vec![PSP22Event::Transfer(Transfer {
+				from: AccountId::from([0u8; 32]),
+				to: accounts.alice,
+				value: 100
+			})]
+		);
+
+		assert_eq!(
+			data.transfer_from(accounts.bob, accounts.alice, accounts.charlie, 10),
+			Err(PSP22Error::InsufficientAllowance)
+		);
+		data.approve(accounts.alice, accounts.bob, 30).unwrap();
+		assert_eq!(
+			data.transfer_from(accounts.bob, accounts.alice, accounts.charlie, 30),
+			Ok(vec![
+				PSP22Event::Approval(Approval {
+					owner: accounts.alice,
+					spender: accounts.bob,
+					amount: 0
+				}),
+				PSP22Event::Transfer(Transfer {
+					from: accounts.alice,
+					to: accounts.charlie,
+					value: 30
+				}),
+			])
+		);
+		assert_eq!(data.balance_of(accounts.alice), 70);
+		assert_eq!(data.balance_of(accounts.charlie), 30);
+		assert!(!data.allowances.contains((accounts.alice, accounts.bob)));
+	}
+
+	#[ink::test]
+	fn test_mint_and_burn_track_the_total_supply() {
+		let accounts = accounts();
+		let (mut data, _) = PSP22Data::new(100, accounts.alice);
+
+		assert_eq!(data.mint(accounts.bob, Balance::MAX), Err(PSP22Error::Overflow));
+		data.mint(accounts.bob, 50).unwrap();
+		assert_eq!(data.burn(accounts.bob, 51), Err(PSP22Error::InsufficientBalance));
+		data.burn(accounts.bob, 50).unwrap();
+
+		assert_eq!(data.total_supply(), 100);
+		assert!(!data.balances.contains(accounts.bob));
+		assert_eq!(
+			data.transfer(accounts.alice, AccountId::from([0u8; 32]), 10),
+			Err(PSP22Error::ZeroRecipientAddress)
+		);
+	}
+}
@@ -0,0 +1,64 @@
+use ink::primitives::AccountId;
+
+use crate::Balance;
+
+/// `owner` allowed `spender` to withdraw up to `amount` of its tokens
+#[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+pub struct Approval {
+	pub owner: AccountId,
+	pub spender: AccountId,
+	pub amount: Balance,
+}
+
+/// `value` tokens moved from `from` to `to`. Minted tokens come from the zero account and
+/// burned tokens go to it, like in ERC-20.
+#[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+pub struct Transfer {
+	pub from: AccountId,
+	pub to: AccountId,
+	pub value: Balance,
+}
+
+/// The events every PSP22 in the workspace declares first, in this order and with these fields
+/// first. The data of a contract event is its event enum encoded, so the data of these events
+/// decodes as a `PSP22Event` whatever else the contract emits. Fields a contract adds at the
+/// end, like the token's event id, are ignored.
+#[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+pub enum PSP22Event {
+	Approval(Approval),
+	Transfer(Transfer),
+}
+
+impl PSP22Event {
+	/// Decodes the data of a contract event, or returns `None` if it is not an approval or a
+	/// transfer
+	pub fn decode_from(mut data: &[u8]) -> Option<PSP22Event> {
+		<PSP22Event as scale::Decode>::decode(&mut data).ok()
+	}
+}
+
+/// Defines `fn emit_events(&self, events: Vec<PSP22Event>)` in the `impl` block of a contract,
+/// which emits the events `PSP22Data` returns as the contract's own `Approval` and `Transfer`
+/// events. The contract's `Event` enum must have both, with the fields of the ones above, and
+/// the contract an `emit_event` function taking the emitter and the event.
+#[macro_export]
+macro_rules! impl_emit_events {
+	() => {
+		fn emit_events(&self, events: ::ink::prelude::vec::Vec<$crate::PSP22Event>) {
+			for event in events {
+				let event = match event {
+					$crate::PSP22Event::Approval($crate::Approval { owner, spender, amount }) => {
+						Event::Approval(Approval { owner, spender, amount })
+					},
+					$crate::PSP22Event::Transfer($crate::Transfer { from, to, value }) => {
+						Event::Transfer(Transfer { from, to, value })
+					},
+				};
+				Self::emit_event(self.env(), event);
+			}
+		}
+	};
+}
@@ -3,18 +3,152 @@
 #![allow(unused)] // TODO remove when approaching completion 🏁
 
 use ink::{
+	codegen::TraitCallBuilder,
 	env::{DefaultEnvironment, Environment},
-	prelude::vec::Vec,
+	prelude::{string::String, vec::Vec},
 	primitives::AccountId,
 };
 
+mod data;
+mod events;
+mod merkle;
+
+pub use data::PSP22Data;
+pub use events::{Approval, PSP22Event, Transfer};
+pub use merkle::{airdrop_leaf, hash_pair, proof_root};
+
 pub type Balance = <DefaultEnvironment as Environment>::Balance;
+pub type BlockNumber = <DefaultEnvironment as Environment>::BlockNumber;
 
 #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
 #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
 pub enum PSP22Error {
 	InsufficientBalance,
 	InsufficientAllowance,
+	/// Only the token owner may perform this action
+	NotOwner,
+	/// The total supply would no longer fit in a `Balance`
+	Overflow,
+	/// Minting would take the total supply above the cap
+	CapExceeded,
+	/// Transfers and approvals are halted while the token is paused
+	Paused,
+	/// Only the proposed new owner may accept ownership
+	NotPendingOwner,
+	/// The caller lacks the role required for this action
+	MissingRole,
+	/// The receiving contract does not take tokens, or failed while being notified
+	ReceiverRejected,
+	/// The sender or recipient is on the token's deny-list
+	AccountFrozen,
+	/// A fee must not exceed the whole amount it is charged on
+	InvalidFee,
+	/// A rebase must leave a positive total supply that fits in a `Balance`
+	InvalidRebase,
+	/// A flash loan was not paid back with its fee by the end of the call
+	FlashLoanNotRepaid,
+	/// A stream needs a deposit and must stop after it starts
+	InvalidStream,
+	/// There is no stream with the given id
+	StreamNotFound,
+	/// Only the sender or recipient of a stream may do this
+	NotStreamParty,
+	/// There is no pending transfer with the given id
+	TransferNotFound,
+	/// Only the sender or recipient of a pending transfer may do this
+	NotTransferParty,
+	/// The escrowed transfer expired and can no longer be claimed
+	TransferExpired,
+	/// The escrowed transfer can't be refunded before it expires
+	TransferNotExpired,
+	/// The scheduled transfer can't be executed yet
+	TransferNotDue,
+	/// The Merkle proof does not match the airdrop root
+	InvalidProof,
+	/// This airdrop entry was already claimed
+	AlreadyClaimed,
+	/// Lists that are matched up pairwise must have the same length
+	LengthMismatch,
+	/// The token's own balance holds escrowed funds and cannot be rescued
+	CannotRescueSelf,
+	/// Replacing the contract code failed
+	UpgradeFailed,
+	/// The storage is already at the latest schema version
+	NothingToMigrate,
+	/// The signature was not made by the expected account
+	InvalidSignature,
+	/// The nonce of a signed message was already used or is out of order
+	InvalidNonce,
+	/// The deadline of a signed message has passed
+	SignatureExpired,
+	/// A signed message can't be used yet
+	SignatureNotYetValid,
+	/// Part of the balance is locked and can't be spent yet
+	TokensLocked,
+	/// The transfer is above the maximum amount or leaves the recipient above the maximum
+	/// balance
+	TransferLimitExceeded,
+	/// The sender transferred too recently
+	TransferCooldown,
+	/// The cross-chain message was already processed
+	AlreadyProcessed,
+	/// The remote chain is not set up for escrow or is paused
+	ChainUnavailable,
+	/// The escrow limit of the remote chain would be exceeded
+	ChainLimitExceeded,
+	/// The account used the faucet too recently
+	FaucetCooldown,
+	/// A call into another contract failed or was reverted
+	CallFailed,
+	/// The counterparty has not offered a swap on exactly these terms
+	NoSwapOffer,
+	/// The deadline of the swap has passed
+	SwapExpired,
+	/// A forced transfer was not given a reason
+	MissingReason,
+	/// The caller is not an operator of the holder
+	NotOperator,
+	/// The transfer volume of the current window would go above its limit
+	VolumeLimitExceeded,
+	/// The emission schedule has no epochs, an empty epoch or a rising rate, or was already set
+	InvalidSchedule,
+	/// The allowance no longer has the value the caller expected
+	AllowanceChanged,
+	/// An error of a contract building on the token, in an encoding of its own
+	Custom(Vec<u8>),
+	/// Tokens can't be sent to the zero account, it stands for burning
+	ZeroRecipientAddress,
+	/// Tokens can't be sent from the zero account, it stands for minting
+	ZeroSenderAddress,
+	/// The receiving contract turned the transfer down, for the given reason
+	SafeTransferCheckFailed(String),
+	/// Sending the native currency failed
+	NativeTransferFailed,
+}
+
+/// The reason a `PSP22Receiver` turned down a transfer
+#[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+pub enum PSP22ReceiverError {
+	TransferRejected(String),
+}
+
+/// Why a `Pausable` contract refused to change its pause state
+#[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+pub enum PausableError {
+	/// The caller may not pause or unpause the contract
+	NotAllowed,
+}
+
+/// Why a `PriceOracle` had no price to give
+#[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+pub enum OracleError {
+	/// The oracle doesn't know the price of the pair
+	PriceNotFound,
+	/// The last price of the pair is too old to rely on
+	StalePrice,
 }
 
 #[ink::trait_definition]
@@ -52,13 +186,206 @@ pub trait PSP22 {
 }
 
 #[ink::trait_definition]
-pub trait Mintable {
+pub trait PSP22Metadata {
 	#[ink(message)]
-	fn mint(&mut self, account: AccountId, amount: Balance) -> Result<(), PSP22Error>;
+	fn token_name(&self) -> Option<String>;
+
+	#[ink(message)]
+	fn token_symbol(&self) -> Option<String>;
+
+	#[ink(message)]
+	fn token_decimals(&self) -> u8;
+}
+
+/// Implemented by contracts that want to be told when they receive tokens. Tokens sent to a
+/// contract that does not implement it are rejected, so they can't get stranded there.
+#[ink::trait_definition]
+pub trait PSP22Receiver {
+	/// Called by the token during a transfer of `value` tokens from `from` to this contract by
+	/// `operator`, once the balances were updated. The caller is the token. Returning an error
+	/// reverts the transfer.
+	#[ink(message)]
+	fn before_received(
+		&mut self,
+		operator: AccountId,
+		from: AccountId,
+		value: Balance,
+		data: Vec<u8>,
+	) -> Result<(), PSP22ReceiverError>;
+}
+
+/// Lets `to` know it received `value` tokens from `from` if it is a contract, and maps what it
+/// answers to the error the token returns. Plain accounts are not notified, and neither is a
+/// contract pulling tokens to itself, which knows about them and can't be called back while it
+/// waits for the transfer anyway.
+pub fn notify_receiver(
+	operator: AccountId,
+	from: AccountId,
+	to: AccountId,
+	value: Balance,
+	data: Vec<u8>,
+) -> Result<(), PSP22Error> {
+	if to == operator || !ink::env::is_contract::<DefaultEnvironment>(&to) {
+		return Ok(());
+	}
+
+	let mut receiver: ink::contract_ref!(PSP22Receiver, DefaultEnvironment) = to.into();
+	match receiver.call_mut().before_received(operator, from, value, data).try_invoke() {
+		Ok(Ok(Ok(()))) => Ok(()),
+		Ok(Ok(Err(PSP22ReceiverError::TransferRejected(reason)))) => {
+			Err(PSP22Error::SafeTransferCheckFailed(reason))
+		},
+		_ => Err(PSP22Error::ReceiverRejected),
+	}
+}
+
+/// Arguments that are already SCALE encoded and are passed on as they are, for calls built
+/// with `ExecutionInput` whose arguments are only known at runtime
+pub struct EncodedArgs(pub Vec<u8>);
+
+impl scale::Encode for EncodedArgs {
+	fn size_hint(&self) -> usize {
+		self.0.len()
+	}
+
+	fn encode_to<T: scale::Output + ?Sized>(&self, dest: &mut T) {
+		dest.write(&self.0);
+	}
+}
+
+/// Implemented by contracts that want to be told when they are given an allowance through
+/// `approve_and_call`
+#[ink::trait_definition]
+pub trait PSP22ApprovalReceiver {
+	/// Called by the token once `owner` allowed this contract to spend `amount` of its tokens.
+	/// The caller is the token, which can't be called back from here. Returning an error
+	/// reverts the approval.
+	#[ink(message)]
+	fn on_approval(
+		&mut self,
+		owner: AccountId,
+		amount: Balance,
+		data: Vec<u8>,
+	) -> Result<(), PSP22ReceiverError>;
+}
+
+/// The PSP22 extension for approvals signed off-chain, so that the spender or a relayer can
+/// submit them and the holder needs no native currency for fees
+#[ink::trait_definition]
+pub trait PSP22Permit {
+	/// Sets the allowance of `spender` over the tokens of `owner` to `value` on behalf of
+	/// `owner`, who signed the approval with its current nonce. The signature is a recoverable
+	/// ECDSA signature and can't be used after block `deadline`.
+	#[ink(message)]
+	fn permit(
+		&mut self,
+		owner: AccountId,
+		spender: AccountId,
+		value: Balance,
+		deadline: BlockNumber,
+		signature: [u8; 65],
+	) -> Result<(), PSP22Error>;
+
+	/// The nonce the next signed message of `owner` must carry
+	#[ink(message)]
+	fn nonce_of(&self, owner: AccountId) -> u64;
+
+	/// The domain signed messages commit to, which binds them to one token on one chain
+	#[ink(message)]
+	fn domain_separator(&self) -> [u8; 32];
 }
 
+/// Implemented by contracts that borrow tokens through a flash loan
 #[ink::trait_definition]
-pub trait Burnable {
+pub trait PSP22FlashBorrower {
+	/// Called by the token once `amount` tokens were minted to this contract. Before returning,
+	/// the contract must hold `amount + fee` tokens, which the token then takes back. Returning an
+	/// error reverts the loan.
 	#[ink(message)]
+	fn on_flash_loan(
+		&mut self,
+		initiator: AccountId,
+		amount: Balance,
+		fee: Balance,
+		data: Vec<u8>,
+	) -> Result<(), PSP22Error>;
+}
+
+/// The PSP22 extension for tokens whose supply can grow. The selector is the standard one, so
+/// any mintable PSP22 token can be called through `ink::contract_ref!(PSP22Mintable)`.
+#[ink::trait_definition]
+pub trait PSP22Mintable {
+	/// Creates `amount` new tokens and credits them to `account`
+	#[ink(message, selector = 0xfc3c75d4)]
+	fn mint(&mut self, account: AccountId, amount: Balance) -> Result<(), PSP22Error>;
+}
+
+/// The PSP22 extension for tokens whose supply can shrink. The selector is the standard one, so
+/// any burnable PSP22 token can be called through `ink::contract_ref!(PSP22Burnable)`.
+#[ink::trait_definition]
+pub trait PSP22Burnable {
+	/// Destroys `amount` tokens held by `account`
+	#[ink(message, selector = 0x7a9da510)]
 	fn burn(&mut self, account: AccountId, amount: Balance) -> Result<(), PSP22Error>;
 }
+
+/// A PSP22 token backed one to one by the native currency, so that contracts can handle the
+/// native currency like any other token
+#[ink::trait_definition]
+pub trait WrappedNative {
+	/// Mints the caller as many tokens as it transferred along with the call
+	#[ink(message, payable)]
+	fn deposit(&mut self) -> Result<(), PSP22Error>;
+
+	/// Burns `amount` of the caller's tokens and sends it as much of the native currency
+	#[ink(message)]
+	fn withdraw(&mut self, amount: Balance) -> Result<(), PSP22Error>;
+}
+
+/// Implemented by contracts that can be halted in an emergency, so that frontends and other
+/// contracts can check the pause state of any of them the same way
+#[ink::trait_definition]
+pub trait Pausable {
+	/// Returns true while the contract is paused
+	#[ink(message)]
+	fn paused(&self) -> bool;
+
+	/// Halts the contract
+	#[ink(message)]
+	fn pause(&mut self) -> Result<(), PausableError>;
+
+	/// Resumes the contract
+	#[ink(message)]
+	fn unpause(&mut self) -> Result<(), PausableError>;
+}
+
+/// Voting power that holders delegate and the token records block by block, so that votes can
+/// be counted as of a past block, e.g. the block a proposal was made in
+#[ink::trait_definition]
+pub trait PSP22Votes {
+	/// Counts the caller's votes towards `delegatee`. Delegate to yourself to vote directly.
+	#[ink(message)]
+	fn delegate(&mut self, delegatee: AccountId) -> Result<(), PSP22Error>;
+
+	/// Returns the account `account` delegates its votes to, if any
+	#[ink(message)]
+	fn delegates(&self, account: AccountId) -> Option<AccountId>;
+
+	/// Returns the current voting power of `account`
+	#[ink(message)]
+	fn get_votes(&self, account: AccountId) -> Balance;
+
+	/// Returns the voting power `account` had at the end of `block`
+	#[ink(message)]
+	fn get_votes_at(&self, account: AccountId, block: BlockNumber) -> Balance;
+}
+
+/// Prices of one token in another, so that markets, lending pools and DEXes can value tokens
+/// through any oracle the same way
+#[ink::trait_definition]
+pub trait PriceOracle {
+	/// Returns how much of `quote` one unit of `base` is worth, as a fixed-point number with 18
+	/// decimals
+	#[ink(message)]
+	fn price(&self, base: AccountId, quote: AccountId) -> Result<u128, OracleError>;
+}
@@ -0,0 +1,28 @@
+use ink::{
+	env::hash::{Blake2x256, HashOutput},
+	primitives::AccountId,
+};
+
+use crate::Balance;
+
+/// The Merkle leaf of the airdrop entry giving `amount` tokens to `account`. The token's own
+/// airdrops and the Merkle distributor build their trees from the same leaves.
+pub fn airdrop_leaf(index: u32, account: AccountId, amount: Balance) -> [u8; 32] {
+	let mut hash = <Blake2x256 as HashOutput>::Type::default();
+	ink::env::hash_encoded::<Blake2x256, _>(&(index, account, amount), &mut hash);
+	hash
+}
+
+/// The parent of two Merkle nodes. Siblings are sorted first, so proofs don't need to say
+/// which side each sibling is on.
+pub fn hash_pair(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+	let pair = if a <= b { (a, b) } else { (b, a) };
+	let mut hash = <Blake2x256 as HashOutput>::Type::default();
+	ink::env::hash_encoded::<Blake2x256, _>(&pair, &mut hash);
+	hash
+}
+
+/// The root `proof` leads to from `leaf`, which is the root of the tree if the proof is valid
+pub fn proof_root(leaf: [u8; 32], proof: &[[u8; 32]]) -> [u8; 32] {
+	proof.iter().fold(leaf, |node, sibling| hash_pair(&node, sibling))
+}
@@ -1,17 +1,292 @@
 #![cfg_attr(not(feature = "std"), no_std, no_main)]
 
+pub use self::psp22::{Token, TokenRef};
+
+/// Conversions between whole tokens, as people write them, and the base units balances are
+/// stored in
+pub mod units {
+	/// The number of base units in one whole token with `decimals` decimals, if it fits
+	pub fn one(decimals: u8) -> Option<u128> {
+		10u128.checked_pow(u32::from(decimals))
+	}
+
+	/// Converts `amount` whole tokens to base units, or `None` on overflow
+	pub fn to_base_units(amount: u128, decimals: u8) -> Option<u128> {
+		amount.checked_mul(one(decimals)?)
+	}
+
+	/// Converts `amount` base units to whole tokens, dropping any fraction of a token
+	pub fn from_base_units(amount: u128, decimals: u8) -> u128 {
+		// More decimals than a u128 can hold means less than one token however large `amount` is
+		one(decimals).map_or(0, |one| amount / one)
+	}
+}
+
+/// The ids of optional features that integrators can look up with `supports`
+pub mod capabilities {
+	/// Transfers signed off-chain and submitted by anyone
+	pub const SIGNED_TRANSFERS: u32 = 0;
+	/// Halting all transfers and approvals
+	pub const PAUSABLE: u32 = 1;
+	/// Accounts barred from sending and receiving
+	pub const DENY_LIST: u32 = 2;
+	/// Fees and burns taken from every transfer
+	pub const TRANSFER_FEES: u32 = 3;
+	/// Supply changes that scale every balance
+	pub const REBASING: u32 = 4;
+	/// Delegated voting power with historical checkpoints
+	pub const VOTES: u32 = 5;
+	/// Loans of freshly minted tokens repaid within the same call
+	pub const FLASH_LOANS: u32 = 6;
+	/// Payments streamed block by block
+	pub const STREAMS: u32 = 7;
+	/// Staking for minted rewards
+	pub const STAKING: u32 = 8;
+	/// Merkle airdrops
+	pub const AIRDROPS: u32 = 9;
+	/// Burning and minting across a bridge
+	pub const BRIDGE: u32 = 10;
+	/// Holding tokens in escrow for other chains
+	pub const ESCROW: u32 = 11;
+	/// Peer-to-peer swaps against other PSP22 tokens
+	pub const SWAPS: u32 = 12;
+	/// Balances split into named partitions
+	pub const PARTITIONS: u32 = 13;
+	/// Balances locked until a given block
+	pub const LOCKS: u32 = 14;
+	/// Anti-bot limits on transfer size, wallet size and frequency
+	pub const TRANSFER_LIMITS: u32 = 15;
+	/// The circuit breaker on transfer volume
+	pub const VOLUME_BREAKER: u32 = 16;
+	/// Code upgrades that keep the storage
+	pub const UPGRADEABLE: u32 = 17;
+	/// Minting a few tokens to anyone who asks, on testnets
+	pub const FAUCET: u32 = 18;
+	/// Freezes and forced transfers by controllers
+	pub const REGULATED: u32 = 19;
+}
+
 #[ink::contract]
 mod psp22 {
 
+	use access_control::{AccessControlError, Ownable, Roles};
 	use ink::{
-		codegen::EmitEvent,
-		env::{balance, call},
-		prelude::vec::Vec,
+		codegen::{EmitEvent, TraitCallBuilder},
+		env::{
+			call::{ExecutionInput, Selector as CallSelector},
+			hash::{Blake2x256, HashOutput},
+			CallFlags,
+		},
+		prelude::{string::String, vec, vec::Vec},
 		reflect::ContractEventBase,
-		storage::Mapping,
+		storage::{
+			traits::{ManualKey, StorageKey},
+			Lazy, Mapping,
+		},
 	};
-	use psp22_traits::{PSP22Error, PSP22};
+	use math::bps_of;
+	use psp22_traits::{
+		airdrop_leaf, proof_root, EncodedArgs, PSP22ApprovalReceiver, PSP22Burnable, PSP22Error,
+		PSP22FlashBorrower, PSP22Metadata, PSP22Mintable, PSP22Permit, PSP22ReceiverError,
+		PSP22Votes, Pausable, PausableError, PSP22,
+	};
+
+	/// Privileged responsibilities that the owner may hand out to accounts
+	#[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+	#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+	pub enum Role {
+		/// May create new tokens
+		Minter,
+		/// May destroy tokens held by any account
+		Burner,
+		/// May halt and resume transfers and approvals
+		Pauser,
+		/// May add accounts to and remove them from the deny-list
+		Compliance,
+		/// May expand or contract the supply of every holder at once, e.g. an oracle
+		Rebaser,
+		/// May replace the contract code and migrate its storage
+		Upgrader,
+		/// May lock part of an account's balance until a given block
+		LockManager,
+		/// May burn tokens leaving for and mint tokens arriving from other chains
+		Bridge,
+		/// May release escrowed tokens when they come back from another chain
+		Relayer,
+		/// May freeze accounts and force transfers, in regulated mode
+		Controller,
+		/// May lift the circuit breaker on transfer volume
+		Guardian,
+	}
+
+	impl Role {
+		pub const ALL: [Role; 11] = [
+			Role::Minter,
+			Role::Burner,
+			Role::Pauser,
+			Role::Compliance,
+			Role::Rebaser,
+			Role::Upgrader,
+			Role::LockManager,
+			Role::Bridge,
+			Role::Relayer,
+			Role::Controller,
+			Role::Guardian,
+		];
+	}
+
+	/// The voting power of a delegate from `from_block` on
+	#[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+	#[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+	pub struct Checkpoint {
+		pub from_block: BlockNumber,
+		pub votes: Balance,
+	}
+
+	/// Tokens paid out to `recipient` block by block between `start` and `stop`
+	#[derive(Debug, Copy, Clone, PartialEq, Eq, scale::Decode, scale::Encode)]
+	#[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout, scale_info::TypeInfo))]
+	pub struct Stream {
+		/// The account which funded the stream
+		pub sender: AccountId,
+		/// The account being paid
+		pub recipient: AccountId,
+		/// The amount paid over the whole stream
+		pub deposit: Balance,
+		/// The block in which payment starts
+		pub start: BlockNumber,
+		/// The block by which everything has been paid
+		pub stop: BlockNumber,
+		/// How much the recipient has withdrawn so far
+		pub withdrawn: Balance,
+	}
+
+	/// `value` tokens held for `recipient` until it claims them or they expire after block
+	/// `expiry`, when `sender` can take them back
+	#[derive(Debug, Copy, Clone, PartialEq, Eq, scale::Decode, scale::Encode)]
+	#[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout, scale_info::TypeInfo))]
+	pub struct EscrowedTransfer {
+		pub sender: AccountId,
+		pub recipient: AccountId,
+		pub value: Balance,
+		pub expiry: BlockNumber,
+	}
+
+	/// `value` tokens held for `recipient` that anyone can pay out to it after block
+	/// `execute_after`, unless `sender` cancels first
+	#[derive(Debug, Copy, Clone, PartialEq, Eq, scale::Decode, scale::Encode)]
+	#[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout, scale_info::TypeInfo))]
+	pub struct ScheduledTransfer {
+		pub sender: AccountId,
+		pub recipient: AccountId,
+		pub value: Balance,
+		pub execute_after: BlockNumber,
+	}
+
+	/// Up to `amount_per_period` tokens a spender may pull in every period of `period` blocks,
+	/// e.g. for a subscription
+	#[derive(Debug, Copy, Clone, PartialEq, Eq, scale::Decode, scale::Encode)]
+	#[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout, scale_info::TypeInfo))]
+	pub struct RecurringAllowance {
+		pub amount_per_period: Balance,
+		pub period: BlockNumber,
+		/// The block the current period started in
+		pub period_start: BlockNumber,
+		/// The tokens pulled so far in the current period
+		pub spent: Balance,
+	}
+
+	/// `amount` of an account's tokens that can't be spent before block `until` has passed
+	#[derive(Debug, Copy, Clone, PartialEq, Eq, scale::Decode, scale::Encode)]
+	#[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout, scale_info::TypeInfo))]
+	pub struct Lock {
+		pub amount: Balance,
+		pub until: BlockNumber,
+	}
+
+	/// `give_amount` of `give_token` offered in exchange for `want_amount` of this token
+	#[derive(Debug, Copy, Clone, PartialEq, Eq, scale::Decode, scale::Encode)]
+	#[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout, scale_info::TypeInfo))]
+	pub struct SwapOffer {
+		pub give_token: AccountId,
+		pub give_amount: Balance,
+		pub want_amount: Balance,
+		/// The last block in which the offer may be taken
+		pub deadline: BlockNumber,
+	}
+
+	/// How transfers are charged
+	#[derive(Debug, Clone, PartialEq, Eq, scale::Decode, scale::Encode)]
+	#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+	pub struct FeeConfig {
+		/// The share of every transfer taken as a fee, in basis points
+		pub fee_bps: u16,
+		/// The share of every transfer burned on top of the fee, in basis points
+		pub burn_bps: u16,
+		/// The accounts the fee is split between, in proportion to their weights
+		pub recipients: Vec<FeeRecipient>,
+	}
+
+	/// `rate` tokens are emitted every block for `blocks` blocks
+	#[derive(Debug, Copy, Clone, PartialEq, Eq, scale::Decode, scale::Encode)]
+	#[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout, scale_info::TypeInfo))]
+	pub struct EmissionEpoch {
+		pub blocks: BlockNumber,
+		pub rate: Balance,
+	}
+
+	/// New supply emitted to `distributor` over consecutive epochs from block `start` on
+	#[derive(Debug, Clone, PartialEq, Eq, scale::Decode, scale::Encode)]
+	#[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout, scale_info::TypeInfo))]
+	pub struct EmissionSchedule {
+		pub start: BlockNumber,
+		pub distributor: AccountId,
+		pub epochs: Vec<EmissionEpoch>,
+		/// The tokens emitted so far
+		pub minted: Balance,
+	}
+
+	/// The escrow of tokens sent to a remote chain
+	#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, scale::Decode, scale::Encode)]
+	#[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout, scale_info::TypeInfo))]
+	pub struct EscrowChain {
+		/// The most tokens that may be held in escrow for the chain at once
+		pub limit: Balance,
+		/// The tokens currently held in escrow for the chain
+		pub locked: Balance,
+		/// Whether locking and releasing are currently halted for the chain
+		pub paused: bool,
+	}
+
+	/// Launch-phase protections against bots buying up the supply. Transfers from or to the owner
+	/// are never limited.
+	#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, scale::Decode, scale::Encode)]
+	#[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout, scale_info::TypeInfo))]
+	pub struct TransferLimits {
+		/// The most tokens a single transfer may move
+		pub max_transfer: Option<Balance>,
+		/// The most tokens a recipient may hold after a transfer
+		pub max_wallet: Option<Balance>,
+		/// The number of blocks an account must wait between two transfers it sends
+		pub cooldown: BlockNumber,
+	}
+
+	/// A circuit breaker on the total volume transferred within a rolling window of blocks
+	#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, scale::Decode, scale::Encode)]
+	#[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout, scale_info::TypeInfo))]
+	pub struct VolumeBreaker {
+		/// The most tokens that may be transferred within a window, if limited
+		pub max_volume: Option<Balance>,
+		/// The length of a window in blocks
+		pub window: BlockNumber,
+		/// The block the current window started in
+		pub window_start: BlockNumber,
+		/// The tokens transferred so far in the current window
+		pub volume: Balance,
+	}
 
+	/// `owner` allowed `spender` to withdraw up to `amount` of its tokens. `Approval` and
+	/// `Transfer` must stay the first events so that they decode as `psp22_traits::PSP22Event`.
 	#[ink(event)]
 	pub struct Approval {
 		#[ink(topic)]
@@ -19,6 +294,7 @@ mod psp22 {
 		#[ink(topic)]
 		spender: AccountId,
 		amount: Balance,
+		event_id: u64,
 	}
 
 	#[ink(event)]
@@ -28,184 +304,6437 @@ mod psp22 {
 		#[ink(topic)]
 		to: AccountId,
 		value: Balance,
+		event_id: u64,
 	}
 
-	#[ink(storage)]
-	#[derive(Default)]
-	pub struct Token {
-		total_supply: Balance,
-		balances: Mapping<AccountId, Balance>,
-		allowances: Mapping<(AccountId, AccountId), Balance>,
+	/// `amount` new tokens were created for `to`
+	#[ink(event)]
+	pub struct Minted {
+		#[ink(topic)]
+		to: AccountId,
+		amount: Balance,
+		event_id: u64,
 	}
 
-	pub type Event = <Token as ContractEventBase>::Type;
-
-	impl Token {
-		#[ink(constructor)]
-		pub fn new(total_supply: Balance) -> Self {
-			Self { total_supply, balances: Default::default(), allowances: Default::default() }
-		}
-
-		fn _approve_from_to(
-			&mut self,
-			owner: AccountId,
-			spender: AccountId,
-			amount: Balance,
-		) -> Result<(), PSP22Error> {
-			self.allowances.insert((&owner, &spender), &amount);
+	/// `amount` tokens held by `from` were destroyed
+	#[ink(event)]
+	pub struct Burned {
+		#[ink(topic)]
+		from: AccountId,
+		amount: Balance,
+		event_id: u64,
+	}
 
-			Self::emit_event(self.env(), Event::Approval(Approval { owner, spender, amount }));
+	/// `owner` allowed `spender` to pull `amount_per_period` tokens every `period` blocks
+	#[ink(event)]
+	pub struct RecurringApproval {
+		#[ink(topic)]
+		owner: AccountId,
+		#[ink(topic)]
+		spender: AccountId,
+		amount_per_period: Balance,
+		period: BlockNumber,
+	}
 
-			Ok(())
-		}
+	/// `referrer` brought `account` in, and gets a cut of the fees it pays from now on
+	#[ink(event)]
+	pub struct ReferrerSet {
+		#[ink(topic)]
+		account: AccountId,
+		#[ink(topic)]
+		referrer: AccountId,
+	}
 
-		fn _transfer_from_to(
-			&mut self,
-			from: &AccountId,
-			to: &AccountId,
-			value: Balance,
-			_data: Vec<u8>,
-		) -> Result<(), PSP22Error> {
-			let from_balance = self.balance_of(*from);
-			if from_balance < value {
-				return Err(PSP22Error::InsufficientBalance);
-			}
+	/// `deployer` deployed `child`, a new copy of the token
+	#[ink(event)]
+	pub struct ChildDeployed {
+		#[ink(topic)]
+		deployer: AccountId,
+		#[ink(topic)]
+		child: AccountId,
+	}
 
-			// NOTE: this should never underflow / overflow as the u128::MAX is orders of magnitude
-			// larger than typical amount of tokens in circulation
-			self.balances.insert(from, &(from_balance - value));
-			let to_balance = self.balance_of(*to);
-			self.balances.insert(to, &(to_balance + value));
+	/// `owner` allowed `operator` to move all of its tokens, or revoked it
+	#[ink(event)]
+	pub struct OperatorSet {
+		#[ink(topic)]
+		owner: AccountId,
+		#[ink(topic)]
+		operator: AccountId,
+		approved: bool,
+	}
 
-			Self::emit_event(self.env(), Event::Transfer(Transfer { from: *from, to: *to, value }));
+	/// The authorization `nonce` of `authorizer` was used or cancelled
+	#[ink(event)]
+	pub struct AuthorizationUsed {
+		#[ink(topic)]
+		authorizer: AccountId,
+		#[ink(topic)]
+		nonce: AuthorizationNonce,
+		cancelled: bool,
+	}
 
-			Ok(())
-		}
+	/// Transfers and approvals were halted
+	#[ink(event)]
+	pub struct Paused {
+		#[ink(topic)]
+		account: AccountId,
+	}
 
-		fn emit_event<EE>(emitter: EE, event: Event)
-		where
-			EE: EmitEvent<Self>,
-		{
-			emitter.emit_event(event);
-		}
+	/// Transfers and approvals were resumed
+	#[ink(event)]
+	pub struct Unpaused {
+		#[ink(topic)]
+		account: AccountId,
 	}
 
-	impl PSP22 for Token {
-		/// Returns the total token supply.
-		#[ink(message)]
-		fn total_supply(&self) -> Balance {
-			self.total_supply
-		}
+	/// The owner proposed handing the token over to `new_owner`
+	#[ink(event)]
+	pub struct OwnershipTransferStarted {
+		#[ink(topic)]
+		previous_owner: AccountId,
+		#[ink(topic)]
+		new_owner: AccountId,
+	}
 
-		/// Returns the account balance for the specified `owner`.
-		#[ink(message)]
-		fn balance_of(&self, owner: AccountId) -> Balance {
-			self.balances.get(owner).expect("no owner found")
-		}
+	/// The proposed owner accepted ownership of the token
+	#[ink(event)]
+	pub struct OwnershipTransferred {
+		#[ink(topic)]
+		previous_owner: AccountId,
+		#[ink(topic)]
+		new_owner: AccountId,
+	}
 
-		/// Returns the amount which `spender` is allowed to withdraw on behalf of the `owner`
-		/// account.
-		#[ink(message)]
-		fn allowance(&self, owner: AccountId, spender: AccountId) -> Balance {
-			self.allowances.get((owner, spender)).expect("no pair owner <-> spender found")
-		}
+	/// The owner granted `role` to `account`
+	#[ink(event)]
+	pub struct RoleGranted {
+		#[ink(topic)]
+		role: Role,
+		#[ink(topic)]
+		account: AccountId,
+	}
 
-		/// Allows `spender` to withdraw from the caller's account multiple times, up to the `value`
-		/// amount.
-		#[ink(message)]
-		fn approve(&mut self, spender: AccountId, amount: Balance) -> Result<(), PSP22Error> {
-			let caller = self.env().caller();
-			self.allowances.insert((caller, spender), &amount);
+	/// The owner revoked `role` from `account`
+	#[ink(event)]
+	pub struct RoleRevoked {
+		#[ink(topic)]
+		role: Role,
+		#[ink(topic)]
+		account: AccountId,
+	}
 
-			Ok(())
-		}
+	/// `account` was put on the deny-list
+	#[ink(event)]
+	pub struct AccountDenied {
+		#[ink(topic)]
+		account: AccountId,
+	}
 
-		/// Increase `spender`'s allowance to withdraw from the caller's account by the `by` amount.
-		#[ink(message)]
-		fn increase_allowance(
-			&mut self,
-			spender: AccountId,
-			by: Balance,
-		) -> Result<(), PSP22Error> {
-			let caller = self.env().caller();
-			let prev = self.allowances.get((caller, spender)).expect("not found");
-			self.allowances.insert((caller, spender), &(prev.saturating_add(by)));
+	/// `account` was taken off the deny-list
+	#[ink(event)]
+	pub struct AccountAllowed {
+		#[ink(topic)]
+		account: AccountId,
+	}
 
-			Ok(())
-		}
+	/// `account` was frozen and can no longer send tokens
+	#[ink(event)]
+	pub struct Frozen {
+		#[ink(topic)]
+		account: AccountId,
+	}
 
-		/// Decrease `spender`'s allowance to withdraw from the caller's account by the `by` amount.
-		#[ink(message)]
-		fn decrease_allowance(
-			&mut self,
-			spender: AccountId,
-			by: Balance,
-		) -> Result<(), PSP22Error> {
-			let caller = self.env().caller();
-			let prev = self.allowances.get((caller, spender)).expect("not found");
-			self.allowances.insert((caller, spender), &(prev.saturating_sub(by)));
+	/// `account` was unfrozen
+	#[ink(event)]
+	pub struct Unfrozen {
+		#[ink(topic)]
+		account: AccountId,
+	}
 
-			Ok(())
-		}
+	/// `controller` forced a transfer of `value` tokens from `from` to `to` for `reason`
+	#[ink(event)]
+	pub struct ControllerTransfer {
+		#[ink(topic)]
+		controller: AccountId,
+		#[ink(topic)]
+		from: AccountId,
+		#[ink(topic)]
+		to: AccountId,
+		value: Balance,
+		reason: String,
+	}
 
-		/// Transfers `value` amount of tokens from the caller's account to account `to`.
-		#[ink(message)]
-		fn transfer(
-			&mut self,
-			to: AccountId,
-			value: Balance,
-			data: Vec<u8>,
-		) -> Result<(), PSP22Error> {
-			let caller = self.env().caller();
-			let caller_balance = self.balance_of(caller);
+	/// The supply was rebased, every balance changed in proportion
+	#[ink(event)]
+	pub struct Rebased {
+		total_supply: Balance,
+	}
 
-			if caller_balance < value {
-				return Err(PSP22Error::InsufficientBalance);
-			}
+	/// `delegator` moved its votes from `from_delegate` to `to_delegate`
+	#[ink(event)]
+	pub struct DelegateChanged {
+		#[ink(topic)]
+		delegator: AccountId,
+		from_delegate: Option<AccountId>,
+		#[ink(topic)]
+		to_delegate: AccountId,
+	}
 
-			self.balances.insert(caller, &(caller_balance.saturating_sub(value)));
-			self.balances.insert(to, &(value));
+	/// The voting power of `delegate` changed
+	#[ink(event)]
+	pub struct DelegateVotesChanged {
+		#[ink(topic)]
+		delegate: AccountId,
+		previous_votes: Balance,
+		new_votes: Balance,
+	}
 
-			Self::emit_event(self.env(), Event::Transfer(Transfer { from: caller, to, value }));
+	/// `sender` started paying `deposit` to `recipient` through stream `id`
+	#[ink(event)]
+	pub struct StreamCreated {
+		#[ink(topic)]
+		id: u64,
+		#[ink(topic)]
+		sender: AccountId,
+		#[ink(topic)]
+		recipient: AccountId,
+		deposit: Balance,
+	}
 
-			Ok(())
-		}
+	/// `sender` sent `value` tokens to `recipient` through escrowed transfer `id`
+	#[ink(event)]
+	pub struct TransferEscrowed {
+		#[ink(topic)]
+		id: u64,
+		#[ink(topic)]
+		sender: AccountId,
+		#[ink(topic)]
+		recipient: AccountId,
+		value: Balance,
+		expiry: BlockNumber,
+	}
 
-		/// Transfers `value` amount of tokens on the behalf of `from` to the account `to`.
-		/// Caller need to be pre-approved
-		#[ink(message)]
-		fn transfer_from(
-			&mut self,
-			from: AccountId,
-			to: AccountId,
-			value: Balance,
-			data: Vec<u8>,
-		) -> Result<(), PSP22Error> {
-			let caller = self.env().caller();
-			if !self.allowances.contains((from, caller)) {
-				panic!("NOT AUTHORIZED")
-			}
+	/// Escrowed transfer `id` was claimed by its recipient, or refunded to its sender
+	#[ink(event)]
+	pub struct EscrowedTransferSettled {
+		#[ink(topic)]
+		id: u64,
+		claimed: bool,
+	}
 
-			let allowance = self.allowances.get((from, caller)).expect("not found");
-			if allowance < value {
-				return Err(PSP22Error::InsufficientAllowance);
-			}
+	/// `sender` scheduled transfer `id` of `value` tokens to `recipient`
+	#[ink(event)]
+	pub struct TransferScheduled {
+		#[ink(topic)]
+		id: u64,
+		#[ink(topic)]
+		sender: AccountId,
+		#[ink(topic)]
+		recipient: AccountId,
+		value: Balance,
+		execute_after: BlockNumber,
+	}
 
-			let balance = self.balances.get(from).expect("no from found");
-			if balance < value {
-				return Err(PSP22Error::InsufficientBalance);
-			}
+	/// Scheduled transfer `id` was paid out to its recipient, or cancelled by its sender
+	#[ink(event)]
+	pub struct ScheduledTransferSettled {
+		#[ink(topic)]
+		id: u64,
+		executed: bool,
+	}
 
-			self.allowances.insert((from, caller), &(allowance.saturating_sub(value)));
-			self.balances.insert(from, &(balance.saturating_sub(value)));
-			self.balances.insert(to, &(balance.saturating_add(value)));
+	/// Stream `id` was cancelled and its remaining tokens split between the parties
+	#[ink(event)]
+	pub struct StreamCancelled {
+		#[ink(topic)]
+		id: u64,
+		recipient_amount: Balance,
+		sender_amount: Balance,
+	}
 
-			Self::emit_event(
+	/// `account` claimed `amount` tokens from entry `index` of the airdrop
+	#[ink(event)]
+	pub struct AirdropClaimed {
+		index: u32,
+		#[ink(topic)]
+		account: AccountId,
+		amount: Balance,
+	}
+
+	/// `account` staked `amount` tokens
+	#[ink(event)]
+	pub struct Staked {
+		#[ink(topic)]
+		account: AccountId,
+		amount: Balance,
+	}
+
+	/// `account` withdrew `amount` staked tokens
+	#[ink(event)]
+	pub struct Unstaked {
+		#[ink(topic)]
+		account: AccountId,
+		amount: Balance,
+	}
+
+	/// `account` was paid `amount` tokens of staking rewards
+	#[ink(event)]
+	pub struct StakingRewardsClaimed {
+		#[ink(topic)]
+		account: AccountId,
+		amount: Balance,
+	}
+
+	/// `amount` tokens of `from` were burned to be minted to `dest_address` on `dest_chain`.
+	/// `nonce` tells apart otherwise identical outgoing transfers.
+	#[ink(event)]
+	pub struct BridgeBurned {
+		#[ink(topic)]
+		from: AccountId,
+		amount: Balance,
+		#[ink(topic)]
+		dest_chain: u32,
+		dest_address: Vec<u8>,
+		nonce: u64,
+	}
+
+	/// `amount` tokens were minted to `to` for the transaction `source_tx` on another chain
+	#[ink(event)]
+	pub struct BridgeMinted {
+		#[ink(topic)]
+		to: AccountId,
+		amount: Balance,
+		#[ink(topic)]
+		source_tx: [u8; 32],
+	}
+
+	/// `amount` tokens of `from` were escrowed to be credited to `remote_address` on `chain`.
+	/// `nonce` tells apart otherwise identical outgoing transfers.
+	#[ink(event)]
+	pub struct EscrowLocked {
+		#[ink(topic)]
+		from: AccountId,
+		#[ink(topic)]
+		chain: u32,
+		amount: Balance,
+		remote_address: Vec<u8>,
+		nonce: u64,
+	}
+
+	/// `amount` escrowed tokens came back from `chain` to `to` with the message `message_id`
+	#[ink(event)]
+	pub struct EscrowReleased {
+		#[ink(topic)]
+		to: AccountId,
+		#[ink(topic)]
+		chain: u32,
+		amount: Balance,
+		message_id: [u8; 32],
+	}
+
+	/// `amount` of the tokens of `account` were locked until block `until`
+	#[ink(event)]
+	pub struct Locked {
+		#[ink(topic)]
+		account: AccountId,
+		amount: Balance,
+		until: BlockNumber,
+	}
+
+	/// Expired locks over `amount` of the tokens of `account` were released
+	#[ink(event)]
+	pub struct Unlocked {
+		#[ink(topic)]
+		account: AccountId,
+		amount: Balance,
+	}
+
+	/// `maker` offered `taker` a swap, replacing any earlier offer between them
+	#[ink(event)]
+	pub struct SwapOffered {
+		#[ink(topic)]
+		maker: AccountId,
+		#[ink(topic)]
+		taker: AccountId,
+		offer: SwapOffer,
+	}
+
+	/// `taker` sent `maker` `amount` tokens in exchange for `other_amount` of `other_token`
+	#[ink(event)]
+	pub struct Swapped {
+		#[ink(topic)]
+		maker: AccountId,
+		#[ink(topic)]
+		taker: AccountId,
+		amount: Balance,
+		other_token: AccountId,
+		other_amount: Balance,
+	}
+
+	/// `operator` moved `value` tokens of `partition` from `from` to `to`
+	#[ink(event)]
+	pub struct TransferByPartition {
+		#[ink(topic)]
+		partition: Partition,
+		operator: AccountId,
+		#[ink(topic)]
+		from: AccountId,
+		#[ink(topic)]
+		to: AccountId,
+		value: Balance,
+	}
+
+	/// `value` tokens of `account` were moved from partition `from` to partition `to`
+	#[ink(event)]
+	pub struct ChangedPartition {
+		#[ink(topic)]
+		account: AccountId,
+		from: Partition,
+		to: Partition,
+		value: Balance,
+	}
+
+	/// `holder` authorized or revoked `operator` for `partition`
+	#[ink(event)]
+	pub struct PartitionOperatorSet {
+		#[ink(topic)]
+		holder: AccountId,
+		#[ink(topic)]
+		operator: AccountId,
+		partition: Partition,
+		authorized: bool,
+	}
+
+	/// `guardian` lifted the circuit breaker, starting a new window
+	#[ink(event)]
+	pub struct VolumeBreakerLifted {
+		#[ink(topic)]
+		guardian: AccountId,
+		volume: Balance,
+	}
+
+	/// What wallets display about the token. Rarely read on-chain, so it is stored apart from the
+	/// fields transfers load.
+	#[derive(Debug, Default)]
+	#[ink::storage_item]
+	pub struct Metadata {
+		name: Option<String>,
+		symbol: Option<String>,
+		decimals: u8,
+		/// A link to the token's logo or metadata JSON
+		token_uri: Option<String>,
+	}
+
+	/// Settings only administrative messages read, stored apart from the fields transfers load
+	#[derive(Debug, Default)]
+	#[ink::storage_item]
+	pub struct Config {
+		/// The maximum total supply, if any
+		cap: Option<Balance>,
+		/// The genesis hash of the chain the token is deployed on. Contracts can't read it, so it
+		/// is passed in at deployment.
+		genesis_hash: Hash,
+		/// The Merkle root of the current airdrop's `(index, account, amount)` entries
+		airdrop_root: Option<[u8; 32]>,
+	}
+
+	/// The fields of `Token` without a key of their own are all stored in a single cell which
+	/// every message loads, so anything not needed to move tokens lives behind a `Lazy`.
+	#[ink(storage)]
+	pub struct Token {
+		total_supply: Balance,
+		/// Each account's share of the total supply. Until the first rebase one share is one token.
+		balances: Mapping<AccountId, Balance>,
+		/// The sum of all shares
+		total_shares: Balance,
+		allowances: Mapping<(AccountId, AccountId), Balance>,
+		/// The last block in which an allowance may be spent. Allowances without an entry never
+		/// expire.
+		allowance_deadlines: Mapping<(AccountId, AccountId), BlockNumber>,
+		metadata: Lazy<Metadata, ManualKey<0x4d455441>>,
+		config: Lazy<Config, ManualKey<0x434f4e46>>,
+		/// The id of the next Transfer, Approval, Minted or Burned event. It has a cell of its own
+		/// so that messages reentering the token from a callback keep counting where the outer
+		/// message left off.
+		next_event_id: Lazy<u64, ManualKey<0x45564e54>>,
+		/// The weighted recipients of the transfer fee. The whole fee goes to the treasury if
+		/// there are none.
+		fee_split: Lazy<Vec<FeeRecipient>, ManualKey<0x46454553>>,
+		/// The schedule new supply is emitted on, if any
+		emission: Lazy<EmissionSchedule, ManualKey<0x454d4954>>,
+		/// The account that grants and revokes roles, defaults to the deployer
+		ownership: Ownable,
+		/// Whether transfers and approvals are currently halted
+		paused: bool,
+		roles: Roles<Role>,
+		/// The number of signed messages each account has used so far
+		nonces: Mapping<AccountId, u64>,
+		/// Existence of an `(authorizer, nonce)` key means the transfer authorization was used or
+		/// cancelled
+		authorizations: Mapping<(AccountId, AuthorizationNonce), ()>,
+		/// Existence of a key means the account may neither send nor receive tokens
+		deny_list: Mapping<AccountId, ()>,
+		/// The share of every transfer sent to the treasury, in basis points
+		fee_bps: u16,
+		/// The account collecting transfer fees, defaults to the deployer
+		treasury: AccountId,
+		/// The share of every transfer that is burned, in basis points
+		burn_bps: u16,
+		/// The share of the fees paid by referred accounts that goes to their referrer, in basis
+		/// points. Referrals are only recorded while it is above zero.
+		referral_bps: u16,
+		/// The account each holder first received tokens from, while referrals were on
+		referrers: Mapping<AccountId, AccountId>,
+		/// Existence of a key means transfers to or from the account are not charged a fee
+		fee_exempt: Mapping<AccountId, ()>,
+		/// The account each holder's votes count towards. Holders that never delegated don't vote.
+		delegates: Mapping<AccountId, AccountId>,
+		/// The history of each delegate's voting power, indexed from zero
+		checkpoints: Mapping<(AccountId, u32), Checkpoint>,
+		/// The number of checkpoints each delegate has
+		num_checkpoints: Mapping<AccountId, u32>,
+		/// Open payment streams, funded by tokens held on the contract's own account
+		streams: Mapping<u64, Stream>,
+		/// The id of the next stream
+		next_stream_id: u64,
+		/// Escrowed transfers not yet claimed or refunded, held on the contract's own account
+		escrowed_transfers: Mapping<u64, EscrowedTransfer>,
+		/// The id of the next escrowed transfer
+		next_escrowed_transfer_id: u64,
+		/// Scheduled transfers not yet executed or cancelled, held on the contract's own account
+		scheduled_transfers: Mapping<u64, ScheduledTransfer>,
+		/// The id of the next scheduled transfer
+		next_scheduled_transfer_id: u64,
+		/// Incremented whenever a new root is set, so each airdrop has its own claimed bitmap
+		airdrop_round: u32,
+		/// Claimed bitmap of each airdrop, in words of 128 entries
+		airdrop_claimed: Mapping<(u32, u32), u128>,
+		/// The storage schema the stored data is in
+		storage_version: u32,
+		/// Free-form metadata for wallets, e.g. a website or a description
+		attributes: Mapping<String, String>,
+		/// Every account with a non-zero balance, indexed from zero
+		holders: Mapping<u32, AccountId>,
+		/// The position of each holder in `holders`
+		holder_index: Mapping<AccountId, u32>,
+		/// The number of accounts with a non-zero balance
+		holders_count: u32,
+		/// The locks on each account's balance, including expired ones not yet released
+		locks: Mapping<AccountId, Vec<Lock>>,
+		/// The tokens each account has staked, held on the contract's own account
+		staked: Mapping<AccountId, Balance>,
+		/// The sum of all stakes
+		total_staked: Balance,
+		/// The tokens minted as staking rewards each block, shared by all stakers
+		reward_rate: Balance,
+		/// The rewards earned by a single staked token since launch, times `REWARD_PRECISION`
+		reward_per_token: Balance,
+		/// The block `reward_per_token` was last brought up to date in
+		last_reward_block: BlockNumber,
+		/// The value of `reward_per_token` each staker's rewards were last settled at
+		reward_per_token_paid: Mapping<AccountId, Balance>,
+		/// Settled rewards each staker has yet to claim
+		rewards: Mapping<AccountId, Balance>,
+		/// The number of outgoing bridge transfers so far
+		bridge_nonce: u64,
+		/// Existence of a key means the incoming bridge transaction was minted
+		bridged_in: Mapping<[u8; 32], ()>,
+		/// The remote chains tokens can be escrowed for
+		escrow_chains: Mapping<u32, EscrowChain>,
+		/// The number of outgoing escrow transfers so far
+		escrow_nonce: u64,
+		/// Existence of a key means the relayed message released its tokens
+		escrow_released: Mapping<[u8; 32], ()>,
+		/// The block each account last used the faucet in
+		#[cfg(feature = "faucet")]
+		last_drip: Mapping<AccountId, BlockNumber>,
+		/// The anti-bot limits currently in force
+		transfer_limits: TransferLimits,
+		/// The block each account last sent a transfer in, tracked while a cooldown is set
+		last_transfer: Mapping<AccountId, BlockNumber>,
+		/// The circuit breaker on transfer volume
+		volume_breaker: VolumeBreaker,
+		/// The swap each maker currently offers each taker
+		swap_offers: Mapping<(AccountId, AccountId), SwapOffer>,
+		/// Existence of a key means the account may not send tokens
+		#[cfg(feature = "regulated")]
+		frozen: Mapping<AccountId, ()>,
+		/// The shares each holder keeps in each named partition
+		partition_shares: Mapping<(AccountId, Partition), Balance>,
+		/// The sum of each holder's shares in named partitions
+		partitioned_shares: Mapping<AccountId, Balance>,
+		/// The named partitions each holder has a balance in
+		partitions: Mapping<AccountId, Vec<Partition>>,
+		/// The recurring allowance of each `(owner, spender)` pair, if any
+		recurring_allowances: Mapping<(AccountId, AccountId), RecurringAllowance>,
+		/// Existence of an `(owner, operator)` key means the operator may move any of the owner's
+		/// tokens
+		operators: Mapping<(AccountId, AccountId), ()>,
+		/// Existence of a key means the operator may move the holder's tokens in the partition
+		partition_operators: Mapping<PartitionOperator, ()>,
+	}
+
+	pub type Event = <Token as ContractEventBase>::Type;
+	pub type Selector = [u8; 4];
+	/// The name of a tranche of balances, e.g. `"restricted"` padded with zeros
+	pub type Partition = [u8; 32];
+	/// A `(holder, partition, operator)` triple
+	type PartitionOperator = (AccountId, Partition, AccountId);
+	/// A random value that identifies a transfer authorization of its signer
+	pub type AuthorizationNonce = [u8; 32];
+	/// An account receiving part of the transfer fee, and its weight
+	pub type FeeRecipient = (AccountId, u16);
+
+	/// Decodes the arguments of a call, all of them and nothing more
+	fn decode_args<T: scale::Decode>(mut args: &[u8]) -> Result<T, PSP22Error> {
+		<T as scale::DecodeAll>::decode_all(&mut args).map_err(|_| PSP22Error::CallFailed)
+	}
+
+	/// The partition plain transfers spend from. It holds whatever part of a balance is not in a
+	/// named partition.
+	pub const DEFAULT_PARTITION: Partition = [0; 32];
+	/// The bitmap of the capabilities this build includes, see the `capabilities` module
+	pub const CAPABILITIES: u128 = {
+		use crate::capabilities::*;
+		let always = [
+			SIGNED_TRANSFERS,
+			PAUSABLE,
+			DENY_LIST,
+			TRANSFER_FEES,
+			REBASING,
+			VOTES,
+			FLASH_LOANS,
+			STREAMS,
+			STAKING,
+			AIRDROPS,
+			BRIDGE,
+			ESCROW,
+			SWAPS,
+			PARTITIONS,
+			LOCKS,
+			TRANSFER_LIMITS,
+			VOLUME_BREAKER,
+			UPGRADEABLE,
+		];
+		let mut bitmap = (cfg!(feature = "faucet") as u128) << FAUCET
+			| (cfg!(feature = "regulated") as u128) << REGULATED;
+		let mut i = 0;
+		while i < always.len() {
+			bitmap |= 1 << always[i];
+			i += 1;
+		}
+		bitmap
+	};
+	/// The storage schema this code expects. Bump it and extend `migrate` whenever an upgrade
+	/// changes the storage layout.
+	pub const STORAGE_VERSION: u32 = 1;
+	/// Fees are expressed in basis points, parts of this denominator
+	pub const FEE_DENOMINATOR: u16 = math::BPS_DENOMINATOR;
+	/// The fee charged on flash loans, in basis points
+	pub const FLASH_FEE_BPS: u16 = 9;
+	/// The fixed-point scale of the staking reward accumulator
+	pub const REWARD_PRECISION: Balance = 1_000_000_000_000;
+	/// The tokens the faucet mints per drip
+	#[cfg(feature = "faucet")]
+	pub const DRIP_AMOUNT: Balance = 1_000;
+	/// The blocks an account must wait between two drips
+	#[cfg(feature = "faucet")]
+	pub const DRIP_INTERVAL: BlockNumber = 100;
+
+	impl Token {
+		/// Creates a token with the given metadata and credits the whole supply to the deployer.
+		/// The deployer starts out as the owner and holds every role.
+		///
+		/// `genesis_hash` must be the genesis hash of the chain the token is deployed on, it is
+		/// part of the domain separator off-chain signers commit to.
+		///
+		/// Panics if the initial supply is above `cap`.
+		#[ink(constructor)]
+		pub fn new(
+			total_supply: Balance,
+			name: Option<String>,
+			symbol: Option<String>,
+			decimals: u8,
+			cap: Option<Balance>,
+			genesis_hash: Hash,
+		) -> Self {
+			assert!(cap.is_none_or(|cap| total_supply <= cap), "initial supply exceeds the cap");
+
+			let owner = Self::env().caller();
+			let mut balances = Mapping::default();
+			balances.insert(owner, &total_supply);
+			let (mut holders, mut holder_index) = (Mapping::default(), Mapping::default());
+			if total_supply > 0 {
+				holders.insert(0, &owner);
+				holder_index.insert(owner, &0);
+			}
+			let mut roles = Roles::default();
+			for role in Role::ALL {
+				roles.grant_role(role, owner);
+			}
+			let mut metadata = Lazy::new();
+			metadata.set(&Metadata { name, symbol, decimals, token_uri: None });
+			let mut config = Lazy::new();
+			config.set(&Config { cap, genesis_hash, airdrop_root: None });
+
+			Self {
+				total_supply,
+				balances,
+				total_shares: total_supply,
+				allowances: Default::default(),
+				allowance_deadlines: Default::default(),
+				metadata,
+				config,
+				next_event_id: Lazy::new(),
+				fee_split: Lazy::new(),
+				emission: Lazy::new(),
+				ownership: Ownable::new(owner),
+				paused: false,
+				roles,
+				nonces: Mapping::default(),
+				authorizations: Mapping::default(),
+				deny_list: Mapping::default(),
+				fee_bps: 0,
+				treasury: owner,
+				burn_bps: 0,
+				referral_bps: 0,
+				referrers: Mapping::default(),
+				fee_exempt: Mapping::default(),
+				delegates: Mapping::default(),
+				checkpoints: Mapping::default(),
+				num_checkpoints: Mapping::default(),
+				streams: Mapping::default(),
+				next_stream_id: 0,
+				escrowed_transfers: Mapping::default(),
+				next_escrowed_transfer_id: 0,
+				scheduled_transfers: Mapping::default(),
+				next_scheduled_transfer_id: 0,
+				airdrop_round: 0,
+				airdrop_claimed: Mapping::default(),
+				storage_version: STORAGE_VERSION,
+				attributes: Mapping::default(),
+				holders,
+				holder_index,
+				holders_count: u32::from(total_supply > 0),
+				locks: Mapping::default(),
+				staked: Mapping::default(),
+				total_staked: 0,
+				reward_rate: 0,
+				reward_per_token: 0,
+				last_reward_block: Self::env().block_number(),
+				reward_per_token_paid: Mapping::default(),
+				rewards: Mapping::default(),
+				bridge_nonce: 0,
+				bridged_in: Mapping::default(),
+				escrow_chains: Mapping::default(),
+				escrow_nonce: 0,
+				escrow_released: Mapping::default(),
+				#[cfg(feature = "faucet")]
+				last_drip: Mapping::default(),
+				transfer_limits: TransferLimits::default(),
+				last_transfer: Mapping::default(),
+				volume_breaker: VolumeBreaker::default(),
+				swap_offers: Mapping::default(),
+				#[cfg(feature = "regulated")]
+				frozen: Mapping::default(),
+				partition_shares: Mapping::default(),
+				partitioned_shares: Mapping::default(),
+				partitions: Mapping::default(),
+				partition_operators: Mapping::default(),
+				operators: Mapping::default(),
+				recurring_allowances: Mapping::default(),
+			}
+		}
+
+		/// Creates a token whose initial supply is `rate` tokens for every unit of native currency
+		/// endowed by the deployer. Each `(account, share)` in `allocations` receives `share` basis
+		/// points of the supply and the deployer keeps the rest. The endowment stays on the
+		/// contract's account.
+		///
+		/// Panics if the initial supply overflows or is above `cap`, or if the allocations add up
+		/// to more than the whole supply.
+		#[ink(constructor, payable)]
+		pub fn new_with_endowment(
+			rate: Balance,
+			allocations: Vec<(AccountId, u16)>,
+			name: Option<String>,
+			symbol: Option<String>,
+			decimals: u8,
+			cap: Option<Balance>,
+			genesis_hash: Hash,
+		) -> Self {
+			let total_supply = Self::env()
+				.transferred_value()
+				.checked_mul(rate)
+				.expect("initial supply overflows");
+			let allocated = allocations.iter().map(|(_, share)| u32::from(*share)).sum::<u32>();
+			assert!(
+				allocated <= u32::from(FEE_DENOMINATOR),
+				"allocations exceed the initial supply"
+			);
+
+			let mut token = Self::new(total_supply, name, symbol, decimals, cap, genesis_hash);
+			let deployer = Self::env().caller();
+			for (account, share) in allocations {
+				token
+					._move(deployer, account, bps_of(total_supply, share))
+					.expect("allocations are within the initial supply");
+			}
+
+			token
+		}
+
+		/// Returns the maximum total supply, or `None` if the supply is uncapped.
+		#[ink(message)]
+		pub fn cap(&self) -> Option<Balance> {
+			self.config().cap
+		}
+
+		/// Returns the current owner of the token
+		#[ink(message)]
+		pub fn owner(&self) -> AccountId {
+			self.ownership.owner()
+		}
+
+		/// Returns the account proposed as the next owner, if any
+		#[ink(message)]
+		pub fn pending_owner(&self) -> Option<AccountId> {
+			self.ownership.pending_owner()
+		}
+
+		/// Proposes `new_owner` as the next owner. Ownership only moves once they accept, so a
+		/// typo cannot lock the token. A new proposal replaces any earlier one.
+		#[ink(message)]
+		pub fn transfer_ownership(&mut self, new_owner: AccountId) -> Result<(), PSP22Error> {
+			self.ownership
+				.transfer_ownership(self.env().caller(), new_owner)
+				.map_err(access_error)?;
+
+			Self::emit_event(
+				self.env(),
+				Event::OwnershipTransferStarted(OwnershipTransferStarted {
+					previous_owner: self.ownership.owner(),
+					new_owner,
+				}),
+			);
+
+			Ok(())
+		}
+
+		/// Completes an ownership transfer. Only the pending owner may accept.
+		#[ink(message)]
+		pub fn accept_ownership(&mut self) -> Result<(), PSP22Error> {
+			let caller = self.env().caller();
+			let previous_owner = self.ownership.accept_ownership(caller).map_err(access_error)?;
+
+			Self::emit_event(
+				self.env(),
+				Event::OwnershipTransferred(OwnershipTransferred {
+					previous_owner,
+					new_owner: caller,
+				}),
+			);
+
+			Ok(())
+		}
+
+		/// Returns true if `account` holds `role`
+		#[ink(message)]
+		pub fn has_role(&self, role: Role, account: AccountId) -> bool {
+			self.roles.has_role(role, account)
+		}
+
+		/// Gives `role` to `account`. Only the owner may grant roles.
+		#[ink(message)]
+		pub fn grant_role(&mut self, role: Role, account: AccountId) -> Result<(), PSP22Error> {
+			self.ensure_owner()?;
+			self.roles.grant_role(role, account);
+
+			Self::emit_event(self.env(), Event::RoleGranted(RoleGranted { role, account }));
+
+			Ok(())
+		}
+
+		/// Takes `role` away from `account`. Only the owner may revoke roles.
+		#[ink(message)]
+		pub fn revoke_role(&mut self, role: Role, account: AccountId) -> Result<(), PSP22Error> {
+			self.ensure_owner()?;
+			self.roles.revoke_role(role, account);
+
+			Self::emit_event(self.env(), Event::RoleRevoked(RoleRevoked { role, account }));
+
+			Ok(())
+		}
+
+		/// Stops `account` from sending or receiving tokens. Only compliance officers may deny.
+		#[ink(message)]
+		pub fn deny(&mut self, account: AccountId) -> Result<(), PSP22Error> {
+			self.ensure_role(Role::Compliance)?;
+			self.deny_list.insert(account, &());
+
+			Self::emit_event(self.env(), Event::AccountDenied(AccountDenied { account }));
+
+			Ok(())
+		}
+
+		/// Lets a denied `account` send and receive tokens again. Only compliance officers may
+		/// allow.
+		#[ink(message)]
+		pub fn allow(&mut self, account: AccountId) -> Result<(), PSP22Error> {
+			self.ensure_role(Role::Compliance)?;
+			self.deny_list.remove(account);
+
+			Self::emit_event(self.env(), Event::AccountAllowed(AccountAllowed { account }));
+
+			Ok(())
+		}
+
+		/// Returns true if `account` is on the deny-list
+		#[ink(message)]
+		pub fn is_denied(&self, account: AccountId) -> bool {
+			self.deny_list.contains(account)
+		}
+
+		/// Stops `account` from sending tokens, though it may still receive them. Only controllers
+		/// may freeze. Only built with the `regulated` feature.
+		#[cfg(feature = "regulated")]
+		#[ink(message)]
+		pub fn freeze(&mut self, account: AccountId) -> Result<(), PSP22Error> {
+			self.ensure_role(Role::Controller)?;
+			self.frozen.insert(account, &());
+
+			Self::emit_event(self.env(), Event::Frozen(Frozen { account }));
+
+			Ok(())
+		}
+
+		/// Lets a frozen `account` send tokens again. Only controllers may unfreeze. Only built
+		/// with the `regulated` feature.
+		#[cfg(feature = "regulated")]
+		#[ink(message)]
+		pub fn unfreeze(&mut self, account: AccountId) -> Result<(), PSP22Error> {
+			self.ensure_role(Role::Controller)?;
+			self.frozen.remove(account);
+
+			Self::emit_event(self.env(), Event::Unfrozen(Unfrozen { account }));
+
+			Ok(())
+		}
+
+		/// Returns true if `account` is frozen. Only built with the `regulated` feature.
+		#[cfg(feature = "regulated")]
+		#[ink(message)]
+		pub fn is_frozen(&self, account: AccountId) -> bool {
+			self.frozen.contains(account)
+		}
+
+		/// Moves `value` tokens from `from` to `to` regardless of allowances, freezes, locks, the
+		/// deny-list or a pause, e.g. to enforce a court order. Tokens held in named partitions
+		/// only move by partition, so they can't be forced out. `reason` is recorded in the event
+		/// and may not be empty. Only controllers may force transfers. Only built with the
+		/// `regulated` feature.
+		#[cfg(feature = "regulated")]
+		#[ink(message)]
+		pub fn controller_transfer(
+			&mut self,
+			from: AccountId,
+			to: AccountId,
+			value: Balance,
+			reason: String,
+		) -> Result<(), PSP22Error> {
+			self.ensure_role(Role::Controller)?;
+			if reason.is_empty() {
+				return Err(PSP22Error::MissingReason);
+			}
+			self.ensure_unpartitioned(from, value)?;
+
+			self._move(from, to, value)?;
+
+			Self::emit_event(
 				self.env(),
-				Event::Approval(Approval { owner: from, spender: caller, amount: value }),
+				Event::ControllerTransfer(ControllerTransfer {
+					controller: self.env().caller(),
+					from,
+					to,
+					value,
+					reason,
+				}),
 			);
 
 			Ok(())
 		}
+
+		/// Charges `fee_bps` basis points of every transfer and sends them to `treasury`. Only the
+		/// owner may set the fee.
+		#[ink(message)]
+		pub fn set_transfer_fee(
+			&mut self,
+			fee_bps: u16,
+			treasury: AccountId,
+		) -> Result<(), PSP22Error> {
+			self.ensure_owner()?;
+			if fee_bps.saturating_add(self.burn_bps) > FEE_DENOMINATOR {
+				return Err(PSP22Error::InvalidFee);
+			}
+
+			self.fee_bps = fee_bps;
+			self.treasury = treasury;
+
+			Ok(())
+		}
+
+		/// Returns the transfer fee in basis points and the account it is paid to
+		#[ink(message)]
+		pub fn transfer_fee(&self) -> (u16, AccountId) {
+			(self.fee_bps, self.treasury)
+		}
+
+		/// Sets the share of every transfer that is burned, in basis points. It is charged on top
+		/// of the treasury fee, and together they can't exceed the whole transfer. Only the owner
+		/// may change it.
+		#[ink(message)]
+		pub fn set_burn_rate(&mut self, burn_bps: u16) -> Result<(), PSP22Error> {
+			self.ensure_owner()?;
+			if burn_bps.saturating_add(self.fee_bps) > FEE_DENOMINATOR {
+				return Err(PSP22Error::InvalidFee);
+			}
+
+			self.burn_bps = burn_bps;
+
+			Ok(())
+		}
+
+		/// Returns the share of every transfer that is burned, in basis points
+		#[ink(message)]
+		pub fn burn_rate(&self) -> u16 {
+			self.burn_bps
+		}
+
+		/// Splits the transfer fee between `recipients` in proportion to their weights, e.g. the
+		/// treasury, liquidity rewards and the zero account, whose part is burned. Without any
+		/// recipients the whole fee goes to the treasury again. Only the owner may set the split.
+		#[ink(message)]
+		pub fn set_fee_split(&mut self, recipients: Vec<FeeRecipient>) -> Result<(), PSP22Error> {
+			self.ensure_owner()?;
+			if !recipients.is_empty() && recipients.iter().all(|(_, weight)| *weight == 0) {
+				return Err(PSP22Error::InvalidFee);
+			}
+
+			self.fee_split.set(&recipients);
+
+			Ok(())
+		}
+
+		/// Returns the transfer fee, the burn rate and how the fee is split
+		#[ink(message)]
+		pub fn fee_config(&self) -> FeeConfig {
+			let recipients = match self.fee_split.get().unwrap_or_default() {
+				split if split.is_empty() => vec![(self.treasury, 1)],
+				split => split,
+			};
+
+			FeeConfig { fee_bps: self.fee_bps, burn_bps: self.burn_bps, recipients }
+		}
+
+		/// Turns on referrals: whoever first sends tokens to a new holder becomes its referrer and
+		/// receives `referral_bps` basis points of every fee the holder pays afterwards. Zero turns
+		/// referrals off. Only the owner may set the share.
+		#[ink(message)]
+		pub fn set_referral_share(&mut self, referral_bps: u16) -> Result<(), PSP22Error> {
+			self.ensure_owner()?;
+			if referral_bps > FEE_DENOMINATOR {
+				return Err(PSP22Error::InvalidFee);
+			}
+
+			self.referral_bps = referral_bps;
+
+			Ok(())
+		}
+
+		/// Returns the share of referred accounts' fees paid to their referrer, in basis points
+		#[ink(message)]
+		pub fn referral_share(&self) -> u16 {
+			self.referral_bps
+		}
+
+		/// Returns the account that referred `account`, if any
+		#[ink(message)]
+		pub fn referrer_of(&self, account: AccountId) -> Option<AccountId> {
+			self.referrers.get(account)
+		}
+
+		/// Exempts transfers to and from `account` from the fee, e.g. for a DEX pool. Only the
+		/// owner may change exemptions.
+		#[ink(message)]
+		pub fn set_fee_exempt(
+			&mut self,
+			account: AccountId,
+			exempt: bool,
+		) -> Result<(), PSP22Error> {
+			self.ensure_owner()?;
+			if exempt {
+				self.fee_exempt.insert(account, &());
+			} else {
+				self.fee_exempt.remove(account);
+			}
+
+			Ok(())
+		}
+
+		/// Returns true if transfers to and from `account` are not charged a fee
+		#[ink(message)]
+		pub fn is_fee_exempt(&self, account: AccountId) -> bool {
+			self.fee_exempt.contains(account)
+		}
+
+		/// Sets the anti-bot limits on transfers. Only the owner may set them, and should lift
+		/// them with `remove_transfer_limits` once trading has stabilized.
+		#[ink(message)]
+		pub fn set_transfer_limits(&mut self, limits: TransferLimits) -> Result<(), PSP22Error> {
+			self.ensure_owner()?;
+			self.transfer_limits = limits;
+
+			Ok(())
+		}
+
+		/// Lifts all anti-bot limits. Only the owner may lift them.
+		#[ink(message)]
+		pub fn remove_transfer_limits(&mut self) -> Result<(), PSP22Error> {
+			self.set_transfer_limits(TransferLimits::default())
+		}
+
+		/// Returns the anti-bot limits currently in force
+		#[ink(message)]
+		pub fn transfer_limits(&self) -> TransferLimits {
+			self.transfer_limits
+		}
+
+		/// Makes transfers fail once more than `max_volume` tokens were transferred within
+		/// `window` blocks, until the window is over or a guardian lifts the breaker. `None` turns
+		/// the breaker off. Only the owner may set the limit.
+		#[ink(message)]
+		pub fn set_volume_limit(
+			&mut self,
+			max_volume: Option<Balance>,
+			window: BlockNumber,
+		) -> Result<(), PSP22Error> {
+			self.ensure_owner()?;
+			self.volume_breaker = VolumeBreaker {
+				max_volume,
+				window,
+				window_start: self.env().block_number(),
+				volume: 0,
+			};
+
+			Ok(())
+		}
+
+		/// Starts a new window so that transfers go through again. Only guardians may lift the
+		/// breaker.
+		#[ink(message)]
+		pub fn lift_volume_breaker(&mut self) -> Result<(), PSP22Error> {
+			self.ensure_role(Role::Guardian)?;
+			let volume = self.volume_breaker.volume;
+			self.volume_breaker.window_start = self.env().block_number();
+			self.volume_breaker.volume = 0;
+
+			Self::emit_event(
+				self.env(),
+				Event::VolumeBreakerLifted(VolumeBreakerLifted {
+					guardian: self.env().caller(),
+					volume,
+				}),
+			);
+
+			Ok(())
+		}
+
+		/// Returns the circuit breaker on transfer volume
+		#[ink(message)]
+		pub fn volume_breaker(&self) -> VolumeBreaker {
+			self.volume_breaker
+		}
+
+		/// Grows the supply by `delta` tokens, or shrinks it if negative. Holders keep their share
+		/// of the supply, so every balance changes in proportion. Only rebasers may rebase.
+		#[ink(message)]
+		pub fn rebase(&mut self, delta: i128) -> Result<(), PSP22Error> {
+			self.ensure_role(Role::Rebaser)?;
+
+			let total_supply = if delta >= 0 {
+				self.total_supply.checked_add(delta.unsigned_abs())
+			} else {
+				self.total_supply.checked_sub(delta.unsigned_abs())
+			};
+			let total_supply = total_supply
+				.filter(|supply| *supply > 0 && self.total_shares > 0)
+				.ok_or(PSP22Error::InvalidRebase)?;
+			if self.cap().is_some_and(|cap| total_supply > cap) {
+				return Err(PSP22Error::CapExceeded);
+			}
+
+			self.total_supply = total_supply;
+
+			Self::emit_event(self.env(), Event::Rebased(Rebased { total_supply }));
+
+			Ok(())
+		}
+
+		/// Returns the number of shares `account` holds
+		#[ink(message)]
+		pub fn shares_of(&self, account: AccountId) -> Balance {
+			self.balances.get(account).unwrap_or(0)
+		}
+
+		/// Returns the sum of all shares
+		#[ink(message)]
+		pub fn total_shares(&self) -> Balance {
+			self.total_shares
+		}
+
+		/// Lends `amount` freshly minted tokens to the `receiver` contract for the duration of one
+		/// call. The receiver's `on_flash_loan` callback must leave it holding `amount` plus the
+		/// flash fee, which are taken back; the fee goes to the treasury.
+		#[ink(message)]
+		pub fn flash_loan(
+			&mut self,
+			receiver: AccountId,
+			amount: Balance,
+			data: Vec<u8>,
+		) -> Result<(), PSP22Error> {
+			self.ensure_not_paused()?;
+			if !self.env().is_contract(&receiver) {
+				return Err(PSP22Error::ReceiverRejected);
+			}
+
+			let fee = self.flash_fee(amount);
+			let owed = amount.checked_add(fee).ok_or(PSP22Error::Overflow)?;
+			self._mint(receiver, amount)?;
+
+			// The borrower will usually move the tokens around, so it must be able to call back in.
+			// Those calls load the token from storage and save it again, so it is saved before the
+			// callback and loaded after it, or they would see stale totals and be overwritten.
+			ink::env::set_contract_storage(&<Self as StorageKey>::KEY, self);
+			let mut borrower: ink::contract_ref!(PSP22FlashBorrower) = receiver.into();
+			borrower
+				.call_mut()
+				.on_flash_loan(self.env().caller(), amount, fee, data)
+				.call_flags(CallFlags::default().set_allow_reentry(true))
+				.invoke()?;
+			*self = ink::env::get_contract_storage(&<Self as StorageKey>::KEY)
+				.ok()
+				.flatten()
+				.expect("the token was saved before the callback");
+
+			if self._balance(receiver) < owed {
+				return Err(PSP22Error::FlashLoanNotRepaid);
+			}
+			self.ensure_unlocked(receiver, owed)?;
+
+			self._burn(receiver, amount)?;
+			self._move(receiver, self.treasury, fee)
+		}
+
+		/// Returns the fee charged for flash borrowing `amount` tokens
+		#[ink(message)]
+		pub fn flash_fee(&self, amount: Balance) -> Balance {
+			bps_of(amount, FLASH_FEE_BPS)
+		}
+
+		/// Returns the largest amount that can currently be flash borrowed
+		#[ink(message)]
+		pub fn max_flash_loan(&self) -> Balance {
+			self.cap().unwrap_or(Balance::MAX).saturating_sub(self.total_supply)
+		}
+
+		/// Locks up `deposit` of the caller's tokens and pays them out to `to` evenly over the
+		/// blocks from `start` to `stop`. The deposit is checked like a transfer when the stream is
+		/// created, and payouts are charged fees like transfers. Returns the id of the new stream.
+		#[ink(message)]
+		pub fn create_stream(
+			&mut self,
+			to: AccountId,
+			deposit: Balance,
+			start: BlockNumber,
+			stop: BlockNumber,
+		) -> Result<u64, PSP22Error> {
+			self.ensure_not_paused()?;
+			let caller = self.env().caller();
+			if deposit == 0 || stop <= start {
+				return Err(PSP22Error::InvalidStream);
+			}
+			self._before_token_transfer(caller, to, deposit)?;
+
+			self._move(caller, self.env().account_id(), deposit)?;
+
+			let id = self.next_stream_id;
+			self.next_stream_id += 1;
+			let stream =
+				Stream { sender: caller, recipient: to, deposit, start, stop, withdrawn: 0 };
+			self.streams.insert(id, &stream);
+
+			Self::emit_event(
+				self.env(),
+				Event::StreamCreated(StreamCreated { id, sender: caller, recipient: to, deposit }),
+			);
+
+			Ok(id)
+		}
+
+		/// Returns the stream with the given id, if it is still open
+		#[ink(message)]
+		pub fn stream(&self, id: u64) -> Option<Stream> {
+			self.streams.get(id)
+		}
+
+		/// Returns how much the recipient of stream `id` can withdraw right now
+		#[ink(message)]
+		pub fn stream_balance(&self, id: u64) -> Result<Balance, PSP22Error> {
+			let stream = self.streams.get(id).ok_or(PSP22Error::StreamNotFound)?;
+			Ok(self.streamed(&stream) - stream.withdrawn)
+		}
+
+		/// Pays `amount` of what stream `id` has paid so far to its recipient. Only the recipient
+		/// may withdraw.
+		#[ink(message)]
+		pub fn withdraw_from_stream(&mut self, id: u64, amount: Balance) -> Result<(), PSP22Error> {
+			let mut stream = self.streams.get(id).ok_or(PSP22Error::StreamNotFound)?;
+			if self.env().caller() != stream.recipient {
+				return Err(PSP22Error::NotStreamParty);
+			}
+			if self.streamed(&stream) - stream.withdrawn < amount {
+				return Err(PSP22Error::InsufficientBalance);
+			}
+
+			stream.withdrawn += amount;
+			if stream.withdrawn == stream.deposit {
+				self.streams.remove(id);
+			} else {
+				self.streams.insert(id, &stream);
+			}
+
+			let this = self.env().account_id();
+			self._pay(this, stream.sender, stream.recipient, amount, Vec::new())
+		}
+
+		/// Closes stream `id`. The recipient gets what has been paid so far and the sender gets
+		/// back the rest. Either party may cancel.
+		#[ink(message)]
+		pub fn cancel_stream(&mut self, id: u64) -> Result<(), PSP22Error> {
+			let stream = self.streams.get(id).ok_or(PSP22Error::StreamNotFound)?;
+			let caller = self.env().caller();
+			if caller != stream.sender && caller != stream.recipient {
+				return Err(PSP22Error::NotStreamParty);
+			}
+
+			let streamed = self.streamed(&stream);
+			let recipient_amount = streamed - stream.withdrawn;
+			let sender_amount = stream.deposit - streamed;
+			self.streams.remove(id);
+
+			let this = self.env().account_id();
+			self._pay(this, stream.sender, stream.recipient, recipient_amount, Vec::new())?;
+			self._move(this, stream.sender, sender_amount)?;
+
+			Self::emit_event(
+				self.env(),
+				Event::StreamCancelled(StreamCancelled { id, recipient_amount, sender_amount }),
+			);
+
+			Ok(())
+		}
+
+		/// How much of `stream` has been paid out by now, withdrawn or not
+		fn streamed(&self, stream: &Stream) -> Balance {
+			let now = self.env().block_number().clamp(stream.start, stream.stop);
+			let elapsed = Balance::from(now - stream.start);
+			mul_div(stream.deposit, elapsed, Balance::from(stream.stop - stream.start))
+		}
+
+		/// Holds `value` of the caller's tokens for `to`, who has to claim them by block `expiry`.
+		/// Afterwards the caller can take them back, so tokens sent to a mistyped address aren't
+		/// lost. The transfer is checked like any other when it is made, and charged fees when it
+		/// is claimed. Returns the id of the escrowed transfer.
+		#[ink(message)]
+		pub fn transfer_escrowed(
+			&mut self,
+			to: AccountId,
+			value: Balance,
+			expiry: BlockNumber,
+		) -> Result<u64, PSP22Error> {
+			self.ensure_not_paused()?;
+			let caller = self.env().caller();
+			self._before_token_transfer(caller, to, value)?;
+
+			self._move(caller, self.env().account_id(), value)?;
+
+			let id = self.next_escrowed_transfer_id;
+			self.next_escrowed_transfer_id += 1;
+			let transfer = EscrowedTransfer { sender: caller, recipient: to, value, expiry };
+			self.escrowed_transfers.insert(id, &transfer);
+
+			Self::emit_event(
+				self.env(),
+				Event::TransferEscrowed(TransferEscrowed {
+					id,
+					sender: caller,
+					recipient: to,
+					value,
+					expiry,
+				}),
+			);
+
+			Ok(id)
+		}
+
+		/// Returns the escrowed transfer with the given id, if it is still pending
+		#[ink(message)]
+		pub fn escrowed_transfer(&self, id: u64) -> Option<EscrowedTransfer> {
+			self.escrowed_transfers.get(id)
+		}
+
+		/// Pays escrowed transfer `id` out to its recipient. Only the recipient may claim, and
+		/// only until the transfer expires.
+		#[ink(message)]
+		pub fn claim_escrowed(&mut self, id: u64) -> Result<(), PSP22Error> {
+			self.ensure_not_paused()?;
+			let transfer = self.escrowed_transfers.get(id).ok_or(PSP22Error::TransferNotFound)?;
+			if self.env().caller() != transfer.recipient {
+				return Err(PSP22Error::NotTransferParty);
+			}
+			if self.env().block_number() > transfer.expiry {
+				return Err(PSP22Error::TransferExpired);
+			}
+
+			self._settle_escrowed(id, transfer, true)
+		}
+
+		/// Gives escrowed transfer `id` back to its sender once it expired unclaimed. Only the
+		/// sender may refund.
+		#[ink(message)]
+		pub fn refund_escrowed(&mut self, id: u64) -> Result<(), PSP22Error> {
+			self.ensure_not_paused()?;
+			let transfer = self.escrowed_transfers.get(id).ok_or(PSP22Error::TransferNotFound)?;
+			if self.env().caller() != transfer.sender {
+				return Err(PSP22Error::NotTransferParty);
+			}
+			if self.env().block_number() <= transfer.expiry {
+				return Err(PSP22Error::TransferNotExpired);
+			}
+
+			self._settle_escrowed(id, transfer, false)
+		}
+
+		/// Sets aside `value` of the caller's tokens to be paid to `to` after block
+		/// `execute_after`. The transfer is checked like any other when it is scheduled, and
+		/// charged fees when it is executed. Returns the id of the scheduled transfer.
+		#[ink(message)]
+		pub fn schedule_transfer(
+			&mut self,
+			to: AccountId,
+			value: Balance,
+			execute_after: BlockNumber,
+		) -> Result<u64, PSP22Error> {
+			self.ensure_not_paused()?;
+			let caller = self.env().caller();
+			self._before_token_transfer(caller, to, value)?;
+
+			self._move(caller, self.env().account_id(), value)?;
+
+			let id = self.next_scheduled_transfer_id;
+			self.next_scheduled_transfer_id += 1;
+			let transfer =
+				ScheduledTransfer { sender: caller, recipient: to, value, execute_after };
+			self.scheduled_transfers.insert(id, &transfer);
+
+			Self::emit_event(
+				self.env(),
+				Event::TransferScheduled(TransferScheduled {
+					id,
+					sender: caller,
+					recipient: to,
+					value,
+					execute_after,
+				}),
+			);
+
+			Ok(id)
+		}
+
+		/// Returns the scheduled transfer with the given id, if it is still pending
+		#[ink(message)]
+		pub fn scheduled_transfer(&self, id: u64) -> Option<ScheduledTransfer> {
+			self.scheduled_transfers.get(id)
+		}
+
+		/// Pays scheduled transfer `id` out to its recipient once it is due. Anyone may execute
+		/// it.
+		#[ink(message)]
+		pub fn execute_scheduled(&mut self, id: u64) -> Result<(), PSP22Error> {
+			self.ensure_not_paused()?;
+			let transfer = self.scheduled_transfers.get(id).ok_or(PSP22Error::TransferNotFound)?;
+			if self.env().block_number() <= transfer.execute_after {
+				return Err(PSP22Error::TransferNotDue);
+			}
+			self.ensure_not_denied(transfer.sender, transfer.recipient)?;
+
+			self._settle_scheduled(id, transfer, true)
+		}
+
+		/// Gives scheduled transfer `id` back to its sender before it was executed. Only the
+		/// sender may cancel.
+		#[ink(message)]
+		pub fn cancel_scheduled(&mut self, id: u64) -> Result<(), PSP22Error> {
+			self.ensure_not_paused()?;
+			let transfer = self.scheduled_transfers.get(id).ok_or(PSP22Error::TransferNotFound)?;
+			if self.env().caller() != transfer.sender {
+				return Err(PSP22Error::NotTransferParty);
+			}
+
+			self._settle_scheduled(id, transfer, false)
+		}
+
+		/// Sets the tokens minted as staking rewards each block. Only the owner may change it.
+		#[ink(message)]
+		pub fn set_reward_rate(&mut self, reward_rate: Balance) -> Result<(), PSP22Error> {
+			self.ensure_owner()?;
+			// Settle the blocks so far at the old rate
+			self._update_rewards(None);
+			self.reward_rate = reward_rate;
+
+			Ok(())
+		}
+
+		/// Returns the tokens minted as staking rewards each block
+		#[ink(message)]
+		pub fn reward_rate(&self) -> Balance {
+			self.reward_rate
+		}
+
+		/// Moves `amount` of the caller's tokens into the staking pool, where they earn a share of
+		/// the rewards emitted each block
+		#[ink(message)]
+		pub fn stake(&mut self, amount: Balance) -> Result<(), PSP22Error> {
+			self.ensure_not_paused()?;
+			let caller = self.env().caller();
+			self.ensure_not_denied(caller, caller)?;
+			self.ensure_unlocked(caller, amount)?;
+
+			self._update_rewards(Some(caller));
+			self._move(caller, self.env().account_id(), amount)?;
+			self.staked.insert(caller, &(self.staked_of(caller) + amount));
+			self.total_staked += amount;
+
+			Self::emit_event(self.env(), Event::Staked(Staked { account: caller, amount }));
+
+			Ok(())
+		}
+
+		/// Moves `amount` of the caller's staked tokens back to the caller. Rewards earned so far
+		/// stay claimable.
+		#[ink(message)]
+		pub fn unstake(&mut self, amount: Balance) -> Result<(), PSP22Error> {
+			let caller = self.env().caller();
+			let staked = self.staked_of(caller);
+			if staked < amount {
+				return Err(PSP22Error::InsufficientBalance);
+			}
+
+			self._update_rewards(Some(caller));
+			self.staked.insert(caller, &(staked - amount));
+			self.total_staked -= amount;
+			self._move(self.env().account_id(), caller, amount)?;
+
+			Self::emit_event(self.env(), Event::Unstaked(Unstaked { account: caller, amount }));
+
+			Ok(())
+		}
+
+		/// Mints the caller's staking rewards to the caller and returns how many were paid
+		#[ink(message)]
+		pub fn claim_staking_rewards(&mut self) -> Result<Balance, PSP22Error> {
+			let caller = self.env().caller();
+			self._update_rewards(Some(caller));
+			let amount = self.rewards.take(caller).unwrap_or(0);
+			if amount == 0 {
+				return Ok(0);
+			}
+
+			self._mint(caller, amount)?;
+
+			Self::emit_event(
+				self.env(),
+				Event::StakingRewardsClaimed(StakingRewardsClaimed { account: caller, amount }),
+			);
+
+			Ok(amount)
+		}
+
+		/// Returns the tokens `account` has staked
+		#[ink(message)]
+		pub fn staked_of(&self, account: AccountId) -> Balance {
+			self.staked.get(account).unwrap_or(0)
+		}
+
+		/// Returns the sum of all stakes
+		#[ink(message)]
+		pub fn total_staked(&self) -> Balance {
+			self.total_staked
+		}
+
+		/// Returns the staking rewards `account` could claim right now
+		#[ink(message)]
+		pub fn earned(&self, account: AccountId) -> Balance {
+			let unsettled = self.current_reward_per_token()
+				- self.reward_per_token_paid.get(account).unwrap_or(0);
+			mul_div(self.staked_of(account), unsettled, REWARD_PRECISION)
+				.saturating_add(self.rewards.get(account).unwrap_or(0))
+		}
+
+		/// The reward accumulator brought up to the current block
+		fn current_reward_per_token(&self) -> Balance {
+			if self.total_staked == 0 {
+				return self.reward_per_token;
+			}
+
+			let blocks = Balance::from(self.env().block_number() - self.last_reward_block);
+			let emitted = self.reward_rate.saturating_mul(blocks);
+			self.reward_per_token.saturating_add(mul_div(
+				emitted,
+				REWARD_PRECISION,
+				self.total_staked,
+			))
+		}
+
+		/// Brings the reward accumulator up to date and settles the rewards of `account`. Must
+		/// run before any stake or the reward rate changes.
+		fn _update_rewards(&mut self, account: Option<AccountId>) {
+			if let Some(account) = account {
+				self.rewards.insert(account, &self.earned(account));
+			}
+			self.reward_per_token = self.current_reward_per_token();
+			self.last_reward_block = self.env().block_number();
+			if let Some(account) = account {
+				self.reward_per_token_paid.insert(account, &self.reward_per_token);
+			}
+		}
+
+		/// Emits new supply to `distributor` over `epochs` from block `start` on. The rate may
+		/// only fall from one epoch to the next. The schedule can be set only once, so that the
+		/// supply policy can't be changed afterwards. Only the owner may set it.
+		#[ink(message)]
+		pub fn set_emission_schedule(
+			&mut self,
+			start: BlockNumber,
+			distributor: AccountId,
+			epochs: Vec<EmissionEpoch>,
+		) -> Result<(), PSP22Error> {
+			self.ensure_owner()?;
+			if self.emission.get().is_some()
+				|| epochs.is_empty()
+				|| epochs.iter().any(|epoch| epoch.blocks == 0)
+				|| epochs.windows(2).any(|pair| pair[1].rate > pair[0].rate)
+			{
+				return Err(PSP22Error::InvalidSchedule);
+			}
+
+			self.emission.set(&EmissionSchedule { start, distributor, epochs, minted: 0 });
+
+			Ok(())
+		}
+
+		/// Returns the emission schedule, if it is set
+		#[ink(message)]
+		pub fn emission_schedule(&self) -> Option<EmissionSchedule> {
+			self.emission.get()
+		}
+
+		/// Returns the tokens the schedule has emitted by now that are yet to be minted
+		#[ink(message)]
+		pub fn pending_emission(&self) -> Balance {
+			self.emission.get().map_or(0, |emission| {
+				let mut elapsed = self.env().block_number().saturating_sub(emission.start);
+				let mut emitted: Balance = 0;
+				for epoch in emission.epochs {
+					let blocks = elapsed.min(epoch.blocks);
+					emitted = emitted.saturating_add(epoch.rate.saturating_mul(blocks.into()));
+					elapsed -= blocks;
+				}
+				emitted - emission.minted
+			})
+		}
+
+		/// Mints the pending emission to the distributor. Anyone may call this.
+		#[ink(message)]
+		pub fn mint_emission(&mut self) -> Result<(), PSP22Error> {
+			let pending = self.pending_emission();
+			let Some(emission) = self.emission.get().filter(|_| pending > 0) else {
+				return Ok(());
+			};
+
+			self._mint(emission.distributor, pending)?;
+			self.emission
+				.set(&EmissionSchedule { minted: emission.minted + pending, ..emission });
+
+			Ok(())
+		}
+
+		/// Starts a new airdrop whose entries are committed to by `root`. Only the owner may set
+		/// the root.
+		#[ink(message)]
+		pub fn set_airdrop_root(&mut self, root: [u8; 32]) -> Result<(), PSP22Error> {
+			self.ensure_owner()?;
+			self.config.set(&Config { airdrop_root: Some(root), ..self.config() });
+			self.airdrop_round += 1;
+
+			Ok(())
+		}
+
+		/// Returns the Merkle root of the current airdrop, if any
+		#[ink(message)]
+		pub fn airdrop_root(&self) -> Option<[u8; 32]> {
+			self.config().airdrop_root
+		}
+
+		/// Returns true if entry `index` of the current airdrop was claimed
+		#[ink(message)]
+		pub fn is_claimed(&self, index: u32) -> bool {
+			let word = self.airdrop_claimed.get((self.airdrop_round, index / 128)).unwrap_or(0);
+			word & (1 << (index % 128)) != 0
+		}
+
+		/// Mints `amount` tokens to the caller if `proof` shows that entry `index` of the airdrop
+		/// belongs to them. Each entry can be claimed once.
+		#[ink(message)]
+		pub fn claim(
+			&mut self,
+			index: u32,
+			amount: Balance,
+			proof: Vec<[u8; 32]>,
+		) -> Result<(), PSP22Error> {
+			let caller = self.env().caller();
+			let root = self.airdrop_root().ok_or(PSP22Error::InvalidProof)?;
+			if self.is_claimed(index) {
+				return Err(PSP22Error::AlreadyClaimed);
+			}
+
+			if proof_root(airdrop_leaf(index, caller, amount), &proof) != root {
+				return Err(PSP22Error::InvalidProof);
+			}
+
+			let key = (self.airdrop_round, index / 128);
+			let word = self.airdrop_claimed.get(key).unwrap_or(0);
+			self.airdrop_claimed.insert(key, &(word | (1 << (index % 128))));
+			self._mint(caller, amount)?;
+
+			Self::emit_event(
+				self.env(),
+				Event::AirdropClaimed(AirdropClaimed { index, account: caller, amount }),
+			);
+
+			Ok(())
+		}
+
+		/// Sends `amount` of some other PSP22 `token` that was sent to this contract by mistake to
+		/// `to`. Only the owner may rescue tokens.
+		#[ink(message)]
+		pub fn rescue_token(
+			&mut self,
+			token: AccountId,
+			to: AccountId,
+			amount: Balance,
+		) -> Result<(), PSP22Error> {
+			self.ensure_owner()?;
+			if token == self.env().account_id() {
+				return Err(PSP22Error::CannotRescueSelf);
+			}
+
+			let mut token: ink::contract_ref!(PSP22) = token.into();
+			token.transfer(to, amount, Vec::new())
+		}
+
+		/// Burns `amount` tokens of `from` so the bridge can release them to `dest_address` on
+		/// `dest_chain`. Only bridges may burn, and `from` must have approved the bridge unless
+		/// it is the bridge itself.
+		#[ink(message)]
+		pub fn bridge_burn(
+			&mut self,
+			from: AccountId,
+			amount: Balance,
+			dest_chain: u32,
+			dest_address: Vec<u8>,
+		) -> Result<(), PSP22Error> {
+			self.ensure_not_paused()?;
+			self.ensure_role(Role::Bridge)?;
+			let caller = self.env().caller();
+			if from != caller {
+				self._spend_allowance(from, caller, amount)?;
+			}
+			self.ensure_unlocked(from, amount)?;
+			self._burn(from, amount)?;
+
+			let nonce = self.bridge_nonce;
+			self.bridge_nonce += 1;
+
+			Self::emit_event(
+				self.env(),
+				Event::BridgeBurned(BridgeBurned { from, amount, dest_chain, dest_address, nonce }),
+			);
+
+			Ok(())
+		}
+
+		/// Mints `amount` tokens to `to` for the transaction `source_tx` that burned or locked them
+		/// on another chain. Each source transaction is minted at most once. Only bridges may mint.
+		#[ink(message)]
+		pub fn bridge_mint(
+			&mut self,
+			to: AccountId,
+			amount: Balance,
+			source_tx: [u8; 32],
+		) -> Result<(), PSP22Error> {
+			self.ensure_not_paused()?;
+			self.ensure_role(Role::Bridge)?;
+			if self.bridged_in.contains(source_tx) {
+				return Err(PSP22Error::AlreadyProcessed);
+			}
+
+			self.bridged_in.insert(source_tx, &());
+			self._mint(to, amount)?;
+
+			Self::emit_event(
+				self.env(),
+				Event::BridgeMinted(BridgeMinted { to, amount, source_tx }),
+			);
+
+			Ok(())
+		}
+
+		/// Returns true if the incoming bridge transaction `source_tx` was already minted
+		#[ink(message)]
+		pub fn is_bridged_in(&self, source_tx: [u8; 32]) -> bool {
+			self.bridged_in.contains(source_tx)
+		}
+
+		/// Allows escrow for `chain` up to `limit` tokens at once. Lowering the limit below the
+		/// tokens already in escrow only blocks new locks. Only the owner may set limits.
+		#[ink(message)]
+		pub fn set_escrow_limit(&mut self, chain: u32, limit: Balance) -> Result<(), PSP22Error> {
+			self.ensure_owner()?;
+			let escrow = self.escrow_chains.get(chain).unwrap_or_default();
+			self.escrow_chains.insert(chain, &EscrowChain { limit, ..escrow });
+
+			Ok(())
+		}
+
+		/// Halts or resumes escrow for `chain` alone. Only pausers may do this.
+		#[ink(message)]
+		pub fn set_escrow_paused(&mut self, chain: u32, paused: bool) -> Result<(), PSP22Error> {
+			self.ensure_role(Role::Pauser)?;
+			let escrow = self.escrow_chains.get(chain).ok_or(PSP22Error::ChainUnavailable)?;
+			self.escrow_chains.insert(chain, &EscrowChain { paused, ..escrow });
+
+			Ok(())
+		}
+
+		/// Returns the escrow of `chain`, if it is set up
+		#[ink(message)]
+		pub fn escrow_chain(&self, chain: u32) -> Option<EscrowChain> {
+			self.escrow_chains.get(chain)
+		}
+
+		/// Holds `amount` of the caller's tokens on the contract's own account so that relayers can
+		/// credit them to `remote_address` on `chain`
+		#[ink(message)]
+		pub fn escrow_lock(
+			&mut self,
+			chain: u32,
+			amount: Balance,
+			remote_address: Vec<u8>,
+		) -> Result<(), PSP22Error> {
+			self.ensure_not_paused()?;
+			let caller = self.env().caller();
+			self.ensure_not_denied(caller, caller)?;
+			let mut escrow = self.escrow_chains.get(chain).ok_or(PSP22Error::ChainUnavailable)?;
+			if escrow.paused {
+				return Err(PSP22Error::ChainUnavailable);
+			}
+			escrow.locked = escrow.locked.checked_add(amount).ok_or(PSP22Error::Overflow)?;
+			if escrow.locked > escrow.limit {
+				return Err(PSP22Error::ChainLimitExceeded);
+			}
+
+			self.ensure_unlocked(caller, amount)?;
+			self._move(caller, self.env().account_id(), amount)?;
+			self.escrow_chains.insert(chain, &escrow);
+			let nonce = self.escrow_nonce;
+			self.escrow_nonce += 1;
+
+			Self::emit_event(
+				self.env(),
+				Event::EscrowLocked(EscrowLocked {
+					from: caller,
+					chain,
+					amount,
+					remote_address,
+					nonce,
+				}),
+			);
+
+			Ok(())
+		}
+
+		/// Releases `amount` escrowed tokens to `to` once they came back from `chain`, as attested
+		/// by the relayed message `message_id`. Each message releases tokens at most once. Only
+		/// relayers may release.
+		#[ink(message)]
+		pub fn escrow_release(
+			&mut self,
+			chain: u32,
+			to: AccountId,
+			amount: Balance,
+			message_id: [u8; 32],
+		) -> Result<(), PSP22Error> {
+			self.ensure_not_paused()?;
+			self.ensure_role(Role::Relayer)?;
+			let mut escrow = self.escrow_chains.get(chain).ok_or(PSP22Error::ChainUnavailable)?;
+			if escrow.paused {
+				return Err(PSP22Error::ChainUnavailable);
+			}
+			if self.escrow_released.contains(message_id) {
+				return Err(PSP22Error::AlreadyProcessed);
+			}
+			escrow.locked =
+				escrow.locked.checked_sub(amount).ok_or(PSP22Error::InsufficientBalance)?;
+
+			self.escrow_released.insert(message_id, &());
+			self.escrow_chains.insert(chain, &escrow);
+			self._move(self.env().account_id(), to, amount)?;
+
+			Self::emit_event(
+				self.env(),
+				Event::EscrowReleased(EscrowReleased { to, chain, amount, message_id }),
+			);
+
+			Ok(())
+		}
+
+		/// Offers `taker` to swap `give_amount` of the caller's `give_token` for `want_amount` of
+		/// the taker's tokens until block `deadline`. The caller must also approve this contract
+		/// to spend `give_amount` of `give_token`. A new offer replaces the previous one and an
+		/// offer of nothing withdraws it.
+		#[ink(message)]
+		pub fn offer_swap(
+			&mut self,
+			taker: AccountId,
+			give_token: AccountId,
+			give_amount: Balance,
+			want_amount: Balance,
+			deadline: BlockNumber,
+		) -> Result<(), PSP22Error> {
+			self.ensure_not_paused()?;
+			let maker = self.env().caller();
+			let offer = SwapOffer { give_token, give_amount, want_amount, deadline };
+			if give_amount == 0 {
+				self.swap_offers.remove((maker, taker));
+			} else {
+				self.swap_offers.insert((maker, taker), &offer);
+			}
+
+			Self::emit_event(self.env(), Event::SwapOffered(SwapOffered { maker, taker, offer }));
+
+			Ok(())
+		}
+
+		/// Returns the swap `maker` currently offers `taker`, if any
+		#[ink(message)]
+		pub fn swap_offer(&self, maker: AccountId, taker: AccountId) -> Option<SwapOffer> {
+			self.swap_offers.get((maker, taker))
+		}
+
+		/// Takes the swap `counterparty` offered the caller: sends them `my_amount` tokens and
+		/// pulls `their_amount` of `their_token` from them, all or nothing. The terms must match
+		/// the offer exactly and neither the offer nor the caller's `deadline` may have passed.
+		#[ink(message)]
+		pub fn swap_exact(
+			&mut self,
+			counterparty: AccountId,
+			my_amount: Balance,
+			their_token: AccountId,
+			their_amount: Balance,
+			deadline: BlockNumber,
+		) -> Result<(), PSP22Error> {
+			self.ensure_not_paused()?;
+			let caller = self.env().caller();
+			let offer =
+				self.swap_offers.get((counterparty, caller)).ok_or(PSP22Error::NoSwapOffer)?;
+			if offer
+				!= (SwapOffer {
+					give_token: their_token,
+					give_amount: their_amount,
+					want_amount: my_amount,
+					deadline: offer.deadline,
+				}) {
+				return Err(PSP22Error::NoSwapOffer);
+			}
+			if self.env().block_number() > deadline.min(offer.deadline) {
+				return Err(PSP22Error::SwapExpired);
+			}
+
+			self.swap_offers.remove((counterparty, caller));
+			self._transfer_from_to(&caller, &counterparty, my_amount, Vec::new())?;
+			let mut other: ink::contract_ref!(PSP22) = their_token.into();
+			other.transfer_from(counterparty, caller, their_amount, Vec::new())?;
+
+			Self::emit_event(
+				self.env(),
+				Event::Swapped(Swapped {
+					maker: counterparty,
+					taker: caller,
+					amount: my_amount,
+					other_token: their_token,
+					other_amount: their_amount,
+				}),
+			);
+
+			Ok(())
+		}
+
+		/// Returns the tokens `owner` holds in `partition`
+		#[ink(message)]
+		pub fn balance_of_by_partition(&self, partition: Partition, owner: AccountId) -> Balance {
+			self.to_tokens(self._partition_shares(owner, partition))
+		}
+
+		/// Returns the named partitions `owner` holds tokens in
+		#[ink(message)]
+		pub fn partitions_of(&self, owner: AccountId) -> Vec<Partition> {
+			self.partitions.get(owner).unwrap_or_default()
+		}
+
+		/// Transfers `value` of the caller's tokens in `partition` to the same partition of `to`
+		#[ink(message)]
+		pub fn transfer_by_partition(
+			&mut self,
+			partition: Partition,
+			to: AccountId,
+			value: Balance,
+			data: Vec<u8>,
+		) -> Result<(), PSP22Error> {
+			self._transfer_by_partition(partition, self.env().caller(), to, value, data)
+		}
+
+		/// Transfers `value` of the tokens `from` holds in `partition` to the same partition of
+		/// `to`. The caller must be an operator of `from` for the partition.
+		#[ink(message)]
+		pub fn operator_transfer_by_partition(
+			&mut self,
+			partition: Partition,
+			from: AccountId,
+			to: AccountId,
+			value: Balance,
+			data: Vec<u8>,
+		) -> Result<(), PSP22Error> {
+			if !self.is_operator_for_partition(partition, self.env().caller(), from) {
+				return Err(PSP22Error::NotOperator);
+			}
+
+			self._transfer_by_partition(partition, from, to, value, data)
+		}
+
+		/// Lets `operator` move the caller's tokens in `partition`
+		#[ink(message)]
+		pub fn authorize_operator_by_partition(
+			&mut self,
+			partition: Partition,
+			operator: AccountId,
+		) {
+			let holder = self.env().caller();
+			self.partition_operators.insert((holder, partition, operator), &());
+
+			Self::emit_event(
+				self.env(),
+				Event::PartitionOperatorSet(PartitionOperatorSet {
+					holder,
+					operator,
+					partition,
+					authorized: true,
+				}),
+			);
+		}
+
+		/// Stops `operator` from moving the caller's tokens in `partition`
+		#[ink(message)]
+		pub fn revoke_operator_by_partition(&mut self, partition: Partition, operator: AccountId) {
+			let holder = self.env().caller();
+			self.partition_operators.remove((holder, partition, operator));
+
+			Self::emit_event(
+				self.env(),
+				Event::PartitionOperatorSet(PartitionOperatorSet {
+					holder,
+					operator,
+					partition,
+					authorized: false,
+				}),
+			);
+		}
+
+		/// Returns true if `operator` may move the tokens `holder` keeps in `partition`
+		#[ink(message)]
+		pub fn is_operator_for_partition(
+			&self,
+			partition: Partition,
+			operator: AccountId,
+			holder: AccountId,
+		) -> bool {
+			self.partition_operators.contains((holder, partition, operator))
+		}
+
+		/// Moves `value` of the tokens `account` holds in partition `from` to partition `to`, e.g.
+		/// to restrict them. Only compliance officers may change partitions.
+		#[ink(message)]
+		pub fn change_partition(
+			&mut self,
+			account: AccountId,
+			from: Partition,
+			to: Partition,
+			value: Balance,
+		) -> Result<(), PSP22Error> {
+			self.ensure_role(Role::Compliance)?;
+			let shares = self.to_shares(value);
+			if self._partition_shares(account, from) < shares {
+				return Err(PSP22Error::InsufficientBalance);
+			}
+
+			self._sub_partition_shares(account, from, shares);
+			self._add_partition_shares(account, to, shares);
+
+			Self::emit_event(
+				self.env(),
+				Event::ChangedPartition(ChangedPartition { account, from, to, value }),
+			);
+
+			Ok(())
+		}
+
+		/// Replaces the code of this contract, keeping its storage. Call `migrate` afterwards if
+		/// the new code changes the storage layout. Only upgraders may upgrade.
+		#[ink(message)]
+		pub fn upgrade(&mut self, code_hash: Hash) -> Result<(), PSP22Error> {
+			self.ensure_role(Role::Upgrader)?;
+			self.env().set_code_hash(&code_hash).map_err(|_| PSP22Error::UpgradeFailed)
+		}
+
+		/// Brings the stored data up to `STORAGE_VERSION` after an upgrade. Only upgraders may
+		/// migrate.
+		#[ink(message)]
+		pub fn migrate(&mut self) -> Result<(), PSP22Error> {
+			self.ensure_role(Role::Upgrader)?;
+			if self.storage_version >= STORAGE_VERSION {
+				return Err(PSP22Error::NothingToMigrate);
+			}
+
+			// Each future schema change adds a step here, e.g.
+			// `if self.storage_version < 2 { ... }`
+			self.storage_version = STORAGE_VERSION;
+
+			Ok(())
+		}
+
+		/// Removes the stored zero balances of `accounts` and the zero or expired allowances
+		/// between them, which reclaims their storage deposit. Balances and allowances that drop to
+		/// zero are removed as they go, so this only finds entries left behind by older code.
+		/// Returns the number of entries removed.
+		#[ink(message)]
+		pub fn purge_empty(&mut self, accounts: Vec<AccountId>) -> u32 {
+			let mut purged = 0;
+			for owner in &accounts {
+				if self.balances.get(owner) == Some(0) {
+					self.balances.remove(owner);
+					purged += 1;
+				}
+
+				for spender in &accounts {
+					let stored = self.allowances.contains((owner, spender));
+					if stored && self._allowance(*owner, *spender) == 0 {
+						self.allowances.remove((owner, spender));
+						self.allowance_deadlines.remove((owner, spender));
+						purged += 1;
+					}
+				}
+			}
+
+			purged
+		}
+
+		/// Returns the version of the contract code
+		#[ink(message)]
+		pub fn version(&self) -> String {
+			String::from(env!("CARGO_PKG_VERSION"))
+		}
+
+		/// Returns true if this deployment includes the capability `feature_id`, one of the ids in
+		/// the `capabilities` module
+		#[ink(message)]
+		pub fn supports(&self, feature_id: u32) -> bool {
+			CAPABILITIES.checked_shr(feature_id).is_some_and(|bits| bits & 1 == 1)
+		}
+
+		/// Returns the storage schema version of the stored data
+		#[ink(message)]
+		pub fn storage_version(&self) -> u32 {
+			self.storage_version
+		}
+
+		/// Converts `amount` whole tokens to base units using the token's decimals
+		#[ink(message)]
+		pub fn to_base_units(&self, amount: Balance) -> Result<Balance, PSP22Error> {
+			crate::units::to_base_units(amount, self.token_decimals()).ok_or(PSP22Error::Overflow)
+		}
+
+		/// Converts `amount` base units to whole tokens using the token's decimals, rounding down
+		// Named after the conversion, not a constructor
+		#[allow(clippy::wrong_self_convention)]
+		#[ink(message)]
+		pub fn from_base_units(&self, amount: Balance) -> Balance {
+			crate::units::from_base_units(amount, self.token_decimals())
+		}
+
+		/// Returns the link to the token's logo or metadata JSON, if set
+		#[ink(message)]
+		pub fn token_uri(&self) -> Option<String> {
+			self.metadata().token_uri
+		}
+
+		/// Sets the link to the token's logo or metadata JSON. Only the owner may set it.
+		#[ink(message)]
+		pub fn set_token_uri(&mut self, token_uri: Option<String>) -> Result<(), PSP22Error> {
+			self.ensure_owner()?;
+			self.metadata.set(&Metadata { token_uri, ..self.metadata() });
+
+			Ok(())
+		}
+
+		/// Returns the metadata entry stored under `key`, if any
+		#[ink(message)]
+		pub fn get_attribute(&self, key: String) -> Option<String> {
+			self.attributes.get(key)
+		}
+
+		/// Stores `value` under `key`, or removes the entry if `value` is `None`. Only the owner
+		/// may set attributes.
+		#[ink(message)]
+		pub fn set_attribute(
+			&mut self,
+			key: String,
+			value: Option<String>,
+		) -> Result<(), PSP22Error> {
+			self.ensure_owner()?;
+			match value {
+				Some(value) => {
+					self.attributes.insert(key, &value);
+				},
+				None => self.attributes.remove(key),
+			}
+
+			Ok(())
+		}
+
+		/// Returns the number of accounts with a non-zero balance
+		#[ink(message)]
+		pub fn holders_count(&self) -> u32 {
+			self.holders_count
+		}
+
+		/// Returns the holder at `index`. Indices are not stable, a holder whose balance drops to
+		/// zero is replaced by the last holder.
+		#[ink(message)]
+		pub fn holder_at(&self, index: u32) -> Option<AccountId> {
+			self.holders.get(index)
+		}
+
+		/// Returns up to `limit` holders, starting at `offset`
+		#[ink(message)]
+		pub fn holders(&self, offset: u32, limit: u32) -> Vec<AccountId> {
+			let end = offset.saturating_add(limit).min(self.holders_count);
+			(offset..end).filter_map(|index| self.holders.get(index)).collect()
+		}
+
+		/// Locks `amount` of the tokens of `account` so they can't be transferred before block
+		/// `until` has passed. Only the unlocked part of a balance can be locked.
+		#[ink(message)]
+		pub fn lock(
+			&mut self,
+			account: AccountId,
+			amount: Balance,
+			until: BlockNumber,
+		) -> Result<(), PSP22Error> {
+			self.ensure_role(Role::LockManager)?;
+			let unlocked = self._balance(account).saturating_sub(self.locked_balance_of(account));
+			if unlocked < amount {
+				return Err(PSP22Error::InsufficientBalance);
+			}
+
+			let mut locks = self.locks.get(account).unwrap_or_default();
+			locks.push(Lock { amount, until });
+			self.locks.insert(account, &locks);
+
+			Self::emit_event(self.env(), Event::Locked(Locked { account, amount, until }));
+
+			Ok(())
+		}
+
+		/// Returns the locks on the balance of `account`, including expired ones
+		#[ink(message)]
+		pub fn locks_of(&self, account: AccountId) -> Vec<Lock> {
+			self.locks.get(account).unwrap_or_default()
+		}
+
+		/// Returns how many of the tokens of `account` are locked as of the current block
+		#[ink(message)]
+		pub fn locked_balance_of(&self, account: AccountId) -> Balance {
+			let block = self.env().block_number();
+			self.locks_of(account)
+				.iter()
+				.filter(|lock| lock.until >= block)
+				.fold(0, |locked: Balance, lock| locked.saturating_add(lock.amount))
+		}
+
+		/// Removes the expired locks of `account` from storage and returns the amount they held.
+		/// Anyone may call this, expired locks don't restrict transfers either way.
+		#[ink(message)]
+		pub fn unlock_expired(&mut self, account: AccountId) -> Balance {
+			let block = self.env().block_number();
+			let (locks, expired): (Vec<Lock>, Vec<Lock>) =
+				self.locks_of(account).into_iter().partition(|lock| lock.until >= block);
+			if expired.is_empty() {
+				return 0;
+			}
+
+			if locks.is_empty() {
+				self.locks.remove(account);
+			} else {
+				self.locks.insert(account, &locks);
+			}
+			let amount = expired
+				.iter()
+				.fold(0, |amount: Balance, lock| amount.saturating_add(lock.amount));
+
+			Self::emit_event(self.env(), Event::Unlocked(Unlocked { account, amount }));
+
+			amount
+		}
+
+		/// Mints `DRIP_AMOUNT` tokens to the caller, at most once every `DRIP_INTERVAL` blocks.
+		/// Only built with the `faucet` feature, for testnets.
+		#[cfg(feature = "faucet")]
+		#[ink(message)]
+		pub fn drip(&mut self) -> Result<(), PSP22Error> {
+			self.ensure_not_paused()?;
+			let caller = self.env().caller();
+			self.ensure_not_denied(caller, caller)?;
+			let block = self.env().block_number();
+			let last = self.last_drip.get(caller);
+			if last.is_some_and(|last| block < last.saturating_add(DRIP_INTERVAL)) {
+				return Err(PSP22Error::FaucetCooldown);
+			}
+
+			self.last_drip.insert(caller, &block);
+			self._mint(caller, DRIP_AMOUNT)
+		}
+
+		/// The hash `from` signs to authorize a relayed transfer
+		#[ink(message)]
+		pub fn transfer_hash(
+			&self,
+			from: AccountId,
+			to: AccountId,
+			value: Balance,
+			nonce: u64,
+			deadline: BlockNumber,
+		) -> [u8; 32] {
+			let mut hash = <Blake2x256 as HashOutput>::Type::default();
+			ink::env::hash_encoded::<Blake2x256, _>(
+				&(self.domain_separator(), from, to, value, nonce, deadline),
+				&mut hash,
+			);
+			hash
+		}
+
+		/// The hash `owner` signs to let `spender` withdraw up to `value` through `permit`. It is
+		/// tagged so that a signed transfer with the same fields can't be used as a permit.
+		#[ink(message)]
+		pub fn permit_hash(
+			&self,
+			owner: AccountId,
+			spender: AccountId,
+			value: Balance,
+			nonce: u64,
+			deadline: BlockNumber,
+		) -> [u8; 32] {
+			let mut hash = <Blake2x256 as HashOutput>::Type::default();
+			ink::env::hash_encoded::<Blake2x256, _>(
+				&(self.domain_separator(), b"permit", owner, spender, value, nonce, deadline),
+				&mut hash,
+			);
+			hash
+		}
+
+		/// Transfers `value` tokens from `from` to `to` on behalf of a holder who signed the
+		/// transfer off-chain, so that a relayer can pay the fees.
+		///
+		/// `signature` is a recoverable ECDSA signature of `transfer_hash(..)` by the key behind
+		/// `from`. It can be used once and only until block `deadline`.
+		#[ink(message)]
+		pub fn transfer_with_signature(
+			&mut self,
+			from: AccountId,
+			to: AccountId,
+			value: Balance,
+			nonce: u64,
+			deadline: BlockNumber,
+			signature: [u8; 65],
+		) -> Result<(), PSP22Error> {
+			self.ensure_not_paused()?;
+			if self.env().block_number() > deadline {
+				return Err(PSP22Error::SignatureExpired);
+			}
+
+			let hash = self.transfer_hash(from, to, value, nonce, deadline);
+			self._use_signature(from, nonce, &hash, &signature)?;
+
+			self._transfer_from_to(&from, &to, value, Vec::new())
+		}
+
+		/// The hash `from` signs to authorize a one-off transfer with `transfer_with_authorization`
+		#[ink(message)]
+		pub fn authorization_hash(
+			&self,
+			from: AccountId,
+			to: AccountId,
+			value: Balance,
+			valid_after: BlockNumber,
+			valid_before: BlockNumber,
+			nonce: AuthorizationNonce,
+		) -> [u8; 32] {
+			let mut hash = <Blake2x256 as HashOutput>::Type::default();
+			ink::env::hash_encoded::<Blake2x256, _>(
+				&(self.domain_separator(), from, to, value, valid_after, valid_before, nonce),
+				&mut hash,
+			);
+			hash
+		}
+
+		/// Transfers `value` tokens from `from` to `to` on behalf of a holder who signed a one-off
+		/// payment off-chain, e.g. for a merchant to collect. Unlike `transfer_with_signature`
+		/// the nonce is any random value, so several payments can be signed and used in any order.
+		///
+		/// `signature` is a recoverable ECDSA signature of `authorization_hash(..)` by the key
+		/// behind `from`. It can be used once, after block `valid_after` and before block
+		/// `valid_before`.
+		// Mirrors the arguments of the `transferWithAuthorization` standard
+		#[allow(clippy::too_many_arguments)]
+		#[ink(message)]
+		pub fn transfer_with_authorization(
+			&mut self,
+			from: AccountId,
+			to: AccountId,
+			value: Balance,
+			valid_after: BlockNumber,
+			valid_before: BlockNumber,
+			nonce: AuthorizationNonce,
+			signature: [u8; 65],
+		) -> Result<(), PSP22Error> {
+			self.ensure_not_paused()?;
+			let block = self.env().block_number();
+			if block <= valid_after {
+				return Err(PSP22Error::SignatureNotYetValid);
+			}
+			if block >= valid_before {
+				return Err(PSP22Error::SignatureExpired);
+			}
+			if self.authorization_state(from, nonce) {
+				return Err(PSP22Error::InvalidNonce);
+			}
+
+			let hash = self.authorization_hash(from, to, value, valid_after, valid_before, nonce);
+			self._verify_signature(from, &hash, &signature)?;
+			self.authorizations.insert((from, nonce), &());
+
+			Self::emit_event(
+				self.env(),
+				Event::AuthorizationUsed(AuthorizationUsed {
+					authorizer: from,
+					nonce,
+					cancelled: false,
+				}),
+			);
+
+			self._transfer_from_to(&from, &to, value, Vec::new())
+		}
+
+		/// Makes the caller's transfer authorization `nonce` unusable before anyone submits it
+		#[ink(message)]
+		pub fn cancel_authorization(
+			&mut self,
+			nonce: AuthorizationNonce,
+		) -> Result<(), PSP22Error> {
+			let authorizer = self.env().caller();
+			if self.authorization_state(authorizer, nonce) {
+				return Err(PSP22Error::InvalidNonce);
+			}
+
+			self.authorizations.insert((authorizer, nonce), &());
+
+			Self::emit_event(
+				self.env(),
+				Event::AuthorizationUsed(AuthorizationUsed { authorizer, nonce, cancelled: true }),
+			);
+
+			Ok(())
+		}
+
+		/// Returns true if the transfer authorization `nonce` of `authorizer` was used or
+		/// cancelled
+		#[ink(message)]
+		pub fn authorization_state(
+			&self,
+			authorizer: AccountId,
+			nonce: AuthorizationNonce,
+		) -> bool {
+			self.authorizations.contains((authorizer, nonce))
+		}
+
+		/// Transfers tokens from the caller to each of `recipients` in one call. Either every leg
+		/// succeeds or none does. One `Transfer` event is emitted per leg.
+		#[ink(message)]
+		pub fn transfer_batch(
+			&mut self,
+			recipients: Vec<(AccountId, Balance)>,
+		) -> Result<(), PSP22Error> {
+			self.ensure_not_paused()?;
+			let caller = self.env().caller();
+
+			let total = Self::batch_total(&recipients)?;
+			if self._balance(caller) < total {
+				return Err(PSP22Error::InsufficientBalance);
+			}
+
+			for (to, value) in recipients {
+				self._transfer_from_to(&caller, &to, value, Vec::new())?;
+			}
+
+			Ok(())
+		}
+
+		/// Transfers tokens from `from` to each of `recipients` on their behalf. The caller's
+		/// allowance is checked and consumed once, for the batch total. Either every leg succeeds
+		/// or none does.
+		#[ink(message)]
+		pub fn transfer_from_batch(
+			&mut self,
+			from: AccountId,
+			recipients: Vec<(AccountId, Balance)>,
+		) -> Result<(), PSP22Error> {
+			self.ensure_not_paused()?;
+			let caller = self.env().caller();
+
+			let total = Self::batch_total(&recipients)?;
+			self._spend_allowance(from, caller, total)?;
+			for (to, value) in recipients {
+				self._transfer_from_to(&from, &to, value, Vec::new())?;
+			}
+
+			Ok(())
+		}
+
+		/// Sends `amounts[i]` of the caller's tokens to `recipients[i]`, like `transfer_batch`
+		/// but with the recipients and amounts passed as separate lists.
+		#[ink(message)]
+		pub fn disperse(
+			&mut self,
+			recipients: Vec<AccountId>,
+			amounts: Vec<Balance>,
+		) -> Result<(), PSP22Error> {
+			if recipients.len() != amounts.len() {
+				return Err(PSP22Error::LengthMismatch);
+			}
+
+			self.transfer_batch(recipients.into_iter().zip(amounts).collect())
+		}
+
+		/// The sum of the amounts sent in a batch
+		fn batch_total(recipients: &[(AccountId, Balance)]) -> Result<Balance, PSP22Error> {
+			recipients.iter().try_fold(0, |total: Balance, (_, value)| {
+				total.checked_add(*value).ok_or(PSP22Error::Overflow)
+			})
+		}
+
+		/// Allows `spender` to withdraw up to `amount` from the caller's account and then tells the
+		/// spender through its `on_approval` hook, passing `data` along, e.g. so that it can
+		/// record a deposit to pull afterwards. The hook can't call back into the token.
+		#[ink(message)]
+		pub fn approve_and_call(
+			&mut self,
+			spender: AccountId,
+			amount: Balance,
+			data: Vec<u8>,
+		) -> Result<(), PSP22Error> {
+			self.ensure_not_paused()?;
+			// The token calling itself would act with its own balance and roles
+			if spender == self.env().account_id() {
+				return Err(PSP22Error::ReceiverRejected);
+			}
+			let caller = self.env().caller();
+			self.allowance_deadlines.remove((caller, spender));
+			self._approve_from_to(caller, spender, amount)?;
+
+			let mut receiver: ink::contract_ref!(PSP22ApprovalReceiver) = spender.into();
+			match receiver.call_mut().on_approval(caller, amount, data).try_invoke() {
+				Ok(Ok(Ok(()))) => Ok(()),
+				Ok(Ok(Err(PSP22ReceiverError::TransferRejected(reason)))) => {
+					Err(PSP22Error::SafeTransferCheckFailed(reason))
+				},
+				_ => Err(PSP22Error::CallFailed),
+			}
+		}
+
+		/// Executes `calls` one after the other as if the caller had sent each of them, e.g. an
+		/// approval, a transfer and a delegation, and reverts all of them if any fails. Each call
+		/// is the selector of a message followed by its SCALE encoded arguments. The messages of
+		/// `PSP22` that change state and `PSP22Votes::delegate` may be called, nothing else.
+		#[ink(message)]
+		pub fn multicall(&mut self, calls: Vec<Vec<u8>>) -> Result<(), PSP22Error> {
+			if calls.iter().any(|call| call.len() < 4) {
+				return Err(PSP22Error::CallFailed);
+			}
+
+			for call in calls {
+				self._dispatch(&call)?;
+			}
+
+			Ok(())
+		}
+
+		/// Deploys a new copy of the token, running the same code, and returns its address.
+		/// `constructor_args` are the SCALE encoded arguments of `new`. The address is derived from
+		/// this token's address, the code, the arguments and `salt`, so it is known before the
+		/// deployment, and the same arguments and salt can only be used once.
+		///
+		/// The token is the deployer of the child, so it hands the initial supply, every role and
+		/// the treasury over to the caller and keeps none of them. The caller is proposed as the
+		/// owner and must still accept the ownership.
+		#[ink(message)]
+		pub fn deploy_child(
+			&mut self,
+			salt: Vec<u8>,
+			constructor_args: Vec<u8>,
+		) -> Result<AccountId, PSP22Error> {
+			let caller = self.env().caller();
+			let code_hash = self.env().own_code_hash().map_err(|_| PSP22Error::CallFailed)?;
+			let mut child = ink::env::call::build_create::<TokenRef>()
+				.code_hash(code_hash)
+				.endowment(0)
+				.exec_input(
+					ExecutionInput::new(CallSelector::new(ink::selector_bytes!("new")))
+						.push_arg(EncodedArgs(constructor_args)),
+				)
+				.salt_bytes(salt)
+				.returns::<TokenRef>()
+				.try_instantiate()
+				.map_err(|_| PSP22Error::CallFailed)?
+				.map_err(|_| PSP22Error::CallFailed)?;
+
+			let this = self.env().account_id();
+			let supply = child.balance_of(this);
+			if supply > 0 {
+				child.transfer(caller, supply, Vec::new())?;
+			}
+			for role in Role::ALL {
+				child.grant_role(role, caller)?;
+				child.revoke_role(role, this)?;
+			}
+			child.set_transfer_fee(0, caller)?;
+			child.transfer_ownership(caller)?;
+
+			let child = ink::ToAccountId::to_account_id(&child);
+			Self::emit_event(
+				self.env(),
+				Event::ChildDeployed(ChildDeployed { deployer: caller, child }),
+			);
+			Ok(child)
+		}
+
+		/// Allows `operator` to move any amount of the caller's tokens, without an allowance to
+		/// keep topped up, e.g. for a market that pulls tokens over and over. Pass `false` to
+		/// revoke it.
+		#[ink(message)]
+		pub fn set_operator(
+			&mut self,
+			operator: AccountId,
+			approved: bool,
+		) -> Result<(), PSP22Error> {
+			self.ensure_not_paused()?;
+			let owner = self.env().caller();
+			if approved {
+				self.operators.insert((owner, operator), &());
+			} else {
+				self.operators.remove((owner, operator));
+			}
+
+			Self::emit_event(
+				self.env(),
+				Event::OperatorSet(OperatorSet { owner, operator, approved }),
+			);
+
+			Ok(())
+		}
+
+		/// Returns true if `operator` may move any of the tokens of `owner`
+		#[ink(message)]
+		pub fn is_operator(&self, owner: AccountId, operator: AccountId) -> bool {
+			self.operators.contains((owner, operator))
+		}
+
+		/// Allows `spender` to pull up to `amount_per_period` of the caller's tokens in every
+		/// period of `period` blocks, starting now. What is not pulled in a period doesn't carry
+		/// over. It is drawn on once the plain allowance doesn't cover a transfer. A zero amount or
+		/// period revokes it.
+		#[ink(message)]
+		pub fn approve_recurring(
+			&mut self,
+			spender: AccountId,
+			amount_per_period: Balance,
+			period: BlockNumber,
+		) -> Result<(), PSP22Error> {
+			self.ensure_not_paused()?;
+			let owner = self.env().caller();
+			if amount_per_period == 0 || period == 0 {
+				self.recurring_allowances.remove((owner, spender));
+			} else {
+				let period_start = self.env().block_number();
+				self.recurring_allowances.insert(
+					(owner, spender),
+					&RecurringAllowance { amount_per_period, period, period_start, spent: 0 },
+				);
+			}
+
+			Self::emit_event(
+				self.env(),
+				Event::RecurringApproval(RecurringApproval {
+					owner,
+					spender,
+					amount_per_period,
+					period,
+				}),
+			);
+
+			Ok(())
+		}
+
+		/// Returns the recurring allowance `owner` gave `spender` as of the current period, if any
+		#[ink(message)]
+		pub fn recurring_allowance(
+			&self,
+			owner: AccountId,
+			spender: AccountId,
+		) -> Option<RecurringAllowance> {
+			let mut allowance = self.recurring_allowances.get((owner, spender))?;
+			let elapsed = self.env().block_number().saturating_sub(allowance.period_start);
+			if elapsed >= allowance.period {
+				allowance.period_start += elapsed - elapsed % allowance.period;
+				allowance.spent = 0;
+			}
+
+			Some(allowance)
+		}
+
+		/// Sets `spender`'s allowance over the caller's tokens to `new_amount`, but only if it is
+		/// still `expected_current`. If the spender front-ran the change and spent the old
+		/// allowance, it fails instead of letting the spender take the new amount on top.
+		#[ink(message)]
+		pub fn compare_and_approve(
+			&mut self,
+			spender: AccountId,
+			expected_current: Balance,
+			new_amount: Balance,
+		) -> Result<(), PSP22Error> {
+			self.ensure_not_paused()?;
+			let caller = self.env().caller();
+			if self._allowance(caller, spender) != expected_current {
+				return Err(PSP22Error::AllowanceChanged);
+			}
+			self.allowance_deadlines.remove((caller, spender));
+
+			self._approve_from_to(caller, spender, new_amount)
+		}
+
+		/// Allows `spender` to withdraw up to `amount` from the caller's account until block
+		/// `expires_at`. Afterwards the allowance is treated as zero.
+		#[ink(message)]
+		pub fn approve_with_deadline(
+			&mut self,
+			spender: AccountId,
+			amount: Balance,
+			expires_at: BlockNumber,
+		) -> Result<(), PSP22Error> {
+			self.ensure_not_paused()?;
+			let caller = self.env().caller();
+			self.allowance_deadlines.insert((caller, spender), &expires_at);
+
+			self._approve_from_to(caller, spender, amount)
+		}
+
+		/// Returns the amount `spender` may currently withdraw from `owner` and the last block in
+		/// which it may do so, if the allowance expires.
+		#[ink(message)]
+		pub fn allowance_with_deadline(
+			&self,
+			owner: AccountId,
+			spender: AccountId,
+		) -> (Balance, Option<BlockNumber>) {
+			(self._allowance(owner, spender), self.allowance_deadlines.get((owner, spender)))
+		}
+
+		/// Returns the allowance of each `(owner, spender)` pair, zero if there is none
+		#[ink(message)]
+		pub fn allowances_of(&self, pairs: Vec<(AccountId, AccountId)>) -> Vec<Balance> {
+			pairs
+				.into_iter()
+				.map(|(owner, spender)| self._allowance(owner, spender))
+				.collect()
+		}
+
+		/// Checks that `spender` may move `value` of the tokens of `owner` and that `owner` holds
+		/// them, then uses up the allowance, or the recurring allowance if the plain one falls
+		/// short. Operators don't use up any.
+		fn _spend_allowance(
+			&mut self,
+			owner: AccountId,
+			spender: AccountId,
+			value: Balance,
+		) -> Result<(), PSP22Error> {
+			let operator = self.is_operator(owner, spender);
+			let allowance = self._allowance(owner, spender);
+			let recurring = match operator || allowance >= value {
+				true => None,
+				false => Some(
+					self.recurring_allowance(owner, spender)
+						.filter(|recurring| recurring.amount_per_period - recurring.spent >= value)
+						.ok_or(PSP22Error::InsufficientAllowance)?,
+				),
+			};
+			if self._balance(owner) < value {
+				return Err(PSP22Error::InsufficientBalance);
+			}
+
+			match recurring {
+				Some(recurring) => {
+					let spent = recurring.spent + value;
+					self.recurring_allowances
+						.insert((owner, spender), &RecurringAllowance { spent, ..recurring });
+					Ok(())
+				},
+				None if operator => Ok(()),
+				None => self._approve_from_to(owner, spender, allowance - value),
+			}
+		}
+
+		/// The allowance of `spender` over `owner`'s tokens, or zero if it has expired
+		fn _allowance(&self, owner: AccountId, spender: AccountId) -> Balance {
+			let deadline = self.allowance_deadlines.get((owner, spender));
+			if deadline.is_some_and(|deadline| self.env().block_number() > deadline) {
+				return 0;
+			}
+
+			self.allowances.get((owner, spender)).unwrap_or(0)
+		}
+
+		fn _approve_from_to(
+			&mut self,
+			owner: AccountId,
+			spender: AccountId,
+			amount: Balance,
+		) -> Result<(), PSP22Error> {
+			self._write_allowance(owner, spender, amount);
+
+			let event_id = self._next_event_id();
+			Self::emit_event(
+				self.env(),
+				Event::Approval(Approval { owner, spender, amount, event_id }),
+			);
+
+			Ok(())
+		}
+
+		/// Stores the allowance, or removes it when it is zero to free its storage deposit
+		fn _write_allowance(&mut self, owner: AccountId, spender: AccountId, amount: Balance) {
+			if amount == 0 {
+				self.allowances.remove((owner, spender));
+			} else {
+				self.allowances.insert((owner, spender), &amount);
+			}
+		}
+
+		fn _transfer_from_to(
+			&mut self,
+			from: &AccountId,
+			to: &AccountId,
+			value: Balance,
+			data: Vec<u8>,
+		) -> Result<(), PSP22Error> {
+			let zero = AccountId::from([0u8; 32]);
+			if *from == zero {
+				return Err(PSP22Error::ZeroSenderAddress);
+			}
+			if *to == zero {
+				return Err(PSP22Error::ZeroRecipientAddress);
+			}
+			self._before_token_transfer(*from, *to, value)?;
+			self._pay(*from, *from, *to, value, data)
+		}
+
+		/// Pays `value` of the tokens `holder` holds to `to` on behalf of `from`, who is charged
+		/// the fee and refers `to`. `holder` is `from` itself unless the token held the tokens for
+		/// it, as for escrowed and scheduled transfers.
+		fn _pay(
+			&mut self,
+			holder: AccountId,
+			from: AccountId,
+			to: AccountId,
+			value: Balance,
+			data: Vec<u8>,
+		) -> Result<(), PSP22Error> {
+			let holder_shares = self.shares_of(holder);
+			if self.to_tokens(holder_shares) < value {
+				return Err(PSP22Error::InsufficientBalance);
+			}
+
+			if self.referral_bps > 0 && self.shares_of(to) == 0 {
+				self._record_referral(from, to);
+			}
+
+			// Shares are moved rather than tokens so that the sum of all shares stays exact
+			let shares = self.to_shares(value).min(holder_shares);
+			self._write_shares(holder, holder_shares - shares);
+			let shares = self._take_fee(holder, from, to, shares);
+			let to_shares = self.shares_of(to);
+			self._write_shares(to, to_shares + shares);
+			let value = self.to_tokens(shares);
+
+			let event_id = self._next_event_id();
+			Self::emit_event(
+				self.env(),
+				Event::Transfer(Transfer { from: holder, to, value, event_id }),
+			);
+
+			self._after_token_transfer(self.env().caller(), from, to, value, data)
+		}
+
+		/// Runs before `value` tokens move from `from` to `to`, and may veto the transfer.
+		/// Extensions that restrict who can send what hook in here.
+		fn _before_token_transfer(
+			&mut self,
+			from: AccountId,
+			to: AccountId,
+			value: Balance,
+		) -> Result<(), PSP22Error> {
+			self.ensure_not_denied(from, to)?;
+			self.ensure_unlocked(from, value)?;
+			self._check_limits(from, to, value)?;
+			self._check_volume(value)
+		}
+
+		/// Runs after `operator` moved tokens from `from` to `to`, with `value` being what `to`
+		/// received after fees. Extensions that react to transfers hook in here.
+		fn _after_token_transfer(
+			&mut self,
+			operator: AccountId,
+			from: AccountId,
+			to: AccountId,
+			value: Balance,
+			data: Vec<u8>,
+		) -> Result<(), PSP22Error> {
+			psp22_traits::notify_receiver(operator, from, to, value, data)
+		}
+
+		/// Splits the transfer fee on `shares` between the fee recipients, burns the burn rate and
+		/// returns the shares left for the recipient. The fee is charged to `from` and paid out of
+		/// what `holder` holds. Nothing is charged if either side is exempt.
+		fn _take_fee(
+			&mut self,
+			holder: AccountId,
+			from: AccountId,
+			to: AccountId,
+			shares: Balance,
+		) -> Balance {
+			if self.is_fee_exempt(from) || self.is_fee_exempt(to) {
+				return shares;
+			}
+
+			let zero = AccountId::from([0u8; 32]);
+			let fee = bps_of(shares, self.fee_bps);
+			let burn = bps_of(shares, self.burn_bps);
+			let mut burned = burn;
+			let mut split = fee;
+			if let Some(referrer) = self.referrers.get(from).filter(|_| self.referral_bps > 0) {
+				let cut = bps_of(fee, self.referral_bps);
+				self._pay_fee_part(holder, referrer, cut);
+				split -= cut;
+			}
+			if split > 0 {
+				let recipients = self.fee_config().recipients;
+				let total_weight =
+					recipients.iter().map(|(_, weight)| Balance::from(*weight)).sum::<Balance>();
+				let mut left = split;
+				for (i, (recipient, weight)) in recipients.iter().enumerate() {
+					// The last recipient also gets what rounding left over
+					let part = match i + 1 == recipients.len() {
+						true => left,
+						false => mul_div(split, Balance::from(*weight), total_weight),
+					};
+					left -= part;
+					match *recipient == zero {
+						true => burned += part,
+						false => self._pay_fee_part(holder, *recipient, part),
+					}
+				}
+			}
+
+			if burned > 0 {
+				let amount = self.to_tokens(burned);
+				self.total_shares -= burned;
+				self.total_supply -= amount;
+
+				let event_id = self._next_event_id();
+				Self::emit_event(
+					self.env(),
+					Event::Transfer(Transfer { from: holder, to: zero, value: amount, event_id }),
+				);
+				let event_id = self._next_event_id();
+				Self::emit_event(
+					self.env(),
+					Event::Burned(Burned { from: holder, amount, event_id }),
+				);
+			}
+
+			shares - fee - burn
+		}
+
+		/// Credits `shares` of the fee paid out of what `from` holds to `recipient`
+		fn _pay_fee_part(&mut self, from: AccountId, recipient: AccountId, shares: Balance) {
+			if shares == 0 {
+				return;
+			}
+
+			let recipient_shares = self.shares_of(recipient);
+			self._write_shares(recipient, recipient_shares + shares);
+
+			let value = self.to_tokens(shares);
+			let event_id = self._next_event_id();
+			Self::emit_event(
+				self.env(),
+				Event::Transfer(Transfer { from, to: recipient, value, event_id }),
+			);
+		}
+
+		/// Makes `referrer` the referrer of `account` unless it already has one
+		fn _record_referral(&mut self, referrer: AccountId, account: AccountId) {
+			if referrer == account || self.referrers.contains(account) {
+				return;
+			}
+
+			self.referrers.insert(account, &referrer);
+
+			Self::emit_event(self.env(), Event::ReferrerSet(ReferrerSet { account, referrer }));
+		}
+
+		/// Sets the shares of `account` and updates the voting power of its delegate to match
+		fn _write_shares(&mut self, account: AccountId, shares: Balance) {
+			let previous = self.shares_of(account);
+			if shares == 0 {
+				self.balances.remove(account);
+			} else {
+				self.balances.insert(account, &shares);
+			}
+
+			if previous == 0 && shares > 0 {
+				self.holders.insert(self.holders_count, &account);
+				self.holder_index.insert(account, &self.holders_count);
+				self.holders_count += 1;
+			} else if previous > 0 && shares == 0 {
+				self._remove_holder(account);
+			}
+
+			if let Some(delegate) = self.delegates.get(account) {
+				self._move_votes(delegate, |votes| votes - previous + shares);
+			}
+		}
+
+		/// Takes `account` out of the holder list by moving the last holder into its place
+		fn _remove_holder(&mut self, account: AccountId) {
+			let Some(index) = self.holder_index.take(account) else {
+				return;
+			};
+
+			self.holders_count -= 1;
+			let last = self.holders.take(self.holders_count);
+			if let Some(last) = last.filter(|_| index != self.holders_count) {
+				self.holders.insert(index, &last);
+				self.holder_index.insert(last, &index);
+			}
+		}
+
+		/// Applies `update` to the voting power of `delegate` and records a checkpoint
+		fn _move_votes(&mut self, delegate: AccountId, update: impl FnOnce(Balance) -> Balance) {
+			let count = self.num_checkpoints.get(delegate).unwrap_or(0);
+			let last = match count {
+				0 => None,
+				_ => self.checkpoints.get((delegate, count - 1)),
+			};
+			let previous_votes = last.map_or(0, |checkpoint| checkpoint.votes);
+			let new_votes = update(previous_votes);
+			if new_votes == previous_votes {
+				return;
+			}
+
+			// Several changes within one block share a checkpoint
+			let from_block = self.env().block_number();
+			let index = match last {
+				Some(last) if last.from_block == from_block => count - 1,
+				_ => {
+					self.num_checkpoints.insert(delegate, &(count + 1));
+					count
+				},
+			};
+			self.checkpoints
+				.insert((delegate, index), &Checkpoint { from_block, votes: new_votes });
+
+			Self::emit_event(
+				self.env(),
+				Event::DelegateVotesChanged(DelegateVotesChanged {
+					delegate,
+					previous_votes,
+					new_votes,
+				}),
+			);
+		}
+
+		/// Creates `amount` tokens for `account`, within the cap
+		fn _mint(&mut self, account: AccountId, amount: Balance) -> Result<(), PSP22Error> {
+			let total_supply = self.total_supply.checked_add(amount).ok_or(PSP22Error::Overflow)?;
+			if self.cap().is_some_and(|cap| total_supply > cap) {
+				return Err(PSP22Error::CapExceeded);
+			}
+			let shares = self.to_shares(amount);
+			let total_shares = self.total_shares.checked_add(shares).ok_or(PSP22Error::Overflow)?;
+
+			self.total_supply = total_supply;
+			self.total_shares = total_shares;
+			self._write_shares(account, self.shares_of(account) + shares);
+
+			// Minted tokens come from the zero account, like in ERC-20
+			let from = AccountId::from([0u8; 32]);
+			let event_id = self._next_event_id();
+			Self::emit_event(
+				self.env(),
+				Event::Transfer(Transfer { from, to: account, value: amount, event_id }),
+			);
+			let event_id = self._next_event_id();
+			Self::emit_event(self.env(), Event::Minted(Minted { to: account, amount, event_id }));
+
+			Ok(())
+		}
+
+		/// Destroys `amount` of the tokens held by `account`
+		fn _burn(&mut self, account: AccountId, amount: Balance) -> Result<(), PSP22Error> {
+			let account_shares = self.shares_of(account);
+			if self.to_tokens(account_shares) < amount {
+				return Err(PSP22Error::InsufficientBalance);
+			}
+
+			let shares = self.to_shares(amount).min(account_shares);
+			self._write_shares(account, account_shares - shares);
+			self.total_shares -= shares;
+			self.total_supply -= amount;
+
+			// Burned tokens go to the zero account, like in ERC-20
+			let to = AccountId::from([0u8; 32]);
+			let event_id = self._next_event_id();
+			Self::emit_event(
+				self.env(),
+				Event::Transfer(Transfer { from: account, to, value: amount, event_id }),
+			);
+			let event_id = self._next_event_id();
+			Self::emit_event(self.env(), Event::Burned(Burned { from: account, amount, event_id }));
+
+			Ok(())
+		}
+
+		/// Moves `value` tokens between accounts without charging fees or notifying anyone. Used
+		/// for the token's own bookkeeping, such as staking or refunds.
+		fn _move(
+			&mut self,
+			from: AccountId,
+			to: AccountId,
+			value: Balance,
+		) -> Result<(), PSP22Error> {
+			let from_shares = self.shares_of(from);
+			if self.to_tokens(from_shares) < value {
+				return Err(PSP22Error::InsufficientBalance);
+			}
+
+			let shares = self.to_shares(value).min(from_shares);
+			self._write_shares(from, from_shares - shares);
+			self._write_shares(to, self.shares_of(to) + shares);
+
+			let event_id = self._next_event_id();
+			Self::emit_event(self.env(), Event::Transfer(Transfer { from, to, value, event_id }));
+
+			Ok(())
+		}
+
+		/// The token balance of `account`
+		fn _balance(&self, account: AccountId) -> Balance {
+			self.to_tokens(self.shares_of(account))
+		}
+
+		/// Converts tokens to shares at the current ratio, rounding down
+		fn to_shares(&self, value: Balance) -> Balance {
+			if self.total_shares == self.total_supply {
+				return value;
+			}
+
+			mul_div(value, self.total_shares, self.total_supply)
+		}
+
+		/// Converts shares to tokens at the current ratio, rounding down
+		fn to_tokens(&self, shares: Balance) -> Balance {
+			if self.total_shares == self.total_supply {
+				return shares;
+			}
+
+			mul_div(shares, self.total_supply, self.total_shares)
+		}
+
+		/// Checks that `signer` signed `hash` with their next nonce, and uses up the nonce
+		fn _use_signature(
+			&mut self,
+			signer: AccountId,
+			nonce: u64,
+			hash: &[u8; 32],
+			signature: &[u8; 65],
+		) -> Result<(), PSP22Error> {
+			self._verify_signature(signer, hash, signature)?;
+
+			let expected = self.nonce_of(signer);
+			if nonce != expected {
+				return Err(PSP22Error::InvalidNonce);
+			}
+			self.nonces.insert(signer, &(expected + 1));
+
+			Ok(())
+		}
+
+		/// Pays escrowed transfer `id` out like a transfer from its sender if `claimed`, and
+		/// gives it back to the sender otherwise
+		fn _settle_escrowed(
+			&mut self,
+			id: u64,
+			transfer: EscrowedTransfer,
+			claimed: bool,
+		) -> Result<(), PSP22Error> {
+			self.escrowed_transfers.remove(id);
+			let this = self.env().account_id();
+			match claimed {
+				true => self._pay(
+					this,
+					transfer.sender,
+					transfer.recipient,
+					transfer.value,
+					Vec::new(),
+				)?,
+				false => self._move(this, transfer.sender, transfer.value)?,
+			}
+
+			Self::emit_event(
+				self.env(),
+				Event::EscrowedTransferSettled(EscrowedTransferSettled { id, claimed }),
+			);
+
+			Ok(())
+		}
+
+		/// Pays scheduled transfer `id` out like a transfer from its sender if `executed`, and
+		/// gives it back to the sender otherwise
+		fn _settle_scheduled(
+			&mut self,
+			id: u64,
+			transfer: ScheduledTransfer,
+			executed: bool,
+		) -> Result<(), PSP22Error> {
+			self.scheduled_transfers.remove(id);
+			let this = self.env().account_id();
+			match executed {
+				true => self._pay(
+					this,
+					transfer.sender,
+					transfer.recipient,
+					transfer.value,
+					Vec::new(),
+				)?,
+				false => self._move(this, transfer.sender, transfer.value)?,
+			}
+
+			Self::emit_event(
+				self.env(),
+				Event::ScheduledTransferSettled(ScheduledTransferSettled { id, executed }),
+			);
+
+			Ok(())
+		}
+
+		/// Runs a single call of `multicall`, which is at least a selector long. The arguments must
+		/// decode exactly, so that a call never runs with arguments it wasn't meant for.
+		fn _dispatch(&mut self, call: &[u8]) -> Result<(), PSP22Error> {
+			const APPROVE: Selector = ink::selector_bytes!("PSP22::approve");
+			const INCREASE_ALLOWANCE: Selector = ink::selector_bytes!("PSP22::increase_allowance");
+			const DECREASE_ALLOWANCE: Selector = ink::selector_bytes!("PSP22::decrease_allowance");
+			const TRANSFER: Selector = ink::selector_bytes!("PSP22::transfer");
+			const TRANSFER_FROM: Selector = ink::selector_bytes!("PSP22::transfer_from");
+			const DELEGATE: Selector = ink::selector_bytes!("PSP22Votes::delegate");
+
+			let (selector, args) = call.split_at(4);
+			let selector = Selector::try_from(selector).map_err(|_| PSP22Error::CallFailed)?;
+			match selector {
+				APPROVE => {
+					let (spender, amount) = decode_args(args)?;
+					self.approve(spender, amount)
+				},
+				INCREASE_ALLOWANCE => {
+					let (spender, by) = decode_args(args)?;
+					self.increase_allowance(spender, by)
+				},
+				DECREASE_ALLOWANCE => {
+					let (spender, by) = decode_args(args)?;
+					self.decrease_allowance(spender, by)
+				},
+				TRANSFER => {
+					let (to, value, data) = decode_args(args)?;
+					self.transfer(to, value, data)
+				},
+				TRANSFER_FROM => {
+					let (from, to, value, data) = decode_args(args)?;
+					self.transfer_from(from, to, value, data)
+				},
+				DELEGATE => {
+					let delegatee = decode_args(args)?;
+					self.delegate(delegatee)
+				},
+				_ => Err(PSP22Error::CallFailed),
+			}
+		}
+
+		/// Checks that `signature` is a signature of `hash` by the key behind `signer`
+		fn _verify_signature(
+			&self,
+			signer: AccountId,
+			hash: &[u8; 32],
+			signature: &[u8; 65],
+		) -> Result<(), PSP22Error> {
+			let mut public_key = [0; 33];
+			ink::env::ecdsa_recover(signature, hash, &mut public_key)
+				.map_err(|_| PSP22Error::InvalidSignature)?;
+			let mut recovered = <Blake2x256 as HashOutput>::Type::default();
+			ink::env::hash_bytes::<Blake2x256>(&public_key, &mut recovered);
+			if AccountId::from(recovered) != signer {
+				return Err(PSP22Error::InvalidSignature);
+			}
+
+			Ok(())
+		}
+
+		fn metadata(&self) -> Metadata {
+			self.metadata.get().unwrap_or_default()
+		}
+
+		fn config(&self) -> Config {
+			self.config.get().unwrap_or_default()
+		}
+
+		fn ensure_owner(&self) -> Result<(), PSP22Error> {
+			self.ownership.ensure_owner(self.env().caller()).map_err(access_error)
+		}
+
+		fn ensure_role(&self, role: Role) -> Result<(), PSP22Error> {
+			self.roles.ensure_role(role, self.env().caller()).map_err(access_error)
+		}
+
+		fn ensure_not_paused(&self) -> Result<(), PSP22Error> {
+			if self.paused {
+				return Err(PSP22Error::Paused);
+			}
+
+			Ok(())
+		}
+
+		fn ensure_not_denied(&self, from: AccountId, to: AccountId) -> Result<(), PSP22Error> {
+			if self.is_denied(from) || self.is_denied(to) {
+				return Err(PSP22Error::AccountFrozen);
+			}
+			#[cfg(feature = "regulated")]
+			if self.is_frozen(from) {
+				return Err(PSP22Error::AccountFrozen);
+			}
+
+			Ok(())
+		}
+
+		/// Adds `value` to the volume of the current window, starting a new window first if the
+		/// current one is over
+		fn _check_volume(&mut self, value: Balance) -> Result<(), PSP22Error> {
+			let mut breaker = self.volume_breaker;
+			let Some(max_volume) = breaker.max_volume else {
+				return Ok(());
+			};
+
+			let block = self.env().block_number();
+			if block >= breaker.window_start.saturating_add(breaker.window) {
+				breaker.window_start = block;
+				breaker.volume = 0;
+			}
+			breaker.volume = breaker.volume.saturating_add(value);
+			if breaker.volume > max_volume {
+				return Err(PSP22Error::VolumeLimitExceeded);
+			}
+
+			self.volume_breaker = breaker;
+
+			Ok(())
+		}
+
+		/// Checks a transfer of `value` tokens against the anti-bot limits and starts the sender's
+		/// cooldown
+		fn _check_limits(
+			&mut self,
+			from: AccountId,
+			to: AccountId,
+			value: Balance,
+		) -> Result<(), PSP22Error> {
+			let owner = self.ownership.owner();
+			if from == owner || to == owner {
+				return Ok(());
+			}
+
+			let limits = self.transfer_limits;
+			if limits.max_transfer.is_some_and(|max| value > max)
+				|| limits
+					.max_wallet
+					.is_some_and(|max| self._balance(to).saturating_add(value) > max)
+			{
+				return Err(PSP22Error::TransferLimitExceeded);
+			}
+
+			if limits.cooldown > 0 {
+				let block = self.env().block_number();
+				let last = self.last_transfer.get(from);
+				if last.is_some_and(|last| block < last.saturating_add(limits.cooldown)) {
+					return Err(PSP22Error::TransferCooldown);
+				}
+				self.last_transfer.insert(from, &block);
+			}
+
+			Ok(())
+		}
+
+		/// Transfers tokens within `partition`. Named partitions are released into the default one
+		/// for the duration of the transfer, and what `to` receives after fees is put back.
+		fn _transfer_by_partition(
+			&mut self,
+			partition: Partition,
+			from: AccountId,
+			to: AccountId,
+			value: Balance,
+			data: Vec<u8>,
+		) -> Result<(), PSP22Error> {
+			self.ensure_not_paused()?;
+			if partition == DEFAULT_PARTITION {
+				return self._transfer_from_to(&from, &to, value, data);
+			}
+			let shares = self.to_shares(value);
+			if self._partition_shares(from, partition) < shares {
+				return Err(PSP22Error::InsufficientBalance);
+			}
+
+			self._sub_partition_shares(from, partition, shares);
+			let to_shares = self.shares_of(to);
+			self._transfer_from_to(&from, &to, value, data)?;
+			// Sending to oneself only loses the fee, so what comes back is the rest
+			let received = match from == to {
+				true => shares.saturating_sub(to_shares.saturating_sub(self.shares_of(to))),
+				false => self.shares_of(to).saturating_sub(to_shares),
+			};
+			self._add_partition_shares(to, partition, received);
+
+			Self::emit_event(
+				self.env(),
+				Event::TransferByPartition(TransferByPartition {
+					partition,
+					operator: self.env().caller(),
+					from,
+					to,
+					value: self.to_tokens(received),
+				}),
+			);
+
+			Ok(())
+		}
+
+		/// The shares `account` holds in `partition`
+		fn _partition_shares(&self, account: AccountId, partition: Partition) -> Balance {
+			match partition {
+				DEFAULT_PARTITION => self
+					.shares_of(account)
+					.saturating_sub(self.partitioned_shares.get(account).unwrap_or(0)),
+				_ => self.partition_shares.get((account, partition)).unwrap_or(0),
+			}
+		}
+
+		fn _add_partition_shares(
+			&mut self,
+			account: AccountId,
+			partition: Partition,
+			shares: Balance,
+		) {
+			if partition == DEFAULT_PARTITION || shares == 0 {
+				return;
+			}
+
+			let previous = self._partition_shares(account, partition);
+			if previous == 0 {
+				let mut partitions = self.partitions_of(account);
+				partitions.push(partition);
+				self.partitions.insert(account, &partitions);
+			}
+			self.partition_shares.insert((account, partition), &(previous + shares));
+			let partitioned = self.partitioned_shares.get(account).unwrap_or(0);
+			self.partitioned_shares.insert(account, &(partitioned + shares));
+		}
+
+		fn _sub_partition_shares(
+			&mut self,
+			account: AccountId,
+			partition: Partition,
+			shares: Balance,
+		) {
+			if partition == DEFAULT_PARTITION || shares == 0 {
+				return;
+			}
+
+			let remaining = self._partition_shares(account, partition) - shares;
+			if remaining == 0 {
+				self.partition_shares.remove((account, partition));
+				let mut partitions = self.partitions_of(account);
+				partitions.retain(|held| *held != partition);
+				self.partitions.insert(account, &partitions);
+			} else {
+				self.partition_shares.insert((account, partition), &remaining);
+			}
+			let partitioned = self.partitioned_shares.get(account).unwrap_or(0) - shares;
+			if partitioned == 0 {
+				self.partitioned_shares.remove(account);
+			} else {
+				self.partitioned_shares.insert(account, &partitioned);
+			}
+		}
+
+		/// Hands out event ids in sequence, so that indexers can spot missed events
+		fn _next_event_id(&mut self) -> u64 {
+			let event_id = self.next_event_id.get().unwrap_or(0);
+			self.next_event_id.set(&(event_id + 1));
+			event_id
+		}
+
+		/// Checks that `account` can spend `value` tokens without touching locked ones, or ones
+		/// held in a named partition. Every path debiting an account runs through here, except
+		/// forced transfers, which override locks and only run `ensure_unpartitioned`.
+		fn ensure_unlocked(&self, account: AccountId, value: Balance) -> Result<(), PSP22Error> {
+			let locked = self.locked_balance_of(account);
+			if locked > 0 && self._balance(account).saturating_sub(locked) < value {
+				return Err(PSP22Error::TokensLocked);
+			}
+			self.ensure_unpartitioned(account, value)
+		}
+
+		/// Checks that `account` holds `value` tokens outside of named partitions
+		fn ensure_unpartitioned(
+			&self,
+			account: AccountId,
+			value: Balance,
+		) -> Result<(), PSP22Error> {
+			if self.to_tokens(self._partition_shares(account, DEFAULT_PARTITION)) < value {
+				return Err(PSP22Error::InsufficientBalance);
+			}
+
+			Ok(())
+		}
+
+		fn emit_event<EE>(emitter: EE, event: Event)
+		where
+			EE: EmitEvent<Self>,
+		{
+			emitter.emit_event(event);
+		}
+	}
+
+	impl PSP22 for Token {
+		/// Returns the total token supply.
+		#[ink(message)]
+		fn total_supply(&self) -> Balance {
+			self.total_supply
+		}
+
+		/// Returns the account balance for the specified `owner`.
+		#[ink(message)]
+		fn balance_of(&self, owner: AccountId) -> Balance {
+			self._balance(owner)
+		}
+
+		/// Returns the amount which `spender` is allowed to withdraw on behalf of the `owner`
+		/// account.
+		#[ink(message)]
+		fn allowance(&self, owner: AccountId, spender: AccountId) -> Balance {
+			self._allowance(owner, spender)
+		}
+
+		/// Allows `spender` to withdraw from the caller's account multiple times, up to the `value`
+		/// amount.
+		#[ink(message)]
+		fn approve(&mut self, spender: AccountId, amount: Balance) -> Result<(), PSP22Error> {
+			self.ensure_not_paused()?;
+			let caller = self.env().caller();
+			self.allowance_deadlines.remove((caller, spender));
+			self._approve_from_to(caller, spender, amount)
+		}
+
+		/// Increase `spender`'s allowance to withdraw from the caller's account by the `by` amount.
+		#[ink(message)]
+		fn increase_allowance(
+			&mut self,
+			spender: AccountId,
+			by: Balance,
+		) -> Result<(), PSP22Error> {
+			self.ensure_not_paused()?;
+			let caller = self.env().caller();
+			let prev = self._allowance(caller, spender);
+			self._approve_from_to(caller, spender, prev.saturating_add(by))
+		}
+
+		/// Decrease `spender`'s allowance to withdraw from the caller's account by the `by` amount.
+		#[ink(message)]
+		fn decrease_allowance(
+			&mut self,
+			spender: AccountId,
+			by: Balance,
+		) -> Result<(), PSP22Error> {
+			self.ensure_not_paused()?;
+			let caller = self.env().caller();
+			let prev = self._allowance(caller, spender);
+			self._approve_from_to(caller, spender, prev.saturating_sub(by))
+		}
+
+		/// Transfers `value` amount of tokens from the caller's account to account `to`.
+		#[ink(message)]
+		fn transfer(
+			&mut self,
+			to: AccountId,
+			value: Balance,
+			data: Vec<u8>,
+		) -> Result<(), PSP22Error> {
+			self.ensure_not_paused()?;
+			let caller = self.env().caller();
+			self._transfer_from_to(&caller, &to, value, data)
+		}
+
+		/// Transfers `value` amount of tokens on the behalf of `from` to the account `to`.
+		/// Caller need to be pre-approved
+		#[ink(message)]
+		fn transfer_from(
+			&mut self,
+			from: AccountId,
+			to: AccountId,
+			value: Balance,
+			data: Vec<u8>,
+		) -> Result<(), PSP22Error> {
+			self.ensure_not_paused()?;
+			let caller = self.env().caller();
+			self._spend_allowance(from, caller, value)?;
+			self._transfer_from_to(&from, &to, value, data)
+		}
+	}
+
+	impl PSP22Metadata for Token {
+		/// Returns the token name.
+		#[ink(message)]
+		fn token_name(&self) -> Option<String> {
+			self.metadata().name
+		}
+
+		/// Returns the token symbol.
+		#[ink(message)]
+		fn token_symbol(&self) -> Option<String> {
+			self.metadata().symbol
+		}
+
+		/// Returns the number of decimals wallets should use to display amounts.
+		#[ink(message)]
+		fn token_decimals(&self) -> u8 {
+			self.metadata().decimals
+		}
+	}
+
+	impl PSP22Votes for Token {
+		/// Counts the caller's votes towards `delegatee`. Delegate to yourself to vote directly.
+		#[ink(message)]
+		fn delegate(&mut self, delegatee: AccountId) -> Result<(), PSP22Error> {
+			let caller = self.env().caller();
+			let previous = self.delegates.get(caller);
+			self.delegates.insert(caller, &delegatee);
+
+			let shares = self.shares_of(caller);
+			if let Some(previous) = previous {
+				self._move_votes(previous, |votes| votes - shares);
+			}
+			self._move_votes(delegatee, |votes| votes + shares);
+
+			Self::emit_event(
+				self.env(),
+				Event::DelegateChanged(DelegateChanged {
+					delegator: caller,
+					from_delegate: previous,
+					to_delegate: delegatee,
+				}),
+			);
+
+			Ok(())
+		}
+
+		/// Returns the account `account` delegates its votes to, if any
+		#[ink(message)]
+		fn delegates(&self, account: AccountId) -> Option<AccountId> {
+			self.delegates.get(account)
+		}
+
+		/// Returns the current voting power of `account`
+		#[ink(message)]
+		fn get_votes(&self, account: AccountId) -> Balance {
+			self.get_votes_at(account, self.env().block_number())
+		}
+
+		/// Returns the voting power `account` had at the end of `block`.
+		///
+		/// Votes are counted in shares, so a rebase doesn't change anyone's voting power.
+		#[ink(message)]
+		fn get_votes_at(&self, account: AccountId, block: BlockNumber) -> Balance {
+			// Find the last checkpoint made at or before `block`
+			let (mut low, mut high) = (0, self.num_checkpoints.get(account).unwrap_or(0));
+			while low < high {
+				let middle = low + (high - low) / 2;
+				let checkpoint = self.checkpoints.get((account, middle));
+				if checkpoint.is_some_and(|checkpoint| checkpoint.from_block > block) {
+					high = middle;
+				} else {
+					low = middle + 1;
+				}
+			}
+
+			match low {
+				0 => 0,
+				_ => self
+					.checkpoints
+					.get((account, low - 1))
+					.map_or(0, |checkpoint| checkpoint.votes),
+			}
+		}
+	}
+
+	impl Pausable for Token {
+		/// Returns true while transfers and approvals are halted
+		#[ink(message)]
+		fn paused(&self) -> bool {
+			self.paused
+		}
+
+		/// Halts transfers and approvals. Only pausers may pause.
+		#[ink(message)]
+		fn pause(&mut self) -> Result<(), PausableError> {
+			self.ensure_role(Role::Pauser).map_err(|_| PausableError::NotAllowed)?;
+			self.paused = true;
+
+			Self::emit_event(self.env(), Event::Paused(Paused { account: self.env().caller() }));
+
+			Ok(())
+		}
+
+		/// Resumes transfers and approvals. Only pausers may unpause.
+		#[ink(message)]
+		fn unpause(&mut self) -> Result<(), PausableError> {
+			self.ensure_role(Role::Pauser).map_err(|_| PausableError::NotAllowed)?;
+			self.paused = false;
+
+			Self::emit_event(
+				self.env(),
+				Event::Unpaused(Unpaused { account: self.env().caller() }),
+			);
+
+			Ok(())
+		}
+	}
+
+	impl PSP22Permit for Token {
+		/// Sets the allowance of `spender` over the tokens of `owner` to `value` on behalf of
+		/// `owner`, who signed `permit_hash(..)` with its current nonce. The signature can't be
+		/// used after block `deadline`.
+		#[ink(message)]
+		fn permit(
+			&mut self,
+			owner: AccountId,
+			spender: AccountId,
+			value: Balance,
+			deadline: BlockNumber,
+			signature: [u8; 65],
+		) -> Result<(), PSP22Error> {
+			self.ensure_not_paused()?;
+			if self.env().block_number() > deadline {
+				return Err(PSP22Error::SignatureExpired);
+			}
+
+			let nonce = self.nonce_of(owner);
+			let hash = self.permit_hash(owner, spender, value, nonce, deadline);
+			self._use_signature(owner, nonce, &hash, &signature)?;
+			self.allowance_deadlines.remove((owner, spender));
+
+			self._approve_from_to(owner, spender, value)
+		}
+
+		/// The nonce the next signed message of `owner` must carry
+		#[ink(message)]
+		fn nonce_of(&self, owner: AccountId) -> u64 {
+			self.nonces.get(owner).unwrap_or(0)
+		}
+
+		/// The domain signed messages commit to. It binds signatures to this token on this chain,
+		/// so they cannot be replayed on other tokens or on other chains.
+		#[ink(message)]
+		fn domain_separator(&self) -> [u8; 32] {
+			let mut hash = <Blake2x256 as HashOutput>::Type::default();
+			ink::env::hash_encoded::<Blake2x256, _>(
+				&(self.env().account_id(), self.config().genesis_hash),
+				&mut hash,
+			);
+			hash
+		}
+	}
+
+	impl PSP22Mintable for Token {
+		/// Creates `amount` new tokens and credits them to `account`. Only minters may mint.
+		#[ink(message)]
+		fn mint(&mut self, account: AccountId, amount: Balance) -> Result<(), PSP22Error> {
+			self.ensure_role(Role::Minter)?;
+			self._mint(account, amount)
+		}
+	}
+
+	impl PSP22Burnable for Token {
+		/// Destroys `amount` tokens held by `account`. Only burners may burn.
+		#[ink(message)]
+		fn burn(&mut self, account: AccountId, amount: Balance) -> Result<(), PSP22Error> {
+			self.ensure_role(Role::Burner)?;
+			self.ensure_unlocked(account, amount)?;
+			self._burn(account, amount)
+		}
+	}
+
+	/// The token's error for a failed access check
+	fn access_error(error: AccessControlError) -> PSP22Error {
+		match error {
+			AccessControlError::NotOwner => PSP22Error::NotOwner,
+			AccessControlError::NotPendingOwner => PSP22Error::NotPendingOwner,
+			AccessControlError::MissingRole => PSP22Error::MissingRole,
+		}
+	}
+
+	/// Computes `a * b / c`, saturating if the result doesn't fit in a balance
+	fn mul_div(a: Balance, b: Balance, c: Balance) -> Balance {
+		assert!(c != 0, "division by zero");
+		math::mul_div(a, b, c).unwrap_or(Balance::MAX)
+	}
+
+	#[cfg(test)]
+	mod tests {
+		use secp256k1::{Message, PublicKey, SecretKey, SECP256K1};
+
+		use super::*;
+
+		fn default_accounts() -> ink::env::test::DefaultAccounts<ink::env::DefaultEnvironment> {
+			ink::env::test::default_accounts::<Environment>()
+		}
+
+		fn set_next_caller(caller: AccountId) {
+			ink::env::test::set_caller::<Environment>(caller);
+		}
+
+		fn new_token(total_supply: Balance) -> Token {
+			Token::new(
+				total_supply,
+				Some("Wood".into()),
+				Some("WOOD".into()),
+				12,
+				None,
+				Hash::default(),
+			)
+		}
+
+		/// The secret key with every byte set to `seed`
+		fn secret_key(seed: u8) -> SecretKey {
+			SecretKey::from_slice(&[seed; 32]).unwrap()
+		}
+
+		/// The account of the key with every byte set to `seed`
+		fn signer(seed: u8) -> AccountId {
+			let public_key = PublicKey::from_secret_key(SECP256K1, &secret_key(seed)).serialize();
+			let mut account = <Blake2x256 as HashOutput>::Type::default();
+			ink::env::hash_bytes::<Blake2x256>(&public_key, &mut account);
+			AccountId::from(account)
+		}
+
+		/// Signs `hash` with the key with every byte set to `seed`
+		fn sign(seed: u8, hash: [u8; 32]) -> [u8; 65] {
+			let message = Message::from_slice(&hash).unwrap();
+			let (recovery_id, compact) = SECP256K1
+				.sign_ecdsa_recoverable(&message, &secret_key(seed))
+				.serialize_compact();
+			let mut signature = [0; 65];
+			signature[..64].copy_from_slice(&compact);
+			signature[64] = recovery_id.to_i32() as u8;
+			signature
+		}
+
+		#[ink::test]
+		fn test_constructor_works() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let token = new_token(1000);
+
+			assert_eq!(token.total_supply(), 1000);
+			assert_eq!(token.balance_of(default_accounts.alice), 1000);
+		}
+
+		#[ink::test]
+		fn test_unknown_accounts_read_as_zero() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+
+			assert_eq!(token.balance_of(default_accounts.bob), 0);
+			assert_eq!(token.allowance(default_accounts.bob, default_accounts.charlie), 0);
+
+			set_next_caller(default_accounts.charlie);
+			assert_eq!(
+				token.transfer_from(default_accounts.bob, default_accounts.charlie, 1, Vec::new()),
+				Err(PSP22Error::InsufficientAllowance)
+			);
+			assert_eq!(
+				token.transfer(default_accounts.bob, 1, Vec::new()),
+				Err(PSP22Error::InsufficientBalance)
+			);
+		}
+
+		#[ink::test]
+		fn test_metadata_is_set_in_constructor() {
+			let token = new_token(1000);
+
+			assert_eq!(token.token_name(), Some("Wood".into()));
+			assert_eq!(token.token_symbol(), Some("WOOD".into()));
+			assert_eq!(token.token_decimals(), 12);
+
+			let anonymous = Token::new(1000, None, None, 0, None, Hash::default());
+			assert_eq!(anonymous.token_name(), None);
+			assert_eq!(anonymous.token_symbol(), None);
+			assert_eq!(anonymous.token_decimals(), 0);
+		}
+
+		#[ink::test]
+		fn test_units_follow_decimals() {
+			let token = new_token(1000);
+
+			assert_eq!(token.to_base_units(3), Ok(3_000_000_000_000));
+			assert_eq!(token.from_base_units(3_999_999_999_999), 3);
+			assert_eq!(token.to_base_units(Balance::MAX), Err(PSP22Error::Overflow));
+
+			assert_eq!(crate::units::to_base_units(1, 39), None);
+			assert_eq!(crate::units::from_base_units(Balance::MAX, 39), 0);
+			assert_eq!(crate::units::from_base_units(Balance::MAX, 0), Balance::MAX);
+		}
+
+		#[ink::test]
+		fn test_domain_separator_depends_on_chain() {
+			let token = new_token(1000);
+			assert_eq!(token.nonce_of(default_accounts().alice), 0);
+			// Off-chain every instance shares one storage, so read it before deploying another
+			let separator = token.domain_separator();
+
+			let other_chain = Token::new(1000, None, None, 0, None, Hash::from([1; 32]));
+			assert_ne!(separator, other_chain.domain_separator());
+		}
+
+		#[ink::test]
+		fn test_owner_sets_extended_metadata() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+			assert_eq!(token.token_uri(), None);
+
+			assert_eq!(token.set_token_uri(Some("ipfs://wood.json".into())), Ok(()));
+			assert_eq!(token.token_uri(), Some("ipfs://wood.json".into()));
+			assert_eq!(token.set_attribute("website".into(), Some("wood.example".into())), Ok(()));
+			assert_eq!(token.get_attribute("website".into()), Some("wood.example".into()));
+			assert_eq!(token.set_attribute("website".into(), None), Ok(()));
+			assert_eq!(token.get_attribute("website".into()), None);
+
+			set_next_caller(default_accounts.bob);
+			assert_eq!(token.set_token_uri(None), Err(PSP22Error::NotOwner));
+			assert_eq!(token.set_attribute("logo".into(), None), Err(PSP22Error::NotOwner));
+		}
+
+		#[ink::test]
+		fn test_holders_are_tracked() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+			assert_eq!(token.holders_count(), 1);
+			assert_eq!(token.holder_at(0), Some(default_accounts.alice));
+
+			let recipients = vec![(default_accounts.bob, 100), (default_accounts.charlie, 100)];
+			assert_eq!(token.transfer_batch(recipients), Ok(()));
+			assert_eq!(token.holders_count(), 3);
+			assert_eq!(token.holders(1, 10), vec![default_accounts.bob, default_accounts.charlie]);
+
+			assert_eq!(token.burn(default_accounts.alice, 800), Ok(()));
+			assert_eq!(token.holders_count(), 2);
+			assert_eq!(token.holders(0, 10), vec![default_accounts.charlie, default_accounts.bob]);
+			assert_eq!(token.holder_at(2), None);
+		}
+
+		#[ink::test]
+		fn test_signed_transfers_are_relayed_once() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+
+			let signer = signer(0x11);
+			assert_eq!(token.transfer_batch(vec![(signer, 100)]), Ok(()));
+
+			let signature = sign(0x11, token.transfer_hash(signer, default_accounts.bob, 40, 0, 5));
+
+			// a relayer submits the transfer on behalf of the signer
+			set_next_caller(default_accounts.charlie);
+			assert_eq!(
+				token.transfer_with_signature(signer, default_accounts.bob, 41, 0, 5, signature),
+				Err(PSP22Error::InvalidSignature)
+			);
+			assert_eq!(
+				token.transfer_with_signature(signer, default_accounts.bob, 40, 0, 5, signature),
+				Ok(())
+			);
+			assert_eq!(token.balance_of(default_accounts.bob), 40);
+			assert_eq!(token.nonce_of(signer), 1);
+			assert_eq!(
+				token.transfer_with_signature(signer, default_accounts.bob, 40, 0, 5, signature),
+				Err(PSP22Error::InvalidNonce)
+			);
+
+			let signature = sign(0x11, token.transfer_hash(signer, default_accounts.bob, 40, 1, 0));
+			ink::env::test::advance_block::<Environment>();
+			assert_eq!(
+				token.transfer_with_signature(signer, default_accounts.bob, 40, 1, 0, signature),
+				Err(PSP22Error::SignatureExpired)
+			);
+		}
+
+		#[ink::test]
+		fn test_permits_approve_on_behalf_of_the_signer() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+
+			let signer = signer(0x33);
+
+			let bob = default_accounts.bob;
+
+			// a signed transfer with the same fields is no permit
+			set_next_caller(bob);
+			let transfer_signature = sign(0x33, token.transfer_hash(signer, bob, 40, 0, 5));
+			assert_eq!(
+				token.permit(signer, bob, 40, 5, transfer_signature),
+				Err(PSP22Error::InvalidSignature)
+			);
+
+			let signature = sign(0x33, token.permit_hash(signer, bob, 40, 0, 5));
+			assert_eq!(token.permit(signer, bob, 40, 5, signature), Ok(()));
+			assert_eq!(token.allowance(signer, bob), 40);
+			assert_eq!(token.nonce_of(signer), 1);
+			assert_eq!(
+				token.permit(signer, bob, 40, 5, signature),
+				Err(PSP22Error::InvalidSignature)
+			);
+		}
+
+		#[ink::test]
+		fn test_transfer_authorizations_are_used_once_within_their_window() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+
+			let signer = signer(0x22);
+			assert_eq!(token.transfer_batch(vec![(signer, 100)]), Ok(()));
+
+			let bob = default_accounts.bob;
+			let (first, second) = ([7; 32], [3; 32]);
+			let first_signature =
+				sign(0x22, token.authorization_hash(signer, bob, 40, 0, 5, first));
+			let second_signature =
+				sign(0x22, token.authorization_hash(signer, bob, 10, 0, 5, second));
+
+			set_next_caller(default_accounts.charlie);
+			assert_eq!(
+				token.transfer_with_authorization(signer, bob, 40, 0, 5, first, first_signature),
+				Err(PSP22Error::SignatureNotYetValid)
+			);
+			ink::env::test::advance_block::<Environment>();
+
+			// authorizations don't have to be used in the order they were signed in
+			assert_eq!(
+				token.transfer_with_authorization(signer, bob, 10, 0, 5, second, first_signature),
+				Err(PSP22Error::InvalidSignature)
+			);
+			assert_eq!(
+				token.transfer_with_authorization(signer, bob, 10, 0, 5, second, second_signature),
+				Ok(())
+			);
+			assert_eq!(
+				token.transfer_with_authorization(signer, bob, 10, 0, 5, second, second_signature),
+				Err(PSP22Error::InvalidNonce)
+			);
+			assert!(token.authorization_state(signer, second));
+			assert!(!token.authorization_state(signer, first));
+
+			set_next_caller(signer);
+			assert_eq!(token.cancel_authorization(first), Ok(()));
+			set_next_caller(default_accounts.charlie);
+			assert_eq!(
+				token.transfer_with_authorization(signer, bob, 40, 0, 5, first, first_signature),
+				Err(PSP22Error::InvalidNonce)
+			);
+			assert_eq!(token.balance_of(bob), 10);
+
+			let third = [9; 32];
+			let signature = sign(0x22, token.authorization_hash(signer, bob, 10, 0, 2, third));
+			ink::env::test::advance_block::<Environment>();
+			assert_eq!(
+				token.transfer_with_authorization(signer, bob, 10, 0, 2, third, signature),
+				Err(PSP22Error::SignatureExpired)
+			);
+		}
+
+		#[ink::test]
+		fn test_locked_tokens_cannot_be_spent_until_expiry() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+			assert_eq!(token.transfer_batch(vec![(default_accounts.bob, 100)]), Ok(()));
+
+			set_next_caller(default_accounts.bob);
+			assert_eq!(token.lock(default_accounts.bob, 60, 2), Err(PSP22Error::MissingRole));
+
+			set_next_caller(default_accounts.alice);
+			assert_eq!(
+				token.lock(default_accounts.bob, 101, 2),
+				Err(PSP22Error::InsufficientBalance)
+			);
+			assert_eq!(token.lock(default_accounts.bob, 60, 2), Ok(()));
+			assert_eq!(token.lock(default_accounts.bob, 10, 1), Ok(()));
+			assert_eq!(token.locked_balance_of(default_accounts.bob), 70);
+
+			set_next_caller(default_accounts.bob);
+			assert_eq!(
+				token.transfer_batch(vec![(default_accounts.charlie, 31)]),
+				Err(PSP22Error::TokensLocked)
+			);
+			assert_eq!(token.transfer_batch(vec![(default_accounts.charlie, 30)]), Ok(()));
+
+			ink::env::test::advance_block::<Environment>();
+			ink::env::test::advance_block::<Environment>();
+			assert_eq!(token.locked_balance_of(default_accounts.bob), 60);
+			assert_eq!(token.unlock_expired(default_accounts.bob), 10);
+			assert_eq!(token.locks_of(default_accounts.bob), vec![Lock { amount: 60, until: 2 }]);
+			assert_eq!(
+				token.transfer_batch(vec![(default_accounts.charlie, 11)]),
+				Err(PSP22Error::TokensLocked)
+			);
+
+			ink::env::test::advance_block::<Environment>();
+			assert_eq!(token.locked_balance_of(default_accounts.bob), 0);
+			assert_eq!(token.transfer_batch(vec![(default_accounts.charlie, 70)]), Ok(()));
+			assert_eq!(token.unlock_expired(default_accounts.bob), 60);
+			assert_eq!(token.locks_of(default_accounts.bob), vec![]);
+		}
+
+		#[ink::test]
+		fn test_transfer_limits_apply_until_removed() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+			let limits =
+				TransferLimits { max_transfer: Some(50), max_wallet: Some(120), cooldown: 2 };
+
+			set_next_caller(default_accounts.bob);
+			assert_eq!(token.set_transfer_limits(limits), Err(PSP22Error::NotOwner));
+
+			// the owner seeds wallets regardless of the limits
+			set_next_caller(default_accounts.alice);
+			assert_eq!(token.set_transfer_limits(limits), Ok(()));
+			assert_eq!(
+				token.transfer_batch(vec![
+					(default_accounts.bob, 200),
+					(default_accounts.charlie, 100)
+				]),
+				Ok(())
+			);
+
+			set_next_caller(default_accounts.bob);
+			let charlie = default_accounts.charlie;
+			assert_eq!(
+				token.transfer_batch(vec![(charlie, 51)]),
+				Err(PSP22Error::TransferLimitExceeded)
+			);
+			assert_eq!(
+				token.transfer_batch(vec![(charlie, 21)]),
+				Err(PSP22Error::TransferLimitExceeded)
+			);
+			assert_eq!(token.transfer_batch(vec![(charlie, 20)]), Ok(()));
+			assert_eq!(
+				token.transfer_batch(vec![(default_accounts.django, 10)]),
+				Err(PSP22Error::TransferCooldown)
+			);
+
+			ink::env::test::advance_block::<Environment>();
+			ink::env::test::advance_block::<Environment>();
+			assert_eq!(token.transfer_batch(vec![(default_accounts.django, 10)]), Ok(()));
+
+			set_next_caller(default_accounts.alice);
+			assert_eq!(token.remove_transfer_limits(), Ok(()));
+			assert_eq!(token.transfer_limits(), TransferLimits::default());
+			set_next_caller(default_accounts.bob);
+			assert_eq!(token.transfer_batch(vec![(charlie, 100), (charlie, 50)]), Ok(()));
+		}
+
+		#[cfg(feature = "faucet")]
+		#[ink::test]
+		fn test_drip_is_rate_limited() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+
+			set_next_caller(default_accounts.bob);
+			assert_eq!(token.drip(), Ok(()));
+			assert_eq!(token.balance_of(default_accounts.bob), DRIP_AMOUNT);
+			assert_eq!(token.drip(), Err(PSP22Error::FaucetCooldown));
+
+			for _ in 0..DRIP_INTERVAL {
+				ink::env::test::advance_block::<Environment>();
+			}
+			assert_eq!(token.drip(), Ok(()));
+			assert_eq!(token.balance_of(default_accounts.bob), 2 * DRIP_AMOUNT);
+		}
+
+		#[cfg(feature = "regulated")]
+		#[ink::test]
+		fn test_controller_can_freeze_and_force_transfers() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+			assert_eq!(token.transfer(default_accounts.bob, 100, Vec::new()), Ok(()));
+
+			set_next_caller(default_accounts.bob);
+			assert_eq!(token.freeze(default_accounts.bob), Err(PSP22Error::MissingRole));
+			assert_eq!(
+				token.controller_transfer(
+					default_accounts.bob,
+					default_accounts.bob,
+					1,
+					"x".into()
+				),
+				Err(PSP22Error::MissingRole)
+			);
+
+			set_next_caller(default_accounts.alice);
+			assert_eq!(token.freeze(default_accounts.bob), Ok(()));
+			assert!(token.is_frozen(default_accounts.bob));
+			assert_eq!(token.transfer(default_accounts.bob, 10, Vec::new()), Ok(()));
+			set_next_caller(default_accounts.bob);
+			assert_eq!(
+				token.transfer(default_accounts.alice, 10, Vec::new()),
+				Err(PSP22Error::AccountFrozen)
+			);
+
+			set_next_caller(default_accounts.alice);
+			assert_eq!(
+				token.controller_transfer(
+					default_accounts.bob,
+					default_accounts.charlie,
+					60,
+					String::new()
+				),
+				Err(PSP22Error::MissingReason)
+			);
+			// Locks don't hold back a forced transfer
+			assert_eq!(token.lock(default_accounts.bob, 80, 1), Ok(()));
+			assert_eq!(
+				token.controller_transfer(
+					default_accounts.bob,
+					default_accounts.charlie,
+					60,
+					"court order 42".into()
+				),
+				Ok(())
+			);
+			assert_eq!(token.balance_of(default_accounts.bob), 50);
+			assert_eq!(token.balance_of(default_accounts.charlie), 60);
+
+			let emitted_events = ink::env::test::recorded_events().collect::<Vec<_>>();
+			let last =
+				<Event as scale::Decode>::decode(&mut &emitted_events.last().unwrap().data[..])
+					.expect("encountered invalid contract event data buffer");
+			assert!(matches!(
+				last,
+				Event::ControllerTransfer(ControllerTransfer { value: 60, ref reason, .. })
+					if reason == "court order 42"
+			));
+
+			assert_eq!(token.unfreeze(default_accounts.bob), Ok(()));
+			ink::env::test::advance_block::<Environment>();
+			ink::env::test::advance_block::<Environment>();
+			set_next_caller(default_accounts.bob);
+			assert_eq!(token.transfer(default_accounts.alice, 10, Vec::new()), Ok(()));
+		}
+
+		#[ink::test]
+		fn test_emission_follows_the_schedule() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+			let distributor = default_accounts.django;
+			let epochs =
+				vec![EmissionEpoch { blocks: 2, rate: 10 }, EmissionEpoch { blocks: 5, rate: 4 }];
+			let rising =
+				vec![EmissionEpoch { blocks: 2, rate: 4 }, EmissionEpoch { blocks: 5, rate: 10 }];
+			assert_eq!(
+				token.set_emission_schedule(0, distributor, rising),
+				Err(PSP22Error::InvalidSchedule)
+			);
+			assert_eq!(token.set_emission_schedule(0, distributor, epochs.clone()), Ok(()));
+			assert_eq!(
+				token.set_emission_schedule(0, distributor, epochs),
+				Err(PSP22Error::InvalidSchedule)
+			);
+
+			for _ in 0..3 {
+				ink::env::test::advance_block::<Environment>();
+			}
+			assert_eq!(token.pending_emission(), 24);
+			set_next_caller(default_accounts.bob);
+			assert_eq!(token.mint_emission(), Ok(()));
+			assert_eq!(token.balance_of(distributor), 24);
+			assert_eq!(token.pending_emission(), 0);
+
+			for _ in 0..10 {
+				ink::env::test::advance_block::<Environment>();
+			}
+			assert_eq!(token.mint_emission(), Ok(()));
+			assert_eq!(token.balance_of(distributor), 40);
+			assert_eq!(token.total_supply(), 1040);
+		}
+
+		#[ink::test]
+		fn test_owner_can_mint() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+
+			assert_eq!(token.mint(default_accounts.bob, 500), Ok(()));
+			assert_eq!(token.total_supply(), 1500);
+			assert_eq!(token.balance_of(default_accounts.bob), 500);
+			// A Transfer from the zero account and a Minted event
+			assert_eq!(ink::env::test::recorded_events().count(), 2);
+		}
+
+		#[ink::test]
+		fn test_only_minters_can_mint() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+
+			set_next_caller(default_accounts.bob);
+			assert_eq!(token.mint(default_accounts.bob, 500), Err(PSP22Error::MissingRole));
+			assert_eq!(token.total_supply(), 1000);
+		}
+
+		#[ink::test]
+		fn test_mint_fails_on_supply_overflow() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(Balance::MAX);
+
+			assert_eq!(token.mint(default_accounts.bob, 1), Err(PSP22Error::Overflow));
+			assert_eq!(token.total_supply(), Balance::MAX);
+		}
+
+		#[ink::test]
+		fn test_mint_respects_cap() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = Token::new(1000, None, None, 0, Some(1500), Hash::default());
+			assert_eq!(token.cap(), Some(1500));
+
+			assert_eq!(token.mint(default_accounts.bob, 500), Ok(()));
+			assert_eq!(token.mint(default_accounts.bob, 1), Err(PSP22Error::CapExceeded));
+			assert_eq!(token.total_supply(), 1500);
+		}
+
+		#[ink::test]
+		fn test_paused_token_rejects_transfers_and_approvals() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+
+			assert_eq!(token.pause(), Ok(()));
+			assert!(token.paused());
+			assert_eq!(
+				token.transfer(default_accounts.bob, 10, Vec::new()),
+				Err(PSP22Error::Paused)
+			);
+			assert_eq!(token.approve(default_accounts.bob, 10), Err(PSP22Error::Paused));
+			assert_eq!(token.increase_allowance(default_accounts.bob, 10), Err(PSP22Error::Paused));
+			assert_eq!(token.decrease_allowance(default_accounts.bob, 10), Err(PSP22Error::Paused));
+			assert_eq!(
+				token.transfer_from(default_accounts.alice, default_accounts.bob, 10, Vec::new()),
+				Err(PSP22Error::Paused)
+			);
+
+			assert_eq!(token.unpause(), Ok(()));
+			assert!(!token.paused());
+			assert_eq!(token.transfer(default_accounts.bob, 10, Vec::new()), Ok(()));
+			assert_eq!(token.balance_of(default_accounts.bob), 10);
+		}
+
+		#[ink::test]
+		fn test_transfer_from_emits_transfer_and_reduced_approval() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+			assert_eq!(token.transfer_batch(vec![(default_accounts.charlie, 5)]), Ok(()));
+			assert_eq!(token.approve(default_accounts.bob, 100), Ok(()));
+
+			set_next_caller(default_accounts.bob);
+			assert_eq!(
+				token.transfer_from(
+					default_accounts.alice,
+					default_accounts.charlie,
+					30,
+					Vec::new()
+				),
+				Ok(())
+			);
+			assert_eq!(token.balance_of(default_accounts.alice), 965);
+			assert_eq!(token.balance_of(default_accounts.charlie), 35);
+			assert_eq!(token.allowance(default_accounts.alice, default_accounts.bob), 70);
+
+			let events = ink::env::test::recorded_events()
+				.skip(2)
+				.map(|event| <Event as scale::Decode>::decode(&mut &event.data[..]).unwrap())
+				.collect::<Vec<_>>();
+			assert!(matches!(
+				&events[..],
+				[
+					Event::Approval(Approval { amount: 70, .. }),
+					Event::Transfer(Transfer { value: 30, .. }),
+				]
+			));
+		}
+
+		#[ink::test]
+		fn test_events_decode_as_shared_psp22_events() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+			assert_eq!(token.compare_and_approve(default_accounts.bob, 0, 20), Ok(()));
+			assert_eq!(token.mint(default_accounts.bob, 30), Ok(()));
+
+			let events = ink::env::test::recorded_events()
+				.filter_map(|event| psp22_traits::PSP22Event::decode_from(&event.data))
+				.collect::<Vec<_>>();
+			let zero = AccountId::from([0u8; 32]);
+			assert_eq!(
+				events,
+				vec![
+					psp22_traits::PSP22Event::Approval(psp22_traits::Approval {
+						owner: default_accounts.alice,
+						spender: default_accounts.bob,
+						amount: 20,
+					}),
+					psp22_traits::PSP22Event::Transfer(psp22_traits::Transfer {
+						from: zero,
+						to: default_accounts.bob,
+						value: 30,
+					}),
+				]
+			);
+		}
+
+		#[ink::test]
+		fn test_events_are_numbered_in_sequence() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+			assert_eq!(token.transfer(default_accounts.bob, 10, Vec::new()), Ok(()));
+			assert_eq!(token.approve(default_accounts.bob, 20), Ok(()));
+			assert_eq!(token.mint(default_accounts.bob, 30), Ok(()));
+			set_next_caller(default_accounts.bob);
+			assert_eq!(
+				token.transfer_from(
+					default_accounts.alice,
+					default_accounts.charlie,
+					5,
+					Vec::new()
+				),
+				Ok(())
+			);
+
+			let event_ids = ink::env::test::recorded_events()
+				.filter_map(|event| {
+					match <Event as scale::Decode>::decode(&mut &event.data[..]).unwrap() {
+						Event::Transfer(Transfer { event_id, .. })
+						| Event::Approval(Approval { event_id, .. })
+						| Event::Minted(Minted { event_id, .. })
+						| Event::Burned(Burned { event_id, .. }) => Some(event_id),
+						_ => None,
+					}
+				})
+				.collect::<Vec<_>>();
+			assert_eq!(event_ids, (0..event_ids.len() as u64).collect::<Vec<_>>());
+			assert_eq!(event_ids.len(), 6);
+		}
+
+		#[ink::test]
+		fn test_allowance_changes_emit_approvals() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+			let (alice, bob) = (default_accounts.alice, default_accounts.bob);
+			assert_eq!(token.approve(bob, 20), Ok(()));
+			assert_eq!(token.increase_allowance(bob, 10), Ok(()));
+			assert_eq!(token.decrease_allowance(bob, 5), Ok(()));
+
+			let amounts = ink::env::test::recorded_events()
+				.filter_map(|event| psp22_traits::PSP22Event::decode_from(&event.data))
+				.filter_map(|event| match event {
+					psp22_traits::PSP22Event::Approval(approval) => {
+						assert_eq!((approval.owner, approval.spender), (alice, bob));
+						Some(approval.amount)
+					},
+					_ => None,
+				})
+				.collect::<Vec<_>>();
+			assert_eq!(amounts, vec![20, 30, 25]);
+			assert_eq!(token.allowance(alice, bob), 25);
+		}
+
+		#[ink::test]
+		fn test_approve_and_call_never_calls_the_token_itself() {
+			let default_accounts = default_accounts();
+			ink::env::test::set_callee::<Environment>(default_accounts.frank);
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+
+			assert_eq!(
+				token.approve_and_call(default_accounts.frank, 10, Vec::new()),
+				Err(PSP22Error::ReceiverRejected)
+			);
+			assert_eq!(token.allowance(default_accounts.alice, default_accounts.frank), 0);
+
+			assert_eq!(token.pause(), Ok(()));
+			assert_eq!(
+				token.approve_and_call(default_accounts.bob, 10, Vec::new()),
+				Err(PSP22Error::Paused)
+			);
+			assert_eq!(token.allowance(default_accounts.alice, default_accounts.bob), 0);
+		}
+
+		#[ink::test]
+		fn test_transfer_to_plain_account_ignores_data() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+
+			assert_eq!(token.transfer(default_accounts.bob, 10, vec![1, 2, 3]), Ok(()));
+			assert_eq!(token.balance_of(default_accounts.bob), 10);
+		}
+
+		#[ink::test]
+		fn test_transfer_adds_to_existing_balance() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+
+			assert_eq!(token.transfer(default_accounts.bob, 100, Vec::new()), Ok(()));
+			assert_eq!(token.transfer(default_accounts.bob, 50, Vec::new()), Ok(()));
+			assert_eq!(token.balance_of(default_accounts.bob), 150);
+			assert_eq!(token.balance_of(default_accounts.alice), 850);
+
+			// a transfer back to a holder that kept tokens doesn't wipe them
+			set_next_caller(default_accounts.bob);
+			assert_eq!(token.transfer(default_accounts.alice, 10, Vec::new()), Ok(()));
+			assert_eq!(token.balance_of(default_accounts.alice), 860);
+			assert_eq!(token.balance_of(default_accounts.bob), 140);
+			assert_eq!(token.total_supply(), 1000);
+		}
+
+		#[ink::test]
+		fn test_transfer_to_self_keeps_balance() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+
+			assert_eq!(token.transfer(default_accounts.alice, 400, Vec::new()), Ok(()));
+			assert_eq!(token.balance_of(default_accounts.alice), 1000);
+			assert_eq!(
+				token.transfer(default_accounts.alice, 1001, Vec::new()),
+				Err(PSP22Error::InsufficientBalance)
+			);
+		}
+
+		#[ink::test]
+		fn test_transfer_batch_pays_every_recipient() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+
+			let recipients = vec![
+				(default_accounts.bob, 100),
+				(default_accounts.charlie, 200),
+				(default_accounts.bob, 50),
+			];
+			assert_eq!(token.transfer_batch(recipients), Ok(()));
+			assert_eq!(token.balance_of(default_accounts.alice), 650);
+			assert_eq!(token.balance_of(default_accounts.bob), 150);
+			assert_eq!(token.balance_of(default_accounts.charlie), 200);
+			assert_eq!(ink::env::test::recorded_events().count(), 3);
+		}
+
+		#[ink::test]
+		fn test_transfer_batch_is_all_or_nothing() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+
+			let recipients = vec![(default_accounts.bob, 600), (default_accounts.charlie, 600)];
+			assert_eq!(token.transfer_batch(recipients), Err(PSP22Error::InsufficientBalance));
+			assert_eq!(token.balance_of(default_accounts.alice), 1000);
+
+			let recipients = vec![(default_accounts.bob, Balance::MAX), (default_accounts.bob, 1)];
+			assert_eq!(token.transfer_batch(recipients), Err(PSP22Error::Overflow));
+		}
+
+		#[ink::test]
+		fn test_operators_transfer_without_an_allowance() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+			let (alice, bob, charlie) =
+				(default_accounts.alice, default_accounts.bob, default_accounts.charlie);
+
+			set_next_caller(bob);
+			assert_eq!(
+				token.transfer_from(alice, charlie, 10, Vec::new()),
+				Err(PSP22Error::InsufficientAllowance)
+			);
+
+			set_next_caller(alice);
+			assert_eq!(token.set_operator(bob, true), Ok(()));
+			assert!(token.is_operator(alice, bob));
+			assert!(!token.is_operator(bob, alice));
+
+			set_next_caller(bob);
+			assert_eq!(token.transfer_from(alice, charlie, 400, Vec::new()), Ok(()));
+			assert_eq!(token.transfer_from_batch(alice, vec![(charlie, 500)]), Ok(()));
+			assert_eq!(
+				token.transfer_from(alice, charlie, 101, Vec::new()),
+				Err(PSP22Error::InsufficientBalance)
+			);
+			assert_eq!(token.balance_of(charlie), 900);
+			assert_eq!(token.allowance(alice, bob), 0);
+
+			set_next_caller(alice);
+			assert_eq!(token.set_operator(bob, false), Ok(()));
+			set_next_caller(bob);
+			assert_eq!(
+				token.transfer_from(alice, charlie, 10, Vec::new()),
+				Err(PSP22Error::InsufficientAllowance)
+			);
+		}
+
+		#[ink::test]
+		fn test_recurring_allowance_resets_every_period() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+			let (alice, bob, charlie) =
+				(default_accounts.alice, default_accounts.bob, default_accounts.charlie);
+			assert_eq!(token.approve_recurring(bob, 30, 3), Ok(()));
+			assert_eq!(token.approve(bob, 5), Ok(()));
+
+			set_next_caller(bob);
+			// the plain allowance is used up first
+			assert_eq!(token.transfer_from(alice, charlie, 5, Vec::new()), Ok(()));
+			assert_eq!(token.transfer_from(alice, charlie, 20, Vec::new()), Ok(()));
+			assert_eq!(
+				token.transfer_from(alice, charlie, 11, Vec::new()),
+				Err(PSP22Error::InsufficientAllowance)
+			);
+			assert_eq!(
+				token.recurring_allowance(alice, bob).map(|recurring| recurring.spent),
+				Some(20)
+			);
+
+			for _ in 0..4 {
+				ink::env::test::advance_block::<Environment>();
+			}
+			assert_eq!(
+				token.recurring_allowance(alice, bob),
+				Some(RecurringAllowance {
+					amount_per_period: 30,
+					period: 3,
+					period_start: 3,
+					spent: 0
+				})
+			);
+			assert_eq!(token.transfer_from(alice, charlie, 30, Vec::new()), Ok(()));
+			assert_eq!(token.balance_of(charlie), 55);
+
+			set_next_caller(alice);
+			assert_eq!(token.approve_recurring(bob, 0, 0), Ok(()));
+			assert_eq!(token.recurring_allowance(alice, bob), None);
+		}
+
+		#[ink::test]
+		fn test_transfer_from_batch_consumes_allowance_once() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+			assert_eq!(token.approve(default_accounts.bob, 500), Ok(()));
+
+			set_next_caller(default_accounts.bob);
+			let recipients = vec![(default_accounts.charlie, 100), (default_accounts.django, 300)];
+			assert_eq!(token.transfer_from_batch(default_accounts.alice, recipients), Ok(()));
+			assert_eq!(token.allowance(default_accounts.alice, default_accounts.bob), 100);
+			assert_eq!(token.balance_of(default_accounts.alice), 600);
+			assert_eq!(token.balance_of(default_accounts.charlie), 100);
+			assert_eq!(token.balance_of(default_accounts.django), 300);
+
+			let recipients = vec![(default_accounts.charlie, 60), (default_accounts.django, 60)];
+			assert_eq!(
+				token.transfer_from_batch(default_accounts.alice, recipients),
+				Err(PSP22Error::InsufficientAllowance)
+			);
+			assert_eq!(token.balance_of(default_accounts.alice), 600);
+		}
+
+		#[ink::test]
+		fn test_allowances_of_reads_many_pairs() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+			assert_eq!(token.approve(default_accounts.bob, 100), Ok(()));
+			assert_eq!(token.approve(default_accounts.charlie, 200), Ok(()));
+
+			let pairs = vec![
+				(default_accounts.alice, default_accounts.bob),
+				(default_accounts.alice, default_accounts.charlie),
+				(default_accounts.bob, default_accounts.alice),
+			];
+			assert_eq!(token.allowances_of(pairs), vec![100, 200, 0]);
+		}
+
+		#[ink::test]
+		fn test_compare_and_approve_fails_if_the_allowance_was_spent() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+			assert_eq!(token.approve(default_accounts.bob, 100), Ok(()));
+
+			set_next_caller(default_accounts.bob);
+			assert_eq!(
+				token.transfer_from(default_accounts.alice, default_accounts.bob, 100, Vec::new()),
+				Ok(())
+			);
+
+			set_next_caller(default_accounts.alice);
+			assert_eq!(
+				token.compare_and_approve(default_accounts.bob, 100, 50),
+				Err(PSP22Error::AllowanceChanged)
+			);
+			assert_eq!(token.allowance(default_accounts.alice, default_accounts.bob), 0);
+
+			assert_eq!(token.compare_and_approve(default_accounts.bob, 0, 50), Ok(()));
+			assert_eq!(token.allowance(default_accounts.alice, default_accounts.bob), 50);
+		}
+
+		#[ink::test]
+		fn test_zero_balances_and_allowances_are_not_stored() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+			assert_eq!(token.approve(default_accounts.bob, 100), Ok(()));
+
+			set_next_caller(default_accounts.bob);
+			assert_eq!(
+				token.transfer_from(default_accounts.alice, default_accounts.bob, 100, Vec::new()),
+				Ok(())
+			);
+			assert!(!token.allowances.contains((default_accounts.alice, default_accounts.bob)));
+
+			assert_eq!(token.transfer(default_accounts.charlie, 100, Vec::new()), Ok(()));
+			assert!(!token.balances.contains(default_accounts.bob));
+			assert_eq!(token.balance_of(default_accounts.bob), 0);
+		}
+
+		#[ink::test]
+		fn test_purge_empty_removes_leftover_entries() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+			// Entries as older code left them behind
+			token.balances.insert(default_accounts.bob, &0);
+			token.allowances.insert((default_accounts.bob, default_accounts.alice), &0);
+			assert_eq!(token.approve_with_deadline(default_accounts.charlie, 50, 0), Ok(()));
+			assert_eq!(token.approve(default_accounts.bob, 70), Ok(()));
+			ink::env::test::advance_block::<Environment>();
+
+			let accounts =
+				vec![default_accounts.alice, default_accounts.bob, default_accounts.charlie];
+			set_next_caller(default_accounts.django);
+			assert_eq!(token.purge_empty(accounts.clone()), 3);
+			assert!(!token.balances.contains(default_accounts.bob));
+			assert!(!token.allowances.contains((default_accounts.alice, default_accounts.charlie)));
+			assert_eq!(token.allowance(default_accounts.alice, default_accounts.bob), 70);
+			assert_eq!(token.balance_of(default_accounts.alice), 1000);
+
+			assert_eq!(token.purge_empty(accounts), 0);
+		}
+
+		#[ink::test]
+		fn test_transfers_from_or_to_the_zero_account_fail() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+			let zero = AccountId::from([0u8; 32]);
+
+			assert_eq!(token.transfer(zero, 10, Vec::new()), Err(PSP22Error::ZeroRecipientAddress));
+			// Nobody holds the key or a balance of the zero account, so only the token's own
+			// bookkeeping could try this
+			assert_eq!(
+				token._transfer_from_to(&zero, &default_accounts.alice, 10, Vec::new()),
+				Err(PSP22Error::ZeroSenderAddress)
+			);
+			assert_eq!(token.balance_of(default_accounts.alice), 1000);
+		}
+
+		#[ink::test]
+		fn test_allowance_expires_after_deadline() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+
+			assert_eq!(token.approve_with_deadline(default_accounts.bob, 100, 1), Ok(()));
+			assert_eq!(
+				token.allowance_with_deadline(default_accounts.alice, default_accounts.bob),
+				(100, Some(1))
+			);
+
+			ink::env::test::advance_block::<Environment>();
+			set_next_caller(default_accounts.bob);
+			assert_eq!(
+				token.transfer_from(default_accounts.alice, default_accounts.bob, 10, Vec::new()),
+				Ok(())
+			);
+
+			ink::env::test::advance_block::<Environment>();
+			assert_eq!(token.allowance(default_accounts.alice, default_accounts.bob), 0);
+			assert_eq!(
+				token.transfer_from(default_accounts.alice, default_accounts.bob, 10, Vec::new()),
+				Err(PSP22Error::InsufficientAllowance)
+			);
+
+			set_next_caller(default_accounts.alice);
+			assert_eq!(token.approve(default_accounts.bob, 50), Ok(()));
+			assert_eq!(
+				token.allowance_with_deadline(default_accounts.alice, default_accounts.bob),
+				(50, None)
+			);
+		}
+
+		#[ink::test]
+		fn test_denied_accounts_cannot_send_or_receive() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+			assert_eq!(token.transfer(default_accounts.bob, 100, Vec::new()), Ok(()));
+
+			assert_eq!(token.deny(default_accounts.bob), Ok(()));
+			assert!(token.is_denied(default_accounts.bob));
+			assert_eq!(
+				token.transfer(default_accounts.bob, 10, Vec::new()),
+				Err(PSP22Error::AccountFrozen)
+			);
+			assert_eq!(
+				token.transfer_batch(vec![(default_accounts.bob, 10)]),
+				Err(PSP22Error::AccountFrozen)
+			);
+
+			set_next_caller(default_accounts.bob);
+			assert_eq!(
+				token.transfer(default_accounts.charlie, 10, Vec::new()),
+				Err(PSP22Error::AccountFrozen)
+			);
+			assert_eq!(token.allow(default_accounts.bob), Err(PSP22Error::MissingRole));
+
+			set_next_caller(default_accounts.alice);
+			assert_eq!(token.allow(default_accounts.bob), Ok(()));
+
+			set_next_caller(default_accounts.bob);
+			assert_eq!(token.transfer(default_accounts.charlie, 10, Vec::new()), Ok(()));
+		}
+
+		#[ink::test]
+		fn test_transfer_fee_goes_to_treasury() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(10_000);
+
+			assert_eq!(token.set_transfer_fee(250, default_accounts.django), Ok(()));
+			assert_eq!(token.transfer_fee(), (250, default_accounts.django));
+			assert_eq!(token.transfer_batch(vec![(default_accounts.bob, 1000)]), Ok(()));
+			assert_eq!(token.balance_of(default_accounts.alice), 9000);
+			assert_eq!(token.balance_of(default_accounts.bob), 975);
+			assert_eq!(token.balance_of(default_accounts.django), 25);
+			// One event for the transfer and one for the fee
+			assert_eq!(ink::env::test::recorded_events().count(), 2);
+
+			assert_eq!(token.set_fee_exempt(default_accounts.charlie, true), Ok(()));
+			assert!(token.is_fee_exempt(default_accounts.charlie));
+			assert_eq!(token.transfer_batch(vec![(default_accounts.charlie, 1000)]), Ok(()));
+			assert_eq!(token.balance_of(default_accounts.charlie), 1000);
+			assert_eq!(token.balance_of(default_accounts.django), 25);
+		}
+
+		#[ink::test]
+		fn test_burn_on_transfer_composes_with_fee() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(100_000);
+			assert_eq!(token.transfer_batch(vec![(default_accounts.bob, 10_000)]), Ok(()));
+			assert_eq!(token.set_transfer_fee(100, default_accounts.django), Ok(()));
+			assert_eq!(token.set_burn_rate(9_901), Err(PSP22Error::InvalidFee));
+			assert_eq!(token.set_burn_rate(200), Ok(()));
+			assert_eq!(token.burn_rate(), 200);
+
+			set_next_caller(default_accounts.bob);
+			assert_eq!(token.set_burn_rate(0), Err(PSP22Error::NotOwner));
+			assert_eq!(token.transfer_batch(vec![(default_accounts.charlie, 1_000)]), Ok(()));
+			assert_eq!(token.balance_of(default_accounts.bob), 9_000);
+			assert_eq!(token.balance_of(default_accounts.django), 10);
+			assert_eq!(token.balance_of(default_accounts.charlie), 970);
+			assert_eq!(token.total_supply(), 99_980);
+
+			// the fee, the burn with its Burned event and the transfer itself
+			assert_eq!(ink::env::test::recorded_events().count(), 5);
+		}
+
+		#[ink::test]
+		fn test_transfer_fee_is_split_by_weight() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(10_000);
+			let (zero, django, eve) =
+				(AccountId::from([0u8; 32]), default_accounts.django, default_accounts.eve);
+			assert_eq!(token.set_transfer_fee(1_000, django), Ok(()));
+			assert_eq!(token.fee_config().recipients, vec![(django, 1)]);
+			assert_eq!(
+				token.set_fee_split(vec![(django, 0), (eve, 0)]),
+				Err(PSP22Error::InvalidFee)
+			);
+			assert_eq!(token.set_fee_split(vec![(django, 2), (eve, 1), (zero, 1)]), Ok(()));
+			assert_eq!(
+				token.fee_config(),
+				FeeConfig {
+					fee_bps: 1_000,
+					burn_bps: 0,
+					recipients: vec![(django, 2), (eve, 1), (zero, 1)]
+				}
+			);
+
+			assert_eq!(token.transfer(default_accounts.bob, 1_000, Vec::new()), Ok(()));
+			assert_eq!(token.balance_of(default_accounts.bob), 900);
+			assert_eq!(token.balance_of(django), 50);
+			assert_eq!(token.balance_of(eve), 25);
+			assert_eq!(token.balance_of(zero), 0);
+			assert_eq!(token.total_supply(), 9_975);
+
+			set_next_caller(default_accounts.bob);
+			assert_eq!(token.set_fee_split(Vec::new()), Err(PSP22Error::NotOwner));
+			set_next_caller(default_accounts.alice);
+			assert_eq!(token.set_fee_split(Vec::new()), Ok(()));
+			assert_eq!(token.fee_config().recipients, vec![(django, 1)]);
+		}
+
+		#[ink::test]
+		fn test_referrers_get_a_cut_of_the_fees_of_holders_they_brought_in() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(10_000);
+			let (alice, bob, charlie, django) = (
+				default_accounts.alice,
+				default_accounts.bob,
+				default_accounts.charlie,
+				default_accounts.django,
+			);
+			// bob already holds tokens when referrals start, so bob never gets a referrer
+			assert_eq!(token.transfer(bob, 1_000, Vec::new()), Ok(()));
+			assert_eq!(token.set_transfer_fee(1_000, django), Ok(()));
+			assert_eq!(token.set_referral_share(FEE_DENOMINATOR + 1), Err(PSP22Error::InvalidFee));
+			assert_eq!(token.set_referral_share(2_500), Ok(()));
+
+			set_next_caller(bob);
+			assert_eq!(token.transfer(charlie, 500, Vec::new()), Ok(()));
+			assert_eq!(token.referrer_of(charlie), Some(bob));
+			assert_eq!(token.referrer_of(bob), None);
+			set_next_caller(alice);
+			assert_eq!(token.transfer(charlie, 100, Vec::new()), Ok(()));
+			assert_eq!(token.referrer_of(charlie), Some(bob));
+
+			let bob_balance = token.balance_of(bob);
+			let django_balance = token.balance_of(django);
+			set_next_caller(charlie);
+			assert_eq!(token.transfer(alice, 400, Vec::new()), Ok(()));
+			assert_eq!(token.balance_of(bob), bob_balance + 10);
+			assert_eq!(token.balance_of(django), django_balance + 30);
+		}
+
+		#[ink::test]
+		fn test_transfer_fee_is_owner_only_and_bounded() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+
+			assert_eq!(
+				token.set_transfer_fee(FEE_DENOMINATOR + 1, default_accounts.alice),
+				Err(PSP22Error::InvalidFee)
+			);
+
+			set_next_caller(default_accounts.bob);
+			assert_eq!(
+				token.set_transfer_fee(100, default_accounts.bob),
+				Err(PSP22Error::NotOwner)
+			);
+			assert_eq!(token.set_fee_exempt(default_accounts.bob, true), Err(PSP22Error::NotOwner));
+		}
+
+		#[ink::test]
+		fn test_rebase_scales_every_balance() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+			assert_eq!(token.transfer_batch(vec![(default_accounts.bob, 250)]), Ok(()));
+
+			assert_eq!(token.rebase(1000), Ok(()));
+			assert_eq!(token.total_supply(), 2000);
+			assert_eq!(token.balance_of(default_accounts.alice), 1500);
+			assert_eq!(token.balance_of(default_accounts.bob), 500);
+			assert_eq!(token.shares_of(default_accounts.bob), 250);
+			assert_eq!(token.total_shares(), 1000);
+
+			assert_eq!(token.transfer_batch(vec![(default_accounts.bob, 100)]), Ok(()));
+			assert_eq!(token.balance_of(default_accounts.bob), 600);
+			assert_eq!(token.mint(default_accounts.charlie, 200), Ok(()));
+			assert_eq!(token.shares_of(default_accounts.charlie), 100);
+
+			assert_eq!(token.rebase(-1100), Ok(()));
+			assert_eq!(token.total_supply(), 1100);
+			assert_eq!(token.balance_of(default_accounts.alice), 700);
+			assert_eq!(token.balance_of(default_accounts.bob), 300);
+			assert_eq!(token.balance_of(default_accounts.charlie), 100);
+		}
+
+		#[ink::test]
+		fn test_rebase_is_restricted_and_bounded() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+
+			assert_eq!(token.rebase(-1000), Err(PSP22Error::InvalidRebase));
+			assert_eq!(token.rebase(i128::MAX), Ok(()));
+			assert_eq!(token.rebase(i128::MAX), Err(PSP22Error::InvalidRebase));
+
+			set_next_caller(default_accounts.bob);
+			assert_eq!(token.rebase(1), Err(PSP22Error::MissingRole));
+		}
+
+		#[ink::test]
+		fn test_delegated_votes_follow_balances() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+			assert_eq!(token.get_votes(default_accounts.alice), 0);
+
+			assert_eq!(token.delegate(default_accounts.alice), Ok(()));
+			assert_eq!(token.delegates(default_accounts.alice), Some(default_accounts.alice));
+			assert_eq!(token.get_votes(default_accounts.alice), 1000);
+
+			ink::env::test::advance_block::<Environment>();
+			assert_eq!(token.transfer_batch(vec![(default_accounts.bob, 300)]), Ok(()));
+			assert_eq!(token.get_votes(default_accounts.alice), 700);
+			assert_eq!(token.get_votes_at(default_accounts.alice, 0), 1000);
+			assert_eq!(token.get_votes_at(default_accounts.alice, 1), 700);
+
+			ink::env::test::advance_block::<Environment>();
+			set_next_caller(default_accounts.bob);
+			assert_eq!(token.delegate(default_accounts.alice), Ok(()));
+			assert_eq!(token.get_votes(default_accounts.alice), 1000);
+			assert_eq!(token.delegate(default_accounts.charlie), Ok(()));
+			assert_eq!(token.get_votes(default_accounts.alice), 700);
+			assert_eq!(token.get_votes(default_accounts.charlie), 300);
+			assert_eq!(token.get_votes_at(default_accounts.charlie, 1), 0);
+		}
+
+		#[ink::test]
+		fn test_flash_loan_terms() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = Token::new(1000, None, None, 0, Some(50_000), Hash::default());
+
+			assert_eq!(token.flash_fee(10_000), 9);
+			assert_eq!(token.flash_fee(100), 0);
+			assert_eq!(token.max_flash_loan(), 49_000);
+			assert_eq!(
+				token.flash_loan(default_accounts.bob, 100, Vec::new()),
+				Err(PSP22Error::ReceiverRejected)
+			);
+		}
+
+		#[ink::test]
+		fn test_stream_pays_out_per_block() {
+			let default_accounts = default_accounts();
+			ink::env::test::set_callee::<Environment>(default_accounts.frank);
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+
+			assert_eq!(token.create_stream(default_accounts.bob, 100, 2, 12), Ok(0));
+			assert_eq!(token.balance_of(default_accounts.alice), 900);
+			assert_eq!(token.stream_balance(0), Ok(0));
+
+			for _ in 0..5 {
+				ink::env::test::advance_block::<Environment>();
+			}
+			assert_eq!(token.stream_balance(0), Ok(30));
+
+			set_next_caller(default_accounts.charlie);
+			assert_eq!(token.withdraw_from_stream(0, 10), Err(PSP22Error::NotStreamParty));
+
+			set_next_caller(default_accounts.bob);
+			assert_eq!(token.withdraw_from_stream(0, 31), Err(PSP22Error::InsufficientBalance));
+			assert_eq!(token.withdraw_from_stream(0, 20), Ok(()));
+			assert_eq!(token.balance_of(default_accounts.bob), 20);
+			assert_eq!(token.stream_balance(0), Ok(10));
+		}
+
+		#[ink::test]
+		fn test_cancel_stream_splits_remainder() {
+			let default_accounts = default_accounts();
+			ink::env::test::set_callee::<Environment>(default_accounts.frank);
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+			assert_eq!(
+				token.create_stream(default_accounts.bob, 100, 5, 5),
+				Err(PSP22Error::InvalidStream)
+			);
+			assert_eq!(token.create_stream(default_accounts.bob, 100, 0, 4), Ok(0));
+
+			ink::env::test::advance_block::<Environment>();
+			assert_eq!(token.cancel_stream(0), Ok(()));
+			assert_eq!(token.balance_of(default_accounts.bob), 25);
+			assert_eq!(token.balance_of(default_accounts.alice), 975);
+			assert_eq!(token.stream(0), None);
+			assert_eq!(token.cancel_stream(0), Err(PSP22Error::StreamNotFound));
+		}
+
+		#[ink::test]
+		fn test_streams_are_checked_and_charged_like_transfers() {
+			let default_accounts = default_accounts();
+			ink::env::test::set_callee::<Environment>(default_accounts.frank);
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(10_000);
+			let (bob, charlie, django) =
+				(default_accounts.bob, default_accounts.charlie, default_accounts.django);
+			assert_eq!(token.transfer(bob, 5_000, Vec::new()), Ok(()));
+			assert_eq!(token.set_transfer_fee(1_000, django), Ok(()));
+			let limits = TransferLimits { max_transfer: Some(2_000), ..Default::default() };
+			assert_eq!(token.set_transfer_limits(limits), Ok(()));
+
+			set_next_caller(bob);
+			assert_eq!(
+				token.create_stream(charlie, 2_001, 0, 4),
+				Err(PSP22Error::TransferLimitExceeded)
+			);
+			assert_eq!(token.create_stream(charlie, 2_000, 0, 4), Ok(0));
+			assert_eq!(token.balance_of(bob), 3_000);
+
+			ink::env::test::advance_block::<Environment>();
+			set_next_caller(charlie);
+			assert_eq!(token.withdraw_from_stream(0, 500), Ok(()));
+			assert_eq!(token.balance_of(charlie), 450);
+			assert_eq!(token.balance_of(django), 50);
+
+			ink::env::test::advance_block::<Environment>();
+			assert_eq!(token.cancel_stream(0), Ok(()));
+			assert_eq!(token.balance_of(charlie), 900);
+			assert_eq!(token.balance_of(django), 100);
+			assert_eq!(token.balance_of(bob), 4_000);
+			assert_eq!(token.balance_of(default_accounts.frank), 0);
+		}
+
+		#[ink::test]
+		fn test_escrowed_transfers_are_claimed_or_refunded() {
+			let default_accounts = default_accounts();
+			ink::env::test::set_callee::<Environment>(default_accounts.frank);
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+			let (alice, bob) = (default_accounts.alice, default_accounts.bob);
+
+			assert_eq!(token.transfer_escrowed(bob, 100, 1), Ok(0));
+			assert_eq!(token.transfer_escrowed(bob, 50, 1), Ok(1));
+			assert_eq!(token.balance_of(alice), 850);
+			assert_eq!(token.refund_escrowed(0), Err(PSP22Error::TransferNotExpired));
+
+			set_next_caller(default_accounts.charlie);
+			assert_eq!(token.claim_escrowed(0), Err(PSP22Error::NotTransferParty));
+			set_next_caller(bob);
+			assert_eq!(token.claim_escrowed(0), Ok(()));
+			assert_eq!(token.balance_of(bob), 100);
+			assert_eq!(token.escrowed_transfer(0), None);
+			assert_eq!(token.claim_escrowed(0), Err(PSP22Error::TransferNotFound));
+
+			ink::env::test::advance_block::<Environment>();
+			ink::env::test::advance_block::<Environment>();
+			assert_eq!(token.claim_escrowed(1), Err(PSP22Error::TransferExpired));
+			assert_eq!(token.refund_escrowed(1), Err(PSP22Error::NotTransferParty));
+			set_next_caller(alice);
+			assert_eq!(token.refund_escrowed(1), Ok(()));
+			assert_eq!(token.balance_of(alice), 900);
+		}
+
+		#[ink::test]
+		fn test_scheduled_transfers_run_when_due_unless_cancelled() {
+			let default_accounts = default_accounts();
+			ink::env::test::set_callee::<Environment>(default_accounts.frank);
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+			let (alice, bob) = (default_accounts.alice, default_accounts.bob);
+
+			assert_eq!(token.schedule_transfer(bob, 100, 1), Ok(0));
+			assert_eq!(token.schedule_transfer(bob, 50, 1), Ok(1));
+			assert_eq!(token.balance_of(alice), 850);
+
+			set_next_caller(default_accounts.charlie);
+			assert_eq!(token.execute_scheduled(0), Err(PSP22Error::TransferNotDue));
+			assert_eq!(token.cancel_scheduled(1), Err(PSP22Error::NotTransferParty));
+			set_next_caller(alice);
+			assert_eq!(token.cancel_scheduled(1), Ok(()));
+			assert_eq!(token.balance_of(alice), 900);
+
+			ink::env::test::advance_block::<Environment>();
+			ink::env::test::advance_block::<Environment>();
+			set_next_caller(default_accounts.charlie);
+			assert_eq!(token.execute_scheduled(1), Err(PSP22Error::TransferNotFound));
+			assert_eq!(token.execute_scheduled(0), Ok(()));
+			assert_eq!(token.balance_of(bob), 100);
+			assert_eq!(token.scheduled_transfer(0), None);
+		}
+
+		#[ink::test]
+		fn test_escrowed_transfers_are_checked_and_charged_like_transfers() {
+			let default_accounts = default_accounts();
+			ink::env::test::set_callee::<Environment>(default_accounts.frank);
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(10_000);
+			let (alice, bob, charlie, django) = (
+				default_accounts.alice,
+				default_accounts.bob,
+				default_accounts.charlie,
+				default_accounts.django,
+			);
+			assert_eq!(token.transfer(bob, 5_000, Vec::new()), Ok(()));
+			assert_eq!(token.set_transfer_fee(1_000, django), Ok(()));
+			assert_eq!(token.set_referral_share(2_500), Ok(()));
+			let limits = TransferLimits { max_transfer: Some(2_000), ..Default::default() };
+			assert_eq!(token.set_transfer_limits(limits), Ok(()));
+
+			set_next_caller(bob);
+			assert_eq!(
+				token.transfer_escrowed(charlie, 2_001, 1),
+				Err(PSP22Error::TransferLimitExceeded)
+			);
+			assert_eq!(token.transfer_escrowed(charlie, 1_000, 1), Ok(0));
+			assert_eq!(token.transfer_escrowed(alice, 1_000, 1), Ok(1));
+			assert_eq!(token.balance_of(bob), 3_000);
+
+			set_next_caller(charlie);
+			assert_eq!(token.claim_escrowed(0), Ok(()));
+			assert_eq!(token.balance_of(charlie), 900);
+			assert_eq!(token.balance_of(django), 100);
+			assert_eq!(token.referrer_of(charlie), Some(bob));
+
+			ink::env::test::advance_block::<Environment>();
+			ink::env::test::advance_block::<Environment>();
+			set_next_caller(bob);
+			assert_eq!(token.refund_escrowed(1), Ok(()));
+			assert_eq!(token.balance_of(bob), 4_000);
+			assert_eq!(token.balance_of(django), 100);
+			assert_eq!(token.balance_of(default_accounts.frank), 0);
+		}
+
+		#[ink::test]
+		fn test_scheduled_transfers_are_checked_and_charged_like_transfers() {
+			let default_accounts = default_accounts();
+			ink::env::test::set_callee::<Environment>(default_accounts.frank);
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(10_000);
+			let (alice, bob, charlie, django) = (
+				default_accounts.alice,
+				default_accounts.bob,
+				default_accounts.charlie,
+				default_accounts.django,
+			);
+			assert_eq!(token.transfer(bob, 5_000, Vec::new()), Ok(()));
+			assert_eq!(token.set_transfer_fee(1_000, django), Ok(()));
+			assert_eq!(token.set_referral_share(2_500), Ok(()));
+			let limits = TransferLimits { max_transfer: Some(2_000), ..Default::default() };
+			assert_eq!(token.set_transfer_limits(limits), Ok(()));
+
+			set_next_caller(bob);
+			assert_eq!(
+				token.schedule_transfer(charlie, 2_001, 1),
+				Err(PSP22Error::TransferLimitExceeded)
+			);
+			assert_eq!(token.schedule_transfer(charlie, 1_000, 1), Ok(0));
+			assert_eq!(token.schedule_transfer(alice, 1_000, 1), Ok(1));
+			assert_eq!(token.balance_of(bob), 3_000);
+			assert_eq!(token.cancel_scheduled(1), Ok(()));
+			assert_eq!(token.balance_of(bob), 4_000);
+
+			ink::env::test::advance_block::<Environment>();
+			ink::env::test::advance_block::<Environment>();
+			assert_eq!(token.execute_scheduled(0), Ok(()));
+			assert_eq!(token.balance_of(charlie), 900);
+			assert_eq!(token.balance_of(django), 100);
+			assert_eq!(token.referrer_of(charlie), Some(bob));
+			assert_eq!(token.balance_of(default_accounts.frank), 0);
+		}
+
+		#[ink::test]
+		fn test_staking_rewards_are_shared_per_block() {
+			let default_accounts = default_accounts();
+			ink::env::test::set_callee::<Environment>(default_accounts.frank);
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+			assert_eq!(token.transfer_batch(vec![(default_accounts.bob, 300)]), Ok(()));
+			assert_eq!(token.set_reward_rate(10), Ok(()));
+
+			assert_eq!(token.stake(100), Ok(()));
+			ink::env::test::advance_block::<Environment>();
+			ink::env::test::advance_block::<Environment>();
+			assert_eq!(token.earned(default_accounts.alice), 20);
+
+			// bob stakes three times as much and gets three quarters of every block from now on
+			set_next_caller(default_accounts.bob);
+			assert_eq!(token.stake(301), Err(PSP22Error::InsufficientBalance));
+			assert_eq!(token.stake(300), Ok(()));
+			assert_eq!(token.total_staked(), 400);
+			ink::env::test::advance_block::<Environment>();
+			ink::env::test::advance_block::<Environment>();
+			assert_eq!(token.earned(default_accounts.alice), 25);
+			assert_eq!(token.earned(default_accounts.bob), 15);
+
+			assert_eq!(token.unstake(301), Err(PSP22Error::InsufficientBalance));
+			assert_eq!(token.unstake(300), Ok(()));
+			assert_eq!(token.claim_staking_rewards(), Ok(15));
+			assert_eq!(token.claim_staking_rewards(), Ok(0));
+			assert_eq!(token.balance_of(default_accounts.bob), 315);
+			assert_eq!(token.total_supply(), 1015);
+
+			ink::env::test::advance_block::<Environment>();
+			assert_eq!(token.earned(default_accounts.alice), 35);
+			assert_eq!(token.earned(default_accounts.bob), 0);
+		}
+
+		#[ink::test]
+		fn test_airdrop_claims_with_proof_once() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+
+			let bob_leaf = airdrop_leaf(0, default_accounts.bob, 100);
+			let charlie_leaf = airdrop_leaf(1, default_accounts.charlie, 50);
+			assert_eq!(
+				token.set_airdrop_root(psp22_traits::hash_pair(&bob_leaf, &charlie_leaf)),
+				Ok(())
+			);
+
+			set_next_caller(default_accounts.bob);
+			assert_eq!(token.claim(0, 1000, vec![charlie_leaf]), Err(PSP22Error::InvalidProof));
+			assert_eq!(token.claim(0, 100, vec![charlie_leaf]), Ok(()));
+			assert_eq!(token.balance_of(default_accounts.bob), 100);
+			assert!(token.is_claimed(0));
+			assert_eq!(token.claim(0, 100, vec![charlie_leaf]), Err(PSP22Error::AlreadyClaimed));
+
+			set_next_caller(default_accounts.charlie);
+			assert_eq!(token.claim(1, 50, vec![bob_leaf]), Ok(()));
+			assert_eq!(token.total_supply(), 1150);
+		}
+
+		#[ink::test]
+		fn test_disperse_checks_lengths_and_balance_upfront() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+			let recipients = vec![default_accounts.bob, default_accounts.charlie];
+
+			assert_eq!(
+				token.disperse(recipients.clone(), vec![100]),
+				Err(PSP22Error::LengthMismatch)
+			);
+			assert_eq!(
+				token.disperse(recipients.clone(), vec![100, 901]),
+				Err(PSP22Error::InsufficientBalance)
+			);
+			assert_eq!(token.disperse(recipients, vec![100, 200]), Ok(()));
+			assert_eq!(token.balance_of(default_accounts.bob), 100);
+			assert_eq!(token.balance_of(default_accounts.charlie), 200);
+			assert_eq!(token.balance_of(default_accounts.alice), 700);
+		}
+
+		#[ink::test]
+		fn test_bridge_burns_with_allowance_and_mints_once() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+			assert_eq!(token.grant_role(Role::Bridge, default_accounts.eve), Ok(()));
+			assert_eq!(token.transfer_batch(vec![(default_accounts.bob, 100)]), Ok(()));
+
+			set_next_caller(default_accounts.bob);
+			assert_eq!(
+				token.bridge_burn(default_accounts.bob, 10, 2, vec![0xb0]),
+				Err(PSP22Error::MissingRole)
+			);
+			assert_eq!(token.approve(default_accounts.eve, 40), Ok(()));
+
+			set_next_caller(default_accounts.eve);
+			assert_eq!(
+				token.bridge_burn(default_accounts.bob, 41, 2, vec![0xb0]),
+				Err(PSP22Error::InsufficientAllowance)
+			);
+			assert_eq!(token.bridge_burn(default_accounts.bob, 40, 2, vec![0xb0]), Ok(()));
+			assert_eq!(token.balance_of(default_accounts.bob), 60);
+			assert_eq!(token.total_supply(), 960);
+
+			let source_tx = [7; 32];
+			assert!(!token.is_bridged_in(source_tx));
+			assert_eq!(token.bridge_mint(default_accounts.charlie, 25, source_tx), Ok(()));
+			assert!(token.is_bridged_in(source_tx));
+			assert_eq!(
+				token.bridge_mint(default_accounts.charlie, 25, source_tx),
+				Err(PSP22Error::AlreadyProcessed)
+			);
+			assert_eq!(token.balance_of(default_accounts.charlie), 25);
+			assert_eq!(token.total_supply(), 985);
+		}
+
+		#[ink::test]
+		fn test_escrow_respects_chain_limits_and_pauses() {
+			let default_accounts = default_accounts();
+			ink::env::test::set_callee::<Environment>(default_accounts.frank);
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+			assert_eq!(token.escrow_lock(2, 10, vec![0xb0]), Err(PSP22Error::ChainUnavailable));
+			assert_eq!(token.set_escrow_limit(2, 100), Ok(()));
+			assert_eq!(token.grant_role(Role::Relayer, default_accounts.eve), Ok(()));
+
+			assert_eq!(token.escrow_lock(2, 101, vec![0xb0]), Err(PSP22Error::ChainLimitExceeded));
+			assert_eq!(token.escrow_lock(2, 60, vec![0xb0]), Ok(()));
+			assert_eq!(token.balance_of(default_accounts.alice), 940);
+			assert_eq!(token.escrow_chain(2).map(|escrow| escrow.locked), Some(60));
+
+			set_next_caller(default_accounts.eve);
+			let message_id = [9; 32];
+			assert_eq!(
+				token.escrow_release(2, default_accounts.bob, 61, message_id),
+				Err(PSP22Error::InsufficientBalance)
+			);
+			assert_eq!(token.escrow_release(2, default_accounts.bob, 20, message_id), Ok(()));
+			assert_eq!(
+				token.escrow_release(2, default_accounts.bob, 20, message_id),
+				Err(PSP22Error::AlreadyProcessed)
+			);
+			assert_eq!(token.balance_of(default_accounts.bob), 20);
+
+			set_next_caller(default_accounts.alice);
+			assert_eq!(token.set_escrow_paused(2, true), Ok(()));
+			assert_eq!(token.escrow_lock(2, 10, vec![0xb0]), Err(PSP22Error::ChainUnavailable));
+			set_next_caller(default_accounts.eve);
+			assert_eq!(
+				token.escrow_release(2, default_accounts.bob, 20, [8; 32]),
+				Err(PSP22Error::ChainUnavailable)
+			);
+		}
+
+		#[ink::test]
+		fn test_swap_exact_requires_a_matching_live_offer() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+			let other_token = default_accounts.django;
+			assert_eq!(
+				token.swap_exact(default_accounts.bob, 10, other_token, 20, 5),
+				Err(PSP22Error::NoSwapOffer)
+			);
+
+			set_next_caller(default_accounts.bob);
+			assert_eq!(token.offer_swap(default_accounts.alice, other_token, 20, 10, 1), Ok(()));
+			assert_eq!(
+				token.swap_offer(default_accounts.bob, default_accounts.alice),
+				Some(SwapOffer {
+					give_token: other_token,
+					give_amount: 20,
+					want_amount: 10,
+					deadline: 1
+				})
+			);
+
+			set_next_caller(default_accounts.alice);
+			assert_eq!(
+				token.swap_exact(default_accounts.bob, 0, other_token, 20, 5),
+				Err(PSP22Error::NoSwapOffer)
+			);
+			assert_eq!(
+				token.swap_exact(default_accounts.bob, 10, default_accounts.eve, 20, 5),
+				Err(PSP22Error::NoSwapOffer)
+			);
+
+			ink::env::test::advance_block::<Environment>();
+			ink::env::test::advance_block::<Environment>();
+			assert_eq!(
+				token.swap_exact(default_accounts.bob, 10, other_token, 20, 5),
+				Err(PSP22Error::SwapExpired)
+			);
+			set_next_caller(default_accounts.bob);
+			assert_eq!(token.offer_swap(default_accounts.alice, other_token, 20, 10, 9), Ok(()));
+			set_next_caller(default_accounts.alice);
+			assert_eq!(
+				token.swap_exact(default_accounts.bob, 10, other_token, 20, 1),
+				Err(PSP22Error::SwapExpired)
+			);
+
+			set_next_caller(default_accounts.bob);
+			assert_eq!(token.offer_swap(default_accounts.alice, other_token, 0, 0, 0), Ok(()));
+			assert_eq!(token.swap_offer(default_accounts.bob, default_accounts.alice), None);
+			assert_eq!(token.balance_of(default_accounts.alice), 1000);
+		}
+
+		fn partition(name: &str) -> Partition {
+			let mut partition = DEFAULT_PARTITION;
+			partition[..name.len()].copy_from_slice(name.as_bytes());
+			partition
+		}
+
+		#[ink::test]
+		fn test_partitioned_tokens_only_move_within_their_partition() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+			let (alice, bob) = (default_accounts.alice, default_accounts.bob);
+			let restricted = partition("restricted");
+
+			assert_eq!(token.change_partition(alice, DEFAULT_PARTITION, restricted, 300), Ok(()));
+			assert_eq!(token.balance_of(alice), 1000);
+			assert_eq!(token.balance_of_by_partition(restricted, alice), 300);
+			assert_eq!(token.balance_of_by_partition(DEFAULT_PARTITION, alice), 700);
+			assert_eq!(token.partitions_of(alice), vec![restricted]);
+
+			assert_eq!(token.transfer(bob, 701, Vec::new()), Err(PSP22Error::InsufficientBalance));
+			assert_eq!(
+				token.transfer_by_partition(restricted, bob, 301, Vec::new()),
+				Err(PSP22Error::InsufficientBalance)
+			);
+			assert_eq!(token.transfer_by_partition(restricted, bob, 100, Vec::new()), Ok(()));
+			assert_eq!(token.balance_of_by_partition(restricted, alice), 200);
+			assert_eq!(token.balance_of_by_partition(restricted, bob), 100);
+			assert_eq!(token.balance_of(alice), 900);
+
+			set_next_caller(bob);
+			assert_eq!(token.transfer(alice, 1, Vec::new()), Err(PSP22Error::InsufficientBalance));
+			assert_eq!(
+				token.change_partition(bob, restricted, DEFAULT_PARTITION, 100),
+				Err(PSP22Error::MissingRole)
+			);
+
+			set_next_caller(alice);
+			assert_eq!(token.change_partition(bob, restricted, DEFAULT_PARTITION, 100), Ok(()));
+			assert_eq!(token.partitions_of(bob), Vec::<Partition>::new());
+			set_next_caller(bob);
+			assert_eq!(token.transfer(alice, 100, Vec::new()), Ok(()));
+		}
+
+		#[ink::test]
+		fn test_partitioned_tokens_cannot_leave_through_the_token() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+			let (alice, bob) = (default_accounts.alice, default_accounts.bob);
+			let restricted = partition("restricted");
+			assert_eq!(token.change_partition(alice, DEFAULT_PARTITION, restricted, 300), Ok(()));
+
+			assert_eq!(token.create_stream(bob, 701, 1, 10), Err(PSP22Error::InsufficientBalance));
+			assert_eq!(token.transfer_escrowed(bob, 701, 10), Err(PSP22Error::InsufficientBalance));
+			assert_eq!(token.schedule_transfer(bob, 701, 10), Err(PSP22Error::InsufficientBalance));
+			assert_eq!(token.stake(701), Err(PSP22Error::InsufficientBalance));
+			assert_eq!(token.stake(700), Ok(()));
+			assert_eq!(token.balance_of_by_partition(restricted, alice), 300);
+
+			assert_eq!(token.transfer_by_partition(restricted, alice, 100, Vec::new()), Ok(()));
+			assert_eq!(token.balance_of_by_partition(restricted, alice), 300);
+		}
+
+		#[ink::test]
+		fn test_partition_operators_are_per_partition() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+			let (alice, bob, charlie) =
+				(default_accounts.alice, default_accounts.bob, default_accounts.charlie);
+			let (locked, restricted) = (partition("locked"), partition("restricted"));
+			assert_eq!(token.change_partition(alice, DEFAULT_PARTITION, locked, 300), Ok(()));
+			token.authorize_operator_by_partition(restricted, charlie);
+
+			set_next_caller(charlie);
+			assert_eq!(
+				token.operator_transfer_by_partition(locked, alice, bob, 50, Vec::new()),
+				Err(PSP22Error::NotOperator)
+			);
+
+			set_next_caller(alice);
+			token.authorize_operator_by_partition(locked, charlie);
+			assert!(token.is_operator_for_partition(locked, charlie, alice));
+			set_next_caller(charlie);
+			assert_eq!(
+				token.operator_transfer_by_partition(locked, alice, bob, 50, Vec::new()),
+				Ok(())
+			);
+			assert_eq!(token.balance_of_by_partition(locked, bob), 50);
+
+			set_next_caller(alice);
+			token.revoke_operator_by_partition(locked, charlie);
+			set_next_caller(charlie);
+			assert_eq!(
+				token.operator_transfer_by_partition(locked, alice, bob, 50, Vec::new()),
+				Err(PSP22Error::NotOperator)
+			);
+		}
+
+		#[ink::test]
+		fn test_volume_breaker_trips_until_the_window_ends_or_it_is_lifted() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+			let bob = default_accounts.bob;
+			assert_eq!(token.set_volume_limit(Some(100), 3), Ok(()));
+
+			assert_eq!(token.transfer(bob, 60, Vec::new()), Ok(()));
+			assert_eq!(token.transfer(bob, 41, Vec::new()), Err(PSP22Error::VolumeLimitExceeded));
+			assert_eq!(token.transfer(bob, 40, Vec::new()), Ok(()));
+			assert_eq!(token.transfer(bob, 1, Vec::new()), Err(PSP22Error::VolumeLimitExceeded));
+
+			for _ in 0..3 {
+				ink::env::test::advance_block::<Environment>();
+			}
+			assert_eq!(token.transfer(bob, 100, Vec::new()), Ok(()));
+			assert_eq!(token.transfer(bob, 1, Vec::new()), Err(PSP22Error::VolumeLimitExceeded));
+
+			set_next_caller(bob);
+			assert_eq!(token.lift_volume_breaker(), Err(PSP22Error::MissingRole));
+			set_next_caller(default_accounts.alice);
+			assert_eq!(token.lift_volume_breaker(), Ok(()));
+			assert_eq!(token.volume_breaker().volume, 0);
+			assert_eq!(token.transfer(bob, 1, Vec::new()), Ok(()));
+
+			assert_eq!(token.set_volume_limit(None, 0), Ok(()));
+			assert_eq!(token.transfer(bob, 500, Vec::new()), Ok(()));
+		}
+
+		#[ink::test]
+		fn test_multicall_rejects_calls_without_a_selector() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+
+			let transfer = [
+				&[0xdb, 0x20, 0xf9, 0xf5][..],
+				&scale::Encode::encode(&(default_accounts.bob, 10u128, Vec::<u8>::new())),
+			]
+			.concat();
+			assert_eq!(
+				token.multicall(vec![transfer, vec![0xdb, 0x20]]),
+				Err(PSP22Error::CallFailed)
+			);
+			assert_eq!(token.balance_of(default_accounts.bob), 0);
+		}
+
+		#[ink::test]
+		fn test_multicall_runs_allowed_messages_with_exact_arguments() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+			let (alice, bob) = (default_accounts.alice, default_accounts.bob);
+			let call = |selector: Selector, args: Vec<u8>| [&selector[..], &args].concat();
+
+			let approve =
+				call(ink::selector_bytes!("PSP22::approve"), scale::Encode::encode(&(bob, 50u128)));
+			let transfer = call(
+				ink::selector_bytes!("PSP22::transfer"),
+				scale::Encode::encode(&(bob, 10u128, Vec::<u8>::new())),
+			);
+			let delegate =
+				call(ink::selector_bytes!("PSP22Votes::delegate"), scale::Encode::encode(&alice));
+			assert_eq!(token.multicall(vec![approve, transfer, delegate]), Ok(()));
+			assert_eq!(token.allowance(alice, bob), 50);
+			assert_eq!(token.balance_of(bob), 10);
+			assert_eq!(token.delegates(alice), Some(alice));
+
+			let mut trailing =
+				call(ink::selector_bytes!("PSP22::approve"), scale::Encode::encode(&(bob, 60u128)));
+			trailing.push(0);
+			assert_eq!(token.multicall(vec![trailing]), Err(PSP22Error::CallFailed));
+			let pause = call(ink::selector_bytes!("pause"), Vec::new());
+			assert_eq!(token.multicall(vec![pause]), Err(PSP22Error::CallFailed));
+			assert!(!token.paused());
+		}
+
+		#[ink::test]
+		fn test_rescue_token_is_owner_only_and_not_for_itself() {
+			let default_accounts = default_accounts();
+			ink::env::test::set_callee::<Environment>(default_accounts.frank);
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+
+			assert_eq!(
+				token.rescue_token(default_accounts.frank, default_accounts.alice, 1),
+				Err(PSP22Error::CannotRescueSelf)
+			);
+
+			set_next_caller(default_accounts.bob);
+			assert_eq!(
+				token.rescue_token(default_accounts.eve, default_accounts.bob, 1),
+				Err(PSP22Error::NotOwner)
+			);
+		}
+
+		#[ink::test]
+		fn test_supports_reports_the_capabilities_of_the_build() {
+			use crate::capabilities::{FAUCET, PAUSABLE, REGULATED, VOTES};
+
+			let token = new_token(1000);
+			assert_eq!(token.version(), env!("CARGO_PKG_VERSION"));
+			assert!(token.supports(PAUSABLE));
+			assert!(token.supports(VOTES));
+			assert_eq!(token.supports(FAUCET), cfg!(feature = "faucet"));
+			assert_eq!(token.supports(REGULATED), cfg!(feature = "regulated"));
+			assert!(!token.supports(REGULATED + 1));
+			assert!(!token.supports(u32::MAX));
+		}
+
+		#[ink::test]
+		fn test_migrate_is_restricted_to_outdated_storage() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+			assert_eq!(token.storage_version(), STORAGE_VERSION);
+			assert_eq!(token.migrate(), Err(PSP22Error::NothingToMigrate));
+
+			token.storage_version = 0;
+			set_next_caller(default_accounts.bob);
+			assert_eq!(token.migrate(), Err(PSP22Error::MissingRole));
+			assert_eq!(token.upgrade(Hash::default()), Err(PSP22Error::MissingRole));
+
+			set_next_caller(default_accounts.alice);
+			assert_eq!(token.migrate(), Ok(()));
+			assert_eq!(token.storage_version(), STORAGE_VERSION);
+		}
+
+		#[ink::test]
+		fn test_only_pausers_can_pause() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+
+			set_next_caller(default_accounts.bob);
+			assert_eq!(token.pause(), Err(PausableError::NotAllowed));
+			assert_eq!(token.unpause(), Err(PausableError::NotAllowed));
+			assert!(!token.paused());
+		}
+
+		#[ink::test]
+		fn test_owner_grants_and_revokes_roles() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+			assert!(token.has_role(Role::Minter, default_accounts.alice));
+			assert!(!token.has_role(Role::Minter, default_accounts.bob));
+
+			assert_eq!(token.grant_role(Role::Minter, default_accounts.bob), Ok(()));
+			assert_eq!(token.revoke_role(Role::Pauser, default_accounts.alice), Ok(()));
+			assert_eq!(token.pause(), Err(PausableError::NotAllowed));
+
+			set_next_caller(default_accounts.bob);
+			assert_eq!(token.mint(default_accounts.bob, 10), Ok(()));
+			assert_eq!(
+				token.grant_role(Role::Pauser, default_accounts.bob),
+				Err(PSP22Error::NotOwner)
+			);
+
+			set_next_caller(default_accounts.alice);
+			assert_eq!(token.revoke_role(Role::Minter, default_accounts.bob), Ok(()));
+
+			set_next_caller(default_accounts.bob);
+			assert_eq!(token.mint(default_accounts.bob, 10), Err(PSP22Error::MissingRole));
+		}
+
+		#[ink::test]
+		fn test_burners_can_burn() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+
+			assert_eq!(token.burn(default_accounts.alice, 400), Ok(()));
+			// A Transfer to the zero account and a Burned event
+			assert_eq!(ink::env::test::recorded_events().count(), 2);
+			assert_eq!(token.total_supply(), 600);
+			assert_eq!(token.balance_of(default_accounts.alice), 600);
+			assert_eq!(
+				token.burn(default_accounts.alice, 601),
+				Err(PSP22Error::InsufficientBalance)
+			);
+
+			set_next_caller(default_accounts.bob);
+			assert_eq!(token.burn(default_accounts.alice, 1), Err(PSP22Error::MissingRole));
+		}
+
+		#[ink::test]
+		fn test_burns_cannot_touch_locked_tokens() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+			let bob = default_accounts.bob;
+			assert_eq!(token.transfer(bob, 100, Vec::new()), Ok(()));
+			assert_eq!(token.lock(bob, 60, 2), Ok(()));
+
+			assert_eq!(token.burn(bob, 41), Err(PSP22Error::TokensLocked));
+			assert_eq!(token.burn(bob, 40), Ok(()));
+			assert_eq!(token.balance_of(bob), 60);
+			assert_eq!(token.total_supply(), 960);
+		}
+
+		#[ink::test]
+		fn test_ownership_transfer_takes_two_steps() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+
+			assert_eq!(token.transfer_ownership(default_accounts.bob), Ok(()));
+			assert_eq!(token.owner(), default_accounts.alice);
+			assert_eq!(token.pending_owner(), Some(default_accounts.bob));
+
+			set_next_caller(default_accounts.charlie);
+			assert_eq!(token.accept_ownership(), Err(PSP22Error::NotPendingOwner));
+
+			set_next_caller(default_accounts.bob);
+			assert_eq!(token.accept_ownership(), Ok(()));
+			assert_eq!(token.owner(), default_accounts.bob);
+			assert_eq!(token.pending_owner(), None);
+			assert_eq!(token.grant_role(Role::Minter, default_accounts.bob), Ok(()));
+
+			set_next_caller(default_accounts.alice);
+			assert_eq!(
+				token.grant_role(Role::Minter, default_accounts.alice),
+				Err(PSP22Error::NotOwner)
+			);
+		}
+
+		#[ink::test]
+		fn test_only_owner_can_transfer_ownership() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = new_token(1000);
+
+			set_next_caller(default_accounts.bob);
+			assert_eq!(token.transfer_ownership(default_accounts.bob), Err(PSP22Error::NotOwner));
+			assert_eq!(token.pending_owner(), None);
+		}
+
+		#[ink::test]
+		#[should_panic(expected = "initial supply exceeds the cap")]
+		fn test_initial_supply_cannot_exceed_cap() {
+			Token::new(1000, None, None, 0, Some(999), Hash::default());
+		}
+
+		#[ink::test]
+		fn test_endowment_sets_supply_and_allocations() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			ink::env::test::set_value_transferred::<Environment>(50);
+			let token = Token::new_with_endowment(
+				20,
+				vec![(default_accounts.bob, 2_500), (default_accounts.charlie, 1_000)],
+				None,
+				None,
+				0,
+				None,
+				Hash::default(),
+			);
+
+			assert_eq!(token.total_supply(), 1000);
+			assert_eq!(token.balance_of(default_accounts.bob), 250);
+			assert_eq!(token.balance_of(default_accounts.charlie), 100);
+			assert_eq!(token.balance_of(default_accounts.alice), 650);
+			assert_eq!(token.holders_count(), 3);
+		}
+
+		#[ink::test]
+		#[should_panic(expected = "allocations exceed the initial supply")]
+		fn test_allocations_cannot_exceed_supply() {
+			let default_accounts = default_accounts();
+			ink::env::test::set_value_transferred::<Environment>(50);
+			Token::new_with_endowment(
+				20,
+				vec![(default_accounts.bob, 6_000), (default_accounts.charlie, 4_001)],
+				None,
+				None,
+				0,
+				None,
+				Hash::default(),
+			);
+		}
+	}
+
+	/// Gas benchmarks for the token messages.
+	///
+	/// Each benchmark deploys fresh tokens to a `substrate-contracts-node` and measures the
+	/// `ref_time` consumed by the messages under test. Run with
+	/// `cargo test --features benchmarks -- --nocapture` to print the measurements.
+	#[cfg(all(test, feature = "benchmarks"))]
+	mod benchmarks {
+		use ink_e2e::{build_message, CallDryRunResult, PolkadotConfig};
+
+		use super::*;
+
+		type E2EResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+		type Client = ink_e2e::Client<PolkadotConfig, Environment>;
+
+		/// Maximum `ref_time` a single `transfer` call may consume
+		const TRANSFER_BUDGET: u64 = 5_000_000_000;
+
+		fn ref_time<V>(result: &CallDryRunResult<Environment, V>) -> u64 {
+			result.exec_result.gas_consumed.ref_time()
+		}
+
+		/// Deploys a token with the given metadata and returns the `ref_time` of a transfer
+		async fn transfer_ref_time(
+			client: &mut Client,
+			name: Option<String>,
+			symbol: Option<String>,
+		) -> u64 {
+			let constructor = TokenRef::new(1000, name, symbol, 12, None, Hash::default());
+			let token = client
+				.instantiate("psp22", &ink_e2e::alice(), constructor, 0, None)
+				.await
+				.expect("instantiate failed")
+				.account_id;
+
+			let bob = ink_e2e::account_id(ink_e2e::AccountKeyring::Bob);
+			let transfer =
+				build_message::<TokenRef>(token).call(|token| token.transfer(bob, 10, Vec::new()));
+			let result = client
+				.call(&ink_e2e::alice(), transfer, 0, None)
+				.await
+				.expect("transfer failed");
+
+			ref_time(&result.dry_run)
+		}
+
+		/// Transfers must not pay for loading the metadata, however long it is
+		#[ink_e2e::test]
+		async fn bench_transfer_ignores_metadata_size(
+			mut client: ink_e2e::Client<C, E>,
+		) -> E2EResult<()> {
+			let short = transfer_ref_time(&mut client, None, None).await;
+			let long = "W".repeat(4096);
+			let long = transfer_ref_time(&mut client, Some(long.clone()), Some(long)).await;
+			println!("transfer: {short} ref_time, {long} with 8 KiB of metadata");
+
+			assert!(short <= TRANSFER_BUDGET, "transfer consumed {short} ref_time");
+			// Allow for noise, but not for 8 KiB of metadata being loaded and decoded
+			assert!(
+				long <= short + short / 100,
+				"transfer consumed {long} ref_time with long metadata, {short} without"
+			);
+
+			Ok(())
+		}
+	}
+
+	/// Property tests checking the token's bookkeeping against a plain model of balances and
+	/// allowances
+	#[cfg(test)]
+	mod invariants {
+		use std::collections::BTreeMap;
+
+		use proptest::{prelude::*, test_runner::FileFailurePersistence};
+
+		use super::*;
+
+		const ACCOUNTS: usize = 4;
+
+		#[derive(Debug, Clone)]
+		enum Op {
+			Transfer { from: usize, to: usize, value: Balance },
+			Approve { owner: usize, spender: usize, value: Balance },
+			TransferFrom { spender: usize, from: usize, to: usize, value: Balance },
+			Mint { to: usize, value: Balance },
+			Burn { from: usize, value: Balance },
+		}
+
+		fn op() -> impl Strategy<Value = Op> {
+			let account = 0..ACCOUNTS;
+			let value = 0..600 as Balance;
+			prop_oneof![
+				(account.clone(), account.clone(), value.clone())
+					.prop_map(|(from, to, value)| Op::Transfer { from, to, value }),
+				(account.clone(), account.clone(), value.clone())
+					.prop_map(|(owner, spender, value)| Op::Approve { owner, spender, value }),
+				(account.clone(), account.clone(), account.clone(), value.clone()).prop_map(
+					|(spender, from, to, value)| Op::TransferFrom { spender, from, to, value }
+				),
+				(account.clone(), value.clone()).prop_map(|(to, value)| Op::Mint { to, value }),
+				(account, value).prop_map(|(from, value)| Op::Burn { from, value }),
+			]
+		}
+
+		/// Runs `ops` against a fresh token and a model of it, checking every result and that the
+		/// balances always add up to the total supply
+		fn check(ops: Vec<Op>) {
+			ink::env::test::run_test::<Environment, _>(|default_accounts| {
+				let accounts = [
+					default_accounts.alice,
+					default_accounts.bob,
+					default_accounts.charlie,
+					default_accounts.django,
+				];
+				let minter = default_accounts.eve;
+				ink::env::test::set_caller::<Environment>(accounts[0]);
+				let mut token = Token::new(1000, None, None, 0, None, Hash::default());
+				token.grant_role(Role::Minter, minter).unwrap();
+				token.grant_role(Role::Burner, minter).unwrap();
+
+				let mut balances = BTreeMap::from([(0, 1000)]);
+				let mut allowances = BTreeMap::new();
+				let mut total_supply: Balance = 1000;
+				for op in ops {
+					match op {
+						Op::Transfer { from, to, value } => {
+							ink::env::test::set_caller::<Environment>(accounts[from]);
+							let result = token.transfer(accounts[to], value, Vec::new());
+							let balance = balances.get(&from).copied().unwrap_or(0);
+							if balance < value {
+								assert_eq!(result, Err(PSP22Error::InsufficientBalance));
+								continue;
+							}
+							assert_eq!(result, Ok(()));
+							*balances.entry(from).or_default() -= value;
+							*balances.entry(to).or_default() += value;
+						},
+						Op::Approve { owner, spender, value } => {
+							ink::env::test::set_caller::<Environment>(accounts[owner]);
+							assert_eq!(token.approve(accounts[spender], value), Ok(()));
+							allowances.insert((owner, spender), value);
+						},
+						Op::TransferFrom { spender, from, to, value } => {
+							ink::env::test::set_caller::<Environment>(accounts[spender]);
+							let result = token.transfer_from(
+								accounts[from],
+								accounts[to],
+								value,
+								Vec::new(),
+							);
+							let allowance = allowances.get(&(from, spender)).copied().unwrap_or(0);
+							let balance = balances.get(&from).copied().unwrap_or(0);
+							if allowance < value {
+								assert_eq!(result, Err(PSP22Error::InsufficientAllowance));
+								continue;
+							}
+							if balance < value {
+								assert_eq!(result, Err(PSP22Error::InsufficientBalance));
+								continue;
+							}
+							assert_eq!(result, Ok(()));
+							allowances.insert((from, spender), allowance - value);
+							*balances.entry(from).or_default() -= value;
+							*balances.entry(to).or_default() += value;
+						},
+						Op::Mint { to, value } => {
+							ink::env::test::set_caller::<Environment>(minter);
+							assert_eq!(token.mint(accounts[to], value), Ok(()));
+							*balances.entry(to).or_default() += value;
+							total_supply += value;
+						},
+						Op::Burn { from, value } => {
+							ink::env::test::set_caller::<Environment>(minter);
+							let result = token.burn(accounts[from], value);
+							let balance = balances.get(&from).copied().unwrap_or(0);
+							if balance < value {
+								assert_eq!(result, Err(PSP22Error::InsufficientBalance));
+								continue;
+							}
+							assert_eq!(result, Ok(()));
+							*balances.entry(from).or_default() -= value;
+							total_supply -= value;
+						},
+					}
+
+					assert_eq!(token.total_supply(), total_supply);
+					let held = token
+						.holders(0, token.holders_count())
+						.into_iter()
+						.map(|holder| token.balance_of(holder))
+						.sum::<Balance>();
+					assert_eq!(held, total_supply);
+				}
+
+				for (index, account) in accounts.iter().enumerate() {
+					let balance = balances.get(&index).copied().unwrap_or(0);
+					assert_eq!(token.balance_of(*account), balance);
+					for (spender_index, spender) in accounts.iter().enumerate() {
+						let allowance =
+							allowances.get(&(index, spender_index)).copied().unwrap_or(0);
+						assert_eq!(token.allowance(*account, *spender), allowance);
+					}
+				}
+
+				Ok(())
+			})
+			.unwrap();
+		}
+
+		proptest! {
+			// The default would look next to the crate rather than in it
+			#![proptest_config(ProptestConfig {
+				failure_persistence: Some(Box::new(FileFailurePersistence::Direct(concat!(
+					env!("CARGO_MANIFEST_DIR"),
+					"/proptest-regressions/lib.txt"
+				)))),
+				..ProptestConfig::default()
+			})]
+
+			#[test]
+			fn test_balances_add_up_to_total_supply(ops in prop::collection::vec(op(), 0..40)) {
+				check(ops);
+			}
+		}
+	}
+
+	#[cfg(all(test, feature = "e2e-tests"))]
+	mod e2e_tests {
+		use ink_e2e::{build_message, subxt::blocks::ExtrinsicEvents, PolkadotConfig};
+		use psp22_mock::{Psp22MockRef, Reentry};
+
+		use super::*;
+
+		type E2EResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+		type Client = ink_e2e::Client<PolkadotConfig, Environment>;
+
+		/// Decodes the token events from the `ContractEmitted` events of a call
+		fn token_events(events: &ExtrinsicEvents<PolkadotConfig>) -> Vec<Event> {
+			events
+				.iter()
+				.map(|event| event.expect("event decoding failed"))
+				.filter(|event| {
+					event.pallet_name() == "Contracts" && event.variant_name() == "ContractEmitted"
+				})
+				.map(|event| {
+					let (_contract, data) =
+						<(AccountId, Vec<u8>) as scale::Decode>::decode(&mut event.field_bytes())
+							.expect("invalid ContractEmitted event");
+					<Event as scale::Decode>::decode(&mut &data[..]).expect("invalid token event")
+				})
+				.collect()
+		}
+
+		async fn balance_of(client: &mut Client, token: AccountId, owner: AccountId) -> Balance {
+			let query = build_message::<TokenRef>(token).call(|token| token.balance_of(owner));
+			client.call_dry_run(&ink_e2e::alice(), &query, 0, None).await.return_value()
+		}
+
+		#[ink_e2e::test]
+		async fn test_transfer_approve_and_transfer_from(
+			mut client: ink_e2e::Client<C, E>,
+		) -> E2EResult<()> {
+			let constructor = TokenRef::new(
+				1000,
+				Some("Wood".into()),
+				Some("WOOD".into()),
+				12,
+				None,
+				Hash::default(),
+			);
+			let token = client
+				.instantiate("psp22", &ink_e2e::alice(), constructor, 0, None)
+				.await
+				.expect("instantiate failed")
+				.account_id;
+			let alice = ink_e2e::account_id(ink_e2e::AccountKeyring::Alice);
+			let bob = ink_e2e::account_id(ink_e2e::AccountKeyring::Bob);
+			let charlie = ink_e2e::account_id(ink_e2e::AccountKeyring::Charlie);
+
+			let transfer =
+				build_message::<TokenRef>(token).call(|token| token.transfer(bob, 100, Vec::new()));
+			let result = client
+				.call(&ink_e2e::alice(), transfer, 0, None)
+				.await
+				.expect("transfer failed");
+			assert!(matches!(
+				&token_events(&result.events)[..],
+				[Event::Transfer(Transfer { from, to, value: 100, .. })]
+					if *from == alice && *to == bob
+			));
+			assert_eq!(balance_of(&mut client, token, alice).await, 900);
+			assert_eq!(balance_of(&mut client, token, bob).await, 100);
+
+			let approve = build_message::<TokenRef>(token).call(|token| token.approve(charlie, 50));
+			client.call(&ink_e2e::bob(), approve, 0, None).await.expect("approve failed");
+			let allowance =
+				build_message::<TokenRef>(token).call(|token| token.allowance(bob, charlie));
+			let allowance =
+				client.call_dry_run(&ink_e2e::alice(), &allowance, 0, None).await.return_value();
+			assert_eq!(allowance, 50);
+
+			let transfer_from = build_message::<TokenRef>(token)
+				.call(|token| token.transfer_from(bob, charlie, 30, Vec::new()));
+			let result = client
+				.call(&ink_e2e::charlie(), transfer_from, 0, None)
+				.await
+				.expect("transfer_from failed");
+			assert!(matches!(
+				&token_events(&result.events)[..],
+				[
+					Event::Approval(Approval { amount: 20, .. }),
+					Event::Transfer(Transfer { from, to, value: 30, .. }),
+				] if *from == bob && *to == charlie
+			));
+			assert_eq!(balance_of(&mut client, token, bob).await, 70);
+			assert_eq!(balance_of(&mut client, token, charlie).await, 30);
+
+			let allowance =
+				build_message::<TokenRef>(token).call(|token| token.allowance(bob, charlie));
+			let allowance =
+				client.call_dry_run(&ink_e2e::alice(), &allowance, 0, None).await.return_value();
+			assert_eq!(allowance, 20);
+
+			Ok(())
+		}
+
+		#[ink_e2e::test]
+		async fn test_transfer_over_balance_fails(
+			mut client: ink_e2e::Client<C, E>,
+		) -> E2EResult<()> {
+			let constructor = TokenRef::new(1000, None, None, 0, None, Hash::default());
+			let token = client
+				.instantiate("psp22", &ink_e2e::alice(), constructor, 0, None)
+				.await
+				.expect("instantiate failed")
+				.account_id;
+			let alice = ink_e2e::account_id(ink_e2e::AccountKeyring::Alice);
+
+			let transfer =
+				build_message::<TokenRef>(token).call(|token| token.transfer(alice, 1, Vec::new()));
+			let result = client.call_dry_run(&ink_e2e::bob(), &transfer, 0, None).await;
+			assert_eq!(result.return_value(), Err(PSP22Error::InsufficientBalance));
+
+			Ok(())
+		}
+
+		#[ink_e2e::test]
+		async fn test_deploy_child(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+			let constructor = TokenRef::new(1000, None, None, 0, None, Hash::default());
+			let token = client
+				.instantiate("psp22", &ink_e2e::alice(), constructor, 0, None)
+				.await
+				.expect("instantiate failed")
+				.account_id;
+			let bob = ink_e2e::account_id(ink_e2e::AccountKeyring::Bob);
+
+			let args = scale::Encode::encode(&(
+				500 as Balance,
+				Some(String::from("Stone")),
+				Some(String::from("STONE")),
+				6u8,
+				None::<Balance>,
+				Hash::default(),
+			));
+			let deploy = build_message::<TokenRef>(token)
+				.call(|token| token.deploy_child(vec![1], args.clone()));
+			let result = client
+				.call(&ink_e2e::bob(), deploy, 0, None)
+				.await
+				.expect("deploy_child failed");
+			let child = result.return_value().expect("deploy_child reverted");
+			assert_eq!(balance_of(&mut client, child, bob).await, 500);
+			assert_eq!(balance_of(&mut client, child, token).await, 0);
+
+			// The parent keeps no roles on the child, or anyone calling through it could use them
+			for role in Role::ALL {
+				let parent_role =
+					build_message::<TokenRef>(child).call(|child| child.has_role(role, token));
+				let parent_role = client
+					.call_dry_run(&ink_e2e::bob(), &parent_role, 0, None)
+					.await
+					.return_value();
+				assert!(!parent_role);
+				let bob_role =
+					build_message::<TokenRef>(child).call(|child| child.has_role(role, bob));
+				let bob_role =
+					client.call_dry_run(&ink_e2e::bob(), &bob_role, 0, None).await.return_value();
+				assert!(bob_role);
+			}
+			let fee = build_message::<TokenRef>(child).call(|child| child.transfer_fee());
+			let fee = client.call_dry_run(&ink_e2e::bob(), &fee, 0, None).await.return_value();
+			assert_eq!(fee, (0, bob));
+
+			let accept = build_message::<TokenRef>(child).call(|child| child.accept_ownership());
+			client
+				.call(&ink_e2e::bob(), accept, 0, None)
+				.await
+				.expect("accept_ownership failed");
+			let owner = build_message::<TokenRef>(child).call(|child| child.owner());
+			let owner = client.call_dry_run(&ink_e2e::bob(), &owner, 0, None).await.return_value();
+			assert_eq!(owner, bob);
+
+			// The address is taken by the first child
+			let deploy = build_message::<TokenRef>(token)
+				.call(|token| token.deploy_child(vec![1], args.clone()));
+			let result = client.call_dry_run(&ink_e2e::bob(), &deploy, 0, None).await;
+			assert_eq!(result.return_value(), Err(PSP22Error::CallFailed));
+
+			Ok(())
+		}
+
+		#[ink_e2e::test(additional_contracts = "../psp22_mock/Cargo.toml")]
+		async fn test_flash_borrowers_can_move_the_loan_around(
+			mut client: ink_e2e::Client<C, E>,
+		) -> E2EResult<()> {
+			let constructor = TokenRef::new(1000, None, None, 0, None, Hash::default());
+			let token = client
+				.instantiate("psp22", &ink_e2e::alice(), constructor, 0, None)
+				.await
+				.expect("instantiate failed")
+				.account_id;
+			let borrower = client
+				.instantiate("psp22_mock", &ink_e2e::alice(), Psp22MockRef::new(0), 0, None)
+				.await
+				.expect("instantiate borrower failed")
+				.account_id;
+			let alice = ink_e2e::account_id(ink_e2e::AccountKeyring::Alice);
+			let bob = ink_e2e::account_id(ink_e2e::AccountKeyring::Bob);
+
+			// The borrower gives 100 of the loan to bob and repays it with the fee out of its own
+			let fund = build_message::<TokenRef>(token)
+				.call(|token| token.transfer(borrower, 109, Vec::new()));
+			client.call(&ink_e2e::alice(), fund, 0, None).await.expect("transfer failed");
+			let reentry = Reentry {
+				target: token,
+				selector: ink::selector_bytes!("PSP22::transfer"),
+				args: scale::Encode::encode(&(bob, 100 as Balance, Vec::<u8>::new())),
+			};
+			let set_reentry = build_message::<Psp22MockRef>(borrower)
+				.call(|borrower| borrower.set_reentry(Some(reentry.clone())));
+			client
+				.call(&ink_e2e::alice(), set_reentry, 0, None)
+				.await
+				.expect("set_reentry failed");
+
+			let loan = build_message::<TokenRef>(token)
+				.call(|token| token.flash_loan(borrower, 10_000, Vec::new()));
+			client
+				.call(&ink_e2e::alice(), loan, 0, None)
+				.await
+				.expect("flash_loan failed")
+				.return_value()
+				.expect("flash_loan reverted");
+
+			// The transfer made during the loan is kept, and the loan is burned again
+			assert_eq!(balance_of(&mut client, token, bob).await, 100);
+			assert_eq!(balance_of(&mut client, token, borrower).await, 0);
+			assert_eq!(balance_of(&mut client, token, alice).await, 900);
+			let total_supply = build_message::<TokenRef>(token).call(|token| token.total_supply());
+			let total_supply = client
+				.call_dry_run(&ink_e2e::alice(), &total_supply, 0, None)
+				.await
+				.return_value();
+			assert_eq!(total_supply, 1000);
+			let holders = build_message::<TokenRef>(token).call(|token| token.holders(0, 10));
+			let mut holders =
+				client.call_dry_run(&ink_e2e::alice(), &holders, 0, None).await.return_value();
+			holders.sort();
+			let mut expected = vec![alice, bob];
+			expected.sort();
+			assert_eq!(holders, expected);
+			let holders_count =
+				build_message::<TokenRef>(token).call(|token| token.holders_count());
+			let holders_count = client
+				.call_dry_run(&ink_e2e::alice(), &holders_count, 0, None)
+				.await
+				.return_value();
+			assert_eq!(holders_count, 2);
+
+			Ok(())
+		}
 	}
 }
@@ -0,0 +1,328 @@
+#![cfg_attr(not(feature = "std"), no_std, no_main)]
+
+pub use self::psp22_mock::{Psp22Mock, Psp22MockRef, Reentry};
+
+/// A PSP22 token that misbehaves on demand, so that contracts which take tokens can be tested
+/// against tokens that fail, charge fees on transfers or call back into them. It also takes
+/// flash loans, and makes the same call back from `on_flash_loan`.
+///
+/// Anyone may mint and anyone may change the failure modes, so never deploy it for real.
+#[ink::contract]
+mod psp22_mock {
+	use ink::{
+		codegen::EmitEvent,
+		env::{
+			call::{build_call, ExecutionInput, Selector},
+			CallFlags,
+		},
+		prelude::vec::Vec,
+		reflect::ContractEventBase,
+	};
+	use psp22_traits::{
+		notify_receiver, EncodedArgs, PSP22Data, PSP22Error, PSP22Event, PSP22FlashBorrower,
+		PSP22Mintable, PSP22,
+	};
+
+	/// Fees are expressed in basis points, parts of this denominator
+	pub const FEE_DENOMINATOR: u16 = math::BPS_DENOMINATOR;
+
+	/// A call the mock makes into `target` during the next transfer, the message `selector` with
+	/// the SCALE encoded arguments `args`
+	#[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+	#[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+	pub struct Reentry {
+		pub target: AccountId,
+		pub selector: [u8; 4],
+		pub args: Vec<u8>,
+	}
+
+	type Event = <Psp22Mock as ContractEventBase>::Type;
+
+	#[ink(event)]
+	pub struct Approval {
+		#[ink(topic)]
+		owner: AccountId,
+		#[ink(topic)]
+		spender: AccountId,
+		amount: Balance,
+	}
+
+	#[ink(event)]
+	pub struct Transfer {
+		#[ink(topic)]
+		from: AccountId,
+		#[ink(topic)]
+		to: AccountId,
+		value: Balance,
+	}
+
+	#[ink(storage)]
+	pub struct Psp22Mock {
+		data: PSP22Data,
+		/// Every transfer fails while this is set. A failing call reverts, so it can't clear
+		/// itself after one failure.
+		fail_transfers: bool,
+		/// The part of every transfer that is burned on the way, in basis points
+		fee_bps: u16,
+		/// The call to make during the next transfer
+		reentry: Option<Reentry>,
+		/// Whether the last call made during a transfer went through
+		reentry_succeeded: Option<bool>,
+	}
+
+	impl Psp22Mock {
+		/// Creates a token whose whole `total_supply` belongs to the deployer
+		#[ink(constructor)]
+		pub fn new(total_supply: Balance) -> Self {
+			let (data, events) = PSP22Data::new(total_supply, Self::env().caller());
+			let mock = Self {
+				data,
+				fail_transfers: false,
+				fee_bps: 0,
+				reentry: None,
+				reentry_succeeded: None,
+			};
+			mock.emit_events(events);
+			mock
+		}
+
+		/// Makes every transfer fail with a `Custom` error until it is turned off
+		#[ink(message)]
+		pub fn set_fail_transfers(&mut self, fail: bool) {
+			self.fail_transfers = fail;
+		}
+
+		/// Burns `fee_bps` basis points of every transfer, so the recipient gets less than was
+		/// sent
+		#[ink(message)]
+		pub fn set_fee(&mut self, fee_bps: u16) -> Result<(), PSP22Error> {
+			if fee_bps > FEE_DENOMINATOR {
+				return Err(PSP22Error::InvalidFee);
+			}
+			self.fee_bps = fee_bps;
+			Ok(())
+		}
+
+		/// Makes the next transfer call `reentry.target` once the balances were updated, e.g. to
+		/// call back into the contract that moves the tokens
+		#[ink(message)]
+		pub fn set_reentry(&mut self, reentry: Option<Reentry>) {
+			self.reentry = reentry;
+		}
+
+		/// Returns whether the last call made during a transfer went through, if one was made
+		#[ink(message)]
+		pub fn reentry_succeeded(&self) -> Option<bool> {
+			self.reentry_succeeded
+		}
+
+		/// Runs the failure modes around a transfer of `value` from `from` to `to`
+		fn _transfer(
+			&mut self,
+			from: AccountId,
+			to: AccountId,
+			value: Balance,
+			mut events: Vec<PSP22Event>,
+			data: Vec<u8>,
+		) -> Result<(), PSP22Error> {
+			if self.fail_transfers {
+				return Err(PSP22Error::Custom(b"mock failure".to_vec()));
+			}
+
+			events.extend(self.data.transfer(from, to, value)?);
+			let fee = math::bps_of(value, self.fee_bps);
+			events.extend(self.data.burn(to, fee)?);
+			self.emit_events(events);
+
+			if let Some(reentry) = self.reentry.take() {
+				self.reentry_succeeded = Some(Self::reenter(reentry));
+			}
+
+			notify_receiver(self.env().caller(), from, to, value - fee, data)
+		}
+
+		/// Calls the message of the reentry target and returns whether it went through
+		fn reenter(reentry: Reentry) -> bool {
+			let result = build_call::<Environment>()
+				.call(reentry.target)
+				.call_flags(CallFlags::default().set_allow_reentry(true))
+				.exec_input(
+					ExecutionInput::new(Selector::new(reentry.selector))
+						.push_arg(EncodedArgs(reentry.args)),
+				)
+				.returns::<()>()
+				.try_invoke();
+			// The mock doesn't know what the target returns, only whether it reverted
+			!matches!(result, Err(ink::env::Error::CalleeTrapped | ink::env::Error::CalleeReverted))
+		}
+
+		psp22_traits::impl_emit_events!();
+
+		fn emit_event<EE>(emitter: EE, event: Event)
+		where
+			EE: EmitEvent<Self>,
+		{
+			emitter.emit_event(event);
+		}
+	}
+
+	impl PSP22 for Psp22Mock {
+		#[ink(message)]
+		fn total_supply(&self) -> Balance {
+			self.data.total_supply()
+		}
+
+		#[ink(message)]
+		fn balance_of(&self, owner: AccountId) -> Balance {
+			self.data.balance_of(owner)
+		}
+
+		#[ink(message)]
+		fn allowance(&self, owner: AccountId, spender: AccountId) -> Balance {
+			self.data.allowance(owner, spender)
+		}
+
+		#[ink(message)]
+		fn approve(&mut self, spender: AccountId, amount: Balance) -> Result<(), PSP22Error> {
+			let events = self.data.approve(self.env().caller(), spender, amount)?;
+			self.emit_events(events);
+			Ok(())
+		}
+
+		#[ink(message)]
+		fn increase_allowance(
+			&mut self,
+			spender: AccountId,
+			by: Balance,
+		) -> Result<(), PSP22Error> {
+			let events = self.data.increase_allowance(self.env().caller(), spender, by)?;
+			self.emit_events(events);
+			Ok(())
+		}
+
+		#[ink(message)]
+		fn decrease_allowance(
+			&mut self,
+			spender: AccountId,
+			by: Balance,
+		) -> Result<(), PSP22Error> {
+			let events = self.data.decrease_allowance(self.env().caller(), spender, by)?;
+			self.emit_events(events);
+			Ok(())
+		}
+
+		#[ink(message)]
+		fn transfer(
+			&mut self,
+			to: AccountId,
+			value: Balance,
+			data: Vec<u8>,
+		) -> Result<(), PSP22Error> {
+			self._transfer(self.env().caller(), to, value, Vec::new(), data)
+		}
+
+		#[ink(message)]
+		fn transfer_from(
+			&mut self,
+			from: AccountId,
+			to: AccountId,
+			value: Balance,
+			data: Vec<u8>,
+		) -> Result<(), PSP22Error> {
+			let caller = self.env().caller();
+			let allowance = self.data.allowance(from, caller);
+			if caller != from && allowance < value {
+				return Err(PSP22Error::InsufficientAllowance);
+			}
+			let events = match caller == from {
+				true => Vec::new(),
+				false => self.data.approve(from, caller, allowance - value)?,
+			};
+			self._transfer(from, to, value, events, data)
+		}
+	}
+
+	impl PSP22Mintable for Psp22Mock {
+		/// Creates `amount` new tokens for `account`. Anyone may mint.
+		#[ink(message)]
+		fn mint(&mut self, account: AccountId, amount: Balance) -> Result<(), PSP22Error> {
+			let events = self.data.mint(account, amount)?;
+			self.emit_events(events);
+			Ok(())
+		}
+	}
+
+	impl PSP22FlashBorrower for Psp22Mock {
+		/// Makes the call set with `set_reentry`, if any, and leaves repaying the loan to whatever
+		/// the mock already holds
+		#[ink(message)]
+		fn on_flash_loan(
+			&mut self,
+			_initiator: AccountId,
+			_amount: Balance,
+			_fee: Balance,
+			_data: Vec<u8>,
+		) -> Result<(), PSP22Error> {
+			if let Some(reentry) = self.reentry.take() {
+				self.reentry_succeeded = Some(Self::reenter(reentry));
+			}
+			Ok(())
+		}
+	}
+
+	#[cfg(test)]
+	mod tests {
+		use super::*;
+
+		fn default_accounts() -> ink::env::test::DefaultAccounts<Environment> {
+			ink::env::test::default_accounts::<Environment>()
+		}
+
+		fn set_next_caller(caller: AccountId) {
+			ink::env::test::set_caller::<Environment>(caller);
+		}
+
+		#[ink::test]
+		fn test_failing_transfers_move_nothing() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut mock = Psp22Mock::new(1000);
+
+			mock.set_fail_transfers(true);
+			assert_eq!(
+				mock.transfer(default_accounts.bob, 10, Vec::new()),
+				Err(PSP22Error::Custom(b"mock failure".to_vec()))
+			);
+			assert_eq!(mock.balance_of(default_accounts.bob), 0);
+
+			mock.set_fail_transfers(false);
+			assert_eq!(mock.transfer(default_accounts.bob, 10, Vec::new()), Ok(()));
+			assert_eq!(mock.balance_of(default_accounts.bob), 10);
+		}
+
+		#[ink::test]
+		fn test_fee_on_transfer_burns_part_of_the_amount() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut mock = Psp22Mock::new(1000);
+			assert_eq!(mock.set_fee(10_001), Err(PSP22Error::InvalidFee));
+			assert_eq!(mock.set_fee(1_000), Ok(()));
+			assert_eq!(mock.approve(default_accounts.bob, 100), Ok(()));
+
+			set_next_caller(default_accounts.bob);
+			assert_eq!(
+				mock.transfer_from(
+					default_accounts.alice,
+					default_accounts.charlie,
+					100,
+					Vec::new()
+				),
+				Ok(())
+			);
+			assert_eq!(mock.balance_of(default_accounts.alice), 900);
+			assert_eq!(mock.balance_of(default_accounts.charlie), 90);
+			assert_eq!(mock.total_supply(), 990);
+			assert_eq!(mock.allowance(default_accounts.alice, default_accounts.bob), 0);
+		}
+	}
+}
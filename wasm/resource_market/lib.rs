@@ -1,34 +1,155 @@
 #![cfg_attr(not(feature = "std"), no_std, no_main)]
 
+pub use self::resource_market::{
+	Error, MarketCall, Resource, ResourceMarket, ResourceMarketRef, Result,
+};
+
 /// Most individuals can only produce one or two of the resources, and therefore collaboration is
 /// necessary for survival. Therefore we create a free market in which participants can contribute
 /// resources when they have them. Later members can withdraw resources in proportion to their
 /// contributions. You are not required to withdraw the same resources you contributed.
 #[ink::contract]
 mod resource_market {
-	use ink::{codegen::EmitEvent, reflect::ContractEventBase, storage::Mapping};
+	use access_control::Ownable;
+	use ink::{
+		codegen::EmitEvent,
+		env::hash::{Blake2x256, HashOutput},
+		prelude::{format, vec::Vec},
+		reflect::ContractEventBase,
+		storage::Mapping,
+	};
+	use psp22_traits::{PSP22Receiver, PSP22ReceiverError, PSP22};
 
 	/// There are three resources needed to survive: Water, Food, and Wood.
+	/// The chain's native currency is traded alongside them.
 	#[derive(Debug, Copy, Clone, PartialEq, Eq, scale::Decode, scale::Encode)]
 	#[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout, scale_info::TypeInfo))]
 	pub enum Resource {
 		Food,
 		Water,
 		Wood,
+		/// The native currency, contributed by transferring it along with the call
+		Native,
+	}
+
+	impl Resource {
+		/// Every resource traded on the market
+		pub const ALL: [Resource; 4] =
+			[Resource::Food, Resource::Water, Resource::Wood, Resource::Native];
+	}
+
+	/// A credit weight change that was proposed but is still timelocked
+	#[derive(Debug, Copy, Clone, PartialEq, Eq, scale::Decode, scale::Encode)]
+	#[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout, scale_info::TypeInfo))]
+	pub struct PendingWeight {
+		/// The weight that will apply once activated
+		pub weight: u32,
+		/// The first block in which the weight can be activated
+		pub activates_at: BlockNumber,
+	}
+
+	/// Incentives for keeping the pools close to their target ratio, in percent
+	#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, scale::Decode, scale::Encode)]
+	#[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout, scale_info::TypeInfo))]
+	pub struct BalancerConfig {
+		/// Extra credits earned by contributing to a pool that is below its target
+		pub bonus: u32,
+		/// Extra credits charged for withdrawing from a pool that is below its target
+		pub surcharge: u32,
+	}
+
+	/// A locked contribution whose credits become available once it is unlocked
+	#[derive(Debug, Copy, Clone, PartialEq, Eq, scale::Decode, scale::Encode)]
+	#[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout, scale_info::TypeInfo))]
+	pub struct Position {
+		/// The account which made the contribution
+		pub owner: AccountId,
+		/// What type of resource was contributed
+		pub resource: Resource,
+		/// How much was contributed
+		pub amount: u64,
+		/// The boosted credits released on unlock
+		pub credits: u64,
+		/// The first block in which the position can be unlocked
+		pub unlock_at: BlockNumber,
+	}
+
+	/// A hypothetical market operation evaluated by `simulate`
+	#[derive(Debug, Copy, Clone, PartialEq, Eq, scale::Decode, scale::Encode)]
+	#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+	pub enum MarketCall {
+		/// See `ResourceMarket::contribute`
+		Contribute { amount: u64, resource: Resource },
+		/// See `ResourceMarket::withdraw`
+		Withdraw { amount: u64, resource: Resource },
+	}
+
+	/// The state a simulated operation would leave behind
+	#[derive(Debug, Copy, Clone, PartialEq, Eq, scale::Decode, scale::Encode)]
+	#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+	pub struct SimOutcome {
+		/// The total amount of the resource that would be available
+		pub total_resource_available: u64,
+		/// The total amount of credits the caller would have available
+		pub total_credits_available: u64,
+	}
+
+	/// A market operation signed off-chain by `signer` and relayed by someone else
+	#[derive(Debug, Copy, Clone, PartialEq, Eq, scale::Decode, scale::Encode)]
+	#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+	pub struct SignedCall {
+		/// The account the operation is made for
+		pub signer: AccountId,
+		/// Must equal the signer's current nonce, so every signature can only be used once
+		pub nonce: u64,
+		/// The operation to execute
+		pub call: MarketCall,
 	}
 
 	/// Defines the storage of your contract.
 	#[ink(storage)]
 	pub struct ResourceMarket {
-		/// The amount of food currently available on the market
-		food: u64,
-		/// The amount of water currently available on the market
-		water: u64,
-		/// The amount of wood currently available on the market
-		wood: u64,
+		/// The amount of each resource currently available on the market.
+		/// Only the pool being touched is loaded, so adding resources does not make every call
+		/// more expensive.
+		stock: Mapping<Resource, u64>,
 		/// The credit that each previous contributor has in the market.
 		/// This is the maximum amount of resources that they can withdraw.
 		credits: Mapping<AccountId, u64>,
+		/// The sum of all outstanding credits
+		total_credits: u64,
+		/// The account allowed to wind the market down, defaults to the deployer
+		ownership: Ownable,
+		/// The block in which the market was terminated, if it has been
+		terminated_at: Option<BlockNumber>,
+		/// How many credits a unit of each resource is worth, in percent.
+		/// Resources without an entry are worth `DEFAULT_WEIGHT`.
+		weights: Mapping<Resource, u32>,
+		/// Weight changes waiting for their timelock to expire
+		pending_weights: Mapping<Resource, PendingWeight>,
+		/// Bonus and surcharge applied when balancing the pools
+		balancer: BalancerConfig,
+		/// The target ratio between the pools, as parts per resource.
+		/// Resources without an entry use `Self::default_target`.
+		targets: Mapping<Resource, u32>,
+		/// Locked contributions by id
+		positions: Mapping<u64, Position>,
+		/// The ids of the positions each account holds
+		account_positions: Mapping<AccountId, Vec<u64>>,
+		/// The id the next position will get
+		next_position_id: u64,
+		/// The last block in which each account's credits changed
+		last_active: Mapping<AccountId, BlockNumber>,
+		/// Credits set aside for aid to new villagers, granted by the owner.
+		/// Fed by expired credits as well as withdrawal surcharges and rounding dust.
+		/// These credits are not part of `total_credits`.
+		community_pool: u64,
+		/// The number of signed calls executed for each account
+		nonces: Mapping<AccountId, u64>,
+		/// The PSP22 token backing each resource, if it has one
+		tokens: Mapping<Resource, AccountId>,
+		/// The resource each backing token stands for
+		token_resources: Mapping<AccountId, Resource>,
 	}
 
 	/// Errors that can occur upon calling this contract.
@@ -39,8 +160,83 @@ mod resource_market {
 		InsufficientCredits,
 		/// Insufficient resources available to complete request
 		InsufficientResources,
+		/// Caller is not the owner of the market
+		NotOwner,
+		/// The market has been terminated and only accepts payout claims
+		MarketTerminated,
+		/// The market has not been terminated yet
+		MarketNotTerminated,
+		/// Caller has no credits left to claim a payout for
+		NothingToClaim,
+		/// Credit holders still have payouts to claim
+		PayoutsOutstanding,
+		/// Native contributions must transfer exactly `amount`, other resources nothing
+		UnexpectedTransferredValue,
+		/// Paying out the native currency failed
+		NativeTransferFailed,
+		/// Credit weights must be positive
+		InvalidWeight,
+		/// There is no weight change proposed for this resource
+		NoPendingWeight,
+		/// The proposed weight change is still timelocked
+		WeightTimelocked,
+		/// Lock durations cannot exceed `MAX_LOCK`
+		LockTooLong,
+		/// There is no position with this id
+		PositionNotFound,
+		/// Only the owner of a position can unlock it
+		NotPositionOwner,
+		/// The position is still locked
+		PositionLocked,
+		/// The account was active too recently for its credits to expire
+		CreditsNotExpired,
+		/// The community pool does not hold enough credits
+		InsufficientCommunityPool,
+		/// The signature was not made by the signer of the call
+		InvalidSignature,
+		/// The signed call does not carry the signer's current nonce
+		InvalidNonce,
+		/// The native currency can't be backed by a token
+		InvalidResource,
+		/// The caller is not the token backing any resource
+		UnknownToken,
+		/// The token amount does not fit in the market's amounts
+		AmountTooLarge,
+		/// Paying out the backing token failed
+		TokenTransferFailed,
+		/// Resources backed by a token are contributed by transferring the token to the market
+		ContributeWithToken,
+		/// The pool holds stock contributed without the token, which it can't pay out in tokens
+		ResourceHasStock,
 	}
 
+	/// Number of blocks credit holders have to claim their payout after termination, after which
+	/// the owner may close the market regardless
+	pub const CLAIM_PERIOD: BlockNumber = 14_400;
+
+	/// Credit weight of a resource that never had its weight changed: one credit per unit
+	pub const DEFAULT_WEIGHT: u32 = 100;
+
+	/// Number of blocks between proposing a weight change and being able to activate it, giving
+	/// participants time to react before resources are repriced
+	pub const WEIGHT_TIMELOCK: BlockNumber = 7_200;
+
+	/// Every full period a contribution is locked for boosts its credits by `LOCK_BOOST` percent
+	pub const LOCK_PERIOD: BlockNumber = 7_200;
+
+	/// Credit boost per locked period, in percent
+	pub const LOCK_BOOST: u32 = 10;
+
+	/// The longest a contribution can be locked for, doubling its credits
+	pub const MAX_LOCK: BlockNumber = LOCK_PERIOD * 10;
+
+	/// Number of blocks without activity after which an account's credits can be expired into
+	/// the community pool
+	pub const CREDIT_EXPIRY: BlockNumber = 432_000;
+
+	/// Scale of the credit rates, which combine a weight and a balancer adjustment (both percent)
+	const RATE_PRECISION: u128 = DEFAULT_WEIGHT as u128 * 100;
+
 	/// Type alias for the contract's `Result` type.
 	pub type Result<T> = core::result::Result<T, Error>;
 
@@ -78,34 +274,140 @@ mod resource_market {
 		total_credits_available: u64,
 	}
 
+	/// Emitted when the owner terminates the market
+	#[ink(event)]
+	pub struct MarketTerminated {
+		/// The block in which the market was terminated
+		block_number: BlockNumber,
+		/// The total amount of credits that can be claimed against the remaining resources
+		outstanding_credits: u64,
+	}
+
+	/// Emitted when the owner proposes a new credit weight
+	#[ink(event)]
+	pub struct WeightProposed {
+		/// The resource being repriced
+		#[ink(topic)]
+		resource: Resource,
+		/// The proposed weight, in percent
+		weight: u32,
+		/// The first block in which the weight can be activated
+		activates_at: BlockNumber,
+	}
+
+	/// Emitted when a proposed credit weight takes effect
+	#[ink(event)]
+	pub struct WeightActivated {
+		/// The resource that was repriced
+		#[ink(topic)]
+		resource: Resource,
+		/// The weight now in effect, in percent
+		weight: u32,
+	}
+
+	/// Emitted when a locked contribution is made
+	#[ink(event)]
+	pub struct PositionOpened {
+		/// The account which owns the position
+		#[ink(topic)]
+		owner: AccountId,
+		/// The id of the new position
+		position_id: u64,
+		/// The boosted credits released on unlock
+		credits: u64,
+		/// The first block in which the position can be unlocked
+		unlock_at: BlockNumber,
+	}
+
+	/// Emitted when a position is unlocked and its credits released
+	#[ink(event)]
+	pub struct PositionUnlocked {
+		/// The account which owned the position
+		#[ink(topic)]
+		owner: AccountId,
+		/// The id of the unlocked position
+		position_id: u64,
+		/// The total amount of credits the account now has available
+		total_credits_available: u64,
+	}
+
+	/// Emitted when an inactive account's credits expire into the community pool
+	#[ink(event)]
+	pub struct CreditsExpired {
+		/// The inactive account
+		#[ink(topic)]
+		account: AccountId,
+		/// How many credits expired
+		amount: u64,
+	}
+
+	/// Emitted when credits are granted from the community pool
+	#[ink(event)]
+	pub struct CommunityGrant {
+		/// The account receiving the grant
+		#[ink(topic)]
+		to: AccountId,
+		/// How many credits were granted
+		amount: u64,
+		/// The credits left in the community pool
+		community_pool: u64,
+	}
+
 	impl ResourceMarket {
 		/// Constructor that initializes the resources values and creates a default mapping
 		#[ink(constructor)]
 		pub fn new(food: u64, water: u64, wood: u64) -> Self {
-			ResourceMarket { food, water, wood, credits: Default::default() }
+			let mut stock = Mapping::new();
+			stock.insert(Resource::Food, &food);
+			stock.insert(Resource::Water, &water);
+			stock.insert(Resource::Wood, &wood);
+
+			ResourceMarket {
+				stock,
+				credits: Default::default(),
+				total_credits: 0,
+				ownership: Ownable::new(Self::env().caller()),
+				terminated_at: None,
+				weights: Mapping::new(),
+				pending_weights: Mapping::new(),
+				balancer: BalancerConfig::default(),
+				targets: Mapping::new(),
+				positions: Mapping::new(),
+				account_positions: Mapping::new(),
+				next_position_id: 0,
+				last_active: Mapping::new(),
+				community_pool: 0,
+				nonces: Mapping::new(),
+				tokens: Mapping::new(),
+				token_resources: Mapping::new(),
+			}
 		}
 
 		/// Contribute some of your own private resources to the market.
 		/// Contributions are made one asset at a time.
-		#[ink(message)]
+		/// The caller is credited according to the current weight of the resource, plus the
+		/// balancer bonus if the pool is below its target.
+		///
+		/// Native contributions must transfer exactly `amount` along with the call. Resources
+		/// backed by a token are contributed by transferring the token to the market instead.
+		#[ink(message, payable)]
 		pub fn contribute(&mut self, amount: u64, resource: Resource) -> Result<()> {
-			let caller = self.env().caller();
-			match resource {
-				Resource::Food => self.food += amount,
-				Resource::Water => self.water += amount,
-				Resource::Wood => self.wood += amount,
-			}
-
-			let mut old_balance = self.credits.get(caller).unwrap_or(0);
-			self.credits.insert(caller, &(old_balance.saturating_add(amount)));
+			self.contribute_as(self.env().caller(), amount, resource, false)
+		}
 
-			let total_resources = match resource {
-				Resource::Food => self.food,
-				Resource::Water => self.water,
-				Resource::Wood => self.wood,
-			};
+		fn contribute_as(
+			&mut self,
+			caller: AccountId,
+			amount: u64,
+			resource: Resource,
+			in_tokens: bool,
+		) -> Result<()> {
+			let (credited, total_resources) = self.receive(amount, resource, in_tokens)?;
 
-			let sender_available_credits = old_balance.saturating_add(amount);
+			let sender_available_credits =
+				self.credits.get(caller).unwrap_or(0).saturating_add(credited);
+			self.set_credits(caller, sender_available_credits);
+			self.total_credits = self.total_credits.saturating_add(credited);
 
 			Self::emit_event(
 				self.env(),
@@ -120,251 +422,1775 @@ mod resource_market {
 			Ok(())
 		}
 
-		/// Withdraw some resources from the market into your own private reserves.
-		#[ink(message)]
-		pub fn withdraw(&mut self, amount: u64, resource: Resource) -> Result<()> {
+		/// Contribute resources and lock the credits earned for `lock` blocks.
+		///
+		/// Every full `LOCK_PERIOD` locked boosts the credits by `LOCK_BOOST` percent. The credits
+		/// are kept in a position and become available once `unlock` is called after the lock
+		/// expired. Native contributions must transfer exactly `amount` along with the call,
+		/// resources backed by a token can't be locked.
+		#[ink(message, payable)]
+		pub fn contribute_locked(
+			&mut self,
+			amount: u64,
+			resource: Resource,
+			lock: BlockNumber,
+		) -> Result<u64> {
+			if lock > MAX_LOCK {
+				return Err(Error::LockTooLong);
+			}
+
 			let caller = self.env().caller();
+			let (credited, total_resources) = self.receive(amount, resource, false)?;
 
-			match resource {
-				Resource::Food => {
-					if self.food < amount {
-						return Err(Error::InsufficientResources);
-					}
-
-					let caller_credits = self.credits.get(caller).unwrap_or(0);
-					if caller_credits < amount {
-						return Err(Error::InsufficientCredits);
-					}
-
-					self.food = self.food.saturating_sub(amount);
-					self.credits.insert(caller, &(caller_credits.saturating_sub(amount)));
-
-					Self::emit_event(
-						self.env(),
-						Event::ResourceWithdrawn(ResourceWithdrawn {
-							sender: caller,
-							amount,
-							resource,
-							total_resource_available: self.food - amount,
-							total_credits_available: caller_credits - amount,
-						}),
-					);
-				},
-				Resource::Water => {
-					if self.water < amount {
-						return Err(Error::InsufficientResources);
-					}
-
-					let caller_credits = self.credits.get(caller).unwrap_or(0);
-					if caller_credits < amount {
-						return Err(Error::InsufficientCredits);
-					}
-
-					self.water = self.water.saturating_sub(amount);
-					self.credits.insert(caller, &(caller_credits.saturating_sub(amount)));
-
-					Self::emit_event(
-						self.env(),
-						Event::ResourceWithdrawn(ResourceWithdrawn {
-							sender: caller,
-							amount,
-							resource,
-							total_resource_available: self.water - amount,
-							total_credits_available: caller_credits - amount,
-						}),
-					);
-				},
-				Resource::Wood => {
-					if self.wood < amount {
-						return Err(Error::InsufficientResources);
-					}
-
-					let caller_credits = self.credits.get(caller).unwrap_or(0);
-					if caller_credits < amount {
-						return Err(Error::InsufficientCredits);
-					}
-
-					self.wood = self.wood.saturating_sub(amount);
-					self.credits.insert(caller, &(caller_credits.saturating_sub(amount)));
-
-					Self::emit_event(
-						self.env(),
-						Event::ResourceWithdrawn(ResourceWithdrawn {
-							sender: caller,
-							amount,
-							resource,
-							total_resource_available: self.wood - amount,
-							total_credits_available: caller_credits - amount,
-						}),
-					);
-				},
+			let boost = u128::from(lock / LOCK_PERIOD) * u128::from(LOCK_BOOST);
+			let credits =
+				u64::try_from(u128::from(credited) * (100 + boost) / 100).unwrap_or(u64::MAX);
+			self.total_credits = self.total_credits.saturating_add(credits);
+
+			let position_id = self.next_position_id;
+			self.next_position_id += 1;
+			let unlock_at = self.env().block_number().saturating_add(lock);
+			self.positions.insert(
+				position_id,
+				&Position { owner: caller, resource, amount, credits, unlock_at },
+			);
+			let mut ids = self.account_positions.get(caller).unwrap_or_default();
+			ids.push(position_id);
+			self.account_positions.insert(caller, &ids);
+
+			Self::emit_event(
+				self.env(),
+				Event::ContributionReceived(ContributionReceived {
+					sender: caller,
+					amount,
+					resource,
+					total_resource_available: total_resources,
+					total_credits_available: self.credits.get(caller).unwrap_or(0),
+				}),
+			);
+			Self::emit_event(
+				self.env(),
+				Event::PositionOpened(PositionOpened {
+					owner: caller,
+					position_id,
+					credits,
+					unlock_at,
+				}),
+			);
+
+			Ok(position_id)
+		}
+
+		/// Release the credits of an expired position to its owner.
+		/// Once the market is terminated positions can be unlocked early to claim a payout.
+		#[ink(message)]
+		pub fn unlock(&mut self, position_id: u64) -> Result<()> {
+			let caller = self.env().caller();
+			let position = self.positions.get(position_id).ok_or(Error::PositionNotFound)?;
+			if position.owner != caller {
+				return Err(Error::NotPositionOwner);
+			}
+			if self.terminated_at.is_none() && self.env().block_number() < position.unlock_at {
+				return Err(Error::PositionLocked);
 			}
 
+			self.positions.remove(position_id);
+			let mut ids = self.account_positions.get(caller).unwrap_or_default();
+			ids.retain(|id| *id != position_id);
+			self.account_positions.insert(caller, &ids);
+
+			let total_credits_available =
+				self.credits.get(caller).unwrap_or(0).saturating_add(position.credits);
+			self.set_credits(caller, total_credits_available);
+
+			Self::emit_event(
+				self.env(),
+				Event::PositionUnlocked(PositionUnlocked {
+					owner: caller,
+					position_id,
+					total_credits_available,
+				}),
+			);
+
 			Ok(())
 		}
 
-		/// Get the amount of resource available
+		/// All positions held by `account`, with their ids
 		#[ink(message)]
-		pub fn get_resource(&self, resource: Resource) -> Result<u64> {
-			match resource {
-				Resource::Food => Ok(self.food),
-				Resource::Water => Ok(self.water),
-				Resource::Wood => Ok(self.wood),
-			}
+		pub fn positions(&self, account: AccountId) -> Vec<(u64, Position)> {
+			self.account_positions
+				.get(account)
+				.unwrap_or_default()
+				.into_iter()
+				.filter_map(|id| self.positions.get(id).map(|position| (id, position)))
+				.collect()
 		}
 
-		fn emit_event<EE>(emitter: EE, event: Event)
-		where
-			EE: EmitEvent<Self>,
-		{
-			emitter.emit_event(event);
+		/// Withdraw some resources from the market into your own private reserves.
+		/// This costs credits according to the current weight of the resource, plus the balancer
+		/// surcharge if the pool is below its target.
+		///
+		/// Native withdrawals are transferred to the caller.
+		#[ink(message)]
+		pub fn withdraw(&mut self, amount: u64, resource: Resource) -> Result<()> {
+			self.withdraw_as(self.env().caller(), amount, resource)
 		}
-	}
 
-	// Enhancement: The first iteration of this contract allow users to contribute
-	// by simply calling a function with an integer parameter. Presumably there is
-	// a security guard somewhere near the real-world marketplace confirming the deposits
-	// are actually made. But there are no on-chain assets underlying the resource market.
-	// Modify the code to interface with three real ERC20 tokens called: Water, Wood, and Food.
+		fn withdraw_as(
+			&mut self,
+			caller: AccountId,
+			amount: u64,
+			resource: Resource,
+		) -> Result<()> {
+			self.ensure_active()?;
 
-	// Enhancement: The resource trading logic in this contract is useful for way more
-	// scenarios than our simple wood, food, water trade. Generalize the contract to
-	// work with up to 5 arbitrary ERC20 tokens.
+			let available = self.stock_of(resource);
+			if available < amount {
+				return Err(Error::InsufficientResources);
+			}
 
-	// Enhancement: If we are trading real food, wood, and water, we have real-world incentives
-	// to deposit ou excess resources. Storage is hard IRL. Water evaporates, food spoils, and wood
-	// rots. And all the resources are subject to robbery. But if we are talking about virtual
-	// assets, there are no such risks. And depositing funds into the market comes with an
-	// opportunity cost. Design a reward system where there is a small fee on every withdrawal, and
-	// that fee is paid to liquidity providers.
+			let cost = Self::cost_of(amount, self.withdrawal_rate(resource, &|r| self.stock_of(r)));
+			let caller_credits = self.credits.get(caller).unwrap_or(0);
+			if caller_credits < cost {
+				return Err(Error::InsufficientCredits);
+			}
+			// surcharges and rounding go to the community pool
+			let fair_cost = Self::credits_for(amount, u128::from(self.weight_of(resource)) * 100);
 
-	#[cfg(test)]
-	mod tests {
-		use super::*;
+			let total_resources = available - amount;
+			let remaining_credits = caller_credits - cost;
+			self.stock.insert(resource, &total_resources);
+			self.set_credits(caller, remaining_credits);
+			self.total_credits -= cost;
+			self.community_pool = self.community_pool.saturating_add(cost - fair_cost);
 
-		use ink::env::test::recorded_events;
-		type Event = <ResourceMarket as ::ink::reflect::ContractEventBase>::Type;
+			Self::emit_event(
+				self.env(),
+				Event::ResourceWithdrawn(ResourceWithdrawn {
+					sender: caller,
+					amount,
+					resource,
+					total_resource_available: total_resources,
+					total_credits_available: remaining_credits,
+				}),
+			);
 
-		fn default_accounts() -> ink::env::test::DefaultAccounts<ink::env::DefaultEnvironment> {
-			ink::env::test::default_accounts::<Environment>()
+			self.pay_out(caller, amount, resource)
 		}
 
-		fn set_next_caller(caller: AccountId) {
-			ink::env::test::set_caller::<Environment>(caller);
+		/// Get the credits `account` has available
+		#[ink(message)]
+		pub fn credits_of(&self, account: AccountId) -> u64 {
+			self.credits.get(account).unwrap_or(0)
 		}
 
-		fn set_next_caller_with_credits(
-			caller: AccountId,
-			credits: u64,
-			market: &mut ResourceMarket,
-		) {
-			ink::env::test::set_caller::<Environment>(caller);
-			market.credits.insert(caller, &credits);
+		/// Get the amount of resource available
+		#[ink(message)]
+		pub fn get_resource(&self, resource: Resource) -> Result<u64> {
+			Ok(self.stock_of(resource))
 		}
 
-		/// Testing the constructor
-		#[ink::test]
-		fn test_constructor_works() {
-			let resource_market = ResourceMarket::new(10, 20, 30);
-			assert_eq!(resource_market.get_resource(Resource::Food), Ok(10));
-			assert_eq!(resource_market.get_resource(Resource::Water), Ok(20));
-			assert_eq!(resource_market.get_resource(Resource::Wood), Ok(30));
+		/// The largest amount of `resource` that `account` could withdraw right now.
+		///
+		/// Takes into account everything `withdraw` checks, so wallets can pre-validate a
+		/// withdrawal with a single query.
+		#[ink(message)]
+		pub fn max_withdrawable(&self, account: AccountId, resource: Resource) -> u64 {
+			if self.terminated_at.is_some() {
+				return 0;
+			}
+			let credits = u128::from(self.credits.get(account).unwrap_or(0));
+			let rate = self.withdrawal_rate(resource, &|r| self.stock_of(r));
+			let affordable = credits * RATE_PRECISION / rate;
+			u64::try_from(affordable).unwrap_or(u64::MAX).min(self.stock_of(resource))
 		}
 
-		#[ink::test]
-		fn test_contributing_works() {
-			let default_accounts = default_accounts();
-			set_next_caller(default_accounts.alice);
-
-			let mut resource_market = ResourceMarket::new(0, 0, 0);
-			let result = resource_market.contribute(10, Resource::Water);
+		/// Evaluate a batch of operations as if the caller made them one after the other, without
+		/// changing any state.
+		///
+		/// Each operation sees the effects of the successful ones before it, a failing operation
+		/// is skipped just like a reverted call would be. Native contributions are assumed to
+		/// transfer the right value.
+		#[ink(message)]
+		pub fn simulate(&self, calls: Vec<MarketCall>) -> Vec<Result<SimOutcome>> {
+			let mut stock = Resource::ALL.map(|resource| self.stock_of(resource));
+			let mut credits = self.credits.get(self.env().caller()).unwrap_or(0);
 
-			assert_eq!(result, Ok(()));
-			assert_eq!(resource_market.get_resource(Resource::Water), Ok(10));
-			assert_eq!(resource_market.credits.get(default_accounts.alice), Some(10));
-		}
+			calls
+				.into_iter()
+				.map(|call| {
+					self.ensure_active()?;
+					let lookup = |resource: Resource| stock[resource as usize];
 
-		#[ink::test]
-		fn test_withdrawing_works() {
-			let default_accounts = default_accounts();
+					let resource = match call {
+						MarketCall::Contribute { amount, resource } => {
+							if self.tokens.contains(resource) {
+								return Err(Error::ContributeWithToken);
+							}
+							let rate = self.contribution_rate(resource, &lookup);
+							credits = credits.saturating_add(Self::credits_for(amount, rate));
+							stock[resource as usize] =
+								stock[resource as usize].saturating_add(amount);
+							resource
+						},
+						MarketCall::Withdraw { amount, resource } => {
+							if stock[resource as usize] < amount {
+								return Err(Error::InsufficientResources);
+							}
+							let cost =
+								Self::cost_of(amount, self.withdrawal_rate(resource, &lookup));
+							if credits < cost {
+								return Err(Error::InsufficientCredits);
+							}
+							credits -= cost;
+							stock[resource as usize] -= amount;
+							resource
+						},
+					};
 
-			let mut resource_market = ResourceMarket::new(100, 100, 100);
-			set_next_caller_with_credits(default_accounts.bob, 100, &mut resource_market);
+					Ok(SimOutcome {
+						total_resource_available: stock[resource as usize],
+						total_credits_available: credits,
+					})
+				})
+				.collect()
+		}
 
-			let result = resource_market.withdraw(50, Resource::Water);
-			assert_eq!(result, Ok(()));
-			assert_eq!(resource_market.get_resource(Resource::Water), Ok(50));
-			assert_eq!(resource_market.credits.get(default_accounts.bob), Some(50)); // contributed 100 water, took 50 water
+		/// The nonce the next signed call of `account` must carry
+		#[ink(message)]
+		pub fn nonce_of(&self, account: AccountId) -> u64 {
+			self.nonces.get(account).unwrap_or(0)
 		}
 
-		#[ink::test]
-		fn test_withdrawing_more_than_contributed_fails() {
-			let default_accounts = default_accounts();
-			set_next_caller(default_accounts.alice);
+		/// The hash a user signs to authorize `payload`.
+		/// It commits to this market's address, so signatures cannot be replayed on other markets.
+		#[ink(message)]
+		pub fn signed_call_hash(&self, payload: SignedCall) -> [u8; 32] {
+			let mut hash = <Blake2x256 as HashOutput>::Type::default();
+			ink::env::hash_encoded::<Blake2x256, _>(&(self.env().account_id(), payload), &mut hash);
+			hash
+		}
 
-			let mut resource_market = ResourceMarket::new(50, 50, 50);
-			let contribute_result = resource_market.contribute(10, Resource::Food);
+		/// Execute a contribution or withdrawal on behalf of a user who signed it off-chain, so
+		/// that someone else can pay the fees.
+		///
+		/// `signature` is a recoverable ECDSA signature of `signed_call_hash(payload)` by the key
+		/// behind `payload.signer`. Native contributions must be paid for by the relayer, native
+		/// withdrawals are transferred to the signer.
+		#[ink(message, payable)]
+		pub fn execute_signed(&mut self, payload: SignedCall, signature: [u8; 65]) -> Result<()> {
+			let mut public_key = [0; 33];
+			ink::env::ecdsa_recover(&signature, &self.signed_call_hash(payload), &mut public_key)
+				.map_err(|_| Error::InvalidSignature)?;
+			let mut signer = <Blake2x256 as HashOutput>::Type::default();
+			ink::env::hash_bytes::<Blake2x256>(&public_key, &mut signer);
+			if AccountId::from(signer) != payload.signer {
+				return Err(Error::InvalidSignature);
+			}
 
-			let last_event = recorded_events().last().unwrap();
-			let decoded_event = <Event as scale::Decode>::decode(&mut &last_event.data[..])
-				.expect("Failed to decode event");
+			let nonce = self.nonce_of(payload.signer);
+			if payload.nonce != nonce {
+				return Err(Error::InvalidNonce);
+			}
+			self.nonces.insert(payload.signer, &(nonce + 1));
 
-			let Event::ContributionReceived(ContributionReceived {
-				sender,
-				amount,
-				resource,
-				total_credits_available,
-				total_resource_available,
-			}) = decoded_event
-			else {
-				panic!("AddressAdded event should be emitted")
-			};
+			match payload.call {
+				MarketCall::Contribute { amount, resource } => {
+					self.contribute_as(payload.signer, amount, resource, false)
+				},
+				MarketCall::Withdraw { amount, resource } => {
+					self.withdraw_as(payload.signer, amount, resource)
+				},
+			}
+		}
 
-			assert_eq!(sender, default_accounts.alice);
-			assert_eq!(amount, 10);
-			assert_eq!(resource, Resource::Food);
-			assert_eq!(total_credits_available, 10);
-			assert_eq!(total_resource_available, 60);
+		/// The credit weight currently in effect for `resource`, in percent
+		#[ink(message)]
+		pub fn weight(&self, resource: Resource) -> u32 {
+			self.weight_of(resource)
+		}
 
-			let withdraw_result = resource_market.withdraw(15, Resource::Water);
+		/// The weight change proposed for `resource`, if any
+		#[ink(message)]
+		pub fn pending_weight(&self, resource: Resource) -> Option<PendingWeight> {
+			self.pending_weights.get(resource)
+		}
 
-			assert_eq!(contribute_result, Ok(()));
-			assert_eq!(withdraw_result, Err(Error::InsufficientCredits));
-			assert_eq!(resource_market.get_resource(Resource::Food), Ok(60));
-			assert_eq!(resource_market.get_resource(Resource::Water), Ok(50));
+		/// The bonus and surcharge currently applied when balancing the pools
+		#[ink(message)]
+		pub fn balancer(&self) -> BalancerConfig {
+			self.balancer
 		}
 
-		#[ink::test]
-		fn test_withdrawing_resources_not_available_fails() {
-			let default_accounts = default_accounts();
-			set_next_caller(default_accounts.bob);
+		/// The number of parts `resource` should make up of the balanced pools
+		#[ink(message)]
+		pub fn target(&self, resource: Resource) -> u32 {
+			self.target_of(resource)
+		}
 
-			let mut resource_market = ResourceMarket::new(0, 0, 0);
-			let result = resource_market.withdraw(50, Resource::Water);
-			assert_eq!(result, Err(Error::InsufficientResources));
+		/// Configure the balancer bonus and surcharge, in percent. Setting both to zero turns
+		/// the balancer off.
+		#[ink(message)]
+		pub fn set_balancer(&mut self, bonus: u32, surcharge: u32) -> Result<()> {
+			self.ensure_owner()?;
+			self.balancer = BalancerConfig { bonus, surcharge };
+			Ok(())
 		}
 
-		#[ink::test]
-		fn test_withdrawing_resources_contributed_by_someone_else() {
-			let default_accounts = default_accounts();
-			set_next_caller(default_accounts.bob);
+		/// Set how many parts `resource` should make up of the balanced pools.
+		/// A resource with zero parts is left out of balancing altogether.
+		#[ink(message)]
+		pub fn set_target(&mut self, resource: Resource, parts: u32) -> Result<()> {
+			self.ensure_owner()?;
+			self.targets.insert(resource, &parts);
+			Ok(())
+		}
 
-			let mut resource_market = ResourceMarket::new(0, 0, 0);
-			resource_market.contribute(100, Resource::Food);
-			resource_market.contribute(50, Resource::Water);
-			resource_market.contribute(150, Resource::Wood);
+		/// Back `resource` with the PSP22 `token`. Transfers of the token to the market count as
+		/// contributions of the resource by the sender, and withdrawals of the resource are paid
+		/// out in the token. Replaces the previous token of the resource.
+		///
+		/// Only possible while the pool is empty, as its stock was either contributed in person or
+		/// in the previous token, and the market can't pay it out in the new one.
+		#[ink(message)]
+		pub fn set_resource_token(&mut self, resource: Resource, token: AccountId) -> Result<()> {
+			self.ensure_owner()?;
+			if resource == Resource::Native || self.token_resources.contains(token) {
+				return Err(Error::InvalidResource);
+			}
+			if self.stock_of(resource) > 0 {
+				return Err(Error::ResourceHasStock);
+			}
+
+			if let Some(previous) = self.tokens.get(resource) {
+				self.token_resources.remove(previous);
+			}
+			self.tokens.insert(resource, &token);
+			self.token_resources.insert(token, &resource);
+			Ok(())
+		}
+
+		/// Get the PSP22 token backing `resource`, if it has one
+		#[ink(message)]
+		pub fn resource_token(&self, resource: Resource) -> Option<AccountId> {
+			self.tokens.get(resource)
+		}
+
+		/// Propose a new credit weight for `resource`.
+		///
+		/// The weight only takes effect once `activate_weight` is called, at least
+		/// `WEIGHT_TIMELOCK` blocks later. Proposing again replaces the pending proposal and
+		/// restarts the timelock.
+		#[ink(message)]
+		pub fn propose_weight(&mut self, resource: Resource, weight: u32) -> Result<()> {
+			self.ensure_owner()?;
+			self.ensure_active()?;
+			if weight == 0 {
+				return Err(Error::InvalidWeight);
+			}
+
+			let activates_at = self.env().block_number().saturating_add(WEIGHT_TIMELOCK);
+			self.pending_weights.insert(resource, &PendingWeight { weight, activates_at });
+
+			Self::emit_event(
+				self.env(),
+				Event::WeightProposed(WeightProposed { resource, weight, activates_at }),
+			);
+
+			Ok(())
+		}
+
+		/// Apply a proposed weight once its timelock has expired. Anyone may call this.
+		#[ink(message)]
+		pub fn activate_weight(&mut self, resource: Resource) -> Result<()> {
+			let pending = self.pending_weights.get(resource).ok_or(Error::NoPendingWeight)?;
+			if self.env().block_number() < pending.activates_at {
+				return Err(Error::WeightTimelocked);
+			}
+
+			self.pending_weights.remove(resource);
+			self.weights.insert(resource, &pending.weight);
+
+			Self::emit_event(
+				self.env(),
+				Event::WeightActivated(WeightActivated { resource, weight: pending.weight }),
+			);
+
+			Ok(())
+		}
+
+		/// Credits currently available for community grants
+		#[ink(message)]
+		pub fn community_pool(&self) -> u64 {
+			self.community_pool
+		}
+
+		/// Move the credits of an account that has been inactive for `CREDIT_EXPIRY` blocks into
+		/// the community pool. Anyone may call this.
+		#[ink(message)]
+		pub fn expire_credits(&mut self, account: AccountId) -> Result<()> {
+			self.ensure_active()?;
+			let last_active = self.last_active.get(account).unwrap_or(0);
+			if self.env().block_number() < last_active.saturating_add(CREDIT_EXPIRY) {
+				return Err(Error::CreditsNotExpired);
+			}
+
+			let amount = self.credits.get(account).unwrap_or(0);
+			if amount == 0 {
+				return Err(Error::NothingToClaim);
+			}
+
+			self.credits.remove(account);
+			self.last_active.remove(account);
+			self.total_credits -= amount;
+			self.community_pool = self.community_pool.saturating_add(amount);
+
+			Self::emit_event(self.env(), Event::CreditsExpired(CreditsExpired { account, amount }));
+
+			Ok(())
+		}
+
+		/// Grant credits from the community pool to `to`, e.g. to help a new villager get started
+		#[ink(message)]
+		pub fn grant(&mut self, to: AccountId, amount: u64) -> Result<()> {
+			self.ensure_owner()?;
+			self.ensure_active()?;
+			if self.community_pool < amount {
+				return Err(Error::InsufficientCommunityPool);
+			}
+
+			self.community_pool -= amount;
+			self.total_credits = self.total_credits.saturating_add(amount);
+			let credits = self.credits.get(to).unwrap_or(0).saturating_add(amount);
+			self.set_credits(to, credits);
+
+			Self::emit_event(
+				self.env(),
+				Event::CommunityGrant(CommunityGrant {
+					to,
+					amount,
+					community_pool: self.community_pool,
+				}),
+			);
+
+			Ok(())
+		}
+
+		/// Wind the market down.
+		///
+		/// No more contributions or withdrawals are accepted afterwards. Instead every credit
+		/// holder can claim their pro-rata share of all remaining resources with `claim_payout`.
+		/// Once every payout has been claimed, or `CLAIM_PERIOD` blocks have passed, the owner can
+		/// remove the contract with `close`.
+		#[ink(message)]
+		pub fn terminate(&mut self) -> Result<()> {
+			self.ensure_owner()?;
+			self.ensure_active()?;
+
+			let block_number = self.env().block_number();
+			self.terminated_at = Some(block_number);
+
+			Self::emit_event(
+				self.env(),
+				Event::MarketTerminated(MarketTerminated {
+					block_number,
+					outstanding_credits: self.total_credits,
+				}),
+			);
+
+			Ok(())
+		}
+
+		/// Claim the caller's share of every resource left in a terminated market.
+		///
+		/// Each resource is paid out in proportion of the caller's credits to all outstanding
+		/// credits. Because both shrink together, the order in which holders claim does not change
+		/// their share. Rounding dust stays in the market.
+		#[ink(message)]
+		pub fn claim_payout(&mut self) -> Result<()> {
+			if self.terminated_at.is_none() {
+				return Err(Error::MarketNotTerminated);
+			}
+
+			let caller = self.env().caller();
+			let caller_credits = self.credits.get(caller).unwrap_or(0);
+			if caller_credits == 0 {
+				return Err(Error::NothingToClaim);
+			}
+
+			let total_credits = self.total_credits;
+			self.credits.remove(caller);
+			self.total_credits -= caller_credits;
+
+			for resource in Resource::ALL {
+				let available = self.stock_of(resource);
+				let amount = Self::pro_rata(available, caller_credits, total_credits);
+				if amount == 0 {
+					continue;
+				}
+
+				let total_resources = available - amount;
+				self.stock.insert(resource, &total_resources);
+				self.pay_out(caller, amount, resource)?;
+
+				Self::emit_event(
+					self.env(),
+					Event::ResourceWithdrawn(ResourceWithdrawn {
+						sender: caller,
+						amount,
+						resource,
+						total_resource_available: total_resources,
+						total_credits_available: 0,
+					}),
+				);
+			}
+
+			Ok(())
+		}
+
+		/// Remove a terminated market once all payouts were claimed or the claim period is over.
+		/// Whatever the market still holds goes to the owner, the backing tokens as well as the
+		/// native currency.
+		#[ink(message)]
+		pub fn close(&mut self) -> Result<()> {
+			self.ensure_owner()?;
+			let terminated_at = self.terminated_at.ok_or(Error::MarketNotTerminated)?;
+
+			let claim_period_over =
+				self.env().block_number() >= terminated_at.saturating_add(CLAIM_PERIOD);
+			if self.total_credits > 0 && !claim_period_over {
+				return Err(Error::PayoutsOutstanding);
+			}
+
+			// Terminating only moves the native currency, the tokens would be left behind
+			let market = self.env().account_id();
+			let owner = self.ownership.owner();
+			for token in Resource::ALL.into_iter().filter_map(|resource| self.tokens.get(resource))
+			{
+				let mut psp22: ink::contract_ref!(PSP22) = token.into();
+				let balance = psp22.balance_of(market);
+				if balance > 0 {
+					psp22
+						.transfer(owner, balance, Vec::new())
+						.map_err(|_| Error::TokenTransferFailed)?;
+				}
+			}
+
+			self.env().terminate_contract(owner)
+		}
+
+		/// Returns true once the market has been terminated
+		#[ink(message)]
+		pub fn is_terminated(&self) -> bool {
+			self.terminated_at.is_some()
+		}
+
+		fn ensure_owner(&self) -> Result<()> {
+			self.ownership.ensure_owner(self.env().caller()).map_err(|_| Error::NotOwner)
+		}
+
+		fn ensure_active(&self) -> Result<()> {
+			if self.terminated_at.is_some() {
+				return Err(Error::MarketTerminated);
+			}
+			Ok(())
+		}
+
+		/// Takes a contribution into the pool, returning the credits it earned and the new stock.
+		/// Native contributions must transfer exactly `amount` along with the call, and resources
+		/// backed by a token are only taken `in_tokens`, when the token reported the transfer.
+		fn receive(
+			&mut self,
+			amount: u64,
+			resource: Resource,
+			in_tokens: bool,
+		) -> Result<(u64, u64)> {
+			self.ensure_active()?;
+			if !in_tokens && self.tokens.contains(resource) {
+				return Err(Error::ContributeWithToken);
+			}
+
+			let expected_value = match resource {
+				Resource::Native => Balance::from(amount),
+				_ => 0,
+			};
+			if self.env().transferred_value() != expected_value {
+				return Err(Error::UnexpectedTransferredValue);
+			}
+
+			let credited =
+				Self::credits_for(amount, self.contribution_rate(resource, &|r| self.stock_of(r)));
+			let total_resources = self.stock_of(resource).saturating_add(amount);
+			self.stock.insert(resource, &total_resources);
+
+			Ok((credited, total_resources))
+		}
+
+		/// Updates an account's credits and marks it as active
+		fn set_credits(&mut self, account: AccountId, credits: u64) {
+			self.credits.insert(account, &credits);
+			self.last_active.insert(account, &self.env().block_number());
+		}
+
+		fn weight_of(&self, resource: Resource) -> u32 {
+			self.weights.get(resource).unwrap_or(DEFAULT_WEIGHT)
+		}
+
+		/// The three goods are balanced 1:1:1, the native currency is left out by default
+		fn default_target(resource: Resource) -> u32 {
+			match resource {
+				Resource::Native => 0,
+				_ => 1,
+			}
+		}
+
+		fn target_of(&self, resource: Resource) -> u32 {
+			self.targets.get(resource).unwrap_or_else(|| Self::default_target(resource))
+		}
+
+		/// Whether the pool of `resource` holds less than its target share of all balanced
+		/// pools. `stock` looks up the pool of a resource, so that hypothetical pools can be
+		/// evaluated too.
+		fn is_below_target(&self, resource: Resource, stock: &impl Fn(Resource) -> u64) -> bool {
+			let parts = self.target_of(resource);
+			if parts == 0 {
+				return false;
+			}
+
+			let (mut total_stock, mut total_parts) = (0u128, 0u128);
+			for other in Resource::ALL {
+				let other_parts = self.target_of(other);
+				if other_parts > 0 {
+					total_stock += u128::from(stock(other));
+					total_parts += u128::from(other_parts);
+				}
+			}
+
+			u128::from(stock(resource)) * total_parts < total_stock * u128::from(parts)
+		}
+
+		/// Credits per unit contributed, scaled by `RATE_PRECISION`
+		fn contribution_rate(&self, resource: Resource, stock: &impl Fn(Resource) -> u64) -> u128 {
+			let bonus = match self.balancer.bonus {
+				0 => 0,
+				bonus if self.is_below_target(resource, stock) => bonus,
+				_ => 0,
+			};
+			u128::from(self.weight_of(resource)) * (100 + u128::from(bonus))
+		}
+
+		/// Credits per unit withdrawn, scaled by `RATE_PRECISION`
+		fn withdrawal_rate(&self, resource: Resource, stock: &impl Fn(Resource) -> u64) -> u128 {
+			let surcharge = match self.balancer.surcharge {
+				0 => 0,
+				surcharge if self.is_below_target(resource, stock) => surcharge,
+				_ => 0,
+			};
+			u128::from(self.weight_of(resource)) * (100 + u128::from(surcharge))
+		}
+
+		/// Credits earned by contributing `amount` at `rate`, rounded down
+		fn credits_for(amount: u64, rate: u128) -> u64 {
+			math::mul_div(u128::from(amount), rate, RATE_PRECISION)
+				.and_then(|credits| u64::try_from(credits).ok())
+				.unwrap_or(u64::MAX)
+		}
+
+		/// Credits spent by withdrawing `amount` at `rate`, rounded up so that small withdrawals
+		/// are never free
+		fn cost_of(amount: u64, rate: u128) -> u64 {
+			math::mul_div_up(u128::from(amount), rate, RATE_PRECISION)
+				.and_then(|cost| u64::try_from(cost).ok())
+				.unwrap_or(u64::MAX)
+		}
+
+		/// Hands withdrawn resources over to `to`.
+		/// Resources without a backing token are collected in person.
+		fn pay_out(&self, to: AccountId, amount: u64, resource: Resource) -> Result<()> {
+			if resource == Resource::Native {
+				return self
+					.env()
+					.transfer(to, Balance::from(amount))
+					.map_err(|_| Error::NativeTransferFailed);
+			}
+			let Some(token) = self.tokens.get(resource) else {
+				return Ok(());
+			};
+
+			let mut psp22: ink::contract_ref!(PSP22) = token.into();
+			psp22
+				.transfer(to, Balance::from(amount), Vec::new())
+				.map_err(|_| Error::TokenTransferFailed)
+		}
+
+		/// `amount * share / total` without intermediate overflow, rounded down
+		fn pro_rata(amount: u64, share: u64, total: u64) -> u64 {
+			// Zero when `total` is, and fits in a u64 whenever `share` is at most `total`
+			math::mul_div(u128::from(amount), u128::from(share), u128::from(total))
+				.map_or(0, |result| result as u64)
+		}
+
+		/// Reads the pool of a single resource, treating a missing entry as empty
+		fn stock_of(&self, resource: Resource) -> u64 {
+			self.stock.get(resource).unwrap_or(0)
+		}
+
+		fn emit_event<EE>(emitter: EE, event: Event)
+		where
+			EE: EmitEvent<Self>,
+		{
+			emitter.emit_event(event);
+		}
+	}
+
+	impl PSP22Receiver for ResourceMarket {
+		/// Credits `from` for the tokens it sent, as if it had contributed the resource the
+		/// calling token backs.
+		#[ink(message)]
+		fn before_received(
+			&mut self,
+			_operator: AccountId,
+			from: AccountId,
+			value: Balance,
+			_data: Vec<u8>,
+		) -> core::result::Result<(), PSP22ReceiverError> {
+			let deposit = || {
+				let resource =
+					self.token_resources.get(self.env().caller()).ok_or(Error::UnknownToken)?;
+				let amount = u64::try_from(value).map_err(|_| Error::AmountTooLarge)?;
+				Ok((resource, amount))
+			};
+			deposit()
+				.and_then(|(resource, amount)| self.contribute_as(from, amount, resource, true))
+				.map_err(|error: Error| PSP22ReceiverError::TransferRejected(format!("{error:?}")))
+		}
+	}
+
+	// Enhancement: Each resource can be backed by a PSP22 token, but the resources themselves are
+	// still the fixed food, water and wood. The resource trading logic is useful for way more
+	// scenarios than that. Generalize the contract to work with up to 5 arbitrary PSP22 tokens.
+
+	// Enhancement: If we are trading real food, wood, and water, we have real-world incentives
+	// to deposit our excess resources. Storage is hard IRL. Water evaporates, food spoils, and wood
+	// rots. And all the resources are subject to robbery. But if we are talking about virtual
+	// assets, there are no such risks. And depositing funds into the market comes with an
+	// opportunity cost. Design a reward system where there is a small fee on every withdrawal, and
+	// that fee is paid to liquidity providers.
+
+	#[cfg(test)]
+	mod tests {
+		use super::*;
+
+		use ink::env::test::recorded_events;
+		type Event = <ResourceMarket as ::ink::reflect::ContractEventBase>::Type;
+
+		fn default_accounts() -> ink::env::test::DefaultAccounts<ink::env::DefaultEnvironment> {
+			ink::env::test::default_accounts::<Environment>()
+		}
+
+		fn set_next_caller(caller: AccountId) {
+			ink::env::test::set_caller::<Environment>(caller);
+		}
+
+		fn set_next_caller_with_credits(
+			caller: AccountId,
+			credits: u64,
+			market: &mut ResourceMarket,
+		) {
+			ink::env::test::set_caller::<Environment>(caller);
+			let previous = market.credits.get(caller).unwrap_or(0);
+			market.credits.insert(caller, &credits);
+			market.total_credits = market.total_credits - previous + credits;
+		}
+
+		/// Testing the constructor
+		#[ink::test]
+		fn test_constructor_works() {
+			let resource_market = ResourceMarket::new(10, 20, 30);
+			assert_eq!(resource_market.get_resource(Resource::Food), Ok(10));
+			assert_eq!(resource_market.get_resource(Resource::Water), Ok(20));
+			assert_eq!(resource_market.get_resource(Resource::Wood), Ok(30));
+		}
+
+		#[ink::test]
+		fn test_contributing_works() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+
+			let mut resource_market = ResourceMarket::new(0, 0, 0);
+			let result = resource_market.contribute(10, Resource::Water);
+
+			assert_eq!(result, Ok(()));
+			assert_eq!(resource_market.get_resource(Resource::Water), Ok(10));
+			assert_eq!(resource_market.credits.get(default_accounts.alice), Some(10));
+		}
+
+		#[ink::test]
+		fn test_withdrawing_works() {
+			let default_accounts = default_accounts();
+
+			let mut resource_market = ResourceMarket::new(100, 100, 100);
+			set_next_caller_with_credits(default_accounts.bob, 100, &mut resource_market);
+
+			let result = resource_market.withdraw(50, Resource::Water);
+			assert_eq!(result, Ok(()));
+			assert_eq!(resource_market.get_resource(Resource::Water), Ok(50));
+			assert_eq!(resource_market.credits.get(default_accounts.bob), Some(50)); // contributed 100 water, took 50 water
+		}
+
+		#[ink::test]
+		fn test_withdrawing_more_than_contributed_fails() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+
+			let mut resource_market = ResourceMarket::new(50, 50, 50);
+			let contribute_result = resource_market.contribute(10, Resource::Food);
+
+			let last_event = recorded_events().last().unwrap();
+			let decoded_event = <Event as scale::Decode>::decode(&mut &last_event.data[..])
+				.expect("Failed to decode event");
+
+			let Event::ContributionReceived(ContributionReceived {
+				sender,
+				amount,
+				resource,
+				total_credits_available,
+				total_resource_available,
+			}) = decoded_event
+			else {
+				panic!("AddressAdded event should be emitted")
+			};
+
+			assert_eq!(sender, default_accounts.alice);
+			assert_eq!(amount, 10);
+			assert_eq!(resource, Resource::Food);
+			assert_eq!(total_credits_available, 10);
+			assert_eq!(total_resource_available, 60);
+
+			let withdraw_result = resource_market.withdraw(15, Resource::Water);
+
+			assert_eq!(contribute_result, Ok(()));
+			assert_eq!(withdraw_result, Err(Error::InsufficientCredits));
+			assert_eq!(resource_market.get_resource(Resource::Food), Ok(60));
+			assert_eq!(resource_market.get_resource(Resource::Water), Ok(50));
+		}
+
+		#[ink::test]
+		fn test_withdrawing_resources_not_available_fails() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.bob);
+
+			let mut resource_market = ResourceMarket::new(0, 0, 0);
+			let result = resource_market.withdraw(50, Resource::Water);
+			assert_eq!(result, Err(Error::InsufficientResources));
+		}
+
+		#[ink::test]
+		fn test_withdrawing_reports_remaining_stock() {
+			let default_accounts = default_accounts();
+
+			let mut resource_market = ResourceMarket::new(100, 0, 0);
+			set_next_caller_with_credits(default_accounts.bob, 100, &mut resource_market);
+			resource_market.withdraw(30, Resource::Food).unwrap();
+
+			let last_event = recorded_events().last().unwrap();
+			let decoded_event = <Event as scale::Decode>::decode(&mut &last_event.data[..])
+				.expect("Failed to decode event");
+
+			let Event::ResourceWithdrawn(ResourceWithdrawn {
+				total_resource_available,
+				total_credits_available,
+				..
+			}) = decoded_event
+			else {
+				panic!("ResourceWithdrawn event should be emitted")
+			};
+
+			assert_eq!(total_resource_available, 70);
+			assert_eq!(total_credits_available, 70);
+			assert_eq!(resource_market.get_resource(Resource::Food), Ok(70));
+		}
+
+		#[ink::test]
+		fn test_withdrawing_resources_contributed_by_someone_else() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.bob);
+
+			let mut resource_market = ResourceMarket::new(0, 0, 0);
+			resource_market.contribute(100, Resource::Food).unwrap();
+			resource_market.contribute(50, Resource::Water).unwrap();
+			resource_market.contribute(150, Resource::Wood).unwrap();
 
 			assert_eq!(resource_market.get_resource(Resource::Water), Ok(50));
 			assert_eq!(resource_market.get_resource(Resource::Food), Ok(100));
 			assert_eq!(resource_market.get_resource(Resource::Wood), Ok(150));
 			assert_eq!(resource_market.credits.get(default_accounts.bob), Some(300));
 
-			set_next_caller_with_credits(default_accounts.alice, 500, &mut resource_market);
-			for resource in [Resource::Water, Resource::Food, Resource::Wood] {
-				resource_market.withdraw(10, resource);
+			set_next_caller_with_credits(default_accounts.alice, 500, &mut resource_market);
+			for resource in [Resource::Water, Resource::Food, Resource::Wood] {
+				resource_market.withdraw(10, resource).unwrap();
+			}
+
+			assert_eq!(resource_market.credits.get(default_accounts.alice), Some(470)); // contributed nothing, took 30 in total
+		}
+
+		#[ink::test]
+		fn test_max_withdrawable_is_bounded_by_credits_and_stock() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut resource_market = ResourceMarket::new(100, 5, 0);
+			set_next_caller_with_credits(default_accounts.bob, 20, &mut resource_market);
+
+			assert_eq!(resource_market.max_withdrawable(default_accounts.bob, Resource::Food), 20);
+			assert_eq!(resource_market.max_withdrawable(default_accounts.bob, Resource::Water), 5);
+			assert_eq!(resource_market.max_withdrawable(default_accounts.bob, Resource::Wood), 0);
+			assert_eq!(resource_market.max_withdrawable(default_accounts.eve, Resource::Food), 0);
+
+			set_next_caller(default_accounts.alice);
+			resource_market.terminate().unwrap();
+			assert_eq!(resource_market.max_withdrawable(default_accounts.bob, Resource::Food), 0);
+		}
+
+		#[ink::test]
+		fn test_weight_changes_are_timelocked() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut resource_market = ResourceMarket::new(0, 0, 0);
+
+			set_next_caller(default_accounts.bob);
+			assert_eq!(resource_market.propose_weight(Resource::Wood, 200), Err(Error::NotOwner));
+
+			set_next_caller(default_accounts.alice);
+			assert_eq!(
+				resource_market.propose_weight(Resource::Wood, 0),
+				Err(Error::InvalidWeight)
+			);
+			assert_eq!(
+				resource_market.activate_weight(Resource::Wood),
+				Err(Error::NoPendingWeight)
+			);
+			resource_market.propose_weight(Resource::Wood, 200).unwrap();
+			assert_eq!(
+				resource_market.pending_weight(Resource::Wood),
+				Some(PendingWeight { weight: 200, activates_at: WEIGHT_TIMELOCK })
+			);
+
+			set_next_caller(default_accounts.bob);
+			assert_eq!(
+				resource_market.activate_weight(Resource::Wood),
+				Err(Error::WeightTimelocked)
+			);
+			resource_market.contribute(10, Resource::Wood).unwrap();
+			assert_eq!(resource_market.credits.get(default_accounts.bob), Some(10));
+
+			for _ in 0..WEIGHT_TIMELOCK {
+				ink::env::test::advance_block::<Environment>();
 			}
+			assert_eq!(resource_market.activate_weight(Resource::Wood), Ok(()));
+			assert_eq!(resource_market.weight(Resource::Wood), 200);
+			assert_eq!(resource_market.pending_weight(Resource::Wood), None);
 
-			assert_eq!(resource_market.credits.get(default_accounts.alice), Some(470)); // contributed nothing, took 30 in total
+			resource_market.contribute(10, Resource::Wood).unwrap();
+			assert_eq!(resource_market.credits.get(default_accounts.bob), Some(30));
+			assert_eq!(resource_market.max_withdrawable(default_accounts.bob, Resource::Wood), 15);
+
+			resource_market.withdraw(5, Resource::Wood).unwrap();
+			assert_eq!(resource_market.credits.get(default_accounts.bob), Some(20));
+		}
+
+		#[ink::test]
+		fn test_balancer_rewards_deposits_toward_target() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut resource_market = ResourceMarket::new(100, 0, 100);
+
+			set_next_caller(default_accounts.bob);
+			assert_eq!(resource_market.set_balancer(10, 20), Err(Error::NotOwner));
+
+			set_next_caller(default_accounts.alice);
+			resource_market.set_balancer(10, 20).unwrap();
+			assert_eq!(resource_market.balancer(), BalancerConfig { bonus: 10, surcharge: 20 });
+
+			// water is below its third of the pools, food is above it
+			set_next_caller(default_accounts.bob);
+			resource_market.contribute(10, Resource::Water).unwrap();
+			resource_market.contribute(10, Resource::Food).unwrap();
+			assert_eq!(resource_market.credits.get(default_accounts.bob), Some(21));
+
+			assert_eq!(resource_market.max_withdrawable(default_accounts.bob, Resource::Water), 10);
+			resource_market.withdraw(5, Resource::Water).unwrap();
+			assert_eq!(resource_market.credits.get(default_accounts.bob), Some(15));
+			resource_market.withdraw(10, Resource::Food).unwrap();
+			assert_eq!(resource_market.credits.get(default_accounts.bob), Some(5));
+
+			// the native currency is not balanced unless it is given a target
+			assert_eq!(resource_market.target(Resource::Native), 0);
+			set_next_caller(default_accounts.alice);
+			resource_market.set_target(Resource::Native, 1).unwrap();
+			assert_eq!(resource_market.target(Resource::Native), 1);
+		}
+
+		#[ink::test]
+		fn test_locked_contributions_are_boosted() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut resource_market = ResourceMarket::new(0, 0, 0);
+
+			assert_eq!(
+				resource_market.contribute_locked(10, Resource::Food, MAX_LOCK + 1),
+				Err(Error::LockTooLong)
+			);
+			let short = resource_market.contribute_locked(100, Resource::Food, 0).unwrap();
+			let long =
+				resource_market.contribute_locked(100, Resource::Wood, LOCK_PERIOD * 3).unwrap();
+
+			assert_eq!(resource_market.get_resource(Resource::Wood), Ok(100));
+			assert_eq!(resource_market.credits.get(default_accounts.alice), None);
+			assert_eq!(
+				resource_market.positions(default_accounts.alice),
+				vec![
+					(
+						short,
+						Position {
+							owner: default_accounts.alice,
+							resource: Resource::Food,
+							amount: 100,
+							credits: 100,
+							unlock_at: 0,
+						}
+					),
+					(
+						long,
+						Position {
+							owner: default_accounts.alice,
+							resource: Resource::Wood,
+							amount: 100,
+							credits: 130,
+							unlock_at: LOCK_PERIOD * 3,
+						}
+					),
+				]
+			);
+
+			assert_eq!(resource_market.unlock(short), Ok(()));
+			assert_eq!(resource_market.credits.get(default_accounts.alice), Some(100));
+			assert_eq!(resource_market.unlock(short), Err(Error::PositionNotFound));
+			assert_eq!(resource_market.unlock(long), Err(Error::PositionLocked));
+
+			set_next_caller(default_accounts.bob);
+			assert_eq!(resource_market.unlock(long), Err(Error::NotPositionOwner));
+
+			for _ in 0..LOCK_PERIOD * 3 {
+				ink::env::test::advance_block::<Environment>();
+			}
+			set_next_caller(default_accounts.alice);
+			assert_eq!(resource_market.unlock(long), Ok(()));
+			assert_eq!(resource_market.credits.get(default_accounts.alice), Some(230));
+			assert_eq!(resource_market.positions(default_accounts.alice), vec![]);
+		}
+
+		#[ink::test]
+		fn test_community_pool_collects_expired_credits_and_surcharges() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut resource_market = ResourceMarket::new(0, 0, 0);
+			resource_market.set_balancer(0, 50).unwrap();
+
+			set_next_caller(default_accounts.bob);
+			resource_market.contribute(20, Resource::Food).unwrap();
+			resource_market.contribute(20, Resource::Water).unwrap();
+			assert_eq!(
+				resource_market.expire_credits(default_accounts.bob),
+				Err(Error::CreditsNotExpired)
+			);
+
+			// water is below target after food was contributed too, so withdrawing it is surcharged
+			set_next_caller(default_accounts.eve);
+			resource_market.contribute(40, Resource::Food).unwrap();
+			resource_market.withdraw(10, Resource::Water).unwrap();
+			assert_eq!(resource_market.credits.get(default_accounts.eve), Some(25));
+			assert_eq!(resource_market.community_pool(), 5);
+
+			for _ in 0..CREDIT_EXPIRY {
+				ink::env::test::advance_block::<Environment>();
+			}
+			assert_eq!(resource_market.expire_credits(default_accounts.bob), Ok(()));
+			assert_eq!(resource_market.credits.get(default_accounts.bob), None);
+			assert_eq!(resource_market.community_pool(), 45);
+
+			assert_eq!(resource_market.grant(default_accounts.charlie, 10), Err(Error::NotOwner));
+			set_next_caller(default_accounts.alice);
+			assert_eq!(
+				resource_market.grant(default_accounts.charlie, 50),
+				Err(Error::InsufficientCommunityPool)
+			);
+			assert_eq!(resource_market.grant(default_accounts.charlie, 10), Ok(()));
+			assert_eq!(resource_market.credits.get(default_accounts.charlie), Some(10));
+			assert_eq!(resource_market.community_pool(), 35);
+		}
+
+		#[ink::test]
+		fn test_simulate_does_not_change_state() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut resource_market = ResourceMarket::new(5, 0, 0);
+			resource_market.contribute(10, Resource::Water).unwrap();
+
+			let outcomes = resource_market.simulate(vec![
+				MarketCall::Withdraw { amount: 8, resource: Resource::Food },
+				MarketCall::Contribute { amount: 20, resource: Resource::Wood },
+				MarketCall::Withdraw { amount: 25, resource: Resource::Water },
+				MarketCall::Withdraw { amount: 25, resource: Resource::Wood },
+				MarketCall::Withdraw { amount: 10, resource: Resource::Water },
+			]);
+
+			assert_eq!(
+				outcomes,
+				vec![
+					Err(Error::InsufficientResources),
+					Ok(SimOutcome { total_resource_available: 20, total_credits_available: 30 }),
+					Err(Error::InsufficientResources),
+					Err(Error::InsufficientResources),
+					Ok(SimOutcome { total_resource_available: 0, total_credits_available: 20 }),
+				]
+			);
+			assert_eq!(resource_market.get_resource(Resource::Wood), Ok(0));
+			assert_eq!(resource_market.get_resource(Resource::Water), Ok(10));
+			assert_eq!(resource_market.credits.get(default_accounts.alice), Some(10));
+		}
+
+		#[ink::test]
+		fn test_signed_calls_are_executed_once() {
+			use secp256k1::{Message, PublicKey, SecretKey, SECP256K1};
+
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut resource_market = ResourceMarket::new(0, 0, 0);
+
+			let secret_key = SecretKey::from_slice(&[0x11; 32]).unwrap();
+			let public_key = PublicKey::from_secret_key(SECP256K1, &secret_key).serialize();
+			let mut signer = <Blake2x256 as HashOutput>::Type::default();
+			ink::env::hash_bytes::<Blake2x256>(&public_key, &mut signer);
+			let signer = AccountId::from(signer);
+
+			let sign = |market: &ResourceMarket, payload: SignedCall| {
+				let message = Message::from_slice(&market.signed_call_hash(payload)).unwrap();
+				let (recovery_id, compact) =
+					SECP256K1.sign_ecdsa_recoverable(&message, &secret_key).serialize_compact();
+				let mut signature = [0; 65];
+				signature[..64].copy_from_slice(&compact);
+				signature[64] = recovery_id.to_i32() as u8;
+				signature
+			};
+
+			let payload = SignedCall {
+				signer,
+				nonce: 0,
+				call: MarketCall::Contribute { amount: 10, resource: Resource::Food },
+			};
+			let signature = sign(&resource_market, payload);
+
+			// a relayer submits the call on behalf of the signer
+			set_next_caller(default_accounts.bob);
+			assert_eq!(resource_market.execute_signed(payload, signature), Ok(()));
+			assert_eq!(resource_market.credits.get(signer), Some(10));
+			assert_eq!(resource_market.credits.get(default_accounts.bob), None);
+			assert_eq!(resource_market.nonce_of(signer), 1);
+
+			assert_eq!(
+				resource_market.execute_signed(payload, signature),
+				Err(Error::InvalidNonce)
+			);
+
+			let forged = SignedCall { signer: default_accounts.bob, ..payload };
+			assert_eq!(
+				resource_market.execute_signed(forged, signature),
+				Err(Error::InvalidSignature)
+			);
+
+			let withdrawal = SignedCall {
+				signer,
+				nonce: 1,
+				call: MarketCall::Withdraw { amount: 4, resource: Resource::Food },
+			};
+			let signature = sign(&resource_market, withdrawal);
+			assert_eq!(resource_market.execute_signed(withdrawal, signature), Ok(()));
+			assert_eq!(resource_market.credits.get(signer), Some(6));
+		}
+
+		#[ink::test]
+		fn test_only_owner_can_terminate() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut resource_market = ResourceMarket::new(10, 10, 10);
+
+			set_next_caller(default_accounts.bob);
+			assert_eq!(resource_market.terminate(), Err(Error::NotOwner));
+
+			set_next_caller(default_accounts.alice);
+			assert_eq!(resource_market.terminate(), Ok(()));
+			assert!(resource_market.is_terminated());
+			assert_eq!(resource_market.terminate(), Err(Error::MarketTerminated));
+			assert_eq!(
+				resource_market.contribute(10, Resource::Food),
+				Err(Error::MarketTerminated)
+			);
+			assert_eq!(resource_market.withdraw(10, Resource::Food), Err(Error::MarketTerminated));
+		}
+
+		#[ink::test]
+		fn test_terminated_market_pays_out_pro_rata() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut resource_market = ResourceMarket::new(0, 0, 0);
+			resource_market.contribute(30, Resource::Food).unwrap();
+			resource_market.contribute(10, Resource::Water).unwrap();
+
+			set_next_caller(default_accounts.bob);
+			resource_market.contribute(20, Resource::Wood).unwrap();
+			assert_eq!(resource_market.claim_payout(), Err(Error::MarketNotTerminated));
+
+			set_next_caller(default_accounts.alice);
+			resource_market.terminate().unwrap();
+
+			// alice holds 40 of the 60 outstanding credits
+			resource_market.claim_payout().unwrap();
+			assert_eq!(resource_market.get_resource(Resource::Food), Ok(10));
+			assert_eq!(resource_market.get_resource(Resource::Water), Ok(4));
+			assert_eq!(resource_market.get_resource(Resource::Wood), Ok(7));
+			assert_eq!(resource_market.claim_payout(), Err(Error::NothingToClaim));
+
+			// bob holds all of the remaining credits
+			set_next_caller(default_accounts.bob);
+			resource_market.claim_payout().unwrap();
+			for resource in Resource::ALL {
+				assert_eq!(resource_market.get_resource(resource), Ok(0));
+			}
+		}
+
+		#[ink::test]
+		fn test_close_waits_for_outstanding_payouts() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut resource_market = ResourceMarket::new(0, 0, 0);
+			assert_eq!(resource_market.close(), Err(Error::MarketNotTerminated));
+
+			set_next_caller(default_accounts.bob);
+			resource_market.contribute(20, Resource::Wood).unwrap();
+
+			set_next_caller(default_accounts.alice);
+			resource_market.terminate().unwrap();
+			assert_eq!(resource_market.close(), Err(Error::PayoutsOutstanding));
+
+			set_next_caller(default_accounts.bob);
+			resource_market.claim_payout().unwrap();
+
+			set_next_caller(default_accounts.alice);
+			let contract = ink::env::test::callee::<Environment>();
+			ink::env::test::set_account_balance::<Environment>(contract, 100);
+			let should_close = move || {
+				resource_market.close().unwrap();
+			};
+			ink::env::test::assert_contract_termination::<Environment, _>(
+				should_close,
+				default_accounts.alice,
+				100,
+			);
+		}
+
+		#[ink::test]
+		fn test_contributing_native_requires_matching_value() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut resource_market = ResourceMarket::new(0, 0, 0);
+
+			ink::env::test::set_value_transferred::<Environment>(5);
+			assert_eq!(
+				resource_market.contribute(10, Resource::Native),
+				Err(Error::UnexpectedTransferredValue)
+			);
+			assert_eq!(
+				resource_market.contribute(5, Resource::Food),
+				Err(Error::UnexpectedTransferredValue)
+			);
+
+			assert_eq!(resource_market.contribute(5, Resource::Native), Ok(()));
+			assert_eq!(resource_market.get_resource(Resource::Native), Ok(5));
+			assert_eq!(resource_market.credits.get(default_accounts.alice), Some(5));
+		}
+
+		#[ink::test]
+		fn test_token_transfers_count_as_contributions() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut resource_market = ResourceMarket::new(0, 0, 0);
+			let wood_token = default_accounts.django;
+			assert_eq!(
+				resource_market.set_resource_token(Resource::Native, wood_token),
+				Err(Error::InvalidResource)
+			);
+			assert_eq!(resource_market.set_resource_token(Resource::Wood, wood_token), Ok(()));
+
+			// The token calls back into the market when bob sends it tokens
+			set_next_caller(wood_token);
+			let bob = default_accounts.bob;
+			assert_eq!(resource_market.before_received(bob, bob, 25, Vec::new()), Ok(()));
+			assert_eq!(resource_market.get_resource(Resource::Wood), Ok(25));
+			assert_eq!(resource_market.credits_of(bob), 25);
+
+			set_next_caller(default_accounts.eve);
+			assert_eq!(
+				resource_market.before_received(bob, bob, 25, Vec::new()),
+				Err(PSP22ReceiverError::TransferRejected("UnknownToken".into()))
+			);
+			assert_eq!(resource_market.credits_of(bob), 25);
+		}
+
+		#[ink::test]
+		fn test_token_backed_resources_are_only_contributed_in_tokens() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut resource_market = ResourceMarket::new(10, 0, 0);
+			let wood_token = default_accounts.django;
+
+			// The food in stock was contributed in person, not in tokens
+			assert_eq!(
+				resource_market.set_resource_token(Resource::Food, default_accounts.eve),
+				Err(Error::ResourceHasStock)
+			);
+			assert_eq!(resource_market.set_resource_token(Resource::Wood, wood_token), Ok(()));
+
+			// Without tokens arriving, contributions would be paid out in tokens for free
+			set_next_caller(default_accounts.bob);
+			assert_eq!(
+				resource_market.contribute(25, Resource::Wood),
+				Err(Error::ContributeWithToken)
+			);
+			assert_eq!(
+				resource_market.contribute_locked(25, Resource::Wood, LOCK_PERIOD),
+				Err(Error::ContributeWithToken)
+			);
+			assert_eq!(
+				resource_market.simulate(vec![MarketCall::Contribute {
+					amount: 25,
+					resource: Resource::Wood
+				}]),
+				vec![Err(Error::ContributeWithToken)]
+			);
+			assert_eq!(resource_market.get_resource(Resource::Wood), Ok(0));
+			assert_eq!(resource_market.credits_of(default_accounts.bob), 0);
+
+			set_next_caller(wood_token);
+			let bob = default_accounts.bob;
+			assert_eq!(resource_market.before_received(bob, bob, 25, Vec::new()), Ok(()));
+			assert_eq!(resource_market.get_resource(Resource::Wood), Ok(25));
+
+			// Once there is stock in tokens, the token can't be swapped out from under it
+			set_next_caller(default_accounts.alice);
+			assert_eq!(
+				resource_market.set_resource_token(Resource::Wood, default_accounts.eve),
+				Err(Error::ResourceHasStock)
+			);
+		}
+
+		#[ink::test]
+		fn test_withdrawing_native_transfers_to_caller() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut resource_market = ResourceMarket::new(0, 0, 0);
+			resource_market.contribute(50, Resource::Food).unwrap();
+
+			set_next_caller(default_accounts.bob);
+			ink::env::test::set_value_transferred::<Environment>(40);
+			resource_market.contribute(40, Resource::Native).unwrap();
+			ink::env::test::set_value_transferred::<Environment>(0);
+
+			let contract = ink::env::test::callee::<Environment>();
+			ink::env::test::set_account_balance::<Environment>(contract, 40);
+			let balance_before =
+				ink::env::test::get_account_balance::<Environment>(default_accounts.alice).unwrap();
+
+			set_next_caller(default_accounts.alice);
+			assert_eq!(resource_market.withdraw(30, Resource::Native), Ok(()));
+
+			let balance_after =
+				ink::env::test::get_account_balance::<Environment>(default_accounts.alice).unwrap();
+			assert_eq!(balance_after, balance_before + 30);
+			assert_eq!(resource_market.get_resource(Resource::Native), Ok(10));
+		}
+	}
+
+	#[cfg(all(test, feature = "e2e-tests"))]
+	mod e2e_tests {
+		use ink_e2e::{build_message, PolkadotConfig};
+		use psp22_mock::{Psp22MockRef, Reentry};
+		use psp22_traits::PSP22Mintable;
+
+		use super::*;
+
+		type E2EResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+		type Client = ink_e2e::Client<PolkadotConfig, Environment>;
+
+		/// Deploys a market with wood backed by a mock token, and gives bob 100 of the tokens.
+		/// Returns the token and the market.
+		async fn market_with_token(client: &mut Client) -> (AccountId, AccountId) {
+			let bob = ink_e2e::account_id(ink_e2e::AccountKeyring::Bob);
+			let token = client
+				.instantiate("psp22_mock", &ink_e2e::alice(), Psp22MockRef::new(1_000), 0, None)
+				.await
+				.expect("instantiate token failed")
+				.account_id;
+			let market = client
+				.instantiate(
+					"resource_market",
+					&ink_e2e::alice(),
+					ResourceMarketRef::new(0, 0, 0),
+					0,
+					None,
+				)
+				.await
+				.expect("instantiate market failed")
+				.account_id;
+			let set_token = build_message::<ResourceMarketRef>(market.clone())
+				.call(|market| market.set_resource_token(Resource::Wood, token.clone()));
+			client
+				.call(&ink_e2e::alice(), set_token, 0, None)
+				.await
+				.expect("set_resource_token failed");
+			let fund = build_message::<Psp22MockRef>(token.clone())
+				.call(|token| token.transfer(bob.clone(), 100, Vec::new()));
+			client.call(&ink_e2e::alice(), fund, 0, None).await.expect("transfer failed");
+
+			(token, market)
+		}
+
+		/// Contributes `amount` wood in tokens for bob
+		async fn contribute(client: &mut Client, token: AccountId, market: AccountId, amount: u64) {
+			let contribute = build_message::<Psp22MockRef>(token)
+				.call(|token| token.transfer(market, Balance::from(amount), Vec::new()));
+			client
+				.call(&ink_e2e::bob(), contribute, 0, None)
+				.await
+				.expect("contribute failed");
+		}
+
+		async fn token_balance(client: &mut Client, token: AccountId, owner: AccountId) -> Balance {
+			let balance =
+				build_message::<Psp22MockRef>(token).call(|token| token.balance_of(owner));
+			client.call_dry_run(&ink_e2e::alice(), &balance, 0, None).await.return_value()
+		}
+
+		async fn wood_stock(client: &mut Client, market: AccountId) -> Balance {
+			let stock = build_message::<ResourceMarketRef>(market)
+				.call(|market| market.get_resource(Resource::Wood));
+			let stock =
+				client.call_dry_run(&ink_e2e::alice(), &stock, 0, None).await.return_value();
+			Balance::from(stock.expect("wood is traded"))
+		}
+
+		#[ink_e2e::test(additional_contracts = "../psp22_mock/Cargo.toml")]
+		async fn test_close_hands_the_tokens_left_to_the_owner(
+			mut client: ink_e2e::Client<C, E>,
+		) -> E2EResult<()> {
+			let alice = ink_e2e::account_id(ink_e2e::AccountKeyring::Alice);
+			let bob = ink_e2e::account_id(ink_e2e::AccountKeyring::Bob);
+			let (token, market) = market_with_token(&mut client).await;
+
+			// Bob contributes wood in tokens, and tokens also arrive without a contribution
+			contribute(&mut client, token.clone(), market.clone(), 50).await;
+			let mint = build_message::<Psp22MockRef>(token.clone())
+				.call(|token| token.mint(market.clone(), 7));
+			client.call(&ink_e2e::alice(), mint, 0, None).await.expect("mint failed");
+
+			let terminate = build_message::<ResourceMarketRef>(market.clone())
+				.call(|market| market.terminate());
+			client
+				.call(&ink_e2e::alice(), terminate, 0, None)
+				.await
+				.expect("terminate failed");
+			let claim = build_message::<ResourceMarketRef>(market.clone())
+				.call(|market| market.claim_payout());
+			client.call(&ink_e2e::bob(), claim, 0, None).await.expect("claim_payout failed");
+
+			let close =
+				build_message::<ResourceMarketRef>(market.clone()).call(|market| market.close());
+			client.call(&ink_e2e::alice(), close, 0, None).await.expect("close failed");
+
+			assert_eq!(token_balance(&mut client, token.clone(), bob).await, 100);
+			assert_eq!(token_balance(&mut client, token.clone(), market).await, 0);
+			assert_eq!(token_balance(&mut client, token, alice).await, 907);
+
+			Ok(())
+		}
+
+		#[ink_e2e::test(additional_contracts = "../psp22_mock/Cargo.toml")]
+		async fn test_failing_tokens_fail_the_withdrawal(
+			mut client: ink_e2e::Client<C, E>,
+		) -> E2EResult<()> {
+			let bob = ink_e2e::account_id(ink_e2e::AccountKeyring::Bob);
+			let (token, market) = market_with_token(&mut client).await;
+			contribute(&mut client, token.clone(), market.clone(), 50).await;
+
+			let fail = build_message::<Psp22MockRef>(token.clone())
+				.call(|token| token.set_fail_transfers(true));
+			client
+				.call(&ink_e2e::alice(), fail, 0, None)
+				.await
+				.expect("set_fail_transfers failed");
+
+			// The withdrawal is refused as a whole, bob keeps his credits and the stock stays
+			let withdraw = build_message::<ResourceMarketRef>(market.clone())
+				.call(|market| market.withdraw(10, Resource::Wood));
+			let result =
+				client.call_dry_run(&ink_e2e::bob(), &withdraw, 0, None).await.return_value();
+			assert_eq!(result, Err(Error::TokenTransferFailed));
+			let _ = client.call(&ink_e2e::bob(), withdraw, 0, None).await;
+
+			let credits = build_message::<ResourceMarketRef>(market.clone())
+				.call(|market| market.credits_of(bob.clone()));
+			let credits =
+				client.call_dry_run(&ink_e2e::bob(), &credits, 0, None).await.return_value();
+			assert_eq!(credits, 50);
+			assert_eq!(wood_stock(&mut client, market.clone()).await, 50);
+			assert_eq!(token_balance(&mut client, token, market).await, 50);
+
+			Ok(())
+		}
+
+		#[ink_e2e::test(additional_contracts = "../psp22_mock/Cargo.toml")]
+		async fn test_fees_on_transfers_are_not_credited(
+			mut client: ink_e2e::Client<C, E>,
+		) -> E2EResult<()> {
+			let bob = ink_e2e::account_id(ink_e2e::AccountKeyring::Bob);
+			let (token, market) = market_with_token(&mut client).await;
+			let set_fee =
+				build_message::<Psp22MockRef>(token.clone()).call(|token| token.set_fee(1_000));
+			client.call(&ink_e2e::alice(), set_fee, 0, None).await.expect("set_fee failed");
+
+			// A tenth of the contribution is burned on the way, only what arrived is credited
+			contribute(&mut client, token.clone(), market.clone(), 100).await;
+			let credits = build_message::<ResourceMarketRef>(market.clone())
+				.call(|market| market.credits_of(bob.clone()));
+			let credits =
+				client.call_dry_run(&ink_e2e::bob(), &credits, 0, None).await.return_value();
+			assert_eq!(credits, 90);
+			assert_eq!(wood_stock(&mut client, market.clone()).await, 90);
+			assert_eq!(token_balance(&mut client, token.clone(), market.clone()).await, 90);
+
+			// Payouts are charged the fee too, the market only hands over what it holds
+			let withdraw = build_message::<ResourceMarketRef>(market.clone())
+				.call(|market| market.withdraw(50, Resource::Wood));
+			client.call(&ink_e2e::bob(), withdraw, 0, None).await.expect("withdraw failed");
+			assert_eq!(token_balance(&mut client, token.clone(), bob).await, 45);
+			assert_eq!(wood_stock(&mut client, market.clone()).await, 40);
+			assert_eq!(token_balance(&mut client, token, market).await, 40);
+
+			Ok(())
+		}
+
+		#[ink_e2e::test(additional_contracts = "../psp22_mock/Cargo.toml")]
+		async fn test_tokens_cannot_call_back_into_the_market(
+			mut client: ink_e2e::Client<C, E>,
+		) -> E2EResult<()> {
+			let (token, market) = market_with_token(&mut client).await;
+			contribute(&mut client, token.clone(), market.clone(), 50).await;
+
+			// The token pays out a withdrawal and tries to withdraw again meanwhile
+			let reentry = Reentry {
+				target: market.clone(),
+				selector: ink::selector_bytes!("withdraw"),
+				args: scale::Encode::encode(&(10u64, Resource::Wood)),
+			};
+			let set_reentry = build_message::<Psp22MockRef>(token.clone())
+				.call(|token| token.set_reentry(Some(reentry.clone())));
+			client
+				.call(&ink_e2e::alice(), set_reentry, 0, None)
+				.await
+				.expect("set_reentry failed");
+			let withdraw = build_message::<ResourceMarketRef>(market.clone())
+				.call(|market| market.withdraw(10, Resource::Wood));
+			let _ = client.call(&ink_e2e::bob(), withdraw, 0, None).await;
+
+			// Whether the runtime fails the whole withdrawal or only the call back, the stock is
+			// what the market holds
+			let succeeded = build_message::<Psp22MockRef>(token.clone())
+				.call(|token| token.reentry_succeeded());
+			let succeeded =
+				client.call_dry_run(&ink_e2e::alice(), &succeeded, 0, None).await.return_value();
+			assert_ne!(succeeded, Some(true));
+			let stock = wood_stock(&mut client, market.clone()).await;
+			assert_eq!(token_balance(&mut client, token, market).await, stock);
+
+			Ok(())
+		}
+	}
+
+	/// Gas benchmarks for the market messages.
+	///
+	/// Each benchmark deploys a fresh market to a `substrate-contracts-node`, measures the
+	/// `ref_time` consumed by the messages under test and fails if it exceeds the baseline
+	/// recorded in `benchmarks.txt` by more than `TOLERANCE_PERCENT`. Run with
+	/// `cargo test --features benchmarks -- --nocapture` to print the measurements, and with
+	/// `RECORD_BASELINES=1` to record them as the new baselines. Only record deliberately, e.g.
+	/// when a storage redesign is known to cost more, and commit the file along with the change.
+	/// Until the file is recorded, the benchmarks only print their measurements.
+	#[cfg(all(test, feature = "benchmarks"))]
+	mod benchmarks {
+		use ink_e2e::{build_message, CallDryRunResult};
+
+		use super::*;
+
+		type E2EResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+		/// How far above its baseline a measurement may be, to allow for noise
+		const TOLERANCE_PERCENT: u64 = 1;
+		/// The `ref_time` last recorded for each benchmark, one `name ref_time` pair per line
+		const BASELINES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/benchmarks.txt");
+
+		fn ref_time<V>(result: &CallDryRunResult<Environment, V>) -> u64 {
+			result.exec_result.gas_consumed.ref_time()
+		}
+
+		/// Records `consumed` as the baseline of `name` if `RECORD_BASELINES` is set, and checks
+		/// it against the recorded baseline otherwise. Without any recorded baselines there is
+		/// nothing to check against, and the measurement is only printed.
+		fn assert_within_baseline(name: &str, consumed: u64) {
+			let recording = std::env::var_os("RECORD_BASELINES").is_some();
+			let recorded = match std::fs::read_to_string(BASELINES) {
+				Ok(recorded) => recorded,
+				Err(_) if recording => String::new(),
+				Err(_) => {
+					println!("{name}: {consumed} ref_time, not checked as {BASELINES} is missing");
+					return;
+				},
+			};
+			let mut baselines = recorded
+				.lines()
+				.filter_map(|line| line.split_once(' '))
+				.filter_map(|(name, ref_time)| Some((name.to_string(), ref_time.parse().ok()?)))
+				.collect::<std::collections::BTreeMap<String, u64>>();
+
+			if recording {
+				println!("{name}: {consumed} ref_time, recorded as the baseline");
+				baselines.insert(name.to_string(), consumed);
+				let lines = baselines
+					.iter()
+					.map(|(name, ref_time)| format!("{name} {ref_time}\n"))
+					.collect::<String>();
+				std::fs::write(BASELINES, lines).expect("writing the baselines failed");
+				return;
+			}
+
+			let baseline = *baselines.get(name).unwrap_or_else(|| {
+				panic!("no baseline for {name}, record it with RECORD_BASELINES=1")
+			});
+			println!("{name}: {consumed} ref_time (baseline {baseline})");
+			assert!(
+				consumed <= baseline + baseline * TOLERANCE_PERCENT / 100,
+				"{name} consumed {consumed} ref_time, baseline is {baseline}"
+			);
+		}
+
+		#[ink_e2e::test]
+		async fn bench_contribute(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+			let constructor = ResourceMarketRef::new(0, 0, 0);
+			let market = client
+				.instantiate("resource_market", &ink_e2e::alice(), constructor, 0, None)
+				.await
+				.expect("instantiate failed")
+				.account_id;
+
+			let contribute = build_message::<ResourceMarketRef>(market.clone())
+				.call(|market| market.contribute(10, Resource::Food));
+			let result = client
+				.call(&ink_e2e::alice(), contribute, 0, None)
+				.await
+				.expect("contribute failed");
+
+			assert_within_baseline("contribute", ref_time(&result.dry_run));
+
+			Ok(())
+		}
+
+		#[ink_e2e::test]
+		async fn bench_withdraw(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+			let constructor = ResourceMarketRef::new(0, 0, 0);
+			let market = client
+				.instantiate("resource_market", &ink_e2e::alice(), constructor, 0, None)
+				.await
+				.expect("instantiate failed")
+				.account_id;
+
+			let contribute = build_message::<ResourceMarketRef>(market.clone())
+				.call(|market| market.contribute(10, Resource::Water));
+			client
+				.call(&ink_e2e::alice(), contribute, 0, None)
+				.await
+				.expect("contribute failed");
+
+			let withdraw = build_message::<ResourceMarketRef>(market.clone())
+				.call(|market| market.withdraw(10, Resource::Water));
+			let result = client
+				.call(&ink_e2e::alice(), withdraw, 0, None)
+				.await
+				.expect("withdraw failed");
+
+			assert_within_baseline("withdraw", ref_time(&result.dry_run));
+
+			Ok(())
+		}
+
+		#[ink_e2e::test]
+		async fn bench_get_resource(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+			let constructor = ResourceMarketRef::new(10, 20, 30);
+			let market = client
+				.instantiate("resource_market", &ink_e2e::alice(), constructor, 0, None)
+				.await
+				.expect("instantiate failed")
+				.account_id;
+
+			let query = build_message::<ResourceMarketRef>(market.clone())
+				.call(|market| market.get_resource(Resource::Wood));
+			let result = client.call_dry_run(&ink_e2e::alice(), &query, 0, None).await;
+
+			assert_within_baseline("get_resource", ref_time(&result));
+			assert_eq!(result.return_value(), Ok(30));
+
+			Ok(())
+		}
+
+		#[ink_e2e::test]
+		async fn bench_batch(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+			let constructor = ResourceMarketRef::new(0, 0, 0);
+			let market = client
+				.instantiate("resource_market", &ink_e2e::alice(), constructor, 0, None)
+				.await
+				.expect("instantiate failed")
+				.account_id;
+
+			let mut consumed = 0;
+			for resource in [Resource::Food, Resource::Water, Resource::Wood] {
+				let contribute = build_message::<ResourceMarketRef>(market.clone())
+					.call(|market| market.contribute(10, resource));
+				let result = client
+					.call(&ink_e2e::alice(), contribute, 0, None)
+					.await
+					.expect("contribute failed");
+				consumed += ref_time(&result.dry_run);
+			}
+			for resource in [Resource::Food, Resource::Water, Resource::Wood] {
+				let withdraw = build_message::<ResourceMarketRef>(market.clone())
+					.call(|market| market.withdraw(10, resource));
+				let result = client
+					.call(&ink_e2e::alice(), withdraw, 0, None)
+					.await
+					.expect("withdraw failed");
+				consumed += ref_time(&result.dry_run);
+			}
+
+			assert_within_baseline("batch", consumed);
+
+			Ok(())
 		}
 	}
 }
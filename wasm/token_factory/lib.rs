@@ -0,0 +1,250 @@
+#![cfg_attr(not(feature = "std"), no_std, no_main)]
+
+pub use self::token_factory::{FactoryError, TokenFactory, TokenFactoryRef};
+
+/// Launches PSP22 tokens for workshop participants, who don't have to build and upload the token
+/// code themselves.
+///
+/// The factory instantiates the token code uploaded once with the name, symbol and supply the
+/// creator picks. The creator gets the whole supply and is proposed as the owner of the token,
+/// which they accept on the token itself. The factory keeps the roles the token gives its
+/// deployer but has no way of using them, so the new owner can revoke them at their leisure.
+/// Every token launched is recorded under its creator.
+#[ink::contract]
+mod token_factory {
+	use ink::{
+		codegen::EmitEvent,
+		prelude::{string::String, vec::Vec},
+		reflect::ContractEventBase,
+		storage::Mapping,
+		ToAccountId,
+	};
+	use psp22::TokenRef;
+	use psp22_traits::{PSP22Error, PSP22};
+	use scale::Encode;
+
+	#[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+	#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+	pub enum FactoryError {
+		/// Tokens need a name and a symbol, and an initial supply within their cap
+		InvalidToken,
+		/// Instantiating the token code failed
+		InstantiationFailed,
+		/// Handing the token over to its creator failed
+		PSP22(PSP22Error),
+	}
+
+	impl From<PSP22Error> for FactoryError {
+		fn from(e: PSP22Error) -> Self {
+			FactoryError::PSP22(e)
+		}
+	}
+
+	pub type Result<T> = core::result::Result<T, FactoryError>;
+
+	type Event = <TokenFactory as ContractEventBase>::Type;
+
+	#[ink(event)]
+	pub struct TokenCreated {
+		#[ink(topic)]
+		creator: AccountId,
+		#[ink(topic)]
+		token: AccountId,
+		name: String,
+		symbol: String,
+		total_supply: Balance,
+	}
+
+	#[ink(storage)]
+	pub struct TokenFactory {
+		/// The code hash of the uploaded PSP22 token
+		token_code_hash: Hash,
+		/// The genesis hash of the chain, which every token commits to in signed messages
+		genesis_hash: Hash,
+		/// The tokens every creator launched, oldest first
+		tokens: Mapping<AccountId, Vec<AccountId>>,
+		tokens_count: u32,
+	}
+
+	impl TokenFactory {
+		/// Launches tokens from the code uploaded with `token_code_hash`, for the chain with
+		/// `genesis_hash`
+		#[ink(constructor)]
+		pub fn new(token_code_hash: Hash, genesis_hash: Hash) -> Self {
+			Self { token_code_hash, genesis_hash, tokens: Mapping::default(), tokens_count: 0 }
+		}
+
+		/// Returns the code hash tokens are instantiated from
+		#[ink(message)]
+		pub fn token_code_hash(&self) -> Hash {
+			self.token_code_hash
+		}
+
+		/// Returns the tokens `creator` launched, oldest first
+		#[ink(message)]
+		pub fn tokens_of(&self, creator: AccountId) -> Vec<AccountId> {
+			self.tokens.get(creator).unwrap_or_default()
+		}
+
+		/// Returns how many tokens were launched in total
+		#[ink(message)]
+		pub fn tokens_count(&self) -> u32 {
+			self.tokens_count
+		}
+
+		/// Launches a token called `name` with `symbol` and `decimals`, whose `total_supply`
+		/// goes to the caller, and which can never grow beyond `cap` if given. The caller is
+		/// proposed as the owner and accepts ownership on the token. Returns the token.
+		#[ink(message)]
+		pub fn create_token(
+			&mut self,
+			name: String,
+			symbol: String,
+			decimals: u8,
+			total_supply: Balance,
+			cap: Option<Balance>,
+		) -> Result<AccountId> {
+			if name.is_empty() || symbol.is_empty() || cap.is_some_and(|cap| total_supply > cap) {
+				return Err(FactoryError::InvalidToken);
+			}
+			let creator = self.env().caller();
+			let mut created = self.tokens_of(creator);
+
+			// Salted per creator and launch, so the same parameters can be launched again
+			let salt = (creator, created.len() as u32).encode();
+			let mut token_ref = TokenRef::new(
+				total_supply,
+				Some(name.clone()),
+				Some(symbol.clone()),
+				decimals,
+				cap,
+				self.genesis_hash,
+			)
+			.code_hash(self.token_code_hash)
+			.endowment(0)
+			.salt_bytes(salt)
+			.try_instantiate()
+			.map_err(|_| FactoryError::InstantiationFailed)?
+			.map_err(|_| FactoryError::InstantiationFailed)?;
+			let token = token_ref.to_account_id();
+
+			if total_supply > 0 {
+				let mut psp22: ink::contract_ref!(PSP22) = token.into();
+				psp22.transfer(creator, total_supply, Vec::new())?;
+			}
+			token_ref.transfer_ownership(creator)?;
+
+			created.push(token);
+			self.tokens.insert(creator, &created);
+			self.tokens_count += 1;
+
+			Self::emit_event(
+				self.env(),
+				Event::TokenCreated(TokenCreated { creator, token, name, symbol, total_supply }),
+			);
+
+			Ok(token)
+		}
+
+		fn emit_event<EE>(emitter: EE, event: Event)
+		where
+			EE: EmitEvent<Self>,
+		{
+			emitter.emit_event(event);
+		}
+	}
+
+	#[cfg(test)]
+	mod tests {
+		use super::*;
+
+		#[ink::test]
+		fn test_tokens_are_checked_before_launching() {
+			let mut factory = TokenFactory::new(Hash::from([1; 32]), Hash::default());
+
+			assert_eq!(
+				factory.create_token(String::new(), "WOOD".into(), 12, 1_000, None),
+				Err(FactoryError::InvalidToken)
+			);
+			assert_eq!(
+				factory.create_token("Wood".into(), String::new(), 12, 1_000, None),
+				Err(FactoryError::InvalidToken)
+			);
+			assert_eq!(
+				factory.create_token("Wood".into(), "WOOD".into(), 12, 1_000, Some(999)),
+				Err(FactoryError::InvalidToken)
+			);
+			assert_eq!(factory.tokens_count(), 0);
+			assert_eq!(factory.token_code_hash(), Hash::from([1; 32]));
+		}
+	}
+
+	#[cfg(all(test, feature = "e2e-tests"))]
+	mod e2e_tests {
+		use ink_e2e::build_message;
+
+		use super::*;
+
+		type E2EResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+		#[ink_e2e::test(additional_contracts = "../psp22/Cargo.toml")]
+		async fn test_creators_get_their_tokens(
+			mut client: ink_e2e::Client<C, E>,
+		) -> E2EResult<()> {
+			let bob = ink_e2e::account_id(ink_e2e::AccountKeyring::Bob);
+
+			let code_hash = client
+				.upload("psp22", &ink_e2e::alice(), None)
+				.await
+				.expect("upload token failed")
+				.code_hash;
+			let factory = client
+				.instantiate(
+					"token_factory",
+					&ink_e2e::alice(),
+					TokenFactoryRef::new(code_hash, Hash::default()),
+					0,
+					None,
+				)
+				.await
+				.expect("instantiate factory failed")
+				.account_id;
+
+			let create = build_message::<TokenFactoryRef>(factory.clone()).call(|factory| {
+				factory.create_token("Wood".into(), "WOOD".into(), 12, 1_000, None)
+			});
+			let token = client
+				.call(&ink_e2e::bob(), create, 0, None)
+				.await
+				.expect("create_token failed")
+				.return_value()
+				.expect("create_token rejected");
+
+			let balance = build_message::<TokenRef>(token.clone())
+				.call(|token| token.balance_of(bob.clone()));
+			let balance =
+				client.call_dry_run(&ink_e2e::bob(), &balance, 0, None).await.return_value();
+			assert_eq!(balance, 1_000);
+
+			let accept =
+				build_message::<TokenRef>(token.clone()).call(|token| token.accept_ownership());
+			client
+				.call(&ink_e2e::bob(), accept, 0, None)
+				.await
+				.expect("accept_ownership failed")
+				.return_value()
+				.expect("accept_ownership rejected");
+			let owner = build_message::<TokenRef>(token.clone()).call(|token| token.owner());
+			let owner = client.call_dry_run(&ink_e2e::bob(), &owner, 0, None).await.return_value();
+			assert_eq!(owner, bob);
+
+			let tokens = build_message::<TokenFactoryRef>(factory.clone())
+				.call(|factory| factory.tokens_of(bob.clone()));
+			let tokens =
+				client.call_dry_run(&ink_e2e::bob(), &tokens, 0, None).await.return_value();
+			assert_eq!(tokens, vec![token]);
+
+			Ok(())
+		}
+	}
+}
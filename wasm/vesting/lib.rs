@@ -0,0 +1,399 @@
+#![cfg_attr(not(feature = "std"), no_std, no_main)]
+
+pub use self::vesting::{Vesting, VestingRef};
+
+/// Holds PSP22 tokens for beneficiaries and hands them out as they vest, for any token and apart
+/// from the token itself.
+///
+/// Whoever funds a schedule picks the beneficiary, a cliff before which nothing vests, and the
+/// number of blocks over which the whole amount vests linearly from the start. A revocable
+/// schedule can be stopped by its funder, who gets back what has not vested yet.
+#[ink::contract]
+mod vesting {
+	use ink::{
+		codegen::EmitEvent, prelude::vec::Vec, reflect::ContractEventBase, storage::Mapping,
+	};
+	use psp22_traits::{PSP22Error, PSP22};
+
+	pub type ScheduleId = u32;
+
+	#[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+	#[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+	pub struct Schedule {
+		pub token: AccountId,
+		/// The account which funded the schedule
+		pub funder: AccountId,
+		pub beneficiary: AccountId,
+		/// The amount that vests over the whole schedule, or what had vested when it was revoked
+		pub amount: Balance,
+		/// The block in which vesting starts
+		pub start: BlockNumber,
+		/// The block before which nothing can be released
+		pub cliff: BlockNumber,
+		/// The number of blocks over which the whole amount vests
+		pub duration: BlockNumber,
+		/// How much the beneficiary has released so far
+		pub released: Balance,
+		/// Whether the funder may stop the schedule
+		pub revocable: bool,
+		pub revoked: bool,
+	}
+
+	impl Schedule {
+		/// How much has vested by `block`
+		pub fn vested_at(&self, block: BlockNumber) -> Balance {
+			if self.revoked {
+				return self.amount;
+			}
+			if block < self.cliff {
+				return 0;
+			}
+
+			let elapsed = block.saturating_sub(self.start).min(self.duration);
+			math::mul_div(self.amount, Balance::from(elapsed), Balance::from(self.duration))
+				.unwrap_or(self.amount)
+		}
+	}
+
+	#[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+	#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+	pub enum VestingError {
+		/// The duration must be positive and the cliff may not come after the end
+		InvalidSchedule,
+		ScheduleNotFound,
+		/// Only the beneficiary may release
+		NotBeneficiary,
+		/// Only the funder may revoke
+		NotFunder,
+		/// The schedule can't be revoked, or already was
+		NotRevocable,
+		/// Nothing has vested since the last release
+		NothingToRelease,
+		/// A token transfer failed
+		PSP22(PSP22Error),
+	}
+
+	impl From<PSP22Error> for VestingError {
+		fn from(e: PSP22Error) -> Self {
+			VestingError::PSP22(e)
+		}
+	}
+
+	pub type Result<T> = core::result::Result<T, VestingError>;
+
+	type Event = <Vesting as ContractEventBase>::Type;
+
+	#[ink(event)]
+	pub struct ScheduleCreated {
+		#[ink(topic)]
+		id: ScheduleId,
+		#[ink(topic)]
+		beneficiary: AccountId,
+		#[ink(topic)]
+		token: AccountId,
+		amount: Balance,
+	}
+
+	#[ink(event)]
+	pub struct Released {
+		#[ink(topic)]
+		id: ScheduleId,
+		#[ink(topic)]
+		beneficiary: AccountId,
+		amount: Balance,
+	}
+
+	#[ink(event)]
+	pub struct Revoked {
+		#[ink(topic)]
+		id: ScheduleId,
+		/// What had not vested and went back to the funder
+		refunded: Balance,
+	}
+
+	#[ink(storage)]
+	#[derive(Default)]
+	pub struct Vesting {
+		schedules: Mapping<ScheduleId, Schedule>,
+		next_schedule_id: ScheduleId,
+	}
+
+	impl Vesting {
+		#[ink(constructor)]
+		pub fn new() -> Self {
+			Self::default()
+		}
+
+		/// Takes `amount` of `token` from the caller, who approves the vesting contract first,
+		/// and vests it for `beneficiary` from block `start` on. Nothing can be released in the
+		/// first `cliff` blocks, and everything has vested after `duration` blocks. Returns the
+		/// id of the schedule.
+		#[allow(clippy::too_many_arguments)] // Mirrors the fields of `Schedule`
+		#[ink(message)]
+		pub fn create_schedule(
+			&mut self,
+			token: AccountId,
+			beneficiary: AccountId,
+			amount: Balance,
+			start: BlockNumber,
+			cliff: BlockNumber,
+			duration: BlockNumber,
+			revocable: bool,
+		) -> Result<ScheduleId> {
+			if duration == 0 || cliff > duration {
+				return Err(VestingError::InvalidSchedule);
+			}
+
+			let funder = self.env().caller();
+			let this = self.env().account_id();
+			let mut psp22: ink::contract_ref!(PSP22) = token.into();
+			let before = psp22.balance_of(this);
+			psp22.transfer_from(funder, this, amount, Vec::new())?;
+			// Vest what arrived, tokens may charge a fee on transfers
+			let amount = psp22.balance_of(this).saturating_sub(before);
+
+			let id = self.next_schedule_id;
+			self.next_schedule_id += 1;
+			self.schedules.insert(
+				id,
+				&Schedule {
+					token,
+					funder,
+					beneficiary,
+					amount,
+					start,
+					cliff: start.saturating_add(cliff),
+					duration,
+					released: 0,
+					revocable,
+					revoked: false,
+				},
+			);
+
+			Self::emit_event(
+				self.env(),
+				Event::ScheduleCreated(ScheduleCreated { id, beneficiary, token, amount }),
+			);
+
+			Ok(id)
+		}
+
+		/// Pays the beneficiary of schedule `id` what has vested since the last release, and
+		/// returns the amount. Only the beneficiary may release.
+		#[ink(message)]
+		pub fn release(&mut self, id: ScheduleId) -> Result<Balance> {
+			let mut schedule = self.schedules.get(id).ok_or(VestingError::ScheduleNotFound)?;
+			if self.env().caller() != schedule.beneficiary {
+				return Err(VestingError::NotBeneficiary);
+			}
+
+			let amount = self.releasable(id);
+			if amount == 0 {
+				return Err(VestingError::NothingToRelease);
+			}
+			schedule.released += amount;
+			self.schedules.insert(id, &schedule);
+
+			let mut psp22: ink::contract_ref!(PSP22) = schedule.token.into();
+			psp22.transfer(schedule.beneficiary, amount, Vec::new())?;
+
+			Self::emit_event(
+				self.env(),
+				Event::Released(Released { id, beneficiary: schedule.beneficiary, amount }),
+			);
+
+			Ok(amount)
+		}
+
+		/// Stops schedule `id` and pays what has not vested back to the funder, who is the only
+		/// one allowed to revoke. What has vested can still be released. Returns the refund.
+		#[ink(message)]
+		pub fn revoke(&mut self, id: ScheduleId) -> Result<Balance> {
+			let mut schedule = self.schedules.get(id).ok_or(VestingError::ScheduleNotFound)?;
+			if self.env().caller() != schedule.funder {
+				return Err(VestingError::NotFunder);
+			}
+			if !schedule.revocable || schedule.revoked {
+				return Err(VestingError::NotRevocable);
+			}
+
+			let vested = schedule.vested_at(self.env().block_number());
+			let refunded = schedule.amount - vested;
+			schedule.amount = vested;
+			schedule.revoked = true;
+			self.schedules.insert(id, &schedule);
+
+			let mut psp22: ink::contract_ref!(PSP22) = schedule.token.into();
+			psp22.transfer(schedule.funder, refunded, Vec::new())?;
+
+			Self::emit_event(self.env(), Event::Revoked(Revoked { id, refunded }));
+
+			Ok(refunded)
+		}
+
+		/// Returns schedule `id`, if there is one
+		#[ink(message)]
+		pub fn schedule(&self, id: ScheduleId) -> Option<Schedule> {
+			self.schedules.get(id)
+		}
+
+		/// Returns what the beneficiary of schedule `id` can release now
+		#[ink(message)]
+		pub fn releasable(&self, id: ScheduleId) -> Balance {
+			self.schedules.get(id).map_or(0, |schedule| {
+				schedule.vested_at(self.env().block_number()) - schedule.released
+			})
+		}
+
+		fn emit_event<EE>(emitter: EE, event: Event)
+		where
+			EE: EmitEvent<Self>,
+		{
+			emitter.emit_event(event);
+		}
+	}
+
+	#[cfg(test)]
+	mod tests {
+		use super::*;
+
+		fn default_accounts() -> ink::env::test::DefaultAccounts<Environment> {
+			ink::env::test::default_accounts::<Environment>()
+		}
+
+		fn schedule(revocable: bool) -> Schedule {
+			let default_accounts = default_accounts();
+			Schedule {
+				token: default_accounts.django,
+				funder: default_accounts.alice,
+				beneficiary: default_accounts.bob,
+				amount: 1_000,
+				start: 10,
+				cliff: 20,
+				duration: 40,
+				released: 0,
+				revocable,
+				revoked: false,
+			}
+		}
+
+		#[ink::test]
+		fn test_nothing_vests_before_the_cliff_then_linearly() {
+			let schedule = schedule(false);
+
+			assert_eq!(schedule.vested_at(0), 0);
+			assert_eq!(schedule.vested_at(19), 0);
+			assert_eq!(schedule.vested_at(20), 250);
+			assert_eq!(schedule.vested_at(30), 500);
+			assert_eq!(schedule.vested_at(50), 1_000);
+			assert_eq!(schedule.vested_at(BlockNumber::MAX), 1_000);
+
+			let revoked = Schedule { amount: 300, revoked: true, ..schedule };
+			assert_eq!(revoked.vested_at(0), 300);
+		}
+
+		#[ink::test]
+		fn test_only_the_beneficiary_releases_and_only_the_funder_revokes() {
+			let default_accounts = default_accounts();
+			let mut vesting = Vesting::new();
+			assert_eq!(
+				vesting.create_schedule(
+					default_accounts.django,
+					default_accounts.bob,
+					1_000,
+					0,
+					50,
+					40,
+					true
+				),
+				Err(VestingError::InvalidSchedule)
+			);
+			vesting.schedules.insert(0, &schedule(false));
+			vesting.schedules.insert(1, &schedule(true));
+
+			ink::env::test::set_caller::<Environment>(default_accounts.alice);
+			assert_eq!(vesting.release(0), Err(VestingError::NotBeneficiary));
+			assert_eq!(vesting.revoke(0), Err(VestingError::NotRevocable));
+			assert_eq!(vesting.revoke(2), Err(VestingError::ScheduleNotFound));
+
+			ink::env::test::set_caller::<Environment>(default_accounts.bob);
+			assert_eq!(vesting.revoke(1), Err(VestingError::NotFunder));
+			assert_eq!(vesting.release(1), Err(VestingError::NothingToRelease));
+			assert_eq!(vesting.releasable(1), 0);
+		}
+	}
+
+	#[cfg(all(test, feature = "e2e-tests"))]
+	mod e2e_tests {
+		use ink_e2e::build_message;
+		use psp22_mock::Psp22MockRef;
+
+		use super::*;
+
+		type E2EResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+		#[ink_e2e::test(additional_contracts = "../psp22_mock/Cargo.toml")]
+		async fn test_revoked_schedule_keeps_what_vested(
+			mut client: ink_e2e::Client<C, E>,
+		) -> E2EResult<()> {
+			let alice = ink_e2e::account_id(ink_e2e::AccountKeyring::Alice);
+			let bob = ink_e2e::account_id(ink_e2e::AccountKeyring::Bob);
+
+			let token = client
+				.instantiate("psp22_mock", &ink_e2e::alice(), Psp22MockRef::new(1_000), 0, None)
+				.await
+				.expect("instantiate token failed")
+				.account_id;
+			let vesting = client
+				.instantiate("vesting", &ink_e2e::alice(), VestingRef::new(), 0, None)
+				.await
+				.expect("instantiate vesting failed")
+				.account_id;
+
+			let approve = build_message::<Psp22MockRef>(token.clone())
+				.call(|token| token.approve(vesting.clone(), 1_000));
+			client.call(&ink_e2e::alice(), approve, 0, None).await.expect("approve failed");
+			let create = build_message::<VestingRef>(vesting.clone()).call(|vesting| {
+				vesting.create_schedule(token.clone(), bob.clone(), 1_000, 0, 0, 1_000_000, true)
+			});
+			let id = client
+				.call(&ink_e2e::alice(), create, 0, None)
+				.await
+				.expect("create_schedule failed")
+				.return_value()
+				.expect("schedule rejected");
+
+			let release =
+				build_message::<VestingRef>(vesting.clone()).call(|vesting| vesting.release(id));
+			let released = client
+				.call(&ink_e2e::bob(), release, 0, None)
+				.await
+				.expect("release failed")
+				.return_value()
+				.expect("nothing released");
+
+			let revoke =
+				build_message::<VestingRef>(vesting.clone()).call(|vesting| vesting.revoke(id));
+			let refunded = client
+				.call(&ink_e2e::alice(), revoke, 0, None)
+				.await
+				.expect("revoke failed")
+				.return_value()
+				.expect("revoke rejected");
+
+			let releasable =
+				build_message::<VestingRef>(vesting.clone()).call(|vesting| vesting.releasable(id));
+			let releasable =
+				client.call_dry_run(&ink_e2e::bob(), &releasable, 0, None).await.return_value();
+			assert_eq!(released + releasable + refunded, 1_000);
+
+			let balance = build_message::<Psp22MockRef>(token.clone())
+				.call(|token| token.balance_of(alice.clone()));
+			let balance =
+				client.call_dry_run(&ink_e2e::alice(), &balance, 0, None).await.return_value();
+			assert_eq!(balance, refunded);
+
+			Ok(())
+		}
+	}
+}
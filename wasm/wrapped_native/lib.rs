@@ -0,0 +1,213 @@
+#![cfg_attr(not(feature = "std"), no_std, no_main)]
+
+pub use self::wrapped_native::{WrappedNativeToken, WrappedNativeTokenRef};
+
+/// A PSP22 token backed one to one by the native currency. Depositing the native currency mints
+/// tokens, withdrawing burns them and pays the currency back, so the contract always holds at
+/// least the total supply.
+#[ink::contract]
+mod wrapped_native {
+	use ink::{
+		codegen::EmitEvent,
+		prelude::{string::String, vec::Vec},
+		reflect::ContractEventBase,
+	};
+	use psp22_traits::{
+		notify_receiver, PSP22Data, PSP22Error, PSP22Metadata, WrappedNative, PSP22,
+	};
+
+	type Event = <WrappedNativeToken as ContractEventBase>::Type;
+
+	#[ink(event)]
+	pub struct Approval {
+		#[ink(topic)]
+		owner: AccountId,
+		#[ink(topic)]
+		spender: AccountId,
+		amount: Balance,
+	}
+
+	#[ink(event)]
+	pub struct Transfer {
+		#[ink(topic)]
+		from: AccountId,
+		#[ink(topic)]
+		to: AccountId,
+		value: Balance,
+	}
+
+	#[ink(storage)]
+	pub struct WrappedNativeToken {
+		data: PSP22Data,
+		name: Option<String>,
+		symbol: Option<String>,
+		/// Should match the decimals of the native currency
+		decimals: u8,
+	}
+
+	impl WrappedNativeToken {
+		#[ink(constructor)]
+		pub fn new(name: Option<String>, symbol: Option<String>, decimals: u8) -> Self {
+			Self { data: PSP22Data::default(), name, symbol, decimals }
+		}
+
+		psp22_traits::impl_emit_events!();
+
+		fn emit_event<EE>(emitter: EE, event: Event)
+		where
+			EE: EmitEvent<Self>,
+		{
+			emitter.emit_event(event);
+		}
+	}
+
+	impl WrappedNative for WrappedNativeToken {
+		/// Mints the caller as many tokens as it transferred along with the call
+		#[ink(message, payable)]
+		fn deposit(&mut self) -> Result<(), PSP22Error> {
+			let events = self.data.mint(self.env().caller(), self.env().transferred_value())?;
+			self.emit_events(events);
+			Ok(())
+		}
+
+		/// Burns `amount` of the caller's tokens and sends it as much of the native currency
+		#[ink(message)]
+		fn withdraw(&mut self, amount: Balance) -> Result<(), PSP22Error> {
+			let caller = self.env().caller();
+			let events = self.data.burn(caller, amount)?;
+			self.emit_events(events);
+
+			self.env()
+				.transfer(caller, amount)
+				.map_err(|_| PSP22Error::NativeTransferFailed)
+		}
+	}
+
+	impl PSP22 for WrappedNativeToken {
+		#[ink(message)]
+		fn total_supply(&self) -> Balance {
+			self.data.total_supply()
+		}
+
+		#[ink(message)]
+		fn balance_of(&self, owner: AccountId) -> Balance {
+			self.data.balance_of(owner)
+		}
+
+		#[ink(message)]
+		fn allowance(&self, owner: AccountId, spender: AccountId) -> Balance {
+			self.data.allowance(owner, spender)
+		}
+
+		#[ink(message)]
+		fn approve(&mut self, spender: AccountId, amount: Balance) -> Result<(), PSP22Error> {
+			let events = self.data.approve(self.env().caller(), spender, amount)?;
+			self.emit_events(events);
+			Ok(())
+		}
+
+		#[ink(message)]
+		fn increase_allowance(
+			&mut self,
+			spender: AccountId,
+			by: Balance,
+		) -> Result<(), PSP22Error> {
+			let events = self.data.increase_allowance(self.env().caller(), spender, by)?;
+			self.emit_events(events);
+			Ok(())
+		}
+
+		#[ink(message)]
+		fn decrease_allowance(
+			&mut self,
+			spender: AccountId,
+			by: Balance,
+		) -> Result<(), PSP22Error> {
+			let events = self.data.decrease_allowance(self.env().caller(), spender, by)?;
+			self.emit_events(events);
+			Ok(())
+		}
+
+		#[ink(message)]
+		fn transfer(
+			&mut self,
+			to: AccountId,
+			value: Balance,
+			data: Vec<u8>,
+		) -> Result<(), PSP22Error> {
+			let caller = self.env().caller();
+			let events = self.data.transfer(caller, to, value)?;
+			self.emit_events(events);
+			notify_receiver(self.env().caller(), caller, to, value, data)
+		}
+
+		#[ink(message)]
+		fn transfer_from(
+			&mut self,
+			from: AccountId,
+			to: AccountId,
+			value: Balance,
+			data: Vec<u8>,
+		) -> Result<(), PSP22Error> {
+			let events = self.data.transfer_from(self.env().caller(), from, to, value)?;
+			self.emit_events(events);
+			notify_receiver(self.env().caller(), from, to, value, data)
+		}
+	}
+
+	impl PSP22Metadata for WrappedNativeToken {
+		#[ink(message)]
+		fn token_name(&self) -> Option<String> {
+			self.name.clone()
+		}
+
+		#[ink(message)]
+		fn token_symbol(&self) -> Option<String> {
+			self.symbol.clone()
+		}
+
+		#[ink(message)]
+		fn token_decimals(&self) -> u8 {
+			self.decimals
+		}
+	}
+
+	#[cfg(test)]
+	mod tests {
+		use super::*;
+
+		fn default_accounts() -> ink::env::test::DefaultAccounts<Environment> {
+			ink::env::test::default_accounts::<Environment>()
+		}
+
+		fn set_next_caller(caller: AccountId) {
+			ink::env::test::set_caller::<Environment>(caller);
+		}
+
+		#[ink::test]
+		fn test_deposit_and_withdraw_keep_the_supply_backed() {
+			let default_accounts = default_accounts();
+			set_next_caller(default_accounts.alice);
+			let mut token = WrappedNativeToken::new(None, Some("WNAT".into()), 12);
+
+			ink::env::test::set_value_transferred::<Environment>(100);
+			assert_eq!(token.deposit(), Ok(()));
+			ink::env::test::set_value_transferred::<Environment>(0);
+			assert_eq!(token.balance_of(default_accounts.alice), 100);
+			assert_eq!(token.total_supply(), 100);
+
+			let contract = ink::env::test::callee::<Environment>();
+			ink::env::test::set_account_balance::<Environment>(contract, 100);
+			let balance_before =
+				ink::env::test::get_account_balance::<Environment>(default_accounts.alice).unwrap();
+
+			assert_eq!(token.withdraw(101), Err(PSP22Error::InsufficientBalance));
+			assert_eq!(token.withdraw(60), Ok(()));
+			let balance_after =
+				ink::env::test::get_account_balance::<Environment>(default_accounts.alice).unwrap();
+			assert_eq!(balance_after, balance_before + 60);
+			assert_eq!(token.balance_of(default_accounts.alice), 40);
+			assert_eq!(token.total_supply(), 40);
+		}
+	}
+}